@@ -0,0 +1,249 @@
+//! Network Analysis Module
+//!
+//! This module derives planning data from the outputs of a previous route
+//! run, such as the corridors shared by several routes and the transfers
+//! between nearby stops.
+
+mod overlap;
+mod transfers;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::analyze::overlap::route_overlaps;
+use crate::analyze::transfers::{StopPoint, WalkRouter, build_transfers};
+use crate::config::{
+    BLOCK_MAX_LAYOVER_MIN, BLOCK_MIN_LAYOVER_MIN, OVERLAP_TOLERANCE_M, TRANSFER_MAX_WALK_M,
+};
+use crate::export::estimate_vehicles;
+use crate::route::load_derived_routes;
+use crate::route::snapper::SnapClient;
+use crate::schedule::load_merged_schedules;
+use crate::settings::Settings;
+use crate::utils::cache::ResponseCache;
+use crate::utils::http::HttpClient;
+use crate::utils::{now, write_atomic};
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Analysis {
+    /// Pairwise shared length of routes (`corridors.json`)
+    Overlap,
+    /// Walking transfers between nearby stops (`transfers.json`)
+    Transfers,
+    /// Vehicles each route needs per day type, by chaining trips into blocks (`vehicles.json`)
+    Vehicles,
+}
+
+#[derive(clap::Args)]
+pub struct AnalyzeArgs {
+    /// Analysis to run
+    #[arg(value_enum)]
+    analysis: Analysis,
+
+    /// Route output directory of a previous run (containing `derived_routes/`)
+    #[arg(short, long, default_value = "./storage/processed_routes")]
+    input_dir: PathBuf,
+
+    /// Destination file (default: `<input_dir>/corridors.json`, `transfers.json`, or `vehicles.json`)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Max distance (meters) between two lines counted as shared
+    #[arg(long, default_value_t = OVERLAP_TOLERANCE_M)]
+    tolerance_m: f64,
+
+    /// Leave out route pairs sharing less than this many meters
+    #[arg(long, default_value_t = 500.0)]
+    min_shared_m: f64,
+
+    /// For `transfers`: max walking distance (meters) between two stops
+    #[arg(long, default_value_t = TRANSFER_MAX_WALK_M)]
+    max_walk_m: f64,
+
+    /// For `transfers`: how walking distances are measured
+    #[arg(long, value_enum, default_value = "straight")]
+    walk_router: WalkRouter,
+
+    /// For `vehicles`: schedule output directory (containing `schedules/`)
+    #[arg(long, default_value = "./storage")]
+    schedule_dir: PathBuf,
+
+    /// For `vehicles`: shortest layover (minutes) between two trips of a bus
+    #[arg(long, default_value_t = BLOCK_MIN_LAYOVER_MIN)]
+    min_layover_min: u32,
+
+    /// For `vehicles`: longest layover (minutes) between two trips of a bus
+    #[arg(long, default_value_t = BLOCK_MAX_LAYOVER_MIN)]
+    max_layover_min: u32,
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: AnalyzeArgs, settings: &Settings) -> Result<()> {
+    match args.analysis {
+        Analysis::Overlap => analyze_overlap(&args),
+        Analysis::Transfers => analyze_transfers(&args, settings).await,
+        Analysis::Vehicles => analyze_vehicles(&args),
+    }
+}
+
+fn analyze_vehicles(args: &AnalyzeArgs) -> Result<()> {
+    let routes = load_derived_routes(&args.input_dir)?;
+    let schedules = load_merged_schedules(&args.schedule_dir)?;
+    if routes.is_empty() || schedules.is_empty() {
+        anyhow::bail!(
+            "Both derived routes ({:?}) and schedules ({:?}) are required",
+            args.input_dir,
+            args.schedule_dir
+        );
+    }
+
+    let (estimates, warnings) = estimate_vehicles(
+        &schedules,
+        &routes,
+        (args.min_layover_min, args.max_layover_min),
+    );
+    for w in &warnings {
+        warn!("{}", w);
+    }
+
+    println!("Vehicles required:");
+    for e in &estimates {
+        println!(
+            " {:>8} {:<8} {:>3} vehicles (peak {:>3}) for {:>3} trips",
+            e.route, e.day_type, e.vehicles, e.peak_vehicles, e.trips
+        );
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("vehicles.json"));
+    let content = serde_json::to_string_pretty(&json!({
+        "generatedAt": now().to_rfc3339(),
+        "minLayoverMin": args.min_layover_min,
+        "maxLayoverMin": args.max_layover_min,
+        "routes": estimates,
+    }))?;
+    write_atomic(&output, content)?;
+
+    info!(
+        "✓ Wrote vehicle estimates of {} route schedules to {:?}",
+        estimates.len(),
+        output
+    );
+
+    Ok(())
+}
+
+async fn analyze_transfers(args: &AnalyzeArgs, settings: &Settings) -> Result<()> {
+    let map_path = args.input_dir.join("routeMap.json");
+    let route_map: Value = serde_json::from_str(
+        &fs::read_to_string(&map_path).with_context(|| format!("Reading {:?}", map_path))?,
+    )
+    .with_context(|| format!("Parsing {:?}", map_path))?;
+
+    let stops: Vec<StopPoint> = route_map["stations"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(node_id, station)| {
+            Some(StopPoint {
+                id: node_id.clone(),
+                coord: vec![station["gpslong"].as_f64()?, station["gpslati"].as_f64()?],
+            })
+        })
+        .collect();
+    if stops.is_empty() {
+        anyhow::bail!("No stations found in {:?}", map_path);
+    }
+
+    let osrm = match args.walk_router {
+        WalkRouter::Straight => None,
+        WalkRouter::Osrm => Some((
+            SnapClient {
+                http: HttpClient::new(&settings.http)?,
+                cache: Some(Arc::new(ResponseCache::new(
+                    args.input_dir.join("osrm_cache"),
+                    Duration::from_secs(settings.route.osrm_cache_days * 24 * 60 * 60),
+                )?)),
+            },
+            settings.urls.osrm_foot_table.clone(),
+        )),
+    };
+
+    let (transfers, failed) = build_transfers(&stops, args.max_walk_m, osrm).await;
+    if failed > 0 {
+        warn!("{} stop(s) fell back to straight-line distances", failed);
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("transfers.json"));
+    let content = serde_json::to_string_pretty(&json!({
+        "generatedAt": now().to_rfc3339(),
+        "maxWalkM": args.max_walk_m,
+        "walkRouter": args.walk_router,
+        "transfers": transfers,
+    }))?;
+    write_atomic(&output, content)?;
+
+    info!(
+        "✓ Wrote {} transfers between {} stops to {:?}",
+        transfers.len(),
+        stops.len(),
+        output
+    );
+
+    Ok(())
+}
+
+fn analyze_overlap(args: &AnalyzeArgs) -> Result<()> {
+    let routes = load_derived_routes(&args.input_dir)?;
+    if routes.is_empty() {
+        anyhow::bail!("No derived routes found in {:?}", args.input_dir);
+    }
+
+    let overlaps = route_overlaps(&routes, args.tolerance_m, args.min_shared_m);
+
+    println!("Top shared corridors:");
+    for o in overlaps.iter().take(10) {
+        println!(
+            " {:>7.0} m  {} ({}) / {} ({})  [{}% / {}%]",
+            o.shared_m, o.route_no_a, o.route_a, o.route_no_b, o.route_b, o.pct_a, o.pct_b
+        );
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("corridors.json"));
+    let content = serde_json::to_string_pretty(&json!({
+        "generatedAt": now().to_rfc3339(),
+        "toleranceM": args.tolerance_m,
+        "pairs": overlaps,
+    }))?;
+    write_atomic(&output, content)?;
+
+    info!(
+        "✓ Wrote {} overlapping route pairs of {} routes to {:?}",
+        overlaps.len(),
+        routes.len(),
+        output
+    );
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Route Overlap Analysis
+//!
+//! Measures how far each pair of routes runs along the same roads. Both lines
+//! are resampled to short segments, and a segment of one route counts as
+//! shared when its midpoint lies within the tolerance of the other route.
+
+use serde::Serialize;
+
+use crate::config::OVERLAP_STEP_M;
+use crate::route::model::RouteFeature;
+use crate::utils::geo::{CoordIndex, calculate_metrics, densify_line, meters_between};
+
+/// Length shared by two routes
+#[derive(Debug, Serialize)]
+pub struct RouteOverlap {
+    pub route_a: String,
+    pub route_no_a: String,
+    pub route_b: String,
+    pub route_no_b: String,
+    /// Shared length in meters (mean of both routes' measurements)
+    pub shared_m: f64,
+    /// Share of route A's length that runs along route B (percent)
+    pub pct_a: f64,
+    /// Share of route B's length that runs along route A (percent)
+    pub pct_b: f64,
+}
+
+/// A route line prepared for overlap queries
+struct Line<'a> {
+    feature: &'a RouteFeature,
+    points: Vec<Vec<f64>>,
+    index: CoordIndex,
+    bbox: [f64; 4],
+    length_m: f64,
+}
+
+/// Computes the overlap of every pair of routes with different route numbers
+/// (variants of one number overlap by design), sorted by shared length.
+/// Pairs sharing less than `min_shared_m` are left out.
+pub fn route_overlaps(
+    routes: &[RouteFeature],
+    tolerance_m: f64,
+    min_shared_m: f64,
+) -> Vec<RouteOverlap> {
+    let lines: Vec<Line> = routes
+        .iter()
+        .filter(|f| f.geometry.coordinates.len() >= 2)
+        .map(|feature| {
+            let points = densify_line(&feature.geometry.coordinates, OVERLAP_STEP_M);
+            let (bbox, length_m) = calculate_metrics(&points);
+            Line {
+                feature,
+                index: CoordIndex::new(&points),
+                points,
+                bbox,
+                length_m,
+            }
+        })
+        .collect();
+
+    // Generous padding in degrees at Korean latitudes
+    let pad = 2.0 * tolerance_m / 111_320.0;
+
+    let mut overlaps = Vec::new();
+    for (i, a) in lines.iter().enumerate() {
+        for b in &lines[i + 1..] {
+            if a.feature.properties.route_no == b.feature.properties.route_no
+                || a.bbox[0] > b.bbox[2] + pad
+                || b.bbox[0] > a.bbox[2] + pad
+                || a.bbox[1] > b.bbox[3] + pad
+                || b.bbox[1] > a.bbox[3] + pad
+            {
+                continue;
+            }
+
+            let along_a = shared_length(a, b, tolerance_m);
+            let along_b = shared_length(b, a, tolerance_m);
+            let shared_m = (along_a + along_b) / 2.0;
+            if shared_m < min_shared_m {
+                continue;
+            }
+
+            overlaps.push(RouteOverlap {
+                route_a: a.feature.id.clone(),
+                route_no_a: a.feature.properties.route_no.clone(),
+                route_b: b.feature.id.clone(),
+                route_no_b: b.feature.properties.route_no.clone(),
+                shared_m: shared_m.round(),
+                pct_a: percent(along_a, a.length_m),
+                pct_b: percent(along_b, b.length_m),
+            });
+        }
+    }
+
+    overlaps.sort_by(|x, y| y.shared_m.total_cmp(&x.shared_m));
+    overlaps
+}
+
+/// Length of `a` within `tolerance_m` of `b`, in meters.
+fn shared_length(a: &Line, b: &Line, tolerance_m: f64) -> f64 {
+    // `b` is resampled, so a point near its line is near one of its vertices.
+    let radius = tolerance_m + OVERLAP_STEP_M / 2.0;
+    a.points
+        .windows(2)
+        .filter(|seg| {
+            let mid = ((seg[0][0] + seg[1][0]) / 2.0, (seg[0][1] + seg[1][1]) / 2.0);
+            !b.index.within(mid, radius).is_empty()
+        })
+        .map(|seg| meters_between(seg[0][0], seg[0][1], seg[1][0], seg[1][1]))
+        .sum()
+}
+
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        (part / whole * 1000.0).round() / 10.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,171 @@
+//! Transfers Between Nearby Stops
+//!
+//! Connects every station to the other stations within walking distance, for
+//! the journey planner and GTFS `transfers.txt`. Candidates are found by
+//! straight-line distance; with OSRM, the walking distance and time along the
+//! foot network replace it, and pairs whose walk is too long are dropped.
+
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::{CONCURRENCY_SNAP, WALK_SPEED_MPS};
+use crate::route::snapper::SnapClient;
+use crate::utils::geo::{CoordIndex, meters_between};
+
+/// How walking distances between stops are measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WalkRouter {
+    /// Straight-line distance, walked at a constant speed
+    Straight,
+    /// OSRM `/table` on the foot profile (`OSRM_FOOT_API_URL`)
+    Osrm,
+}
+
+/// A walk from one station to another (listed in both directions)
+#[derive(Debug, Serialize)]
+pub struct Transfer {
+    pub from_stop: String,
+    pub to_stop: String,
+    /// Walking distance in meters
+    pub distance_m: f64,
+    /// Walking time in seconds
+    pub walk_secs: u32,
+}
+
+/// A walk to another stop: (stop index, meters, seconds)
+type Walk = (usize, f64, f64);
+
+/// A station with its [lon, lat]
+pub struct StopPoint {
+    pub id: String,
+    pub coord: Vec<f64>,
+}
+
+/// Builds the transfers between `stops` within `max_walk_m`. `osrm` is the
+/// client and `/table` URL to measure walks with, or `None` for straight
+/// lines. Returns the transfers and the number of stops whose OSRM request
+/// failed, which fall back to straight lines.
+pub async fn build_transfers(
+    stops: &[StopPoint],
+    max_walk_m: f64,
+    osrm: Option<(SnapClient, String)>,
+) -> (Vec<Transfer>, usize) {
+    let coords: Vec<Vec<f64>> = stops.iter().map(|s| s.coord.clone()).collect();
+    let index = CoordIndex::new(&coords);
+
+    // Neighbors of each stop within the straight-line radius, with distances.
+    // A walk is never shorter than the straight line, so nothing is missed.
+    let candidates: Vec<Vec<(usize, f64)>> = stops
+        .iter()
+        .enumerate()
+        .map(|(i, stop)| {
+            index
+                .within((stop.coord[0], stop.coord[1]), max_walk_m)
+                .into_iter()
+                .filter(|&j| j != i)
+                .map(|j| {
+                    let d =
+                        meters_between(stop.coord[0], stop.coord[1], coords[j][0], coords[j][1]);
+                    (j, d)
+                })
+                .filter(|&(_, d)| d <= max_walk_m)
+                .collect()
+        })
+        .collect();
+
+    let mut failed = 0;
+    let mut walks: Vec<(usize, Vec<Walk>)> = Vec::new();
+    match &osrm {
+        None => {
+            for (i, neighbors) in candidates.iter().enumerate() {
+                walks.push((i, straight_walks(neighbors)));
+            }
+        }
+        Some((client, url)) => {
+            let mut results = stream::iter(candidates.iter().enumerate())
+                .filter(|(_, neighbors)| std::future::ready(!neighbors.is_empty()))
+                .map(|(i, neighbors)| async move {
+                    (
+                        i,
+                        neighbors,
+                        foot_walks(client, url, stops, i, neighbors).await,
+                    )
+                })
+                .buffer_unordered(CONCURRENCY_SNAP);
+            while let Some((i, neighbors, result)) = results.next().await {
+                match result {
+                    Ok(found) => walks.push((i, found)),
+                    Err(e) => {
+                        warn!("Walking distances from {} failed: {}", stops[i].id, e);
+                        failed += 1;
+                        walks.push((i, straight_walks(neighbors)));
+                    }
+                }
+            }
+        }
+    }
+
+    walks.sort_by_key(|(i, _)| *i);
+    let transfers = walks
+        .into_iter()
+        .flat_map(|(i, found)| {
+            found
+                .into_iter()
+                .filter(|&(_, distance, _)| distance <= max_walk_m)
+                .map(move |(j, distance, secs)| Transfer {
+                    from_stop: stops[i].id.clone(),
+                    to_stop: stops[j].id.clone(),
+                    distance_m: distance.round(),
+                    walk_secs: secs.round() as u32,
+                })
+        })
+        .collect();
+
+    (transfers, failed)
+}
+
+/// Straight-line walks to `neighbors`.
+fn straight_walks(neighbors: &[(usize, f64)]) -> Vec<Walk> {
+    neighbors
+        .iter()
+        .map(|&(j, d)| (j, d, d / WALK_SPEED_MPS))
+        .collect()
+}
+
+/// Walks from stop `i` to `neighbors` along the foot network, from a single
+/// OSRM `/table` request with the stop as the only source.
+async fn foot_walks(
+    client: &SnapClient,
+    table_url: &str,
+    stops: &[StopPoint],
+    i: usize,
+    neighbors: &[(usize, f64)],
+) -> anyhow::Result<Vec<Walk>> {
+    let coords: Vec<String> = std::iter::once(i)
+        .chain(neighbors.iter().map(|&(j, _)| j))
+        .map(|k| format!("{:.6},{:.6}", stops[k].coord[0], stops[k].coord[1]))
+        .collect();
+    let url = format!(
+        "{}/{}?sources=0&annotations=distance,duration",
+        table_url,
+        coords.join(";")
+    );
+
+    let json = client.cached_get_json(&url, |j| j["code"] == "Ok").await?;
+    if json["code"] != "Ok" {
+        anyhow::bail!("OSRM returned {}", json["code"]);
+    }
+
+    // Row 0 holds the walks from the source; column 0 is the source itself.
+    Ok(neighbors
+        .iter()
+        .enumerate()
+        .filter_map(|(col, &(j, _))| {
+            let distance = json["distances"][0][col + 1].as_f64()?;
+            let duration = json["durations"][0][col + 1].as_f64()?;
+            Some((j, distance, duration))
+        })
+        .collect())
+}
@@ -0,0 +1,99 @@
+//! Changelog Module
+//!
+//! This module lists the routes and schedules whose content changed in
+//! recent crawls, as logged in the crawl state databases (`crawl_state.db`)
+//! of the route and schedule output directories. A re-fetch that returns
+//! the same data is not a change.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use chrono::TimeDelta;
+use tracing::warn;
+
+use crate::utils::now;
+use crate::utils::state::{self, ChangeEntry, STATE_FILE};
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct ChangelogArgs {
+    /// Only list changes of the last N days
+    #[arg(long, default_value_t = 7)]
+    since: u32,
+
+    /// Route output directory (containing the route crawl state)
+    #[arg(long, default_value = "./storage/processed_routes")]
+    route_dir: PathBuf,
+
+    /// Schedule output directory (containing the schedule crawl state)
+    #[arg(long, default_value = "./storage")]
+    schedule_dir: PathBuf,
+
+    /// Print the changes as JSON instead of a list
+    #[arg(long)]
+    json: bool,
+
+    /// Print only the changed IDs, one per line, for `--routes-file`
+    #[arg(long, conflicts_with = "json")]
+    ids: bool,
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: ChangelogArgs) -> Result<()> {
+    let since = (now() - TimeDelta::days(i64::from(args.since))).to_rfc3339();
+
+    let mut entries: Vec<ChangeEntry> = Vec::new();
+    let mut found = false;
+    for dir in [&args.route_dir, &args.schedule_dir] {
+        if !dir.join(STATE_FILE).exists() {
+            continue;
+        }
+        found = true;
+        match state::changes(dir, &since) {
+            Ok(changes) => entries.extend(changes),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+    if !found {
+        bail!(
+            "No {} in {:?} or {:?}; run `route` or `schedule` first",
+            STATE_FILE,
+            args.route_dir,
+            args.schedule_dir
+        );
+    }
+    entries.sort_by(|a, b| a.changed_at.cmp(&b.changed_at));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if args.ids {
+        let ids: BTreeSet<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        for id in ids {
+            println!("{}", id);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "Changes in the last {} day(s): {}",
+        args.since,
+        entries.len()
+    );
+    for entry in &entries {
+        println!(
+            " {}  {:<8} {:<7} {}",
+            entry.changed_at, entry.subsystem, entry.change, entry.id
+        );
+    }
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
 //! Configuration Constants
+//!
+//! Defaults of the layered `settings`, and fixed tuning values.
 
 // ============================================================================
 // Constants
@@ -6,15 +8,115 @@
 
 // API Endpoints
 pub const TAGO_URL: &str = "http://apis.data.go.kr/1613000/BusRouteInfoInqireService";
+pub const TAGO_ARRIVAL_URL: &str = "http://apis.data.go.kr/1613000/ArvlInfoInqireService";
+pub const TAGO_LOCATION_URL: &str = "http://apis.data.go.kr/1613000/BusLcInfoInqireService";
 pub const OSRM_URL: &str = "http://router.project-osrm.org/route/v1/driving";
+pub const VALHALLA_URL: &str = "https://valhalla1.openstreetmap.de";
+pub const OSRM_FOOT_TABLE_URL: &str = "https://routing.openstreetmap.de/routed-foot/table/v1/foot";
+pub const KAKAO_DIRECTIONS_URL: &str =
+    "https://apis-navi.kakaomobility.com/v1/waypoints/directions";
 
 // Constants for the Wonju Bus Information System website.
+pub const ITS_HOST: &str = "its.wonju.go.kr";
 pub const BASE_URL: &str = "http://its.wonju.go.kr/bus/bus04.do";
 pub const DETAIL_URL: &str = "http://its.wonju.go.kr/bus/bus04Detail.do";
+pub const ITS_ROBOTS_URL: &str = "http://its.wonju.go.kr/robots.txt";
+
+// Product token matched against the `User-agent` lines of robots.txt
+pub const ROBOTS_AGENT: &str = "polly";
+
+// Pause (ms) between schedule detail page requests, to be polite to the site
+pub const SCHEDULE_DELAY_MS: u64 = 300;
 
 // Concurrency settings for async tasks
 pub const CONCURRENCY_FETCH: usize = 10;
 pub const CONCURRENCY_SNAP: usize = 4;
 
-// OSRM chunk size (number of stops per request)
-pub const OSRM_CHUNK_SIZE: usize = 120;
+// Default TAGO request rate limit (requests per second, shared by all fetch tasks)
+pub const TAGO_REQUESTS_PER_SEC: f64 = 10.0;
+
+// TAGO list page size (numOfRows); larger lists are fetched page by page
+pub const TAGO_PAGE_SIZE: usize = 1000;
+
+// OSRM chunk size (number of stops per request; osrm-routed allows at most 100 for /match)
+pub const OSRM_CHUNK_SIZE: usize = 100;
+
+// Max bytes of coordinates (and radiuses) in one OSRM request URL, leaving room
+// for the base URL and options under the common 8 KiB request line limit
+pub const OSRM_MAX_COORDS_LEN: usize = 7000;
+
+// Smallest chunk (stops) a failed snapping request is split down to before its
+// stops fall back to straight lines
+pub const OSRM_MIN_CHUNK_SIZE: usize = 5;
+
+// Kakao multi-waypoint directions limit (waypoints between origin and destination)
+pub const KAKAO_MAX_WAYPOINTS: usize = 30;
+
+// Max distance (meters) OSRM /match may move a stop onto the road network
+pub const OSRM_MATCH_RADIUS_M: f64 = 50.0;
+
+// Days a cached OSRM response stays valid
+pub const OSRM_CACHE_TTL_DAYS: u64 = 7;
+
+// OSRM match confidence below which a leg is reported as a doubtful match
+pub const OSRM_LOW_CONFIDENCE: f64 = 0.5;
+
+// Max distance (meters) a drifted stop is moved onto its road corridor
+pub const SANITIZE_SNAP_M: f64 = 90.0;
+
+// Max distance (meters) from a stop to a pass of the route line considered
+// when mapping stops onto the geometry
+pub const STOP_MAPPING_RADIUS_M: f64 = 150.0;
+
+// Stop bearings follow the line this far (meters) before and after the stop
+pub const STOP_BEARING_REACH_M: f64 = 20.0;
+
+// Vertex spacing (meters) the lines are densified to for the Fréchet distance
+// of the snap quality metrics
+pub const FRECHET_STEP_M: f64 = 25.0;
+
+// Geometry anomaly thresholds: how far (meters) a single vertex may stick out
+// of the line, and the turn (degrees) counted as doubling back
+pub const ANOMALY_SPIKE_M: f64 = 200.0;
+pub const ANOMALY_UTURN_DEG: f64 = 170.0;
+
+// Douglas-Peucker tolerance (meters) of `all_routes_simplified.geojson`
+pub const ALL_ROUTES_SIMPLIFY_M: f64 = 30.0;
+
+// Route overlap analysis: max distance (meters) between two lines counted as a
+// shared corridor, and the spacing (meters) lines are resampled at to measure it
+pub const OVERLAP_TOLERANCE_M: f64 = 25.0;
+pub const OVERLAP_STEP_M: f64 = 10.0;
+
+// Max distance (meters) between first and last stop for a route to close as a loop
+pub const LOOP_CLOSURE_M: f64 = 100.0;
+
+// Transfers between nearby stops: default max walking distance (meters), and
+// the walking speed used to estimate walking times without OSRM (~4.3 km/h)
+pub const TRANSFER_MAX_WALK_M: f64 = 300.0;
+pub const WALK_SPEED_MPS: f64 = 1.2;
+
+// Average bus speed used to estimate travel times where OSRM gave none (~20 km/h)
+pub const FALLBACK_BUS_SPEED_MPS: f64 = 5.5;
+
+// Trip chaining into blocks: the shortest and longest layover (minutes) of a
+// bus at a terminus between two trips of the same vehicle
+pub const BLOCK_MIN_LAYOVER_MIN: u32 = 5;
+pub const BLOCK_MAX_LAYOVER_MIN: u32 = 60;
+
+// Output schema versions (bump when the file structure changes incompatibly,
+// and add the corresponding step to `migrate`)
+pub const SCHEDULE_SCHEMA_VERSION: u32 = 1;
+pub const ROUTE_MAP_SCHEMA_VERSION: u32 = 1;
+
+// Shared HTTP client settings (seconds)
+pub const HTTP_TIMEOUT_SECS: u64 = 30;
+pub const HTTP_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const HTTP_POOL_IDLE_SECS: u64 = 90;
+
+// Retry policy for TAGO/OSRM requests: per-request attempts, backoff bounds (ms),
+// and the total number of retries allowed per run
+pub const HTTP_MAX_RETRIES: u32 = 4;
+pub const HTTP_RETRY_BASE_MS: u64 = 500;
+pub const HTTP_RETRY_MAX_MS: u64 = 30_000;
+pub const HTTP_RETRY_BUDGET: u32 = 500;
@@ -0,0 +1,184 @@
+//! Process Exit Codes
+//!
+//! Most failures exit with status 1. Failures that schedulers (CI, cron) may
+//! want to handle differently, such as the upstream site having changed its
+//! markup, are wrapped in an `ExitError` so `main` can exit with a distinct code.
+//! Network errors that abort a run (an upstream that cannot be reached at all)
+//! are recognized by their `reqwest::Error` source.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use reqwest::StatusCode;
+
+use crate::tago::error::TagoError;
+
+/// Failure kinds with their own process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Invalid settings, or a required key or option missing
+    Config,
+    /// The configured selectors no longer match the upstream HTML
+    SelectorCheck,
+    /// The TAGO service key was rejected
+    TagoAuth,
+    /// The TAGO daily request quota is used up
+    TagoQuota,
+    /// Another run holds the lock on the output directory
+    Locked,
+    /// An upstream could not be reached (connection failure or timeout)
+    Unreachable,
+    /// More items failed than `--fail-on-partial` allows
+    PartialFailure,
+    /// Every item of the run failed
+    TotalFailure,
+    /// Stopped by Ctrl+C after saving partial results
+    Interrupted,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Config => 2,
+            Self::SelectorCheck => 3,
+            Self::TagoAuth => 4,
+            Self::TagoQuota => 5,
+            Self::Locked => 6,
+            Self::Unreachable => 7,
+            Self::PartialFailure => 8,
+            Self::TotalFailure => 9,
+            Self::Interrupted => 130,
+        }
+    }
+}
+
+/// An error that makes the process exit with a specific status.
+#[derive(Debug)]
+pub struct ExitError {
+    pub status: ExitStatus,
+    pub message: String,
+}
+
+impl ExitError {
+    pub fn new(status: ExitStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ExitError {}
+
+/// Error classes of the route and schedule pipelines.
+///
+/// They travel inside `anyhow::Error`, under whatever context callers add, so
+/// code that reacts to the class of a failure (retries, failure manifests,
+/// library callers) finds it with [`PipelineError::find`].
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    /// An upstream answered with an error status
+    #[error("{upstream} returned HTTP status {status}")]
+    UpstreamHttp {
+        upstream: String,
+        status: StatusCode,
+    },
+    /// An upstream response lacks what it should contain (a JSON field, an
+    /// HTML table), most likely after an API or page layout change
+    #[error("Unexpected response from {upstream}: {detail}")]
+    UpstreamSchema { upstream: String, detail: String },
+    /// A page was parsed without errors, but yielded nothing
+    #[error("0 {what} parsed")]
+    ParseEmpty { what: &'static str },
+    /// A routing backend could not snap the stops
+    #[error("{backend} could not snap the stops: {reason}")]
+    SnapFailed {
+        backend: &'static str,
+        reason: String,
+    },
+    /// Reading or writing a local file failed
+    #[error("{op} {path:?}")]
+    Io {
+        op: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl PipelineError {
+    pub fn schema(upstream: &str, detail: impl fmt::Display) -> Self {
+        Self::UpstreamSchema {
+            upstream: upstream.to_string(),
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn snap(backend: &'static str, reason: impl fmt::Display) -> Self {
+        Self::SnapFailed {
+            backend,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn io(op: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::Io {
+            op,
+            path: path.into(),
+            source,
+        }
+    }
+
+    /// Name of the error class, as recorded in failure manifests.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::UpstreamHttp { .. } => "upstreamHttp",
+            Self::UpstreamSchema { .. } => "upstreamSchema",
+            Self::ParseEmpty { .. } => "parseEmpty",
+            Self::SnapFailed { .. } => "snapFailed",
+            Self::Io { .. } => "io",
+        }
+    }
+
+    /// Failures that may go away on their own: rate limiting and server errors.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::UpstreamHttp { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            _ => false,
+        }
+    }
+
+    /// The pipeline error in the chain of `err`, if any.
+    pub fn find(err: &anyhow::Error) -> Option<&Self> {
+        err.chain().find_map(|e| e.downcast_ref::<Self>())
+    }
+}
+
+/// Exit code for an error returned from a subcommand.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|e| {
+            e.downcast_ref::<ExitError>()
+                .map(|e| e.status)
+                .or_else(|| e.downcast_ref::<TagoError>()?.exit_status())
+                .or_else(|| {
+                    let e = e.downcast_ref::<reqwest::Error>()?;
+                    (e.is_connect() || e.is_timeout()).then_some(ExitStatus::Unreachable)
+                })
+        })
+        .map_or(1, |status| status.code())
+}
+
+/// A configuration error, exiting with [`ExitStatus::Config`].
+pub fn config_error(message: impl Into<String>) -> anyhow::Error {
+    ExitError::new(ExitStatus::Config, message).into()
+}
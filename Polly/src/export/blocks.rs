@@ -0,0 +1,252 @@
+//! Block Assignment
+//!
+//! Chains the trips of a route into blocks (vehicle runs): a bus that ends a
+//! trip at a terminus takes the next trip leaving from there after a layover
+//! between the minimum and maximum, which for a two-way route is a trip in
+//! the opposite direction. Among the buses waiting, the one that arrived
+//! first leaves first. Trips no bus is waiting for start a new block, so the
+//! number of blocks estimates the vehicles the timetable needs.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::export::feed_check::parse_hms;
+use crate::export::model::{StopTime, TripRow, VehicleEstimate};
+
+/// A trip, by its first and last stop time
+struct Trip<'a> {
+    /// Index into the trips
+    index: usize,
+    start: u32,
+    end: u32,
+    first_stop: &'a str,
+    last_stop: &'a str,
+}
+
+/// A vehicle run being built
+struct Block<'a> {
+    last_stop: &'a str,
+    /// Start of the first trip and end of the last, to count the blocks in
+    /// service at once
+    span: (u32, u32),
+}
+
+/// Sets the `block_id` of every trip, from the stop times in `rows`, with
+/// layovers of `min_layover_secs` to `max_layover_secs`, and returns the
+/// vehicles needed per route and day type.
+pub fn assign_blocks(
+    trips: &mut [TripRow],
+    rows: &[StopTime],
+    min_layover_secs: u32,
+    max_layover_secs: u32,
+) -> Vec<VehicleEstimate> {
+    // Rows of a trip are consecutive, in stop order.
+    let mut ends: HashMap<&str, (&StopTime, &StopTime)> = HashMap::new();
+    let mut start = 0;
+    for i in 1..=rows.len() {
+        if i < rows.len() && rows[i].trip_id == rows[start].trip_id {
+            continue;
+        }
+        ends.insert(&rows[start].trip_id, (&rows[start], &rows[i - 1]));
+        start = i;
+    }
+
+    let mut groups: BTreeMap<(&str, &str, &str), Vec<Trip>> = BTreeMap::new();
+    for (index, trip) in trips.iter().enumerate() {
+        let Some(&(first, last)) = ends.get(trip.trip_id.as_str()) else {
+            continue;
+        };
+        if let (Some(begin), Some(end)) = (
+            parse_hms(&first.departure_time),
+            parse_hms(&last.arrival_time),
+        ) {
+            groups
+                .entry((&first.route, &trip.route_id, &trip.service_id))
+                .or_default()
+                .push(Trip {
+                    index,
+                    start: begin,
+                    end,
+                    first_stop: &first.stop_id,
+                    last_stop: &last.stop_id,
+                });
+        }
+    }
+
+    let mut block_ids: Vec<(usize, String)> = Vec::new();
+    let mut estimates = Vec::new();
+    for ((route, route_id, day_type), mut group) in groups {
+        group.sort_by_key(|t| t.start);
+        let mut blocks: Vec<Block> = Vec::new();
+        for trip in &group {
+            let waiting = blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| {
+                    b.last_stop == trip.first_stop
+                        && trip.start.checked_sub(b.span.1).is_some_and(|layover| {
+                            (min_layover_secs..=max_layover_secs).contains(&layover)
+                        })
+                })
+                .min_by_key(|(_, b)| b.span.1)
+                .map(|(i, _)| i);
+            let index = match waiting {
+                Some(i) => {
+                    let block = &mut blocks[i];
+                    block.last_stop = trip.last_stop;
+                    block.span.1 = trip.end;
+                    i
+                }
+                None => {
+                    blocks.push(Block {
+                        last_stop: trip.last_stop,
+                        span: (trip.start, trip.end),
+                    });
+                    blocks.len() - 1
+                }
+            };
+            block_ids.push((
+                trip.index,
+                format!("{}_{}_b{}", route_id, day_type, index + 1),
+            ));
+        }
+
+        let peak_vehicles = blocks
+            .iter()
+            .map(|b| {
+                blocks
+                    .iter()
+                    .filter(|other| other.span.0 <= b.span.0 && b.span.0 <= other.span.1)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+        estimates.push(VehicleEstimate {
+            route: route.to_string(),
+            route_id: route_id.to_string(),
+            day_type: day_type.to_string(),
+            trips: group.len(),
+            vehicles: blocks.len(),
+            peak_vehicles,
+        });
+    }
+
+    for (index, block_id) in block_ids {
+        trips[index].block_id = Some(block_id);
+    }
+    estimates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::stop_times::format_hms;
+
+    const MIN: u32 = 60;
+
+    /// A trip from `from` to `to` between two times given in minutes.
+    fn trip(id: &str, from: &str, to: &str, start: u32, end: u32) -> (TripRow, Vec<StopTime>) {
+        let trip = TripRow {
+            route_id: "WJB1".to_string(),
+            service_id: "weekday".to_string(),
+            trip_id: id.to_string(),
+            direction_id: usize::from(from == "B"),
+            shape_id: format!("WJB1_{}", usize::from(from == "B")),
+            block_id: None,
+        };
+        let row = |seq: usize, stop: &str, time: u32| StopTime {
+            trip_id: id.to_string(),
+            route: "34".to_string(),
+            route_id: "WJB1".to_string(),
+            day_type: "weekday".to_string(),
+            direction: from.to_string(),
+            stop_sequence: seq,
+            stop_id: stop.to_string(),
+            stop_name: stop.to_string(),
+            arrival_time: format_hms(time * MIN),
+            departure_time: format_hms(time * MIN),
+            shape_dist_traveled: 0.0,
+        };
+        (trip, vec![row(1, from, start), row(2, to, end)])
+    }
+
+    fn chain(specs: &[(&str, &str, &str, u32, u32)]) -> (Vec<TripRow>, VehicleEstimate) {
+        let (mut trips, rows): (Vec<TripRow>, Vec<Vec<StopTime>>) = specs
+            .iter()
+            .map(|&(id, from, to, start, end)| trip(id, from, to, start, end))
+            .unzip();
+        let rows: Vec<StopTime> = rows.into_iter().flatten().collect();
+        let mut estimates = assign_blocks(&mut trips, &rows, 5 * MIN, 60 * MIN);
+        assert_eq!(estimates.len(), 1);
+        (trips, estimates.remove(0))
+    }
+
+    fn blocks(trips: &[TripRow]) -> Vec<&str> {
+        trips
+            .iter()
+            .map(|t| t.block_id.as_deref().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn chains_trips_in_opposite_directions() {
+        let (trips, estimate) = chain(&[
+            ("t1", "A", "B", 360, 390),
+            ("t2", "B", "A", 400, 430),
+            ("t3", "A", "B", 440, 470),
+        ]);
+        assert_eq!(blocks(&trips), ["WJB1_weekday_b1"; 3]);
+        assert_eq!((estimate.trips, estimate.vehicles), (3, 1));
+    }
+
+    #[test]
+    fn same_direction_trips_need_another_bus() {
+        // The first bus ends at B, so it cannot leave from A again.
+        let (trips, estimate) = chain(&[("t1", "A", "B", 360, 390), ("t2", "A", "B", 400, 430)]);
+        assert_eq!(blocks(&trips), ["WJB1_weekday_b1", "WJB1_weekday_b2"]);
+        assert_eq!(estimate.vehicles, 2);
+    }
+
+    #[test]
+    fn layovers_outside_the_limits_start_a_new_block() {
+        // 2 minutes is too short, 70 minutes too long; 5 and 60 are allowed.
+        let (_, short) = chain(&[("t1", "A", "B", 360, 390), ("t2", "B", "A", 392, 420)]);
+        assert_eq!(short.vehicles, 2);
+        let (_, long) = chain(&[("t1", "A", "B", 360, 390), ("t2", "B", "A", 460, 490)]);
+        assert_eq!(long.vehicles, 2);
+        let (_, shortest) = chain(&[("t1", "A", "B", 360, 390), ("t2", "B", "A", 395, 420)]);
+        assert_eq!(shortest.vehicles, 1);
+        let (_, longest) = chain(&[("t1", "A", "B", 360, 390), ("t2", "B", "A", 450, 480)]);
+        assert_eq!(longest.vehicles, 1);
+    }
+
+    #[test]
+    fn first_bus_to_arrive_leaves_first() {
+        let (trips, estimate) = chain(&[
+            ("t1", "A", "B", 360, 390),
+            ("t2", "A", "B", 370, 400),
+            ("t3", "B", "A", 410, 440),
+            ("t4", "B", "A", 415, 445),
+        ]);
+        assert_eq!(
+            blocks(&trips),
+            [
+                "WJB1_weekday_b1",
+                "WJB1_weekday_b2",
+                "WJB1_weekday_b1",
+                "WJB1_weekday_b2"
+            ]
+        );
+        assert_eq!((estimate.vehicles, estimate.peak_vehicles), (2, 2));
+    }
+
+    #[test]
+    fn peak_counts_blocks_in_service_at_once() {
+        // Three buses, but the third only starts after the first has ended.
+        let (_, estimate) = chain(&[
+            ("t1", "A", "B", 360, 390),
+            ("t2", "A", "B", 370, 400),
+            ("t3", "A", "B", 500, 530),
+        ]);
+        assert_eq!((estimate.vehicles, estimate.peak_vehicles), (3, 2));
+    }
+}
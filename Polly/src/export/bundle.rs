@@ -0,0 +1,212 @@
+//! Route Bundle Export
+//!
+//! Packs the derived routes and merged schedules into one protobuf message,
+//! as defined in `proto/bundle.proto`. The messages are declared with
+//! `prost` derives instead of generated at build time, so building does not
+//! need `protoc`; keep the tags in sync with the `.proto` file.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::export::flatten_schedule;
+use crate::route::model::{RouteFeature, SnapQuality};
+
+/// Version of the bundle format, stored as `Bundle.schema_version`
+pub const BUNDLE_SCHEMA_VERSION: u32 = 1;
+
+/// Scale of the integer coordinates
+const COORD_SCALE: f64 = 1e6;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Bundle {
+    #[prost(uint32, tag = "1")]
+    pub schema_version: u32,
+    #[prost(string, tag = "2")]
+    pub last_updated: String,
+    #[prost(message, repeated, tag = "3")]
+    pub routes: Vec<Route>,
+    #[prost(message, repeated, tag = "4")]
+    pub schedules: Vec<Schedule>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Route {
+    #[prost(string, tag = "1")]
+    pub route_id: String,
+    #[prost(string, tag = "2")]
+    pub route_no: String,
+    #[prost(bool, tag = "3")]
+    pub is_circular: bool,
+    /// Delta-encoded [lon, lat] pairs in E6
+    #[prost(sint32, repeated, tag = "4")]
+    pub coords: Vec<i32>,
+    #[prost(message, repeated, tag = "5")]
+    pub stops: Vec<Stop>,
+    #[prost(uint32, optional, tag = "6")]
+    pub turn_idx: Option<u32>,
+    #[prost(float, tag = "7")]
+    pub total_dist: f32,
+    #[prost(bool, tag = "8")]
+    pub degraded: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Stop {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(sint32, tag = "3")]
+    pub ord: i32,
+    #[prost(sint32, tag = "4")]
+    pub up_down: i32,
+    #[prost(uint32, tag = "5")]
+    pub coord_idx: u32,
+    #[prost(float, tag = "6")]
+    pub dist: f32,
+    #[prost(float, optional, tag = "7")]
+    pub bearing: Option<f32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Schedule {
+    #[prost(string, tag = "1")]
+    pub route_no: String,
+    #[prost(string, tag = "2")]
+    pub route_name: String,
+    #[prost(bool, tag = "3")]
+    pub circular: bool,
+    #[prost(string, repeated, tag = "4")]
+    pub directions: Vec<String>,
+    #[prost(string, repeated, tag = "5")]
+    pub notes: Vec<String>,
+    #[prost(message, repeated, tag = "6")]
+    pub trips: Vec<Trips>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Trips {
+    #[prost(string, tag = "1")]
+    pub day_type: String,
+    #[prost(uint32, tag = "2")]
+    pub direction: u32,
+    #[prost(uint32, repeated, tag = "3")]
+    pub departures: Vec<u32>,
+    #[prost(uint32, repeated, tag = "4")]
+    pub notes: Vec<u32>,
+}
+
+/// Converts a derived route, delta-encoding its line in E6.
+pub fn encode_route(feature: &RouteFeature) -> Route {
+    let props = &feature.properties;
+
+    let mut coords = Vec::with_capacity(feature.geometry.coordinates.len() * 2);
+    let mut prev = [0i32; 2];
+    for c in &feature.geometry.coordinates {
+        let lon = (c[0] * COORD_SCALE).round() as i32;
+        let lat = (c[1] * COORD_SCALE).round() as i32;
+        coords.push(lon - prev[0]);
+        coords.push(lat - prev[1]);
+        prev = [lon, lat];
+    }
+
+    let stops = props
+        .stops
+        .iter()
+        .enumerate()
+        .map(|(i, stop)| Stop {
+            id: stop.id.clone(),
+            name: stop.name.clone(),
+            ord: stop.ord as i32,
+            up_down: stop.up_down as i32,
+            coord_idx: props.indices.stop_to_coord.get(i).copied().unwrap_or(0) as u32,
+            dist: props.indices.stop_dist.get(i).copied().unwrap_or(0.0) as f32,
+            bearing: stop.bearing.map(|b| b as f32),
+        })
+        .collect();
+
+    Route {
+        route_id: feature.id.clone(),
+        route_no: props.route_no.clone(),
+        is_circular: props.is_circular,
+        coords,
+        stops,
+        turn_idx: props.indices.turn_idx.map(|i| i as u32),
+        total_dist: props.meta.total_dist as f32,
+        degraded: props.snap_quality == SnapQuality::Degraded,
+    }
+}
+
+/// Converts a merged schedule, grouping its departures by day type and
+/// direction.
+pub fn encode_schedule(route: &str, data: &Value) -> Schedule {
+    let mut directions: Vec<String> = data["directions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|d| d.as_str().map(str::to_string))
+        .collect();
+    let notes: Vec<String> = data["notes"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(_, n)| n.as_str().map(str::to_string))
+        .collect();
+
+    // (day type, direction index) -> (minutes, note index) per departure
+    let mut groups: BTreeMap<(String, u32), Vec<(u32, u32)>> = BTreeMap::new();
+    for dep in flatten_schedule(route, data) {
+        let Some(minutes) = parse_minutes(&dep.time) else {
+            continue;
+        };
+        let direction = match directions.iter().position(|d| *d == dep.direction) {
+            Some(i) => i,
+            None => {
+                directions.push(dep.direction.clone());
+                directions.len() - 1
+            }
+        };
+        let note = notes
+            .iter()
+            .position(|n| !dep.note.is_empty() && *n == dep.note)
+            .map_or(0, |i| i as u32 + 1);
+        groups
+            .entry((dep.day_type, direction as u32))
+            .or_default()
+            .push((minutes, note));
+    }
+
+    let trips = groups
+        .into_iter()
+        .map(|((day_type, direction), mut departures)| {
+            departures.sort_unstable();
+            let has_notes = departures.iter().any(|&(_, note)| note != 0);
+            Trips {
+                day_type,
+                direction,
+                departures: departures.iter().map(|&(m, _)| m).collect(),
+                notes: if has_notes {
+                    departures.iter().map(|&(_, n)| n).collect()
+                } else {
+                    Vec::new()
+                },
+            }
+        })
+        .collect();
+
+    Schedule {
+        route_no: route.to_string(),
+        route_name: data["routeName"].as_str().unwrap_or(route).to_string(),
+        circular: data["circular"].as_bool().unwrap_or(false),
+        directions,
+        notes,
+        trips,
+    }
+}
+
+/// Parses "HH:MM" into minutes after midnight.
+fn parse_minutes(time: &str) -> Option<u32> {
+    let (h, m) = time.split_once(':')?;
+    Some(h.parse::<u32>().ok()? * 60 + m.parse::<u32>().ok()?)
+}
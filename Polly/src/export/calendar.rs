@@ -0,0 +1,131 @@
+//! Service Calendar Generation
+//!
+//! Maps the schedule day types onto GTFS service calendars: `calendar.txt`
+//! gives the weekdays each day type runs on, and `calendar_dates.txt` moves
+//! public holidays (including substitute holidays) that fall on weekdays
+//! from the weekday service to the weekend service, as buses run their
+//! holiday timetable on them.
+
+use std::collections::BTreeSet;
+
+use anyhow::{Result, bail};
+use chrono::NaiveDate;
+use tracing::debug;
+
+use crate::export::feed_check::SERVICE_DAYS;
+use crate::export::model::{ServiceCalendar, ServiceException};
+use crate::utils::holidays::{Holidays, is_weekend};
+
+/// Builds the calendar rows of `day_types` for the dates from `start` to
+/// `end`. Fails on day types that map to no service days.
+pub fn build_calendar(
+    day_types: &BTreeSet<String>,
+    start: NaiveDate,
+    end: NaiveDate,
+    holidays: &Holidays,
+) -> Result<(Vec<ServiceCalendar>, Vec<ServiceException>)> {
+    let mut calendar = Vec::new();
+    let mut exceptions = Vec::new();
+
+    let weekday_holidays: Vec<NaiveDate> = holidays
+        .iter()
+        .filter(|(date, _)| (start..=end).contains(date) && !is_weekend(*date))
+        .map(|(date, name)| {
+            debug!("{} ({}) runs the holiday timetable", date, name);
+            date
+        })
+        .collect();
+
+    for day_type in day_types {
+        let Some(&(_, weekdays, weekends)) =
+            SERVICE_DAYS.iter().find(|(name, ..)| name == day_type)
+        else {
+            bail!(
+                "Day type {:?} maps to no service days (known: {})",
+                day_type,
+                SERVICE_DAYS
+                    .iter()
+                    .map(|(name, ..)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+
+        let (weekdays, weekends) = (u8::from(weekdays), u8::from(weekends));
+        calendar.push(ServiceCalendar {
+            service_id: day_type.clone(),
+            monday: weekdays,
+            tuesday: weekdays,
+            wednesday: weekdays,
+            thursday: weekdays,
+            friday: weekdays,
+            saturday: weekends,
+            sunday: weekends,
+            start_date: gtfs_date(start),
+            end_date: gtfs_date(end),
+        });
+
+        // Services running every day are unaffected by holidays.
+        if weekdays == weekends {
+            continue;
+        }
+        let exception_type = if weekends == 1 { 1 } else { 2 };
+        exceptions.extend(weekday_holidays.iter().map(|date| ServiceException {
+            service_id: day_type.clone(),
+            date: gtfs_date(*date),
+            exception_type,
+        }));
+    }
+
+    Ok((calendar, exceptions))
+}
+
+fn gtfs_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(day_types: &[&str]) -> Result<(Vec<ServiceCalendar>, Vec<ServiceException>)> {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 12, 31).unwrap();
+        let holidays = Holidays::between(start, end, None)?;
+        let day_types = day_types.iter().map(|d| d.to_string()).collect();
+        build_calendar(&day_types, start, end, &holidays)
+    }
+
+    #[test]
+    fn weekday_holidays_run_the_weekend_service() {
+        let (calendar, exceptions) = build(&["weekday", "weekend", "general"]).unwrap();
+        assert_eq!(calendar.len(), 3);
+        assert_eq!(
+            (
+                calendar[0].service_id.as_str(),
+                calendar[0].monday,
+                calendar[0].sunday
+            ),
+            ("general", 1, 1)
+        );
+
+        let on = |date: &str| -> Vec<(&str, u8)> {
+            exceptions
+                .iter()
+                .filter(|e| e.date == date)
+                .map(|e| (e.service_id.as_str(), e.exception_type))
+                .collect()
+        };
+        // Substitute holiday for Independence Movement Day, a Monday
+        assert_eq!(on("20250303"), [("weekday", 2), ("weekend", 1)]);
+        // Independence Movement Day itself was a Saturday.
+        assert!(on("20250301").is_empty());
+        // "general" runs every day anyway.
+        assert!(exceptions.iter().all(|e| e.service_id != "general"));
+    }
+
+    #[test]
+    fn unknown_day_types_are_an_error() {
+        assert!(build(&["holiday"]).is_err());
+    }
+}
@@ -0,0 +1,795 @@
+//! Feed Validation
+//!
+//! Checks the exported tables before they are written, the way GTFS
+//! consumers such as OpenTripPlanner would: every stop time must refer to a
+//! known route and one of its stops, each trip must visit at least two stops
+//! in order without going back in time, and each day type must map onto the
+//! service calendar. Trips, frequencies, calendars, and shapes are checked
+//! for the values consumers rely on, and a whole feed for the references
+//! between its tables. Errors block the export;
+//! gaps in the calendar (no weekend service at all, say) are only warned
+//! about.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use chrono::NaiveDate;
+
+use crate::export::model::{
+    Frequency, RouteRow, ServiceCalendar, ServiceException, ShapePoint, StopRow, StopTime, TripRow,
+};
+use crate::route::model::RouteFeature;
+
+/// Day types of the schedules, and whether each runs on weekdays and weekends
+/// (public holidays count as weekends)
+pub const SERVICE_DAYS: &[(&str, bool, bool)] = &[
+    ("weekday", true, false),
+    ("weekend", false, true),
+    ("general", true, true),
+];
+
+/// Errors list problems consumers would reject the data for.
+#[derive(Debug, Default)]
+pub struct FeedIssues {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl FeedIssues {
+    /// Adds the issues found by another check.
+    pub fn merge(&mut self, other: FeedIssues) {
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+    }
+}
+
+pub fn check_stop_times(rows: &[StopTime], routes: &[RouteFeature]) -> FeedIssues {
+    let mut issues = FeedIssues::default();
+
+    let stops_of: HashMap<&str, HashSet<&str>> = routes
+        .iter()
+        .map(|f| {
+            let stops = f.properties.stops.iter().map(|s| s.id.as_str()).collect();
+            (f.id.as_str(), stops)
+        })
+        .collect();
+
+    // Rows of a trip are consecutive, in stop order.
+    let mut trips: Vec<&[StopTime]> = Vec::new();
+    let mut start = 0;
+    for i in 1..=rows.len() {
+        if i == rows.len() || rows[i].trip_id != rows[start].trip_id {
+            trips.push(&rows[start..i]);
+            start = i;
+        }
+    }
+
+    let mut seen_trips: HashSet<&str> = HashSet::new();
+    let mut day_types: BTreeSet<&str> = BTreeSet::new();
+    for trip in trips {
+        let first = &trip[0];
+        let id = first.trip_id.as_str();
+        if !seen_trips.insert(id) {
+            issues
+                .errors
+                .push(format!("{}: rows of the trip are not contiguous", id));
+        }
+        day_types.insert(first.day_type.as_str());
+        if trip.len() < 2 {
+            issues.errors.push(format!("{}: fewer than two stops", id));
+        }
+
+        // Referential integrity
+        match stops_of.get(first.route_id.as_str()) {
+            None => issues
+                .errors
+                .push(format!("{}: unknown route {}", id, first.route_id)),
+            Some(stops) => {
+                for row in trip.iter().filter(|r| !stops.contains(r.stop_id.as_str())) {
+                    issues.errors.push(format!(
+                        "{}: stop {:?} is not a stop of route {}",
+                        id, row.stop_id, first.route_id
+                    ));
+                }
+            }
+        }
+        if trip
+            .iter()
+            .any(|r| r.route_id != first.route_id || r.day_type != first.day_type)
+        {
+            issues
+                .errors
+                .push(format!("{}: rows disagree on the route or day type", id));
+        }
+
+        // Monotonicity
+        let mut previous: Option<(usize, u32)> = None;
+        for row in trip {
+            let (Some(arrival), Some(departure)) =
+                (parse_hms(&row.arrival_time), parse_hms(&row.departure_time))
+            else {
+                issues.errors.push(format!(
+                    "{}: invalid time at stop {}",
+                    id, row.stop_sequence
+                ));
+                break;
+            };
+            if departure < arrival {
+                issues.errors.push(format!(
+                    "{}: departs before arriving at stop {}",
+                    id, row.stop_sequence
+                ));
+            }
+            if let Some((sequence, time)) = previous {
+                if row.stop_sequence <= sequence {
+                    issues.errors.push(format!(
+                        "{}: stop_sequence {} does not increase after {}",
+                        id, row.stop_sequence, sequence
+                    ));
+                }
+                if arrival < time {
+                    issues.errors.push(format!(
+                        "{}: arrives at stop {} ({}) before leaving the previous one",
+                        id, row.stop_sequence, row.arrival_time
+                    ));
+                }
+            }
+            previous = Some((row.stop_sequence, departure));
+        }
+    }
+
+    // Calendar coverage
+    let mut weekdays = false;
+    let mut weekends = false;
+    for day_type in &day_types {
+        match SERVICE_DAYS.iter().find(|(name, ..)| name == day_type) {
+            Some((_, on_weekdays, on_weekends)) => {
+                weekdays |= on_weekdays;
+                weekends |= on_weekends;
+            }
+            None => issues.errors.push(format!(
+                "Day type {:?} maps to no service days (known: {})",
+                day_type,
+                SERVICE_DAYS
+                    .iter()
+                    .map(|(name, ..)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+    if !rows.is_empty() {
+        if !weekdays {
+            issues.warnings.push("No trips run on weekdays".to_string());
+        }
+        if !weekends {
+            issues.warnings.push("No trips run on weekends".to_string());
+        }
+    }
+
+    issues
+}
+
+/// Checks that every trip has stop times, and the stop times of every trip
+/// agree with its route and service.
+pub fn check_trips(trips: &[TripRow], rows: &[StopTime]) -> FeedIssues {
+    let mut issues = FeedIssues::default();
+
+    let mut by_id: HashMap<&str, &TripRow> = HashMap::new();
+    for trip in trips {
+        if by_id.insert(&trip.trip_id, trip).is_some() {
+            issues
+                .errors
+                .push(format!("{}: listed twice in trips.txt", trip.trip_id));
+        }
+    }
+
+    let mut timed: HashSet<&str> = HashSet::new();
+    for row in rows {
+        if !timed.insert(&row.trip_id) {
+            continue;
+        }
+        match by_id.get(row.trip_id.as_str()) {
+            None => issues
+                .errors
+                .push(format!("{}: stop times of an unknown trip", row.trip_id)),
+            Some(trip) if trip.route_id != row.route_id || trip.service_id != row.day_type => {
+                issues.errors.push(format!(
+                    "{}: stop times disagree with the trip's route or service",
+                    row.trip_id
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    for trip in trips {
+        if !timed.contains(trip.trip_id.as_str()) {
+            issues
+                .errors
+                .push(format!("{}: trip has no stop times", trip.trip_id));
+        }
+    }
+
+    issues
+}
+
+/// Checks that frequency entries repeat known trips over valid periods that
+/// do not overlap.
+pub fn check_frequencies(frequencies: &[Frequency], trips: &[TripRow]) -> FeedIssues {
+    let mut issues = FeedIssues::default();
+    let known: HashSet<&str> = trips.iter().map(|t| t.trip_id.as_str()).collect();
+
+    let mut periods: BTreeMap<&str, Vec<(u32, u32)>> = BTreeMap::new();
+    for frequency in frequencies {
+        let id = frequency.trip_id.as_str();
+        if !known.contains(id) {
+            issues
+                .errors
+                .push(format!("{}: frequency of an unknown trip", id));
+        }
+        if frequency.headway_secs == 0 {
+            issues.errors.push(format!("{}: headway of 0 seconds", id));
+        }
+        let (Some(start), Some(end)) = (
+            parse_hms(&frequency.start_time),
+            parse_hms(&frequency.end_time),
+        ) else {
+            issues
+                .errors
+                .push(format!("{}: invalid frequency period", id));
+            continue;
+        };
+        if end <= start {
+            issues.errors.push(format!(
+                "{}: frequency ends at {} before starting at {}",
+                id, frequency.end_time, frequency.start_time
+            ));
+        }
+        periods.entry(id).or_default().push((start, end));
+    }
+    for (id, mut spans) in periods {
+        spans.sort();
+        if spans.windows(2).any(|w| w[1].0 < w[0].1) {
+            issues
+                .errors
+                .push(format!("{}: frequency periods overlap", id));
+        }
+    }
+
+    issues
+}
+
+/// Checks that each service runs over a valid period, and the exceptions
+/// refer to known services on distinct dates.
+pub fn check_calendar(calendar: &[ServiceCalendar], exceptions: &[ServiceException]) -> FeedIssues {
+    let mut issues = FeedIssues::default();
+
+    let mut periods: HashMap<&str, Option<(NaiveDate, NaiveDate)>> = HashMap::new();
+    for service in calendar {
+        let id = service.service_id.as_str();
+        let period = match (gtfs_date(&service.start_date), gtfs_date(&service.end_date)) {
+            (Some(start), Some(end)) if start <= end => Some((start, end)),
+            (Some(_), Some(_)) => {
+                issues
+                    .errors
+                    .push(format!("Service {}: ends before it starts", id));
+                None
+            }
+            _ => {
+                issues
+                    .errors
+                    .push(format!("Service {}: invalid start or end date", id));
+                None
+            }
+        };
+        if periods.insert(id, period).is_some() {
+            issues
+                .errors
+                .push(format!("Service {}: listed twice in calendar.txt", id));
+        }
+
+        let days = [
+            service.monday,
+            service.tuesday,
+            service.wednesday,
+            service.thursday,
+            service.friday,
+            service.saturday,
+            service.sunday,
+        ];
+        if days.iter().any(|&d| d > 1) {
+            issues
+                .errors
+                .push(format!("Service {}: weekday flags must be 0 or 1", id));
+        } else if days.iter().all(|&d| d == 0) {
+            issues
+                .warnings
+                .push(format!("Service {}: runs on no day of the week", id));
+        }
+    }
+
+    let mut seen: HashSet<(&str, &str)> = HashSet::new();
+    for exception in exceptions {
+        let id = exception.service_id.as_str();
+        if !seen.insert((id, &exception.date)) {
+            issues.errors.push(format!(
+                "Service {}: more than one exception on {}",
+                id, exception.date
+            ));
+        }
+        if !matches!(exception.exception_type, 1 | 2) {
+            issues.errors.push(format!(
+                "Service {}: exception type {} on {} is neither 1 nor 2",
+                id, exception.exception_type, exception.date
+            ));
+        }
+        let Some(date) = gtfs_date(&exception.date) else {
+            issues.errors.push(format!(
+                "Service {}: invalid exception date {:?}",
+                id, exception.date
+            ));
+            continue;
+        };
+        match periods.get(id) {
+            None => issues
+                .errors
+                .push(format!("Service {}: exception of an unknown service", id)),
+            Some(Some((start, end))) if !(*start..=*end).contains(&date) => {
+                issues.warnings.push(format!(
+                    "Service {}: exception on {} is outside its period",
+                    id, date
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    issues
+}
+
+/// Checks that the points of each shape are contiguous, with valid
+/// coordinates, and increasing sequence and distance.
+pub fn check_shapes(points: &[ShapePoint]) -> FeedIssues {
+    let mut issues = FeedIssues::default();
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut start = 0;
+    for i in 1..=points.len() {
+        if i < points.len() && points[i].shape_id == points[start].shape_id {
+            continue;
+        }
+        let shape = &points[start..i];
+        let id = shape[0].shape_id.as_str();
+        start = i;
+
+        if !seen.insert(id) {
+            issues
+                .errors
+                .push(format!("{}: points of the shape are not contiguous", id));
+        }
+        if shape.len() < 2 {
+            issues.errors.push(format!("{}: fewer than two points", id));
+        }
+        if shape.iter().any(|p| {
+            !(-90.0..=90.0).contains(&p.shape_pt_lat) || !(-180.0..=180.0).contains(&p.shape_pt_lon)
+        }) {
+            issues
+                .errors
+                .push(format!("{}: coordinates out of range", id));
+        }
+        for w in shape.windows(2) {
+            if w[1].shape_pt_sequence <= w[0].shape_pt_sequence {
+                issues.errors.push(format!(
+                    "{}: shape_pt_sequence {} does not increase after {}",
+                    id, w[1].shape_pt_sequence, w[0].shape_pt_sequence
+                ));
+            }
+            if w[1].shape_dist_traveled < w[0].shape_dist_traveled {
+                issues.errors.push(format!(
+                    "{}: shape_dist_traveled decreases at point {}",
+                    id, w[1].shape_pt_sequence
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+/// Tables of a whole feed, to check their references
+pub struct FeedTables<'a> {
+    pub routes: &'a [RouteRow],
+    pub stops: &'a [StopRow],
+    pub trips: &'a [TripRow],
+    pub stop_times: &'a [StopTime],
+    pub calendar: &'a [ServiceCalendar],
+    pub shapes: &'a [ShapePoint],
+}
+
+/// Checks that every trip refers to a known route, service, and shape, and
+/// every stop time to a known stop, with a `shape_dist_traveled` that does not
+/// decrease along the trip nor exceed the length of the trip's shape.
+/// Services no trip uses are warned about.
+pub fn check_references(feed: &FeedTables) -> FeedIssues {
+    let mut issues = FeedIssues::default();
+
+    let routes: HashSet<&str> = feed.routes.iter().map(|r| r.route_id.as_str()).collect();
+    let services: BTreeSet<&str> = feed
+        .calendar
+        .iter()
+        .map(|s| s.service_id.as_str())
+        .collect();
+    let shapes: HashSet<&str> = feed.shapes.iter().map(|p| p.shape_id.as_str()).collect();
+    for trip in feed.trips {
+        let id = trip.trip_id.as_str();
+        if !routes.contains(trip.route_id.as_str()) {
+            issues.errors.push(format!(
+                "{}: route {} is not in routes.txt",
+                id, trip.route_id
+            ));
+        }
+        if !services.contains(trip.service_id.as_str()) {
+            issues.errors.push(format!(
+                "{}: service {} is not in calendar.txt",
+                id, trip.service_id
+            ));
+        }
+        if !shapes.contains(trip.shape_id.as_str()) {
+            issues.errors.push(format!(
+                "{}: shape {} is not in shapes.txt",
+                id, trip.shape_id
+            ));
+        }
+    }
+
+    let stops: HashSet<&str> = feed.stops.iter().map(|s| s.stop_id.as_str()).collect();
+    let mut missing: BTreeSet<&str> = BTreeSet::new();
+    for row in feed.stop_times {
+        if !stops.contains(row.stop_id.as_str()) && missing.insert(&row.stop_id) {
+            issues.errors.push(format!(
+                "{}: stop {} is not in stops.txt",
+                row.trip_id, row.stop_id
+            ));
+        }
+    }
+
+    // Distances along the shapes
+    let mut shape_ends: HashMap<&str, f64> = HashMap::new();
+    for point in feed.shapes {
+        let end = shape_ends.entry(&point.shape_id).or_insert(0.0);
+        *end = end.max(point.shape_dist_traveled);
+    }
+    let shape_of: HashMap<&str, &str> = feed
+        .trips
+        .iter()
+        .map(|t| (t.trip_id.as_str(), t.shape_id.as_str()))
+        .collect();
+    for trip in feed.stop_times.chunk_by(|a, b| a.trip_id == b.trip_id) {
+        let id = trip[0].trip_id.as_str();
+        for w in trip.windows(2) {
+            if w[1].shape_dist_traveled < w[0].shape_dist_traveled {
+                issues.errors.push(format!(
+                    "{}: shape_dist_traveled decreases at stop {}",
+                    id, w[1].stop_sequence
+                ));
+            }
+        }
+        let end = shape_of
+            .get(id)
+            .and_then(|shape| Some((shape, shape_ends.get(shape)?)));
+        if let Some((shape, &end)) = end
+            && let Some(row) = trip.iter().find(|r| r.shape_dist_traveled > end)
+        {
+            issues.errors.push(format!(
+                "{}: shape_dist_traveled {} at stop {} exceeds the length of shape {} ({})",
+                id, row.shape_dist_traveled, row.stop_sequence, shape, end
+            ));
+        }
+    }
+
+    let used: HashSet<&str> = feed.trips.iter().map(|t| t.service_id.as_str()).collect();
+    for service in services.into_iter().filter(|s| !used.contains(s)) {
+        issues
+            .warnings
+            .push(format!("Service {}: no trip runs on it", service));
+    }
+
+    issues
+}
+
+/// Parses a GTFS "YYYYMMDD" date.
+fn gtfs_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+/// Parses GTFS "HH:MM:SS" (hours may exceed 23) into seconds after midnight.
+pub fn parse_hms(time: &str) -> Option<u32> {
+    let mut parts = time.split(':').map(|p| p.parse::<u32>().ok());
+    let (Some(Some(h)), Some(Some(m)), Some(Some(s)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    (m < 60 && s < 60).then_some(h * 3600 + m * 60 + s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trip(id: &str) -> TripRow {
+        TripRow {
+            route_id: "WJB1".to_string(),
+            service_id: "weekday".to_string(),
+            trip_id: id.to_string(),
+            direction_id: 0,
+            shape_id: "WJB1_0".to_string(),
+            block_id: None,
+        }
+    }
+
+    fn frequency(id: &str, start: &str, end: &str, headway_secs: u32) -> Frequency {
+        Frequency {
+            trip_id: id.to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            headway_secs,
+            exact_times: 1,
+        }
+    }
+
+    fn service(id: &str, weekdays: u8, weekends: u8) -> ServiceCalendar {
+        ServiceCalendar {
+            service_id: id.to_string(),
+            monday: weekdays,
+            tuesday: weekdays,
+            wednesday: weekdays,
+            thursday: weekdays,
+            friday: weekdays,
+            saturday: weekends,
+            sunday: weekends,
+            start_date: "20260101".to_string(),
+            end_date: "20261231".to_string(),
+        }
+    }
+
+    fn exception(id: &str, date: &str, exception_type: u8) -> ServiceException {
+        ServiceException {
+            service_id: id.to_string(),
+            date: date.to_string(),
+            exception_type,
+        }
+    }
+
+    fn point(id: &str, sequence: usize, dist: f64) -> ShapePoint {
+        ShapePoint {
+            shape_id: id.to_string(),
+            shape_pt_lat: 37.34,
+            shape_pt_lon: 127.92,
+            shape_pt_sequence: sequence,
+            shape_dist_traveled: dist,
+        }
+    }
+
+    #[test]
+    fn parses_hms_past_midnight() {
+        assert_eq!(parse_hms("24:10:00"), Some(87000));
+        assert_eq!(parse_hms("06:60:00"), None);
+        assert_eq!(parse_hms("06:00"), None);
+    }
+
+    #[test]
+    fn frequencies_must_refer_to_trips() {
+        let trips = [trip("t1")];
+        let ok = check_frequencies(&[frequency("t1", "06:00:00", "08:00:00", 600)], &trips);
+        assert!(ok.errors.is_empty());
+
+        let bad = check_frequencies(
+            &[
+                frequency("t2", "06:00:00", "08:00:00", 600),
+                frequency("t1", "09:00:00", "08:00:00", 0),
+            ],
+            &trips,
+        );
+        assert_eq!(bad.errors.len(), 3, "{:?}", bad.errors);
+    }
+
+    #[test]
+    fn overlapping_frequencies_are_rejected() {
+        let issues = check_frequencies(
+            &[
+                frequency("t1", "06:00:00", "08:00:00", 600),
+                frequency("t1", "07:30:00", "09:00:00", 900),
+            ],
+            &[trip("t1")],
+        );
+        assert_eq!(issues.errors, ["t1: frequency periods overlap"]);
+    }
+
+    #[test]
+    fn calendar_exceptions_need_a_known_service_and_date() {
+        let calendar = [service("weekday", 1, 0), service("weekend", 0, 1)];
+        let ok = check_calendar(
+            &calendar,
+            &[
+                exception("weekday", "20260302", 2),
+                exception("weekend", "20260302", 1),
+            ],
+        );
+        assert!(ok.errors.is_empty() && ok.warnings.is_empty());
+
+        let bad = check_calendar(
+            &calendar,
+            &[
+                exception("holiday", "20260302", 1),
+                exception("weekday", "20260230", 2),
+                exception("weekend", "20260302", 3),
+                exception("weekend", "20260302", 1),
+            ],
+        );
+        assert_eq!(bad.errors.len(), 4, "{:?}", bad.errors);
+    }
+
+    #[test]
+    fn calendar_periods_must_be_valid() {
+        let mut backwards = service("weekday", 1, 0);
+        backwards.end_date = "20251231".to_string();
+        let issues = check_calendar(&[backwards, service("none", 0, 0)], &[]);
+        assert_eq!(issues.errors, ["Service weekday: ends before it starts"]);
+        assert_eq!(
+            issues.warnings,
+            ["Service none: runs on no day of the week"]
+        );
+    }
+
+    #[test]
+    fn shapes_must_advance() {
+        let ok = check_shapes(&[
+            point("a", 1, 0.0),
+            point("a", 2, 10.5),
+            point("b", 1, 0.0),
+            point("b", 2, 3.0),
+        ]);
+        assert!(ok.errors.is_empty());
+
+        let bad = check_shapes(&[
+            point("a", 1, 0.0),
+            point("a", 1, 10.0),
+            point("a", 3, 5.0),
+            point("b", 1, 0.0),
+            point("a", 4, 20.0),
+        ]);
+        assert_eq!(bad.errors.len(), 5, "{:?}", bad.errors);
+    }
+
+    fn stop_time(trip_id: &str) -> StopTime {
+        StopTime {
+            trip_id: trip_id.to_string(),
+            route: "34".to_string(),
+            route_id: "WJB1".to_string(),
+            day_type: "weekday".to_string(),
+            direction: "a".to_string(),
+            stop_sequence: 1,
+            stop_id: "N1".to_string(),
+            stop_name: "N1".to_string(),
+            arrival_time: "06:00:00".to_string(),
+            departure_time: "06:00:00".to_string(),
+            shape_dist_traveled: 0.0,
+        }
+    }
+
+    #[test]
+    fn trips_and_stop_times_must_match() {
+        let row = stop_time;
+        assert!(check_trips(&[trip("t1")], &[row("t1")]).errors.is_empty());
+
+        let issues = check_trips(&[trip("t1"), trip("t2")], &[row("t1"), row("t3")]);
+        assert_eq!(
+            issues.errors,
+            [
+                "t3: stop times of an unknown trip",
+                "t2: trip has no stop times"
+            ]
+        );
+    }
+
+    #[test]
+    fn feed_tables_must_refer_to_each_other() {
+        let routes = [RouteRow {
+            route_id: "WJB1".to_string(),
+            route_short_name: "34".to_string(),
+            route_type: 3,
+        }];
+        let stops = [StopRow {
+            stop_id: "N1".to_string(),
+            stop_code: None,
+            stop_name: "N1".to_string(),
+            stop_lat: 37.34,
+            stop_lon: 127.92,
+        }];
+        let calendar = [service("weekday", 1, 0), service("weekend", 0, 1)];
+        let shapes = [point("WJB1_0", 1, 0.0), point("WJB1_0", 2, 10.0)];
+        let trips = [trip("t1")];
+        let stop_times = [stop_time("t1")];
+        let tables = FeedTables {
+            routes: &routes,
+            stops: &stops,
+            trips: &trips,
+            stop_times: &stop_times,
+            calendar: &calendar,
+            shapes: &shapes,
+        };
+        let issues = check_references(&tables);
+        assert!(issues.errors.is_empty());
+        assert_eq!(issues.warnings, ["Service weekend: no trip runs on it"]);
+
+        let mut stray = trip("t1");
+        stray.route_id = "WJB2".to_string();
+        stray.service_id = "holiday".to_string();
+        stray.shape_id = "WJB2_0".to_string();
+        let mut elsewhere = stop_time("t1");
+        elsewhere.stop_id = "N9".to_string();
+        let issues = check_references(&FeedTables {
+            trips: &[stray],
+            stop_times: &[elsewhere],
+            ..tables
+        });
+        assert_eq!(
+            issues.errors,
+            [
+                "t1: route WJB2 is not in routes.txt",
+                "t1: service holiday is not in calendar.txt",
+                "t1: shape WJB2_0 is not in shapes.txt",
+                "t1: stop N9 is not in stops.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_distances_must_advance_within_the_shape() {
+        let shapes = [point("WJB1_0", 1, 0.0), point("WJB1_0", 2, 250.0)];
+        let stop_times: Vec<StopTime> = [0.0, 120.0, 90.0, 250.5]
+            .into_iter()
+            .enumerate()
+            .map(|(i, dist)| StopTime {
+                stop_sequence: i + 1,
+                shape_dist_traveled: dist,
+                ..stop_time("t1")
+            })
+            .collect();
+        let tables = FeedTables {
+            routes: &[RouteRow {
+                route_id: "WJB1".to_string(),
+                route_short_name: "34".to_string(),
+                route_type: 3,
+            }],
+            stops: &[StopRow {
+                stop_id: "N1".to_string(),
+                stop_code: None,
+                stop_name: "N1".to_string(),
+                stop_lat: 37.34,
+                stop_lon: 127.92,
+            }],
+            trips: &[trip("t1")],
+            stop_times: &stop_times,
+            calendar: &[service("weekday", 1, 0)],
+            shapes: &shapes,
+        };
+        assert_eq!(
+            check_references(&tables).errors,
+            [
+                "t1: shape_dist_traveled decreases at stop 3",
+                "t1: shape_dist_traveled 250.5 at stop 4 exceeds the length of shape WJB1_0 (250)",
+            ]
+        );
+
+        let within = &stop_times[..2];
+        assert!(
+            check_references(&FeedTables {
+                stop_times: within,
+                ..tables
+            })
+            .errors
+            .is_empty()
+        );
+    }
+}
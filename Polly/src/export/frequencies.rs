@@ -0,0 +1,189 @@
+//! Headway Compression
+//!
+//! Finds runs of trips that leave at regular intervals (e.g., every 15
+//! minutes all day) and replaces each run with one template trip plus a GTFS
+//! `frequencies.txt` entry, instead of enumerating every trip. Trips of a run
+//! share the route, day type, and direction, so their estimated stop times
+//! differ only by the start time.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::export::feed_check::parse_hms;
+use crate::export::model::{Frequency, StopTime, TripRow};
+use crate::export::stop_times::format_hms;
+
+/// Fewest trips worth replacing with a frequency entry
+const MIN_TRIPS: usize = 4;
+
+/// Compresses the regular runs of `rows`, whose intervals stay within
+/// `tolerance_secs` of the run's first interval. Returns the remaining trips,
+/// their rows, and the frequency entries of their template trips.
+pub fn compress_headways(
+    trips: Vec<TripRow>,
+    rows: Vec<StopTime>,
+    tolerance_secs: u32,
+) -> (Vec<TripRow>, Vec<StopTime>, Vec<Frequency>) {
+    // (route ID, day type, direction) -> (start, trip ID) of each trip
+    type TripKey<'a> = (&'a str, &'a str, &'a str);
+    let mut groups: BTreeMap<TripKey, Vec<(u32, &str)>> = BTreeMap::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for row in &rows {
+        if !seen.insert(&row.trip_id) {
+            continue;
+        }
+        let Some(start) = parse_hms(&row.departure_time) else {
+            continue;
+        };
+        groups
+            .entry((&row.route_id, &row.day_type, &row.direction))
+            .or_default()
+            .push((start, &row.trip_id));
+    }
+
+    let mut frequencies = Vec::new();
+    let mut dropped: HashSet<String> = HashSet::new();
+    for mut trips in groups.into_values() {
+        trips.sort();
+        let mut i = 0;
+        while i + 1 < trips.len() {
+            let headway = trips[i + 1].0 - trips[i].0;
+            let mut j = i + 1;
+            while j + 1 < trips.len()
+                && (trips[j + 1].0 - trips[j].0).abs_diff(headway) <= tolerance_secs
+            {
+                j += 1;
+            }
+            let run = &trips[i..=j];
+            if headway == 0 || run.len() < MIN_TRIPS {
+                i += 1;
+                continue;
+            }
+
+            let (first, last) = (run[0].0, run[run.len() - 1].0);
+            let mean = (last - first) / (run.len() as u32 - 1);
+            let exact = run.windows(2).all(|w| w[1].0 - w[0].0 == headway);
+            frequencies.push(Frequency {
+                trip_id: run[0].1.to_string(),
+                start_time: format_hms(first),
+                end_time: format_hms(last + mean),
+                headway_secs: mean,
+                exact_times: u8::from(exact),
+            });
+            dropped.extend(run[1..].iter().map(|(_, id)| id.to_string()));
+            i = j + 1;
+        }
+    }
+
+    let trips = trips
+        .into_iter()
+        .filter(|trip| !dropped.contains(&trip.trip_id))
+        .collect();
+    let rows = rows
+        .into_iter()
+        .filter(|row| !dropped.contains(&row.trip_id))
+        .collect();
+    (trips, rows, frequencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trips of one route, day type, and direction leaving at `starts`
+    /// (minutes after midnight), each with two stops.
+    fn timetable(direction: &str, starts: &[u32]) -> (Vec<TripRow>, Vec<StopTime>) {
+        let mut trips = Vec::new();
+        let mut rows = Vec::new();
+        for &start in starts {
+            let trip_id = format!("34_weekday_{}_{}", direction, start);
+            trips.push(TripRow {
+                route_id: "WJB1".to_string(),
+                service_id: "weekday".to_string(),
+                trip_id: trip_id.clone(),
+                direction_id: 0,
+                shape_id: "WJB1_0".to_string(),
+                block_id: None,
+            });
+            for (seq, offset) in [(1, 0), (2, 5)] {
+                let time = format_hms((start + offset) * 60);
+                rows.push(StopTime {
+                    trip_id: trip_id.clone(),
+                    route: "34".to_string(),
+                    route_id: "WJB1".to_string(),
+                    day_type: "weekday".to_string(),
+                    direction: direction.to_string(),
+                    stop_sequence: seq,
+                    stop_id: format!("N{}", seq),
+                    stop_name: format!("N{}", seq),
+                    arrival_time: time.clone(),
+                    departure_time: time,
+                    shape_dist_traveled: 0.0,
+                });
+            }
+        }
+        (trips, rows)
+    }
+
+    fn compress(starts: &[u32], tolerance_min: u32) -> (Vec<TripRow>, Vec<Frequency>) {
+        let (trips, rows) = timetable("a", starts);
+        let (trips, rows, frequencies) = compress_headways(trips, rows, tolerance_min * 60);
+        // Trips and rows are dropped together.
+        let kept: HashSet<&str> = rows.iter().map(|r| r.trip_id.as_str()).collect();
+        assert_eq!(kept.len(), trips.len());
+        assert!(trips.iter().all(|t| kept.contains(t.trip_id.as_str())));
+        (trips, frequencies)
+    }
+
+    #[test]
+    fn regular_run_becomes_one_frequency() {
+        let (trips, frequencies) = compress(&[360, 375, 390, 405, 420], 0);
+        assert_eq!(trips.len(), 1);
+        assert_eq!(frequencies.len(), 1);
+        let f = &frequencies[0];
+        assert_eq!(f.trip_id, trips[0].trip_id);
+        assert_eq!(
+            (f.start_time.as_str(), f.end_time.as_str()),
+            ("06:00:00", "07:15:00")
+        );
+        assert_eq!((f.headway_secs, f.exact_times), (900, 1));
+    }
+
+    #[test]
+    fn short_runs_are_kept() {
+        let (trips, frequencies) = compress(&[360, 375, 390], 0);
+        assert_eq!(trips.len(), 3);
+        assert!(frequencies.is_empty());
+    }
+
+    #[test]
+    fn headway_change_splits_the_runs() {
+        // Every 10 minutes, then every 30 minutes
+        let (trips, frequencies) = compress(&[360, 370, 380, 390, 420, 450, 480, 510], 0);
+        assert_eq!(trips.len(), 2);
+        let headways: Vec<u32> = frequencies.iter().map(|f| f.headway_secs).collect();
+        assert_eq!(headways, [600, 1800]);
+        assert_eq!(frequencies[1].start_time, "07:00:00");
+    }
+
+    #[test]
+    fn tolerance_accepts_irregular_intervals() {
+        let starts = [360, 375, 392, 405, 420];
+        let (_, strict) = compress(&starts, 0);
+        assert!(strict.is_empty());
+
+        let (trips, loose) = compress(&starts, 2);
+        assert_eq!(trips.len(), 1);
+        assert_eq!((loose[0].headway_secs, loose[0].exact_times), (900, 0));
+    }
+
+    #[test]
+    fn directions_are_compressed_separately() {
+        let (mut trips, mut rows) = timetable("a", &[360, 380]);
+        let (more_trips, more_rows) = timetable("b", &[370, 390]);
+        trips.extend(more_trips);
+        rows.extend(more_rows);
+        let (trips, _, frequencies) = compress_headways(trips, rows, 0);
+        assert_eq!(trips.len(), 4);
+        assert!(frequencies.is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+//! GTFS Feed Assembly
+//!
+//! Builds the tables of a complete GTFS feed that the other exports leave
+//! out: `routes.txt` for the routes with trips, and `stops.txt` for the stops
+//! they call at. Stops are placed at their station coordinates from
+//! `routeMap.json`, or else at their position on the derived route line.
+//! The tables are zipped into one archive, as consumers expect.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::export::model::{RouteRow, StopRow, StopTime, TripRow};
+use crate::route::model::{RouteFeature, Station};
+
+/// GTFS `route_type` of buses
+const ROUTE_TYPE_BUS: u8 = 3;
+
+/// Rows of `routes.txt` for the routes with at least one trip.
+pub fn build_routes(routes: &[RouteFeature], trips: &[TripRow]) -> Vec<RouteRow> {
+    let used: HashSet<&str> = trips.iter().map(|t| t.route_id.as_str()).collect();
+    let mut rows: Vec<RouteRow> = routes
+        .iter()
+        .filter(|f| used.contains(f.id.as_str()))
+        .map(|f| RouteRow {
+            route_id: f.id.clone(),
+            route_short_name: f.properties.route_no.clone(),
+            route_type: ROUTE_TYPE_BUS,
+        })
+        .collect();
+    rows.sort_by(|a, b| a.route_id.cmp(&b.route_id));
+    rows.dedup_by(|a, b| a.route_id == b.route_id);
+    rows
+}
+
+/// Rows of `stops.txt` for the stops of `stop_times`, sorted by ID.
+pub fn build_stops(
+    routes: &[RouteFeature],
+    stop_times: &[StopTime],
+    stations: &BTreeMap<String, Station>,
+) -> Vec<StopRow> {
+    let used: HashSet<&str> = stop_times.iter().map(|r| r.stop_id.as_str()).collect();
+    let mut stops: BTreeMap<&str, StopRow> = BTreeMap::new();
+    for feature in routes {
+        let props = &feature.properties;
+        for (i, stop) in props.stops.iter().enumerate() {
+            if !used.contains(stop.id.as_str()) || stops.contains_key(stop.id.as_str()) {
+                continue;
+            }
+            let row = match stations.get(&stop.id) {
+                Some(station) => StopRow {
+                    stop_id: stop.id.clone(),
+                    stop_code: Some(station.nodeno.clone()).filter(|code| !code.is_empty()),
+                    stop_name: station.nodenm.clone(),
+                    stop_lat: station.gpslati,
+                    stop_lon: station.gpslong,
+                },
+                None => {
+                    let Some(coord) = props
+                        .indices
+                        .stop_to_coord
+                        .get(i)
+                        .and_then(|&c| feature.geometry.coordinates.get(c))
+                    else {
+                        continue;
+                    };
+                    StopRow {
+                        stop_id: stop.id.clone(),
+                        stop_code: None,
+                        stop_name: stop.name.clone(),
+                        stop_lat: coord[1],
+                        stop_lon: coord[0],
+                    }
+                }
+            };
+            stops.insert(&stop.id, row);
+        }
+    }
+    stops.into_values().collect()
+}
+
+/// Zips named tables into one archive.
+pub fn zip_tables(tables: &[(&str, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, data) in tables {
+        zip.start_file(*name, options)?;
+        zip.write_all(data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
@@ -0,0 +1,678 @@
+//! Data Export Module
+//!
+//! This module converts previously generated outputs into formats used
+//! outside the wBus frontend, such as flat CSV tables for spreadsheet-based
+//! analysis, a compact protobuf bundle for the mobile client, or a GTFS feed
+//! for journey planners.
+
+mod blocks;
+mod bundle;
+mod calendar;
+mod feed_check;
+mod frequencies;
+mod gtfs;
+mod model;
+mod shapes;
+mod stop_times;
+
+use std::fs;
+use std::path::PathBuf;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use chrono::{Months, NaiveDate};
+use prost::Message;
+use serde_json::{Value, json};
+use tracing::{error, info, warn};
+
+use crate::config::{BLOCK_MAX_LAYOVER_MIN, BLOCK_MIN_LAYOVER_MIN};
+use crate::export::blocks::assign_blocks;
+use crate::export::bundle::{BUNDLE_SCHEMA_VERSION, Bundle, encode_route, encode_schedule};
+use crate::export::calendar::build_calendar;
+use crate::export::feed_check::{
+    FeedIssues, FeedTables, check_calendar, check_frequencies, check_references, check_shapes,
+    check_stop_times, check_trips,
+};
+use crate::export::frequencies::compress_headways;
+use crate::export::gtfs::{build_routes, build_stops, zip_tables};
+use crate::export::model::{
+    AgencyRow, FeedInfo, FlatDeparture, Frequency, StopTime, StopTimeRow, TripRow, VehicleEstimate,
+};
+use crate::export::shapes::build_shapes;
+use crate::export::stop_times::build_stop_times;
+use crate::route::load_derived_routes;
+use crate::route::model::{RouteFeature, Station};
+use crate::schedule::load_merged_schedules;
+use crate::settings::{AgencySettings, Settings};
+use crate::utils::holidays::Holidays;
+use crate::utils::{ensure_dir, now, write_atomic};
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Flat CSV of all departures (route, day_type, direction, time, note)
+    Csv,
+    /// Estimated per-stop arrival times for every scheduled trip
+    StopTimes,
+    /// First/last departure per route, day type, and direction (`first_last.json`)
+    FirstLast,
+    /// Route geometry, stops, and schedules as one protobuf message (`bundle.pb`)
+    Bundle,
+    /// GTFS `agency.txt` and `feed_info.txt` from the `[agency]` settings
+    Agency,
+    /// Derived route lines per direction as GTFS `shapes.txt`
+    Shapes,
+    /// GTFS service calendars of the day types (`calendar.txt`, `calendar_dates.txt`)
+    Calendar,
+    /// Complete GTFS feed of the estimated trips, zipped (`gtfs.zip`)
+    Gtfs,
+}
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// Export format
+    #[arg(value_enum)]
+    format: ExportFormat,
+
+    /// Output directory of a previous crawl (containing `schedules/`)
+    #[arg(short, long, default_value = "./storage")]
+    input_dir: PathBuf,
+
+    /// Route output directory of a previous crawl (containing `derived_routes/`)
+    #[arg(long, default_value = "./storage/processed_routes")]
+    routes_dir: PathBuf,
+
+    /// Destination file (default: `<input_dir>/<format>.<ext>`), or directory for
+    /// `calendar` and `agency`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// For `first-last`: also summarize every stop using estimated stop times
+    #[arg(long)]
+    per_stop: bool,
+
+    /// For `stop-times` and `gtfs`: replace trips leaving at regular intervals with
+    /// entries of `frequencies.txt`
+    #[arg(long)]
+    frequencies: bool,
+
+    /// For `--frequencies`: minutes an interval may differ from the headway
+    #[arg(long, default_value_t = 0, requires = "frequencies")]
+    headway_tolerance_min: u32,
+
+    /// For `stop-times` and `gtfs`: chain trips into vehicle runs, filling `block_id` of `trips.txt`
+    #[arg(long, conflicts_with = "frequencies")]
+    blocks: bool,
+
+    /// For `--blocks`: shortest layover (minutes) between two trips of a bus
+    #[arg(long, default_value_t = BLOCK_MIN_LAYOVER_MIN, requires = "blocks")]
+    min_layover_min: u32,
+
+    /// For `--blocks`: longest layover (minutes) between two trips of a bus
+    #[arg(long, default_value_t = BLOCK_MAX_LAYOVER_MIN, requires = "blocks")]
+    max_layover_min: u32,
+
+    /// For `calendar` and `gtfs`: first service date, as YYYY-MM-DD (default: today)
+    #[arg(long)]
+    start_date: Option<NaiveDate>,
+
+    /// For `calendar` and `gtfs`: last service date, as YYYY-MM-DD (default: a year after the start)
+    #[arg(long)]
+    end_date: Option<NaiveDate>,
+
+    /// For `calendar` and `gtfs`: additional holidays, one `YYYY-MM-DD [name]` per line
+    #[arg(long)]
+    holidays_file: Option<PathBuf>,
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: ExportArgs, settings: &Settings) -> Result<()> {
+    match args.format {
+        ExportFormat::Csv => export_csv(&args),
+        ExportFormat::StopTimes => export_stop_times(&args),
+        ExportFormat::FirstLast => export_first_last(&args, &settings.agency),
+        ExportFormat::Agency => export_agency(&args, &settings.agency),
+        ExportFormat::Bundle => export_bundle(&args),
+        ExportFormat::Calendar => export_calendar(&args),
+        ExportFormat::Shapes => export_shapes(&args),
+        ExportFormat::Gtfs => export_gtfs(&args, &settings.agency),
+    }
+}
+
+fn export_gtfs(args: &ExportArgs, agency: &AgencySettings) -> Result<()> {
+    let schedules = load_merged_schedules(&args.input_dir)?;
+    let routes = load_derived_routes(&args.routes_dir)?;
+    if schedules.is_empty() || routes.is_empty() {
+        anyhow::bail!(
+            "Both schedules ({:?}) and derived routes ({:?}) are required",
+            args.input_dir,
+            args.routes_dir
+        );
+    }
+
+    let (trips, stop_times, frequencies) = build_timetable(args, &schedules, &routes)?;
+
+    let service_ids: BTreeSet<String> = trips.iter().map(|t| t.service_id.clone()).collect();
+    let (start, end, holidays) = service_period(args)?;
+    let (calendar, exceptions) = build_calendar(&service_ids, start, end, &holidays)?;
+
+    let shape_ids: BTreeSet<&str> = trips.iter().map(|t| t.shape_id.as_str()).collect();
+    let shapes: Vec<_> = build_shapes(&routes)
+        .into_iter()
+        .filter(|p| shape_ids.contains(p.shape_id.as_str()))
+        .collect();
+
+    let map_path = args.routes_dir.join("routeMap.json");
+    let stations: BTreeMap<String, Station> = match fs::read_to_string(&map_path) {
+        Ok(text) => serde_json::from_value(
+            serde_json::from_str::<Value>(&text)
+                .with_context(|| format!("Parsing {:?}", map_path))?["stations"]
+                .take(),
+        )
+        .with_context(|| format!("Parsing the stations of {:?}", map_path))?,
+        Err(e) => {
+            warn!(
+                "Cannot read {:?} ({}); stops are placed on the route lines",
+                map_path, e
+            );
+            BTreeMap::new()
+        }
+    };
+    let route_rows = build_routes(&routes, &trips);
+    let stops = build_stops(&routes, &stop_times, &stations);
+
+    let mut issues = check_calendar(&calendar, &exceptions);
+    issues.merge(check_shapes(&shapes));
+    issues.merge(check_references(&FeedTables {
+        routes: &route_rows,
+        stops: &stops,
+        trips: &trips,
+        stop_times: &stop_times,
+        calendar: &calendar,
+        shapes: &shapes,
+    }));
+    ensure_valid(issues, "GTFS feed")?;
+
+    let stop_time_rows: Vec<StopTimeRow> = stop_times.iter().map(StopTimeRow::from).collect();
+    let (agency_txt, feed_info_txt) = agency_tables(agency)?;
+    let mut tables = vec![
+        ("agency.txt", agency_txt),
+        ("feed_info.txt", feed_info_txt),
+        ("stops.txt", write_csv(&stops)?),
+        ("routes.txt", write_csv(&route_rows)?),
+        ("trips.txt", write_csv(&trips)?),
+        ("stop_times.txt", write_csv(&stop_time_rows)?),
+        ("calendar.txt", write_csv(&calendar)?),
+        ("calendar_dates.txt", write_csv(&exceptions)?),
+        ("shapes.txt", write_csv(&shapes)?),
+    ];
+    if args.frequencies {
+        tables.push(("frequencies.txt", write_csv(&frequencies)?));
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("gtfs.zip"));
+    write_atomic(&output, zip_tables(&tables)?)?;
+
+    info!(
+        "✓ Exported a GTFS feed of {} routes, {} stops, and {} trips ({} to {}) to {:?}",
+        route_rows.len(),
+        stops.len(),
+        trips.len(),
+        start,
+        end,
+        output
+    );
+
+    Ok(())
+}
+
+fn export_shapes(args: &ExportArgs) -> Result<()> {
+    let routes = load_derived_routes(&args.routes_dir)?;
+    if routes.is_empty() {
+        anyhow::bail!("No derived routes found in {:?}", args.routes_dir);
+    }
+
+    let points = build_shapes(&routes);
+    ensure_valid(check_shapes(&points), "Shapes")?;
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("shapes.txt"));
+    write_atomic(&output, write_csv(&points)?)?;
+
+    info!(
+        "✓ Exported the shapes of {} routes ({} points) to {:?}",
+        routes.len(),
+        points.len(),
+        output
+    );
+
+    Ok(())
+}
+
+fn export_calendar(args: &ExportArgs) -> Result<()> {
+    let schedules = load_merged_schedules(&args.input_dir)?;
+    if schedules.is_empty() {
+        anyhow::bail!("No schedules found in {:?}", args.input_dir);
+    }
+    let day_types: BTreeSet<String> = schedules
+        .iter()
+        .flat_map(|(route, data)| flatten_schedule(route, data))
+        .map(|dep| dep.day_type)
+        .collect();
+
+    let (start, end, holidays) = service_period(args)?;
+    let (calendar, exceptions) = build_calendar(&day_types, start, end, &holidays)?;
+    ensure_valid(check_calendar(&calendar, &exceptions), "Calendar")?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.clone());
+    ensure_dir(&output)?;
+    for (name, rows) in [
+        ("calendar.txt", write_csv(&calendar)?),
+        ("calendar_dates.txt", write_csv(&exceptions)?),
+    ] {
+        write_atomic(&output.join(name), rows)?;
+    }
+
+    info!(
+        "✓ Exported {} services and {} holiday exceptions ({} to {}) to {:?}",
+        calendar.len(),
+        exceptions.len(),
+        start,
+        end,
+        output
+    );
+
+    Ok(())
+}
+
+/// The service period of `--start-date` and `--end-date`, with its holidays.
+fn service_period(args: &ExportArgs) -> Result<(NaiveDate, NaiveDate, Holidays)> {
+    let start = args.start_date.unwrap_or_else(|| now().date_naive());
+    let end = match args.end_date {
+        Some(end) => end,
+        None => start
+            .checked_add_months(Months::new(12))
+            .and_then(|d| d.pred_opt())
+            .context("Service period out of range")?,
+    };
+    if end < start {
+        anyhow::bail!("--end-date {} is before the start date {}", end, start);
+    }
+
+    let holidays = Holidays::between(start, end, args.holidays_file.as_deref())
+        .context("Holidays of the service period (add missing years with --holidays-file)")?;
+    Ok((start, end, holidays))
+}
+
+/// Estimates the vehicles each route needs per day type by chaining its
+/// estimated trips into blocks, with layovers between `layover_min` minutes.
+pub fn estimate_vehicles(
+    schedules: &BTreeMap<String, Value>,
+    routes: &[RouteFeature],
+    layover_min: (u32, u32),
+) -> (Vec<VehicleEstimate>, Vec<String>) {
+    let (mut trips, rows, warnings) = build_stop_times(schedules, routes);
+    let estimates = assign_blocks(&mut trips, &rows, layover_min.0 * 60, layover_min.1 * 60);
+    (estimates, warnings)
+}
+
+/// Logs the issues found, failing on errors: consumers reject a whole feed
+/// over one bad row, so nothing is written then.
+fn ensure_valid(issues: FeedIssues, what: &str) -> Result<()> {
+    for w in &issues.warnings {
+        warn!("{}", w);
+    }
+    if !issues.errors.is_empty() {
+        for e in &issues.errors {
+            error!("{}", e);
+        }
+        anyhow::bail!(
+            "{} failed validation with {} error(s); nothing was written",
+            what,
+            issues.errors.len()
+        );
+    }
+    Ok(())
+}
+
+/// Serializes `rows` as CSV with a header.
+fn write_csv<T: serde::Serialize>(rows: &[T]) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+fn export_bundle(args: &ExportArgs) -> Result<()> {
+    let routes = load_derived_routes(&args.routes_dir)?;
+    if routes.is_empty() {
+        anyhow::bail!("No derived routes found in {:?}", args.routes_dir);
+    }
+    let schedules = load_merged_schedules(&args.input_dir)?;
+    if schedules.is_empty() {
+        warn!(
+            "no schedules found in {:?}; exporting routes only",
+            args.input_dir
+        );
+    }
+
+    let bundle = Bundle {
+        schema_version: BUNDLE_SCHEMA_VERSION,
+        last_updated: now().format("%Y-%m-%d").to_string(),
+        routes: routes.iter().map(encode_route).collect(),
+        schedules: schedules
+            .iter()
+            .map(|(route, data)| encode_schedule(route, data))
+            .collect(),
+    };
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("bundle.pb"));
+    let bytes = bundle.encode_to_vec();
+    write_atomic(&output, &bytes)?;
+
+    info!(
+        "✓ Exported {} routes and {} schedules to {:?} ({} KB)",
+        bundle.routes.len(),
+        bundle.schedules.len(),
+        output,
+        bytes.len().div_ceil(1024)
+    );
+
+    Ok(())
+}
+
+fn export_agency(args: &ExportArgs, agency: &AgencySettings) -> Result<()> {
+    let (agency_txt, feed_info_txt) = agency_tables(agency)?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.clone());
+    ensure_dir(&output)?;
+    write_atomic(&output.join("agency.txt"), agency_txt)?;
+    write_atomic(&output.join("feed_info.txt"), feed_info_txt)?;
+
+    info!(
+        "✓ Exported agency.txt and feed_info.txt ({}) to {:?}",
+        agency.name, output
+    );
+
+    Ok(())
+}
+
+/// `agency.txt` and `feed_info.txt` of the `[agency]` settings.
+fn agency_tables(agency: &AgencySettings) -> Result<(Vec<u8>, Vec<u8>)> {
+    let provenance = agency.provenance();
+    let agency_rows = [AgencyRow {
+        agency_name: &agency.name,
+        agency_url: &agency.url,
+        agency_timezone: &agency.timezone,
+        agency_lang: &agency.lang,
+    }];
+    let feed_info = [FeedInfo {
+        feed_publisher_name: &provenance.publisher,
+        feed_publisher_url: &provenance.publisher_url,
+        feed_lang: &agency.lang,
+        feed_version: now().format("%Y%m%d").to_string(),
+    }];
+    Ok((write_csv(&agency_rows)?, write_csv(&feed_info)?))
+}
+
+fn export_first_last(args: &ExportArgs, agency: &AgencySettings) -> Result<()> {
+    let schedules = load_merged_schedules(&args.input_dir)?;
+    if schedules.is_empty() {
+        anyhow::bail!("No schedules found in {:?}", args.input_dir);
+    }
+
+    // route -> day type -> direction -> summary
+    let mut routes: BTreeMap<String, BTreeMap<String, BTreeMap<String, Value>>> = BTreeMap::new();
+    for (route, data) in &schedules {
+        let mut groups: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+        for dep in flatten_schedule(route, data) {
+            groups
+                .entry((dep.day_type, dep.direction))
+                .or_default()
+                .push(dep.time);
+        }
+
+        for ((day_type, direction), times) in groups {
+            routes
+                .entry(route.clone())
+                .or_default()
+                .entry(day_type)
+                .or_default()
+                .insert(direction, first_last_summary(times));
+        }
+    }
+
+    let route_count = routes.len();
+    let mut output_json = json!({
+        "lastUpdated": now().format("%Y-%m-%d").to_string(),
+        "provenance": agency.provenance(),
+        "routes": routes,
+    });
+
+    if args.per_stop {
+        let derived = load_derived_routes(&args.routes_dir)?;
+        let (_, rows, warnings) = build_stop_times(&schedules, &derived);
+        for w in &warnings {
+            warn!("{}", w);
+        }
+
+        // (route, day type, direction, stop id) -> (stop name, times)
+        type StopKey = (String, String, String, String);
+        let mut groups: BTreeMap<StopKey, (String, Vec<String>)> = BTreeMap::new();
+        for row in rows {
+            groups
+                .entry((row.route, row.day_type, row.direction, row.stop_id))
+                .or_insert_with(|| (row.stop_name, Vec::new()))
+                .1
+                .push(row.departure_time);
+        }
+
+        let mut stops = json!({});
+        for ((route, day_type, direction, stop_id), (name, times)) in groups {
+            let mut summary = first_last_summary(times);
+            summary["name"] = json!(name);
+            stops[&route][&day_type][&direction][&stop_id] = summary;
+        }
+        output_json["stops"] = stops;
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("first_last.json"));
+    write_atomic(&output, serde_json::to_string_pretty(&output_json)?)?;
+
+    info!(
+        "✓ Exported first/last departures of {} routes to {:?}",
+        route_count, output
+    );
+
+    Ok(())
+}
+
+/// Summarizes a list of "HH:MM[:SS]" times into first, last, and count.
+fn first_last_summary(mut times: Vec<String>) -> Value {
+    times.sort_by(|a, b| service_day_key(a).cmp(&service_day_key(b)));
+
+    json!({
+        "first": times.first(),
+        "last": times.last(),
+        "count": times.len(),
+    })
+}
+
+/// Sort key of an "HH:MM[:SS]" time within its service day. Departures before
+/// 03:00 are treated as belonging to the previous service day, so they sort last.
+pub fn service_day_key(time: &str) -> (bool, &str) {
+    (time.get(..2).is_some_and(|h| h < "03"), time)
+}
+
+fn export_stop_times(args: &ExportArgs) -> Result<()> {
+    let schedules = load_merged_schedules(&args.input_dir)?;
+    let routes = load_derived_routes(&args.routes_dir)?;
+    if schedules.is_empty() || routes.is_empty() {
+        anyhow::bail!(
+            "Both schedules ({:?}) and derived routes ({:?}) are required",
+            args.input_dir,
+            args.routes_dir
+        );
+    }
+
+    let (trips, rows, frequencies) = build_timetable(args, &schedules, &routes)?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("stop_times.csv"));
+    if args.frequencies {
+        let path = output.with_file_name("frequencies.txt");
+        write_atomic(&path, write_csv(&frequencies)?)?;
+        info!("✓ Exported {} frequencies to {:?}", frequencies.len(), path);
+    }
+    let trips_path = output.with_file_name("trips.txt");
+    write_atomic(&trips_path, write_csv(&trips)?)?;
+    write_atomic(&output, write_csv(&rows)?)?;
+
+    info!(
+        "✓ Exported {} trips to {:?} and {} stop times to {:?}",
+        trips.len(),
+        trips_path,
+        rows.len(),
+        output
+    );
+
+    Ok(())
+}
+
+/// The estimated trips of `--blocks` and `--frequencies`, their stop times,
+/// and the frequency entries, validated before anything is written.
+fn build_timetable(
+    args: &ExportArgs,
+    schedules: &BTreeMap<String, Value>,
+    routes: &[RouteFeature],
+) -> Result<(Vec<TripRow>, Vec<StopTime>, Vec<Frequency>)> {
+    let (mut trips, rows, warnings) = build_stop_times(schedules, routes);
+    for w in &warnings {
+        warn!("{}", w);
+    }
+    if args.blocks {
+        let vehicles = assign_blocks(
+            &mut trips,
+            &rows,
+            args.min_layover_min * 60,
+            args.max_layover_min * 60,
+        );
+        info!(
+            "Chained {} route schedules into {} blocks",
+            vehicles.len(),
+            vehicles.iter().map(|v| v.vehicles).sum::<usize>()
+        );
+    }
+
+    let (trips, rows, frequencies) = if args.frequencies {
+        compress_headways(trips, rows, args.headway_tolerance_min * 60)
+    } else {
+        (trips, rows, Vec::new())
+    };
+
+    // Consumers reject the whole feed over one bad trip, so none is written.
+    let mut issues = check_stop_times(&rows, routes);
+    issues.merge(check_trips(&trips, &rows));
+    issues.merge(check_frequencies(&frequencies, &trips));
+    ensure_valid(issues, "Stop times")?;
+
+    Ok((trips, rows, frequencies))
+}
+
+fn export_csv(args: &ExportArgs) -> Result<()> {
+    let schedules = load_merged_schedules(&args.input_dir)?;
+    if schedules.is_empty() {
+        anyhow::bail!("No schedules found in {:?}", args.input_dir);
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("schedules.csv"));
+
+    let rows: Vec<FlatDeparture> = schedules
+        .iter()
+        .flat_map(|(route, data)| flatten_schedule(route, data))
+        .collect();
+    write_atomic(&output, write_csv(&rows)?)?;
+
+    info!(
+        "✓ Exported {} departures of {} routes to {:?}",
+        rows.len(),
+        schedules.len(),
+        output
+    );
+
+    Ok(())
+}
+
+/// Flattens a merged schedule (`schedule.<dayType>.<HH>.<direction>[]`) into
+/// one record per departure, sorted by day type, direction, and time.
+pub fn flatten_schedule(route: &str, data: &Value) -> Vec<FlatDeparture> {
+    let mut rows = Vec::new();
+
+    let Some(day_types) = data["schedule"].as_object() else {
+        return rows;
+    };
+
+    for (day_type, hours) in day_types {
+        let Some(hours) = hours.as_object() else {
+            continue;
+        };
+
+        for (hour, directions) in hours {
+            let Some(directions) = directions.as_object() else {
+                continue;
+            };
+
+            for (direction, minutes) in directions {
+                for entry in minutes.as_array().into_iter().flatten() {
+                    let minute = entry["minute"].as_str().unwrap_or("00");
+                    let note = entry["noteId"]
+                        .as_str()
+                        .and_then(|id| data["notes"][id].as_str())
+                        .unwrap_or_default();
+
+                    rows.push(FlatDeparture {
+                        route: route.to_string(),
+                        day_type: day_type.clone(),
+                        direction: direction.clone(),
+                        time: format!("{}:{}", hour, minute),
+                        note: note.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    rows.sort_by(|a, b| {
+        (&a.day_type, &a.direction, &a.time).cmp(&(&b.day_type, &b.direction, &b.time))
+    });
+    rows
+}
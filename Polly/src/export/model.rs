@@ -0,0 +1,171 @@
+//! Export Data Models
+//!
+//! This module defines the flat record types written by the export formats.
+
+use serde::Serialize;
+
+/// A single departure, flattened out of the hour-grouped schedule format.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatDeparture {
+    pub route: String,
+    pub day_type: String,
+    pub direction: String,
+    pub time: String,
+    pub note: String,
+}
+
+/// Estimated arrival/departure of one trip at one stop.
+#[derive(Debug, Clone, Serialize)]
+pub struct StopTime {
+    pub trip_id: String,
+    pub route: String,
+    pub route_id: String,
+    pub day_type: String,
+    pub direction: String,
+    pub stop_sequence: usize,
+    pub stop_id: String,
+    pub stop_name: String,
+    pub arrival_time: String,
+    pub departure_time: String,
+    /// Meters from the start of the shape to the stop
+    pub shape_dist_traveled: f64,
+}
+
+/// The GTFS columns of a stop time, as written to `stop_times.txt`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StopTimeRow<'a> {
+    pub trip_id: &'a str,
+    pub arrival_time: &'a str,
+    pub departure_time: &'a str,
+    pub stop_id: &'a str,
+    pub stop_sequence: usize,
+    pub shape_dist_traveled: f64,
+}
+
+impl<'a> From<&'a StopTime> for StopTimeRow<'a> {
+    fn from(row: &'a StopTime) -> Self {
+        Self {
+            trip_id: &row.trip_id,
+            arrival_time: &row.arrival_time,
+            departure_time: &row.departure_time,
+            stop_id: &row.stop_id,
+            stop_sequence: row.stop_sequence,
+            shape_dist_traveled: row.shape_dist_traveled,
+        }
+    }
+}
+
+/// A row of GTFS `routes.txt`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteRow {
+    pub route_id: String,
+    /// Route number
+    pub route_short_name: String,
+    pub route_type: u8,
+}
+
+/// A row of GTFS `stops.txt`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StopRow {
+    pub stop_id: String,
+    /// Stop number shown at the stop
+    pub stop_code: Option<String>,
+    pub stop_name: String,
+    pub stop_lat: f64,
+    pub stop_lon: f64,
+}
+
+/// A row of GTFS `trips.txt`: one scheduled trip of a route.
+#[derive(Debug, Clone, Serialize)]
+pub struct TripRow {
+    pub route_id: String,
+    /// Day type of the trip, the service of `calendar.txt`
+    pub service_id: String,
+    pub trip_id: String,
+    /// 0 for the outbound direction, 1 for the inbound one
+    pub direction_id: usize,
+    /// Shape of the trip's direction in `shapes.txt`
+    pub shape_id: String,
+    /// Vehicle run the trip belongs to (`--blocks`)
+    pub block_id: Option<String>,
+}
+
+/// A row of GTFS `calendar.txt`: the weekdays a service runs on.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceCalendar {
+    pub service_id: String,
+    pub monday: u8,
+    pub tuesday: u8,
+    pub wednesday: u8,
+    pub thursday: u8,
+    pub friday: u8,
+    pub saturday: u8,
+    pub sunday: u8,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+/// A row of GTFS `calendar_dates.txt`: a service added (1) or removed (2)
+/// on one date.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceException {
+    pub service_id: String,
+    pub date: String,
+    pub exception_type: u8,
+}
+
+/// A row of GTFS `frequencies.txt`: a template trip repeated every
+/// `headway_secs` from `start_time` until `end_time`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Frequency {
+    pub trip_id: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub headway_secs: u32,
+    /// 1 if the trips run exactly on the headway, 0 if only about as often
+    pub exact_times: u8,
+}
+
+/// A row of GTFS `shapes.txt`: one point of a route line.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShapePoint {
+    pub shape_id: String,
+    pub shape_pt_lat: f64,
+    pub shape_pt_lon: f64,
+    pub shape_pt_sequence: usize,
+    /// Meters from the start of the shape
+    pub shape_dist_traveled: f64,
+}
+
+/// A row of GTFS `agency.txt`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgencyRow<'a> {
+    pub agency_name: &'a str,
+    pub agency_url: &'a str,
+    pub agency_timezone: &'a str,
+    pub agency_lang: &'a str,
+}
+
+/// A row of GTFS `feed_info.txt`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedInfo<'a> {
+    pub feed_publisher_name: &'a str,
+    pub feed_publisher_url: &'a str,
+    pub feed_lang: &'a str,
+    /// Date of the export (YYYYMMDD)
+    pub feed_version: String,
+}
+
+/// Vehicles a route needs on one day type, from its trips chained into blocks.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleEstimate {
+    pub route: String,
+    pub route_id: String,
+    pub day_type: String,
+    pub trips: usize,
+    /// Blocks (vehicle runs) the trips were chained into
+    pub vehicles: usize,
+    /// Most blocks in service at the same time
+    pub peak_vehicles: usize,
+}
@@ -0,0 +1,57 @@
+//! Shape Generation
+//!
+//! Writes the derived route lines as GTFS shapes, one per direction: the
+//! line is split at the turning point (`turn_idx`), and the turning
+//! coordinate ends the outbound shape and starts the inbound one. Circular
+//! routes form a single shape. `shape_dist_traveled` is the distance in
+//! meters from the start of each shape, so trips can refer to it.
+
+use crate::export::model::ShapePoint;
+use crate::route::model::RouteFeature;
+use crate::utils::geo::cumulative_distances;
+
+/// ID of the shape of direction `dir_idx` (0: outbound, 1: inbound) of a route.
+pub fn shape_id(feature: &RouteFeature, dir_idx: usize) -> String {
+    format!("{}_{}", feature.id, dir_idx)
+}
+
+/// Coordinate index each direction's shape starts at, and where it ends
+/// (inclusive).
+pub fn shape_ranges(feature: &RouteFeature) -> Vec<(usize, usize)> {
+    let Some(last) = feature.geometry.coordinates.len().checked_sub(1) else {
+        return Vec::new();
+    };
+    match feature.properties.indices.turn_idx {
+        Some(turn) if !feature.properties.is_circular && turn > 0 && turn < last => {
+            vec![(0, turn), (turn, last)]
+        }
+        _ => vec![(0, last)],
+    }
+}
+
+/// Builds the shape points of every route, in order.
+pub fn build_shapes(routes: &[RouteFeature]) -> Vec<ShapePoint> {
+    let mut points = Vec::new();
+    for feature in routes {
+        let coords = &feature.geometry.coordinates;
+        let cum = cumulative_distances(coords);
+        for (dir_idx, (first, last)) in shape_ranges(feature).into_iter().enumerate() {
+            let id = shape_id(feature, dir_idx);
+            for (seq, i) in (first..=last).enumerate() {
+                points.push(ShapePoint {
+                    shape_id: id.clone(),
+                    shape_pt_lat: coords[i][1],
+                    shape_pt_lon: coords[i][0],
+                    shape_pt_sequence: seq + 1,
+                    shape_dist_traveled: round_dm(cum[i] - cum[first]),
+                });
+            }
+        }
+    }
+    points
+}
+
+/// Rounds meters to 0.1 m.
+pub fn round_dm(meters: f64) -> f64 {
+    (meters * 10.0).round() / 10.0
+}
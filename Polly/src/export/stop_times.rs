@@ -0,0 +1,346 @@
+//! Stop Times Estimation
+//!
+//! Expands terminus departure times from the schedules into estimated
+//! arrival times at every intermediate stop, using the OSRM travel time
+//! recorded for each leg of the derived route geometry.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::config::FALLBACK_BUS_SPEED_MPS;
+use crate::export::model::{FlatDeparture, StopTime, TripRow};
+use crate::export::shapes::{round_dm, shape_id, shape_ranges};
+use crate::export::{flatten_schedule, service_day_key};
+use crate::route::model::RouteFeature;
+use crate::utils::geo::cumulative_distances;
+
+/// One direction of travel of a derived route: a contiguous slice of its stops.
+struct Pattern<'a> {
+    feature: &'a RouteFeature,
+    /// Index of the first and last stop of this direction (inclusive)
+    first: usize,
+    last: usize,
+}
+
+/// Builds the trips and their stop_times rows for every scheduled departure
+/// that can be matched to a derived route. Returns the trips, the rows, and a
+/// list of warnings for unmatched data.
+pub fn build_stop_times(
+    schedules: &BTreeMap<String, Value>,
+    routes: &[RouteFeature],
+) -> (Vec<TripRow>, Vec<StopTime>, Vec<String>) {
+    let mut trips = Vec::new();
+    let mut rows = Vec::new();
+    let mut warnings = Vec::new();
+
+    let mut by_route_no: HashMap<&str, Vec<&RouteFeature>> = HashMap::new();
+    for feature in routes {
+        by_route_no
+            .entry(feature.properties.route_no.as_str())
+            .or_default()
+            .push(feature);
+    }
+
+    for (route, data) in schedules {
+        let Some(candidates) = by_route_no.get(route.as_str()) else {
+            warnings.push(format!("{}: no derived route geometry", route));
+            continue;
+        };
+
+        // Several TAGO route IDs can share a route number; use the first one.
+        let feature = candidates[0];
+        if candidates.len() > 1 {
+            warnings.push(format!(
+                "{}: {} route variants, using {}",
+                route,
+                candidates.len(),
+                feature.id
+            ));
+        }
+
+        let patterns = split_directions(feature);
+        let cum = cumulative_distances(&feature.geometry.coordinates);
+        let stop_dist = stop_distances(feature, &cum);
+        let leg_secs = leg_seconds(feature, &stop_dist);
+        // Trips follow the shape of their direction, measured from its start.
+        let shapes = shape_ranges(feature);
+
+        // Trip IDs must be unique even if two departures share a minute.
+        let mut seen_trips: HashMap<String, usize> = HashMap::new();
+
+        let mut departures = flatten_schedule(route, data);
+        let starts = departure_starts(&mut departures);
+        for (departure, start) in departures.iter().zip(starts) {
+            let Some((dir_idx, pattern)) = match_direction(&departure.direction, &patterns) else {
+                let w = format!(
+                    "{}: direction '{}' matches no terminus",
+                    route, departure.direction
+                );
+                if !warnings.contains(&w) {
+                    warnings.push(w);
+                }
+                continue;
+            };
+            let Some(start) = start else {
+                continue;
+            };
+
+            let base_id = format!(
+                "{}_{}_{}_{}",
+                route,
+                departure.day_type,
+                dir_idx,
+                departure.time.replace(':', "")
+            );
+            let n = seen_trips.entry(base_id.clone()).or_insert(0);
+            *n += 1;
+            let trip_id = if *n == 1 {
+                base_id
+            } else {
+                format!("{}_{}", base_id, n)
+            };
+
+            let shape_idx = dir_idx.min(shapes.len().saturating_sub(1));
+            let shape_start = shapes
+                .get(shape_idx)
+                .and_then(|&(first, _)| cum.get(first))
+                .copied()
+                .unwrap_or(0.0);
+            trips.push(TripRow {
+                route_id: feature.id.clone(),
+                service_id: departure.day_type.clone(),
+                trip_id: trip_id.clone(),
+                direction_id: dir_idx,
+                shape_id: shape_id(feature, shape_idx),
+                block_id: None,
+            });
+
+            let mut elapsed = 0.0;
+            for (seq, stop_idx) in (pattern.first..=pattern.last).enumerate() {
+                if stop_idx > pattern.first {
+                    elapsed += leg_secs[stop_idx - 1];
+                }
+
+                let stop = &pattern.feature.properties.stops[stop_idx];
+                let time = format_hms(start + elapsed.round() as u32);
+                rows.push(StopTime {
+                    trip_id: trip_id.clone(),
+                    route: route.clone(),
+                    route_id: feature.id.clone(),
+                    day_type: departure.day_type.clone(),
+                    direction: departure.direction.clone(),
+                    stop_sequence: seq + 1,
+                    stop_id: stop.id.clone(),
+                    stop_name: stop.name.clone(),
+                    arrival_time: time.clone(),
+                    departure_time: time,
+                    shape_dist_traveled: round_dm((stop_dist[stop_idx] - shape_start).max(0.0)),
+                });
+            }
+        }
+    }
+
+    (trips, rows, warnings)
+}
+
+/// Splits a route's stops at the turning point (first change of up/down code)
+/// into the outbound and inbound patterns. The turning stop belongs to both.
+/// Circular routes form a single pattern over all stops.
+fn split_directions(feature: &RouteFeature) -> Vec<Pattern<'_>> {
+    let stops = &feature.properties.stops;
+    if stops.is_empty() {
+        return Vec::new();
+    }
+
+    let last = stops.len() - 1;
+    if feature.properties.is_circular {
+        return vec![Pattern {
+            feature,
+            first: 0,
+            last,
+        }];
+    }
+
+    let turn = (0..last)
+        .find(|&i| stops[i].up_down != stops[i + 1].up_down)
+        .unwrap_or(last);
+
+    let mut patterns = vec![Pattern {
+        feature,
+        first: 0,
+        last: turn,
+    }];
+    if turn < last {
+        patterns.push(Pattern {
+            feature,
+            first: turn,
+            last,
+        });
+    }
+    patterns
+}
+
+/// Finds the pattern whose first stop matches the schedule direction, which is
+/// named after the terminus the bus departs from (e.g., "연세대" for "연세대발").
+fn match_direction<'a>(
+    direction: &str,
+    patterns: &'a [Pattern<'a>],
+) -> Option<(usize, &'a Pattern<'a>)> {
+    // Circular routes have a single direction, whatever it is called.
+    if let [only] = patterns
+        && only.feature.properties.is_circular
+    {
+        return Some((0, only));
+    }
+
+    let dir: String = direction.chars().filter(|c| !c.is_whitespace()).collect();
+    if dir.is_empty() {
+        return None;
+    }
+
+    patterns.iter().enumerate().find(|(_, p)| {
+        let name: String = p.feature.properties.stops[p.first]
+            .name
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        name.contains(&dir) || dir.contains(&name)
+    })
+}
+
+/// Travel time in seconds for each leg, estimating legs OSRM didn't time from
+/// the leg's road distance, or else the geometry distance between the two
+/// stops' snapped positions.
+fn leg_seconds(feature: &RouteFeature, stop_dist: &[f64]) -> Vec<f64> {
+    let props = &feature.properties;
+    let at = |stop_idx: usize| stop_dist[stop_idx];
+
+    (0..props.stops.len().saturating_sub(1))
+        .map(|i| match props.leg_durations.get(i).copied().flatten() {
+            Some(secs) => secs,
+            None => {
+                let meters = props
+                    .leg_distances
+                    .get(i)
+                    .copied()
+                    .flatten()
+                    .unwrap_or_else(|| (at(i + 1) - at(i)).abs());
+                meters / FALLBACK_BUS_SPEED_MPS
+            }
+        })
+        .collect()
+}
+
+/// Distance in meters along the line to each stop.
+fn stop_distances(feature: &RouteFeature, cum: &[f64]) -> Vec<f64> {
+    let indices = &feature.properties.indices;
+    // Files written before `stop_dist` existed only have `stop_to_coord`
+    (0..feature.properties.stops.len())
+        .map(|stop_idx| {
+            indices
+                .stop_dist
+                .get(stop_idx)
+                .or_else(|| {
+                    indices
+                        .stop_to_coord
+                        .get(stop_idx)
+                        .and_then(|&c| cum.get(c))
+                })
+                .copied()
+                .unwrap_or(0.0)
+        })
+        .collect()
+}
+
+/// Orders `departures` by service day and returns the start of each in
+/// seconds. A departure earlier than the previous one of its day type and
+/// direction runs after midnight, so it rolls over past 24:00 as GTFS requires.
+fn departure_starts(departures: &mut [FlatDeparture]) -> Vec<Option<u32>> {
+    departures.sort_by(|a, b| {
+        (&a.day_type, &a.direction, service_day_key(&a.time)).cmp(&(
+            &b.day_type,
+            &b.direction,
+            service_day_key(&b.time),
+        ))
+    });
+
+    let mut previous: Option<(&str, &str, u32)> = None;
+    departures
+        .iter()
+        .map(|departure| {
+            let mut start = parse_hhmm(&departure.time)?;
+            if let Some((day_type, direction, before)) = previous
+                && day_type == departure.day_type
+                && direction == departure.direction
+                && start < before
+            {
+                start += 24 * 3600;
+            }
+            previous = Some((&departure.day_type, &departure.direction, start));
+            Some(start)
+        })
+        .collect()
+}
+
+/// Parses "HH:MM" into seconds after midnight.
+fn parse_hhmm(time: &str) -> Option<u32> {
+    let (h, m) = time.split_once(':')?;
+    Some(h.parse::<u32>().ok()? * 3600 + m.parse::<u32>().ok()? * 60)
+}
+
+/// Formats seconds after midnight as GTFS-style "HH:MM:SS" (hours may exceed 23).
+pub fn format_hms(secs: u32) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn departure(day_type: &str, direction: &str, time: &str) -> FlatDeparture {
+        FlatDeparture {
+            route: "34".to_string(),
+            day_type: day_type.to_string(),
+            direction: direction.to_string(),
+            time: time.to_string(),
+            note: String::new(),
+        }
+    }
+
+    #[test]
+    fn departures_after_midnight_roll_over() {
+        let mut departures = vec![
+            departure("weekday", "연세대", "00:10"),
+            departure("weekday", "연세대", "23:50"),
+            departure("weekday", "연세대", "06:00"),
+            departure("weekday", "연세대", "00:40"),
+        ];
+        let starts = departure_starts(&mut departures);
+
+        let times: Vec<&str> = departures.iter().map(|d| d.time.as_str()).collect();
+        assert_eq!(times, ["06:00", "23:50", "00:10", "00:40"]);
+        let starts: Vec<String> = starts.into_iter().map(|s| format_hms(s.unwrap())).collect();
+        assert_eq!(starts, ["06:00:00", "23:50:00", "24:10:00", "24:40:00"]);
+    }
+
+    #[test]
+    fn rollover_is_per_day_type_and_direction() {
+        let mut departures = vec![
+            departure("weekday", "a", "23:00"),
+            departure("weekday", "b", "05:30"),
+            departure("weekend", "a", "05:00"),
+        ];
+        let starts = departure_starts(&mut departures);
+        assert_eq!(
+            starts,
+            [Some(23 * 3600), Some(5 * 3600 + 1800), Some(5 * 3600)]
+        );
+    }
+
+    #[test]
+    fn unparsable_times_are_skipped() {
+        let mut departures = vec![departure("weekday", "a", "첫차")];
+        assert_eq!(departure_starts(&mut departures), [None]);
+    }
+}
@@ -0,0 +1,267 @@
+//! GTFS Feed Reader
+//!
+//! Reads the tables of a zipped GTFS feed that the comparison needs. Tables
+//! are streamed record by record, since national feeds carry hundreds of MB
+//! of `stop_times.txt`, and fields that are not UTF-8 are decoded as EUC-KR,
+//! which older Korean feeds still use.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, NaiveDate};
+use csv::ByteRecord;
+use zip::ZipArchive;
+
+/// A stop of `stops.txt`
+#[derive(Debug)]
+pub struct FeedStop {
+    pub code: Option<String>,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// A trip of `trips.txt`, with its first departure from `stop_times.txt`
+#[derive(Debug)]
+pub struct FeedTrip {
+    pub route_id: String,
+    pub service_id: String,
+    /// Stop sequence and `HH:MM` departure of the first stop
+    pub first: Option<(u32, String)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Feed {
+    pub stops: HashMap<String, FeedStop>,
+    /// Short names (route numbers) by route ID
+    pub routes: HashMap<String, String>,
+    /// Trips of the routes asked for
+    pub trips: HashMap<String, FeedTrip>,
+    /// Weekdays (Monday first) each service runs on
+    pub services: HashMap<String, [bool; 7]>,
+    /// Routes with a trip calling at one of the stops asked for
+    pub routes_at_stops: HashSet<String>,
+}
+
+impl Feed {
+    /// Loads the feed at `path`. Only the trips of routes named in
+    /// `route_numbers` are kept, and `routes_at_stops` is filled for the
+    /// stops `in_area` accepts.
+    pub fn load(
+        path: &Path,
+        route_numbers: &HashSet<&str>,
+        in_area: impl Fn(&FeedStop) -> bool,
+    ) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Opening GTFS feed {:?}", path))?;
+        let mut zip =
+            ZipArchive::new(file).with_context(|| format!("Invalid GTFS feed {:?}", path))?;
+        let mut feed = Feed::default();
+
+        read_table(&mut zip, "stops.txt", true, |row| {
+            let (Some(id), Some(lat), Some(lon)) = (
+                row.get("stop_id"),
+                row.get("stop_lat").and_then(|v| v.parse().ok()),
+                row.get("stop_lon").and_then(|v| v.parse().ok()),
+            ) else {
+                return;
+            };
+            let stop = FeedStop {
+                code: row.get("stop_code").map(Cow::into_owned),
+                name: row.get("stop_name").unwrap_or_default().into_owned(),
+                lat,
+                lon,
+            };
+            feed.stops.insert(id.into_owned(), stop);
+        })?;
+        let area_stops: HashSet<&str> = feed
+            .stops
+            .iter()
+            .filter(|(_, stop)| in_area(stop))
+            .map(|(id, _)| id.as_str())
+            .collect();
+
+        read_table(&mut zip, "routes.txt", true, |row| {
+            if let Some(id) = row.get("route_id") {
+                let short_name = row
+                    .get("route_short_name")
+                    .or_else(|| row.get("route_long_name"))
+                    .unwrap_or_default();
+                feed.routes
+                    .insert(id.into_owned(), short_name.trim().to_string());
+            }
+        })?;
+
+        // Trip IDs of every route, to tell which routes call in the area
+        let mut route_of_trip: HashMap<String, String> = HashMap::new();
+        read_table(&mut zip, "trips.txt", true, |row| {
+            let (Some(route_id), Some(trip_id)) = (row.get("route_id"), row.get("trip_id")) else {
+                return;
+            };
+            let asked = feed
+                .routes
+                .get(route_id.as_ref())
+                .is_some_and(|no| route_numbers.contains(no.as_str()));
+            if asked {
+                feed.trips.insert(
+                    trip_id.to_string(),
+                    FeedTrip {
+                        route_id: route_id.to_string(),
+                        service_id: row.get("service_id").unwrap_or_default().into_owned(),
+                        first: None,
+                    },
+                );
+            }
+            route_of_trip.insert(trip_id.into_owned(), route_id.into_owned());
+        })?;
+
+        let mut routes_at_stops = HashSet::new();
+        read_table(&mut zip, "stop_times.txt", true, |row| {
+            let Some(trip_id) = row.get("trip_id") else {
+                return;
+            };
+            if let Some(stop_id) = row.get("stop_id")
+                && area_stops.contains(stop_id.as_ref())
+                && let Some(route_id) = route_of_trip.get(trip_id.as_ref())
+            {
+                routes_at_stops.insert(route_id.clone());
+            }
+            let Some(trip) = feed.trips.get_mut(trip_id.as_ref()) else {
+                return;
+            };
+            let (Some(sequence), Some(time)) = (
+                row.get("stop_sequence").and_then(|v| v.parse::<u32>().ok()),
+                row.get("departure_time")
+                    .or_else(|| row.get("arrival_time"))
+                    .filter(|t| !t.is_empty()),
+            ) else {
+                return;
+            };
+            if trip
+                .first
+                .as_ref()
+                .is_none_or(|(first, _)| sequence < *first)
+            {
+                trip.first = Some((sequence, hhmm(&time)));
+            }
+        })?;
+        feed.routes_at_stops = routes_at_stops;
+
+        let has_calendar = read_table(&mut zip, "calendar.txt", false, |row| {
+            let Some(id) = row.get("service_id") else {
+                return;
+            };
+            let mut days = [false; 7];
+            for (day, column) in days.iter_mut().zip(WEEKDAY_COLUMNS) {
+                *day = row.get(column).is_some_and(|v| v == "1");
+            }
+            feed.services.insert(id.into_owned(), days);
+        })?;
+        // Feeds without `calendar.txt` list every service date instead.
+        let has_dates = read_table(&mut zip, "calendar_dates.txt", false, |row| {
+            let (Some(id), Some(date)) = (row.get("service_id"), row.get("date")) else {
+                return;
+            };
+            if has_calendar || row.get("exception_type").as_deref() != Some("1") {
+                return;
+            }
+            if let Ok(date) = NaiveDate::parse_from_str(&date, "%Y%m%d") {
+                let days = feed.services.entry(id.into_owned()).or_default();
+                days[date.weekday().num_days_from_monday() as usize] = true;
+            }
+        })?;
+        if !has_calendar && !has_dates {
+            bail!(
+                "GTFS feed {:?} has neither calendar.txt nor calendar_dates.txt",
+                path
+            );
+        }
+
+        Ok(feed)
+    }
+}
+
+const WEEKDAY_COLUMNS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// A record of a feed table, with its fields looked up by column name.
+struct Row<'a> {
+    columns: &'a HashMap<String, usize>,
+    record: &'a ByteRecord,
+}
+
+impl Row<'_> {
+    fn get(&self, column: &str) -> Option<Cow<'_, str>> {
+        let bytes = self.record.get(*self.columns.get(column)?)?;
+        let text = match std::str::from_utf8(bytes) {
+            Ok(text) => Cow::Borrowed(text),
+            Err(_) => encoding_rs::EUC_KR.decode(bytes).0,
+        };
+        Some(match text {
+            Cow::Borrowed(text) => Cow::Borrowed(text.trim()),
+            Cow::Owned(text) => Cow::Owned(text.trim().to_string()),
+        })
+    }
+}
+
+/// Calls `row` for each record of the table `name`, which may sit in a
+/// folder of the archive. Returns whether the table exists; a missing
+/// `required` table is an error.
+fn read_table(
+    zip: &mut ZipArchive<File>,
+    name: &str,
+    required: bool,
+    mut row: impl FnMut(&Row),
+) -> Result<bool> {
+    let Some(index) = (0..zip.len()).find(|&i| {
+        zip.name_for_index(i)
+            .is_some_and(|n| n == name || n.ends_with(&format!("/{}", name)))
+    }) else {
+        if required {
+            bail!("GTFS feed has no {}", name);
+        }
+        return Ok(false);
+    };
+    let entry = zip.by_index(index)?;
+    read_records(entry, &mut row).with_context(|| format!("Reading {} of the GTFS feed", name))?;
+    Ok(true)
+}
+
+fn read_records(entry: impl Read, row: &mut impl FnMut(&Row)) -> Result<()> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(entry);
+    let columns: HashMap<String, usize> = reader
+        .byte_headers()?
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let header = String::from_utf8_lossy(h);
+            (header.trim_start_matches('\u{feff}').trim().to_string(), i)
+        })
+        .collect();
+    let mut record = ByteRecord::new();
+    while reader.read_byte_record(&mut record)? {
+        row(&Row {
+            columns: &columns,
+            record: &record,
+        });
+    }
+    Ok(())
+}
+
+/// "5:30:00" or "05:30:00" as "05:30" (hours past midnight stay above 23).
+fn hhmm(time: &str) -> String {
+    let mut parts = time.split(':');
+    let hour = parts.next().unwrap_or_default();
+    let minute = parts.next().unwrap_or("00");
+    format!("{:0>2}:{}", hour, minute)
+}
@@ -0,0 +1,390 @@
+//! GTFS Comparison Module
+//!
+//! This module compares the crawled data with an external GTFS feed, such
+//! as the official national feed: stops whose coordinates disagree, stops
+//! and routes missing on either side, and timetables whose number of trips
+//! or first and last departures differ. The feed is narrowed down to the
+//! routes calling at stops around the crawled stations, since national
+//! feeds reuse route numbers across cities.
+
+mod feed;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::export::{flatten_schedule, service_day_key};
+use crate::gtfs::feed::{Feed, FeedStop};
+use crate::schedule::load_merged_schedules;
+use crate::utils::geo::meters_between;
+
+/// Margin (degrees, ~1 km) around the crawled stations within which feed
+/// stops count as the same area
+const AREA_MARGIN_DEG: f64 = 0.01;
+
+/// Day compared for each schedule day type (0: Monday), so services split
+/// over several days are not counted twice
+const REPRESENTATIVE_DAYS: &[(&str, usize)] = &[("weekday", 2), ("weekend", 6), ("general", 2)];
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct CompareGtfsArgs {
+    /// Zipped GTFS feed to compare with
+    feed: PathBuf,
+
+    /// Route output directory (containing `routeMap.json`)
+    #[arg(long, default_value = "./storage/processed_routes")]
+    route_dir: PathBuf,
+
+    /// Schedule output directory (containing `schedules/` or `schedules.json`)
+    #[arg(long, default_value = "./storage")]
+    schedule_dir: PathBuf,
+
+    /// Report stops whose coordinates differ by more than this many meters
+    #[arg(long, default_value_t = 50.0)]
+    max_distance_m: f64,
+
+    /// Print the report as JSON instead of a list
+    #[arg(long)]
+    json: bool,
+}
+
+// ============================================================================
+// Report
+// ============================================================================
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Comparison {
+    stops_compared: usize,
+    /// Stops more than `--max-distance-m` away from their feed counterpart
+    stops_moved: Vec<MovedStop>,
+    /// Crawled stations with no feed stop of the same ID or code
+    stops_missing_in_feed: Vec<String>,
+    routes_compared: usize,
+    routes_missing_in_feed: Vec<String>,
+    /// Feed routes calling in the area that were not crawled
+    routes_missing_in_crawl: Vec<String>,
+    /// Day types whose trip count or first/last departure differ
+    timetables: Vec<TimetableDiff>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MovedStop {
+    node_id: String,
+    name: String,
+    feed_stop_id: String,
+    feed_name: String,
+    distance_m: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TimetableDiff {
+    route: String,
+    day_type: String,
+    trips: usize,
+    feed_trips: usize,
+    first: Option<String>,
+    feed_first: Option<String>,
+    last: Option<String>,
+    feed_last: Option<String>,
+}
+
+/// Departures of a route on one day type
+#[derive(Default)]
+struct Timetable {
+    trips: usize,
+    first: Option<String>,
+    last: Option<String>,
+}
+
+impl Timetable {
+    fn add(&mut self, time: &str) {
+        self.trips += 1;
+        if self.first.as_deref().is_none_or(|t| time < t) {
+            self.first = Some(time.to_string());
+        }
+        if self.last.as_deref().is_none_or(|t| time > t) {
+            self.last = Some(time.to_string());
+        }
+    }
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: CompareGtfsArgs) -> Result<()> {
+    let map_path = args.route_dir.join("routeMap.json");
+    let route_map: Value = serde_json::from_str(
+        &fs::read_to_string(&map_path).with_context(|| format!("Reading {:?}", map_path))?,
+    )
+    .with_context(|| format!("Parsing {:?}", map_path))?;
+    let stations = route_map["stations"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+    let route_numbers: BTreeSet<&str> = route_map["route_numbers"]
+        .as_object()
+        .into_iter()
+        .flat_map(|m| m.keys().map(String::as_str))
+        .collect();
+    if stations.is_empty() || route_numbers.is_empty() {
+        bail!("No routes or stations in {:?}", map_path);
+    }
+    let schedules = load_merged_schedules(&args.schedule_dir)?;
+
+    // The area of the crawled stations, with a margin
+    let coords: Vec<(f64, f64)> = stations
+        .values()
+        .filter_map(|s| Some((s["gpslong"].as_f64()?, s["gpslati"].as_f64()?)))
+        .collect();
+    let (mut min_lon, mut min_lat, mut max_lon, mut max_lat) = (180.0, 90.0, -180.0, -90.0);
+    for &(lon, lat) in &coords {
+        min_lon = f64::min(min_lon, lon);
+        min_lat = f64::min(min_lat, lat);
+        max_lon = f64::max(max_lon, lon);
+        max_lat = f64::max(max_lat, lat);
+    }
+    let in_area = |stop: &FeedStop| {
+        (min_lon - AREA_MARGIN_DEG..=max_lon + AREA_MARGIN_DEG).contains(&stop.lon)
+            && (min_lat - AREA_MARGIN_DEG..=max_lat + AREA_MARGIN_DEG).contains(&stop.lat)
+    };
+
+    info!("Reading GTFS feed {:?}...", args.feed);
+    let asked: HashSet<&str> = route_numbers.iter().copied().collect();
+    let feed = Feed::load(&args.feed, &asked, in_area)?;
+    info!(
+        stops = feed.stops.len(),
+        routes = feed.routes.len(),
+        "Feed has {} stops and {} routes, {} of them calling in the area",
+        feed.stops.len(),
+        feed.routes.len(),
+        feed.routes_at_stops.len()
+    );
+
+    let mut report = Comparison::default();
+
+    // Stops, matched by ID (TAGO node IDs), else by stop code (node number)
+    let area_by_code: HashMap<&str, (&String, &FeedStop)> = feed
+        .stops
+        .iter()
+        .filter(|(_, stop)| in_area(stop))
+        .filter_map(|(id, stop)| Some((stop.code.as_deref()?, (id, stop))))
+        .collect();
+    for (node_id, station) in &stations {
+        let (Some(lon), Some(lat)) = (station["gpslong"].as_f64(), station["gpslati"].as_f64())
+        else {
+            continue;
+        };
+        let matched = feed
+            .stops
+            .get_key_value(node_id)
+            .or_else(|| area_by_code.get(station["nodeno"].as_str()?).copied());
+        let Some((feed_id, feed_stop)) = matched else {
+            report.stops_missing_in_feed.push(node_id.clone());
+            continue;
+        };
+        report.stops_compared += 1;
+        let distance_m = meters_between(lon, lat, feed_stop.lon, feed_stop.lat);
+        if distance_m > args.max_distance_m {
+            report.stops_moved.push(MovedStop {
+                node_id: node_id.clone(),
+                name: station["nodenm"].as_str().unwrap_or("?").to_string(),
+                feed_stop_id: feed_id.clone(),
+                feed_name: feed_stop.name.clone(),
+                distance_m: distance_m.round(),
+            });
+        }
+    }
+    report
+        .stops_moved
+        .sort_by(|a, b| b.distance_m.total_cmp(&a.distance_m));
+
+    // Routes calling in the area, by number
+    let feed_numbers: BTreeSet<&str> = feed
+        .routes_at_stops
+        .iter()
+        .filter_map(|id| feed.routes.get(id))
+        .map(String::as_str)
+        .collect();
+    report.routes_compared = route_numbers.intersection(&feed_numbers).count();
+    report.routes_missing_in_feed = route_numbers
+        .difference(&feed_numbers)
+        .map(|s| s.to_string())
+        .collect();
+    report.routes_missing_in_crawl = feed_numbers
+        .difference(&route_numbers)
+        .map(|s| s.to_string())
+        .collect();
+
+    // Timetables per route number and day type
+    let mut feed_timetables: BTreeMap<&str, BTreeMap<&str, Timetable>> = BTreeMap::new();
+    for trip in feed.trips.values() {
+        if !feed.routes_at_stops.contains(&trip.route_id) {
+            continue;
+        }
+        let (Some(number), Some((_, time)), Some(days)) = (
+            feed.routes.get(&trip.route_id),
+            &trip.first,
+            feed.services.get(&trip.service_id),
+        ) else {
+            continue;
+        };
+        for &(day_type, day) in REPRESENTATIVE_DAYS {
+            if days[day] {
+                feed_timetables
+                    .entry(number.as_str())
+                    .or_default()
+                    .entry(day_type)
+                    .or_default()
+                    .add(time);
+            }
+        }
+    }
+    for (route, data) in &schedules {
+        if !feed_numbers.contains(route.as_str()) {
+            continue;
+        }
+        let mut timetables: BTreeMap<String, Timetable> = BTreeMap::new();
+        for departure in flatten_schedule(route, data) {
+            timetables
+                .entry(departure.day_type)
+                .or_default()
+                .add(&gtfs_clock(&departure.time));
+        }
+        let mut theirs_by_day = feed_timetables.remove(route.as_str()).unwrap_or_default();
+        // "general" overlaps both other day types, so compare one or the others
+        let general = timetables.contains_key("general");
+        theirs_by_day.retain(|day_type, _| (*day_type == "general") == general);
+        for day_type in theirs_by_day.keys() {
+            timetables.entry(day_type.to_string()).or_default();
+        }
+        for (day_type, ours) in timetables {
+            let theirs = theirs_by_day.remove(day_type.as_str()).unwrap_or_default();
+            if ours.trips != theirs.trips || ours.first != theirs.first || ours.last != theirs.last
+            {
+                report.timetables.push(TimetableDiff {
+                    route: route.clone(),
+                    day_type,
+                    trips: ours.trips,
+                    feed_trips: theirs.trips,
+                    first: ours.first,
+                    feed_first: theirs.first,
+                    last: ours.last,
+                    feed_last: theirs.last,
+                });
+            }
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report, &args);
+    }
+    Ok(())
+}
+
+fn print_report(report: &Comparison, args: &CompareGtfsArgs) {
+    println!(
+        "Compared with {:?}: {} stops, {} routes",
+        args.feed, report.stops_compared, report.routes_compared
+    );
+
+    println!(
+        "\nStops more than {} m from the feed: {}",
+        args.max_distance_m,
+        report.stops_moved.len()
+    );
+    for stop in &report.stops_moved {
+        println!(
+            " {:>6.0} m  {} ({}, feed {} {})",
+            stop.distance_m, stop.name, stop.node_id, stop.feed_stop_id, stop.feed_name
+        );
+    }
+    println!(
+        "Stops not in the feed: {}",
+        report.stops_missing_in_feed.len()
+    );
+    if !report.stops_missing_in_feed.is_empty() {
+        println!("  {}", report.stops_missing_in_feed.join(", "));
+    }
+
+    println!(
+        "\nRoutes not in the feed: {}",
+        report.routes_missing_in_feed.len()
+    );
+    if !report.routes_missing_in_feed.is_empty() {
+        println!("  {}", report.routes_missing_in_feed.join(", "));
+    }
+    println!(
+        "Feed routes in the area not crawled: {}",
+        report.routes_missing_in_crawl.len()
+    );
+    if !report.routes_missing_in_crawl.is_empty() {
+        println!("  {}", report.routes_missing_in_crawl.join(", "));
+    }
+
+    println!("\nTimetables differing: {}", report.timetables.len());
+    let time = |t: &Option<String>| t.clone().unwrap_or_else(|| "-".to_string());
+    for diff in &report.timetables {
+        println!(
+            " {} {}: {} trips (feed {}), first {} (feed {}), last {} (feed {})",
+            diff.route,
+            diff.day_type,
+            diff.trips,
+            diff.feed_trips,
+            time(&diff.first),
+            time(&diff.feed_first),
+            time(&diff.last),
+            time(&diff.feed_last)
+        );
+    }
+}
+
+/// A crawled "HH:MM" departure on the GTFS clock of the feed, where
+/// departures after midnight stay on the previous service day, past 24:00.
+fn gtfs_clock(time: &str) -> String {
+    match (service_day_key(time).0, time.split_once(':')) {
+        (true, Some((hour, minute))) => match hour.parse::<u32>() {
+            Ok(hour) => format!("{:02}:{}", hour + 24, minute),
+            Err(_) => time.to_string(),
+        },
+        _ => time.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn departures_after_midnight_run_past_24() {
+        assert_eq!(gtfs_clock("00:10"), "24:10");
+        assert_eq!(gtfs_clock("02:59"), "26:59");
+        assert_eq!(gtfs_clock("03:00"), "03:00");
+        assert_eq!(gtfs_clock("23:50"), "23:50");
+    }
+
+    #[test]
+    fn timetable_keeps_first_and_last() {
+        let mut timetable = Timetable::default();
+        for time in ["06:00", "23:50", "24:10", "05:40"] {
+            timetable.add(time);
+        }
+        assert_eq!(timetable.trips, 4);
+        assert_eq!(timetable.first.as_deref(), Some("05:40"));
+        assert_eq!(timetable.last.as_deref(), Some("24:10"));
+    }
+}
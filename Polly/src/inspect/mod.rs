@@ -0,0 +1,311 @@
+//! Output Query Module
+//!
+//! This module answers common questions directly from the output
+//! directories, instead of jq queries over the nested, hour-grouped schedule
+//! files: the departure times of a route, the stops of a route, and the
+//! stops several routes share. Each query prints a list, or JSON with `--json`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::schedule::load_merged_schedules;
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct InspectArgs {
+    #[command(subcommand)]
+    query: Query,
+
+    /// Route output directory (containing `routeMap.json`)
+    #[arg(long, global = true, default_value = "./storage/processed_routes")]
+    route_dir: PathBuf,
+
+    /// Schedule output directory (containing `schedules/` or `schedules.json`)
+    #[arg(long, global = true, default_value = "./storage")]
+    schedule_dir: PathBuf,
+
+    /// Print the answer as JSON instead of a list
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(clap::Subcommand)]
+enum Query {
+    /// Departure times of a route, per day type and direction
+    Departures {
+        /// Route number (e.g., "34-1")
+        route: String,
+
+        /// Only this day type (e.g., "weekday", "weekend")
+        #[arg(long)]
+        day: Option<String>,
+
+        /// Only directions containing this text (e.g., a terminus name)
+        #[arg(long)]
+        direction: Option<String>,
+    },
+    /// Stops of a route, in order, per TAGO route ID
+    Stops {
+        /// Route number (e.g., "34-1")
+        route: String,
+    },
+    /// Stops served by all of the given routes
+    SharedStops {
+        /// Route numbers (e.g., "2 4")
+        #[arg(num_args = 2.., required = true)]
+        routes: Vec<String>,
+    },
+}
+
+/// The departures of one direction on one day type
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Departures<'a> {
+    day_type: &'a str,
+    direction: &'a str,
+    /// `HH:MM`, in order
+    times: Vec<String>,
+    /// Note texts by note ID, for the times that carry one
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    notes: BTreeMap<&'a str, &'a str>,
+}
+
+/// A stop of `routeMap.json`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Stop<'a> {
+    node_id: &'a str,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    node_no: Option<&'a str>,
+    /// TAGO direction code (0: outbound, 1: inbound), for route stops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updowncd: Option<i64>,
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: InspectArgs) -> Result<()> {
+    match &args.query {
+        Query::Departures {
+            route,
+            day,
+            direction,
+        } => departures(&args, route, day.as_deref(), direction.as_deref()),
+        Query::Stops { route } => stops(&args, route),
+        Query::SharedStops { routes } => shared_stops(&args, routes),
+    }
+}
+
+fn departures(
+    args: &InspectArgs,
+    route: &str,
+    day: Option<&str>,
+    direction: Option<&str>,
+) -> Result<()> {
+    let schedules = load_merged_schedules(&args.schedule_dir)?;
+    let Some(schedule) = schedules.get(route) else {
+        bail!(
+            "No schedule of route {} in {:?} (known: {})",
+            route,
+            args.schedule_dir,
+            schedules.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+    };
+
+    let mut answer: Vec<Departures> = Vec::new();
+    for (day_type, hours) in schedule["schedule"].as_object().into_iter().flatten() {
+        if day.is_some_and(|d| d != day_type) {
+            continue;
+        }
+        // The schedule is grouped by hour, then direction.
+        let mut by_direction: BTreeMap<&str, Vec<(String, Option<&str>)>> = BTreeMap::new();
+        for (hour, directions) in hours.as_object().into_iter().flatten() {
+            for (name, entries) in directions.as_object().into_iter().flatten() {
+                if direction.is_some_and(|d| !name.contains(d)) {
+                    continue;
+                }
+                let times = by_direction.entry(name.as_str()).or_default();
+                for entry in entries.as_array().into_iter().flatten() {
+                    let minute = entry["minute"].as_str().unwrap_or("??");
+                    times.push((format!("{}:{}", hour, minute), entry["noteId"].as_str()));
+                }
+            }
+        }
+
+        for (name, mut times) in by_direction {
+            times.sort();
+            let notes = times
+                .iter()
+                .filter_map(|(_, id)| *id)
+                .filter_map(|id| Some((id, schedule["notes"][id].as_str()?)))
+                .collect();
+            answer.push(Departures {
+                day_type,
+                direction: name.trim(),
+                times: times
+                    .into_iter()
+                    .map(|(time, id)| match id {
+                        Some(id) if !args.json => format!("{}[{}]", time, id),
+                        _ => time,
+                    })
+                    .collect(),
+                notes,
+            });
+        }
+    }
+    if answer.is_empty() {
+        bail!(
+            "No departures of route {} match the day type and direction",
+            route
+        );
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&answer)?);
+        return Ok(());
+    }
+    for departures in &answer {
+        println!(
+            "{} {} -> {} ({} departures)",
+            route,
+            departures.day_type,
+            departures.direction,
+            departures.times.len()
+        );
+        println!("  {}", departures.times.join(" "));
+        for (id, text) in &departures.notes {
+            println!("  [{}] {}", id, text);
+        }
+    }
+    Ok(())
+}
+
+fn stops(args: &InspectArgs, route: &str) -> Result<()> {
+    let route_map = load_route_map(&args.route_dir)?;
+    let route_ids = route_ids(&route_map, route)?;
+
+    let mut answer: BTreeMap<&str, Vec<Stop>> = BTreeMap::new();
+    for route_id in route_ids {
+        let stops = route_map["route_details"][route_id]["sequence"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|s| {
+                let node_id = s["nodeid"].as_str()?;
+                let mut stop = station(&route_map, node_id);
+                stop.updowncd = s["updowncd"].as_i64();
+                Some(stop)
+            })
+            .collect();
+        answer.insert(route_id, stops);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&answer)?);
+        return Ok(());
+    }
+    for (route_id, stops) in &answer {
+        println!("{} ({}): {} stops", route, route_id, stops.len());
+        for (i, stop) in stops.iter().enumerate() {
+            println!(
+                " {:>3}. {} ({}, #{}){}",
+                i + 1,
+                stop.name,
+                stop.node_id,
+                stop.node_no.unwrap_or("-"),
+                if stop.updowncd == Some(1) {
+                    "  ↩"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    Ok(())
+}
+
+fn shared_stops(args: &InspectArgs, routes: &[String]) -> Result<()> {
+    let route_map = load_route_map(&args.route_dir)?;
+    for route in routes {
+        route_ids(&route_map, route)?;
+    }
+
+    let answer: Vec<Stop> = route_map["stations"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, station)| {
+            let serving: Vec<&str> = station["routes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            routes.iter().all(|r| serving.contains(&r.as_str()))
+        })
+        .map(|(node_id, _)| station(&route_map, node_id))
+        .collect();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&answer)?);
+        return Ok(());
+    }
+    println!(
+        "Stops shared by routes {}: {}",
+        routes.join(", "),
+        answer.len()
+    );
+    for stop in &answer {
+        println!(
+            " {} ({}, #{})",
+            stop.name,
+            stop.node_id,
+            stop.node_no.unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+fn load_route_map(route_dir: &Path) -> Result<Value> {
+    let map_path = route_dir.join("routeMap.json");
+    serde_json::from_str(
+        &fs::read_to_string(&map_path).with_context(|| format!("Reading {:?}", map_path))?,
+    )
+    .with_context(|| format!("Parsing {:?}", map_path))
+}
+
+/// The TAGO route IDs of route number `route`, failing if it is unknown.
+fn route_ids<'a>(route_map: &'a Value, route: &str) -> Result<Vec<&'a str>> {
+    let ids: Vec<&str> = route_map["route_numbers"][route]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    if ids.is_empty() {
+        bail!("No route {} in routeMap.json", route);
+    }
+    Ok(ids)
+}
+
+/// The stop `node_id` as listed in the stations of `routeMap.json`.
+fn station<'a>(route_map: &'a Value, node_id: &'a str) -> Stop<'a> {
+    let station = &route_map["stations"][node_id];
+    Stop {
+        node_id,
+        name: station["nodenm"].as_str().unwrap_or("?"),
+        node_no: station["nodeno"].as_str(),
+        updowncd: None,
+    }
+}
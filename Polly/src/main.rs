@@ -5,20 +5,125 @@
 //! and bus schedule crawling. It utilizes command-line arguments to
 //! determine which operation to perform.
 
+mod analyze;
+mod changelog;
 mod config;
+mod error;
+mod export;
+mod gtfs;
+mod inspect;
+mod migrate;
+mod near;
+mod pipeline;
 mod route;
 mod schedule;
+mod schema;
+mod settings;
+mod tago;
+mod types;
 mod utils;
+mod watch;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 
+use analyze::AnalyzeArgs;
+use changelog::ChangelogArgs;
+use export::ExportArgs;
+use gtfs::CompareGtfsArgs;
+use inspect::InspectArgs;
+use migrate::MigrateArgs;
+use near::NearArgs;
+use pipeline::PipelineArgs;
 use route::RouteArgs;
+#[cfg(feature = "bench")]
+use route::bench::BenchArgs;
 use schedule::ScheduleArgs;
+use schedule::fixtures::FixturesArgs;
+use schema::SchemaArgs;
+use settings::{Settings, TlsBackend};
+use types::TypesArgs;
+#[cfg(feature = "bench")]
+use utils::alloc::CountingAllocator;
+use utils::events::EventFormat;
+use utils::i18n::Lang;
+use utils::logging::{self, LogFormat};
+use watch::WatchArgs;
+
+#[cfg(feature = "bench")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Cli {
+    /// Settings file (default: ./polly.toml if present, or `POLLY_CONFIG`)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Proxy for all outbound requests (`http://`, `https://`, or `socks5://`
+    /// URL); without it, `HTTP_PROXY`, `HTTPS_PROXY`, and `ALL_PROXY` are used
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    /// PEM file of extra trusted root certificates (e.g., of a TLS-intercepting
+    /// proxy, or an intermediate the server does not send)
+    #[arg(long, global = true)]
+    ca_cert: Option<PathBuf>,
+
+    /// TLS implementation (`native` needs the `native-tls` feature; default: rustls)
+    #[arg(long, global = true, value_enum)]
+    tls: Option<TlsBackend>,
+
+    /// Log output format; filter events with `RUST_LOG` (e.g., `info,reqwest=warn`)
+    #[arg(long, global = true, value_enum, default_value = "pretty")]
+    log_format: LogFormat,
+
+    /// Also append every log event, with its fields, to this file
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Log more (-v: per-route progress, -vv: traces and HTTP details);
+    /// overrides `RUST_LOG`
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only log warnings and errors, for scripts and pipes; overrides `RUST_LOG`
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Language of progress messages and run summaries (default: `lang` in
+    /// the settings, else the locale, e.g., `LANG=ko_KR.UTF-8`)
+    #[arg(long, global = true, value_enum)]
+    lang: Option<Lang>,
+
+    /// Stream events (route fetched or failed, file written, warning) as
+    /// NDJSON to stdout, for wrapper scripts
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    events: Option<EventFormat>,
+
+    /// Append the event stream to this file instead of stdout (implies
+    /// `--events ndjson`)
+    #[arg(long, global = true, value_name = "PATH")]
+    events_file: Option<PathBuf>,
+
+    /// Record every HTTP exchange of the run to this cassette file
+    #[arg(long, global = true, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Answer HTTP requests from a cassette recorded with `--record`, offline
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+
+    /// Serve Prometheus metrics on `http://<ADDR>/metrics` while the command
+    /// runs (e.g., `0.0.0.0:9464`)
+    #[arg(long, global = true, value_name = "ADDR")]
+    metrics_addr: Option<SocketAddr>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -29,27 +134,171 @@ enum Commands {
     Route(RouteArgs),
     /// Bus Schedule Crawling
     Schedule(ScheduleArgs),
+    /// Run Routes, Schedules, Bundle, and Validation as One Pipeline
+    Pipeline(PipelineArgs),
+    /// Re-crawl Whenever the Upstream Route Lists Change
+    Watch(WatchArgs),
+    /// List Routes and Schedules Changed in Recent Crawls
+    Changelog(ChangelogArgs),
+    /// Manage Golden Test Fixtures of the Schedule Parser
+    Fixtures(FixturesArgs),
+    /// Upgrade Output Files to the Current Schema Version
+    Migrate(MigrateArgs),
+    /// Export Collected Data to Other Formats
+    Export(ExportArgs),
+    /// Analyze the Route Network (e.g., Shared Corridors)
+    Analyze(AnalyzeArgs),
+    /// List the Stops and Routes Near a Point
+    Near(NearArgs),
+    /// Query Departures and Stops in the Output Files
+    Inspect(InspectArgs),
+    /// Compare Stops, Routes, and Timetables With a GTFS Feed
+    CompareGtfs(CompareGtfsArgs),
+    /// Generate TypeScript Definitions of the Output Files
+    Types(TypesArgs),
+    /// Write JSON Schemas of the Output Files
+    Schema(SchemaArgs),
+    /// Measure the Geometry Pipeline on Previously Fetched Routes
+    #[cfg(feature = "bench")]
+    Bench(BenchArgs),
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(error::exit_code(&e))
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     // Load environment variables from .env file, if present
     dotenvy::dotenv().ok();
 
-    // Initialize tracing subscriber for logging
-    tracing_subscriber::fmt::init();
-
     // Parse command-line arguments
     let cli = Cli::parse();
-    match cli.command {
+
+    // Initialize tracing subscriber for logging
+    logging::init(
+        cli.log_format,
+        cli.log_file.as_deref(),
+        logging::verbosity_filter(cli.verbose, cli.quiet),
+    )?;
+
+    // The schedule printed by `schedule --stdout` must not mix with events.
+    if cli.events.is_some()
+        && cli.events_file.is_none()
+        && matches!(&cli.command, Commands::Schedule(args) if args.stdout)
+    {
+        return Err(error::config_error(
+            "`schedule --stdout` prints the schedule to stdout; use --events-file for the event stream",
+        ));
+    }
+    if cli.events.is_some() || cli.events_file.is_some() {
+        utils::events::init(cli.events_file.as_deref())?;
+    }
+
+    let mut settings = Settings::load(cli.config.as_deref())
+        .map_err(|e| error::config_error(format!("{:#}", e)))?;
+    utils::i18n::init(cli.lang.or(settings.lang));
+    if let Some(proxy) = cli.proxy {
+        settings.http.proxy = Some(proxy);
+    }
+    if let Some(ca_cert) = cli.ca_cert {
+        settings.http.ca_cert = Some(ca_cert);
+    }
+    if let Some(tls) = cli.tls {
+        settings.http.tls = tls;
+    }
+    if let Some(path) = &cli.record {
+        utils::cassette::record(path);
+    }
+    if let Some(path) = &cli.replay {
+        utils::cassette::replay(path)?;
+    }
+    if let Some(addr) = cli.metrics_addr {
+        utils::metrics::serve(addr).await?;
+    }
+
+    // The cassette is saved even when the command fails: that is often the
+    // run worth reproducing.
+    let result = run_command(cli.command, &settings).await;
+    utils::status::finish(&result);
+    utils::alert::send_summary(&settings.notify, &settings.http).await;
+    utils::cassette::save()?;
+    result?;
+    if utils::dry_run::is_enabled() {
+        utils::dry_run::log_summary();
+    }
+
+    Ok(())
+}
+
+async fn run_command(command: Commands, settings: &Settings) -> Result<()> {
+    match command {
         Commands::Route(args) => {
-            route::run(args).await.context("Route processing failed")?;
+            route::run(args, settings)
+                .await
+                .context("Route processing failed")?;
         }
         Commands::Schedule(args) => {
-            schedule::run(args)
+            schedule::run(args, settings)
                 .await
                 .context("Schedule processing failed")?;
         }
+        Commands::Pipeline(args) => {
+            pipeline::run(args, settings)
+                .await
+                .context("Pipeline failed")?;
+        }
+        Commands::Watch(args) => {
+            watch::run(args, settings).await.context("Watch failed")?;
+        }
+        Commands::Changelog(args) => {
+            changelog::run(args).await.context("Changelog failed")?;
+        }
+        Commands::Fixtures(args) => {
+            schedule::fixtures::run(args, settings)
+                .await
+                .context("Fixture command failed")?;
+        }
+        Commands::Migrate(args) => {
+            migrate::run(args).await.context("Migration failed")?;
+        }
+        Commands::Export(args) => {
+            export::run(args, settings).await.context("Export failed")?;
+        }
+        Commands::Analyze(args) => {
+            analyze::run(args, settings)
+                .await
+                .context("Analysis failed")?;
+        }
+        Commands::Near(args) => {
+            near::run(args).await.context("Nearby query failed")?;
+        }
+        Commands::Inspect(args) => {
+            inspect::run(args).await.context("Query failed")?;
+        }
+        Commands::CompareGtfs(args) => {
+            gtfs::run(args).await.context("GTFS comparison failed")?;
+        }
+        Commands::Schema(args) => {
+            schema::run(args)
+                .await
+                .context("Schema generation failed")?;
+        }
+        Commands::Types(args) => {
+            types::run(args).await.context("Type generation failed")?;
+        }
+        #[cfg(feature = "bench")]
+        Commands::Bench(args) => {
+            route::bench::run(args, settings)
+                .await
+                .context("Benchmark failed")?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,182 @@
+//! Output Schema Migration Module
+//!
+//! This module upgrades previously generated output files (schedules and
+//! routeMap.json) to the current schema version, so archived data and
+//! long-lived frontends keep working across format changes.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use tracing::info;
+
+use crate::config::{ROUTE_MAP_SCHEMA_VERSION, SCHEDULE_SCHEMA_VERSION};
+use crate::utils::write_atomic;
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct MigrateArgs {
+    /// Output files or directories to migrate (directories are scanned recursively)
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Only report which files need migration, without rewriting them
+    #[arg(long)]
+    check: bool,
+}
+
+// ============================================================================
+// Migration Registry
+// ============================================================================
+
+/// A single migration step, upgrading a document by exactly one version.
+type Migration = fn(&mut Value);
+
+/// Schedule migrations; entry `i` upgrades version `i` to `i + 1`.
+const SCHEDULE_MIGRATIONS: &[Migration] = &[schedule_v0_to_v1];
+
+/// routeMap.json migrations; entry `i` upgrades version `i` to `i + 1`.
+const ROUTE_MAP_MIGRATIONS: &[Migration] = &[route_map_v0_to_v1];
+
+const _: () = assert!(SCHEDULE_MIGRATIONS.len() == SCHEDULE_SCHEMA_VERSION as usize);
+const _: () = assert!(ROUTE_MAP_MIGRATIONS.len() == ROUTE_MAP_SCHEMA_VERSION as usize);
+
+/// Version 0 files predate `schemaVersion`; the structure is otherwise unchanged.
+fn schedule_v0_to_v1(_doc: &mut Value) {}
+
+/// Version 0 files predate `schemaVersion`; the structure is otherwise unchanged.
+fn route_map_v0_to_v1(_doc: &mut Value) {}
+
+/// Kind of output file, detected from its top-level keys.
+#[derive(Debug, Clone, Copy)]
+enum OutputKind {
+    Schedule,
+    /// Combined `schedules.json`; each entry under `routes` is a schedule document.
+    ScheduleBundle,
+    RouteMap,
+}
+
+impl OutputKind {
+    fn detect(doc: &Value) -> Option<Self> {
+        if doc.get("routeId").is_some() && doc.get("schedule").is_some() {
+            Some(Self::Schedule)
+        } else if doc.get("index").is_some() && doc.get("routes").is_some() {
+            Some(Self::ScheduleBundle)
+        } else if doc.get("route_numbers").is_some() && doc.get("stations").is_some() {
+            Some(Self::RouteMap)
+        } else {
+            None
+        }
+    }
+
+    fn migrations(self) -> &'static [Migration] {
+        match self {
+            Self::Schedule | Self::ScheduleBundle => SCHEDULE_MIGRATIONS,
+            Self::RouteMap => ROUTE_MAP_MIGRATIONS,
+        }
+    }
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: MigrateArgs) -> Result<()> {
+    let mut files = Vec::new();
+    for path in &args.paths {
+        collect_json_files(path, &mut files)?;
+    }
+
+    let mut migrated = 0usize;
+    for file in &files {
+        if migrate_file(file, args.check)? {
+            migrated += 1;
+        }
+    }
+
+    if args.check {
+        info!("✓ {} of {} files need migration.", migrated, files.len());
+    } else {
+        info!("✓ Migrated {} of {} files.", migrated, files.len());
+    }
+
+    Ok(())
+}
+
+/// Recursively collects `.json` files under `path`.
+fn collect_json_files(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        let mut entries: Vec<_> = fs::read_dir(path)?.filter_map(|e| e.ok()).collect();
+        entries.sort_by_key(|e| e.path());
+        for entry in entries {
+            collect_json_files(&entry.path(), out)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "json") {
+        out.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Upgrades a single file in place. Returns true if the file was (or, in
+/// check mode, would be) changed.
+fn migrate_file(path: &Path, check: bool) -> Result<bool> {
+    let content = fs::read_to_string(path)?;
+    let mut doc: Value =
+        serde_json::from_str(&content).with_context(|| format!("Invalid JSON in {:?}", path))?;
+
+    let Some(kind) = OutputKind::detect(&doc) else {
+        return Ok(false);
+    };
+
+    let migrations = kind.migrations();
+    let current = doc["schemaVersion"].as_u64().unwrap_or(0) as usize;
+
+    if current > migrations.len() {
+        anyhow::bail!(
+            "{:?} has schemaVersion {} which is newer than supported ({})",
+            path,
+            current,
+            migrations.len()
+        );
+    }
+
+    if current == migrations.len() {
+        return Ok(false);
+    }
+
+    info!(
+        " {:?}: {:?} v{} -> v{}",
+        path,
+        kind,
+        current,
+        migrations.len()
+    );
+
+    if check {
+        return Ok(true);
+    }
+
+    for (version, step) in migrations.iter().enumerate().skip(current) {
+        if let OutputKind::ScheduleBundle = kind {
+            for route in doc["routes"]
+                .as_object_mut()
+                .into_iter()
+                .flat_map(|m| m.values_mut())
+            {
+                step(route);
+                route["schemaVersion"] = json!(version + 1);
+            }
+        } else {
+            step(&mut doc);
+        }
+        doc["schemaVersion"] = json!(version + 1);
+    }
+
+    write_atomic(path, serde_json::to_string_pretty(&doc)?)?;
+
+    Ok(true)
+}
@@ -0,0 +1,123 @@
+//! Nearby Stops Query Module
+//!
+//! This module answers "what is near this point" from a previous route run:
+//! the closest stations in `routeMap.json` with the routes serving them, and
+//! the derived route lines passing within the radius. Useful for support
+//! questions and for checking stop coordinates in the field.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::route::combined::serving_routes;
+use crate::route::load_derived_routes;
+use crate::utils::geo::{closest_point_on_polyline, meters_between};
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct NearArgs {
+    /// Latitude of the point (WGS84)
+    #[arg(allow_negative_numbers = true)]
+    lat: f64,
+
+    /// Longitude of the point (WGS84)
+    #[arg(allow_negative_numbers = true)]
+    lng: f64,
+
+    /// Search radius in meters
+    #[arg(short, long, default_value_t = 300.0)]
+    radius: f64,
+
+    /// Maximum number of stops to list
+    #[arg(short, long, default_value_t = 10)]
+    limit: usize,
+
+    /// Route output directory of a previous run (containing `routeMap.json`)
+    #[arg(short, long, default_value = "./storage/processed_routes")]
+    input_dir: PathBuf,
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: NearArgs) -> Result<()> {
+    let map_path = args.input_dir.join("routeMap.json");
+    let route_map: Value = serde_json::from_str(
+        &fs::read_to_string(&map_path).with_context(|| format!("Reading {:?}", map_path))?,
+    )
+    .with_context(|| format!("Parsing {:?}", map_path))?;
+
+    let details: BTreeMap<String, Value> = route_map["route_details"]
+        .as_object()
+        .map(|m| m.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+        .unwrap_or_default();
+    let stations: BTreeMap<String, Value> = route_map["stations"]
+        .as_object()
+        .map(|m| m.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+        .unwrap_or_default();
+    let serving = serving_routes(&details);
+
+    // Closest stations within the radius
+    let mut stops: Vec<(f64, &str, &Value)> = stations
+        .iter()
+        .filter_map(|(node_id, station)| {
+            let lon = station["gpslong"].as_f64()?;
+            let lat = station["gpslati"].as_f64()?;
+            let d = meters_between(args.lng, args.lat, lon, lat);
+            (d <= args.radius).then_some((d, node_id.as_str(), station))
+        })
+        .collect();
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    println!(
+        "Stops within {} m of ({}, {}): {}",
+        args.radius,
+        args.lat,
+        args.lng,
+        stops.len()
+    );
+    for (d, node_id, station) in stops.iter().take(args.limit) {
+        let routes = serving
+            .get(node_id)
+            .map(|(numbers, _)| numbers.iter().copied().collect::<Vec<_>>().join(", "))
+            .unwrap_or_else(|| "no routes".to_string());
+        println!(
+            " {:>6.0} m  {} ({}, #{})  [{}]",
+            d,
+            station["nodenm"].as_str().unwrap_or("?"),
+            node_id,
+            station["nodeno"].as_str().unwrap_or("-"),
+            routes
+        );
+    }
+    if stops.len() > args.limit {
+        println!(" ... {} more", stops.len() - args.limit);
+    }
+
+    // Route lines passing within the radius, if derived routes exist
+    if args.input_dir.join("derived_routes").is_dir() {
+        let mut lines: Vec<(f64, String, String)> = load_derived_routes(&args.input_dir)?
+            .into_iter()
+            .filter_map(|f| {
+                let (_, d) =
+                    closest_point_on_polyline((args.lng, args.lat), &f.geometry.coordinates)?;
+                (d <= args.radius).then_some((d, f.properties.route_no, f.id))
+            })
+            .collect();
+        lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        println!("\nRoutes passing within {} m: {}", args.radius, lines.len());
+        for (d, route_no, route_id) in &lines {
+            println!(" {:>6.0} m  {} ({})", d, route_no, route_id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,416 @@
+//! Pipeline Module
+//!
+//! Runs the whole data pipeline as one command: the route collection and
+//! snapping (`route`), the schedule crawl (`schedule`), the protobuf bundle
+//! for the mobile client (`export bundle`), and a final schema validation of
+//! everything in the storage directory. The steps are the subcommands of the
+//! same names, so they behave (and lock, and record failures) exactly as when
+//! chained by hand, but the run keeps one `status.json`, sends one summary, and
+//! writes one `pipeline_report.json`.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use clap::{Args, FromArgMatches, ValueEnum};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::export::{self, ExportArgs};
+use crate::route::model::Snapper;
+use crate::route::{self, RouteArgs};
+use crate::schedule::{self, ScheduleArgs, load_merged_schedules};
+use crate::schema::{OutputFormat, validate_files, validate_values};
+use crate::settings::Settings;
+use crate::utils::compress::Compression;
+use crate::utils::i18n::Msg;
+use crate::utils::status::{self, STATUS_FILE};
+use crate::utils::{dry_run, now, write_atomic};
+
+/// File name of the pipeline report within the storage directory.
+pub const PIPELINE_REPORT_FILE: &str = "pipeline_report.json";
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(Clone, clap::Args)]
+pub struct PipelineArgs {
+    /// Storage directory: routes go to `processed_routes/`, schedules to
+    /// `schedules/`, and the bundle to `bundle.pb`
+    #[arg(short, long, default_value = "./storage")]
+    pub storage_dir: PathBuf,
+
+    /// City code of the routes (default: Wonju -> 32020)
+    #[arg(long)]
+    pub city_code: Option<String>,
+
+    /// Snapping backends tried in order, as with `route --snapper`
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "osrm")]
+    pub snapper: Vec<Snapper>,
+
+    /// Skip the route step and use the routes already in the storage directory
+    #[arg(long)]
+    pub skip_routes: bool,
+
+    /// Skip the schedule step and use the schedules already in the storage directory
+    #[arg(long)]
+    pub skip_schedules: bool,
+
+    /// Skip writing `bundle.pb`
+    #[arg(long)]
+    pub no_bundle: bool,
+
+    /// Also write `all_routes.geojson` and `schedules.json`
+    #[arg(long)]
+    pub combine: bool,
+
+    /// Also write pre-compressed copies of large outputs (e.g., `gz,br`)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub compress: Vec<Compression>,
+
+    /// Add romanized names to stations and schedule directions
+    #[arg(long)]
+    pub romanize: bool,
+
+    /// Treat schedule parse warnings as errors
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Only re-process the routes and schedules recorded in the last run's
+    /// `failures.json` files
+    #[arg(long)]
+    pub retry_failed: bool,
+
+    /// Run every step, but only report the files that would be written
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Wait for other runs writing to the storage directory to finish,
+    /// instead of failing
+    #[arg(long)]
+    pub wait_lock: bool,
+
+    /// Fail a step (exit code 8) if more than this percentage of its items
+    /// failed, even though the others were written
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub fail_on_partial: Option<u8>,
+
+    /// Crawl the schedules even if the site's robots.txt disallows it
+    #[arg(long)]
+    pub ignore_robots: bool,
+}
+
+// ============================================================================
+// Report
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StepReport {
+    name: &'static str,
+    outcome: Outcome,
+    elapsed_ms: u64,
+    /// Items the step recorded as failed (see its `failures.json`)
+    failed_items: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PipelineReport {
+    generated_at: String,
+    steps: Vec<StepReport>,
+}
+
+impl PipelineReport {
+    fn skip(&mut self, name: &'static str) {
+        info!(
+            step = name,
+            outcome = "skipped",
+            "{}",
+            Msg::StepSkipped(name)
+        );
+        self.steps.push(StepReport {
+            name,
+            outcome: Outcome::Skipped,
+            elapsed_ms: 0,
+            failed_items: 0,
+            error: None,
+        });
+    }
+
+    fn failed(&self) -> bool {
+        self.steps.iter().any(|s| s.outcome == Outcome::Failed)
+    }
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: PipelineArgs, settings: &Settings) -> Result<()> {
+    if args.dry_run {
+        dry_run::enable();
+    }
+    let routes_dir = args.storage_dir.join("processed_routes");
+    status::start("pipeline", args.storage_dir.join(STATUS_FILE));
+
+    let mut report = PipelineReport::default();
+    // The first failure is returned (with its exit code) once every step ran.
+    let mut first_error: Option<anyhow::Error> = None;
+
+    // Routes and schedules come from different upstreams; one failing does
+    // not keep the other from being refreshed.
+    if args.skip_routes {
+        report.skip("route");
+    } else {
+        let route_args: RouteArgs = parse_args("route", route_argv(&args, &routes_dir))?;
+        let result = step(&mut report, "route", route::run(route_args, settings)).await;
+        first_error = first_error.or(result.err());
+    }
+
+    if args.skip_schedules {
+        report.skip("schedule");
+    } else {
+        let schedule_args: ScheduleArgs = parse_args("schedule", schedule_argv(&args))?;
+        let result = step(
+            &mut report,
+            "schedule",
+            schedule::run(schedule_args, settings),
+        )
+        .await;
+        first_error = first_error.or(result.err());
+    }
+
+    // The bundle and the validation only make sense over complete outputs.
+    if report.failed() || args.no_bundle {
+        report.skip("bundle");
+    } else {
+        let export_args: ExportArgs = parse_args("export", bundle_argv(&args, &routes_dir))?;
+        let result = step(&mut report, "bundle", export::run(export_args, settings)).await;
+        first_error = first_error.or(result.err());
+    }
+
+    if report.failed() || args.dry_run {
+        report.skip("validate");
+    } else {
+        let result = step(&mut report, "validate", async {
+            validate_storage(&args.storage_dir, &routes_dir)
+        })
+        .await;
+        first_error = first_error.or(result.err());
+    }
+
+    report.generated_at = now().to_rfc3339();
+    write_atomic(
+        &args.storage_dir.join(PIPELINE_REPORT_FILE),
+        serde_json::to_string_pretty(&report)?,
+    )?;
+    for s in &report.steps {
+        info!(
+            step = s.name,
+            outcome = s.outcome.as_str(),
+            elapsed_ms = s.elapsed_ms,
+            "   {:<9} {:<8} {:>7.1}s  {}",
+            s.name,
+            s.outcome.as_str(),
+            s.elapsed_ms as f64 / 1000.0,
+            Msg::SummaryFailedItems(s.failed_items)
+        );
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => {
+            info!(outcome = "ok", "{}", Msg::PipelineFinished);
+            Ok(())
+        }
+    }
+}
+
+/// Runs one step and records how it went.
+async fn step(
+    report: &mut PipelineReport,
+    name: &'static str,
+    work: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    info!(step = name, "{}", Msg::StepStart(name));
+    let errors_before = status::current().map_or(0, |s| s.errors);
+    let started = Instant::now();
+
+    let result = work.await.with_context(|| format!("Step {} failed", name));
+
+    let errors_after = status::current().map_or(0, |s| s.errors);
+    let error = result.as_ref().err().map(|e| format!("{:#}", e));
+    if let Some(e) = &error {
+        error!(step = name, outcome = "failed", "{}", e);
+        status::error(e.clone());
+    } else if errors_after > errors_before {
+        warn!(
+            step = name,
+            count = errors_after - errors_before,
+            "{}",
+            Msg::StepFailedItems {
+                step: name,
+                count: errors_after - errors_before
+            }
+        );
+    }
+    report.steps.push(StepReport {
+        name,
+        outcome: if error.is_some() {
+            Outcome::Failed
+        } else {
+            Outcome::Ok
+        },
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        failed_items: errors_after.saturating_sub(errors_before),
+        error,
+    });
+    result
+}
+
+/// Parses the arguments of a step as its subcommand would, so the step gets
+/// the same defaults and checks as when run on its own.
+fn parse_args<T: Args + FromArgMatches>(command: &'static str, argv: Vec<OsString>) -> Result<T> {
+    let matches = T::augment_args(clap::Command::new(command))
+        .no_binary_name(true)
+        .try_get_matches_from(argv)
+        .with_context(|| format!("Building the {} step", command))?;
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+fn route_argv(args: &PipelineArgs, routes_dir: &Path) -> Vec<OsString> {
+    let mut argv: Vec<OsString> = vec!["--output-dir".into(), routes_dir.into()];
+    if let Some(city_code) = &args.city_code {
+        argv.extend(["--city-code".into(), city_code.into()]);
+    }
+    argv.extend(["--snapper".into(), value_list(&args.snapper).into()]);
+    if !args.compress.is_empty() {
+        argv.extend(["--compress".into(), value_list(&args.compress).into()]);
+    }
+    let flags = [
+        (args.combine, "--all-routes"),
+        (args.romanize, "--romanize"),
+        (args.retry_failed, "--retry-failed"),
+        (args.dry_run, "--dry-run"),
+        (args.wait_lock, "--wait-lock"),
+    ];
+    argv.extend(
+        flags
+            .into_iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, f)| f.into()),
+    );
+    if let Some(percent) = args.fail_on_partial {
+        argv.extend(["--fail-on-partial".into(), percent.to_string().into()]);
+    }
+    argv
+}
+
+fn schedule_argv(args: &PipelineArgs) -> Vec<OsString> {
+    let mut argv: Vec<OsString> = vec!["--output-dir".into(), args.storage_dir.clone().into()];
+    if !args.compress.is_empty() {
+        argv.extend(["--compress".into(), value_list(&args.compress).into()]);
+    }
+    let flags = [
+        (args.combine, "--combine"),
+        (args.romanize, "--romanize"),
+        (args.strict, "--strict"),
+        (args.retry_failed, "--retry-failed"),
+        (args.dry_run, "--dry-run"),
+        (args.wait_lock, "--wait-lock"),
+        (args.ignore_robots, "--ignore-robots"),
+    ];
+    argv.extend(
+        flags
+            .into_iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, f)| f.into()),
+    );
+    if let Some(percent) = args.fail_on_partial {
+        argv.extend(["--fail-on-partial".into(), percent.to_string().into()]);
+    }
+    argv
+}
+
+fn bundle_argv(args: &PipelineArgs, routes_dir: &Path) -> Vec<OsString> {
+    vec![
+        "bundle".into(),
+        "--input-dir".into(),
+        args.storage_dir.clone().into(),
+        "--routes-dir".into(),
+        routes_dir.into(),
+    ]
+}
+
+/// E.g., "osrm,valhalla".
+fn value_list<T: ValueEnum>(values: &[T]) -> String {
+    values
+        .iter()
+        .filter_map(|v| v.to_possible_value())
+        .map(|v| v.get_name().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Checks every route and schedule file in the storage directory against its
+/// schema, including those a skipped step left from an earlier run.
+fn validate_storage(storage_dir: &Path, routes_dir: &Path) -> Result<()> {
+    let route_map = routes_dir.join("routeMap.json");
+    if route_map.exists() {
+        validate_files(OutputFormat::RouteMap, &[route_map])?;
+    }
+
+    let derived_dir = routes_dir.join("derived_routes");
+    let mut derived: Vec<PathBuf> = Vec::new();
+    if derived_dir.is_dir() {
+        derived = fs::read_dir(&derived_dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "geojson"))
+            .collect();
+        validate_files(OutputFormat::DerivedRoute, &derived)?;
+    }
+
+    let schedules = load_merged_schedules(storage_dir)?;
+    validate_values(
+        OutputFormat::Schedule,
+        schedules
+            .iter()
+            .map(|(route, doc)| (format!("schedule {}", route), doc)),
+    )?;
+
+    info!(
+        routes = derived.len(),
+        schedules = schedules.len(),
+        "{}",
+        Msg::Validated {
+            routes: derived.len(),
+            schedules: schedules.len()
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! Geometry Anomaly Detection
+//!
+//! Flags suspicious artifacts in snapped route lines, which the chunk merging
+//! is prone to: single-point spikes off the corridor, immediate back-and-forth
+//! U-turns, and self-intersections. Findings are listed in the run report.
+
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{AABB, RTree};
+
+use crate::config::{ANOMALY_SPIKE_M, ANOMALY_UTURN_DEG};
+use crate::utils::geo::{bearing, closest_point_on_polyline, meters_between};
+use crate::utils::report::Issue;
+
+/// Self-intersections reported per route before the rest are summarized
+const MAX_INTERSECTIONS: usize = 10;
+
+/// Detects anomalies in `line`. `chunk_starts` holds the coordinate indices
+/// where merged snapping chunks meet, so artifacts there can be told apart.
+pub fn detect_anomalies(line: &[Vec<f64>], chunk_starts: &[usize]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if line.len() < 3 {
+        return issues;
+    }
+
+    let boundary_note = |i: usize| {
+        if chunk_starts.iter().any(|&b| b.abs_diff(i) <= 1) {
+            " at a chunk boundary"
+        } else {
+            ""
+        }
+    };
+
+    for i in 1..line.len() - 1 {
+        let (prev, cur, next) = (&line[i - 1], &line[i], &line[i + 1]);
+
+        // Spike: one vertex far off the line joining its neighbours, which are
+        // themselves close together (a narrow excursion, not a corner)
+        let offset = closest_point_on_polyline((cur[0], cur[1]), &[prev.clone(), next.clone()])
+            .map_or_else(
+                || meters_between(prev[0], prev[1], cur[0], cur[1]),
+                |(_, d)| d,
+            );
+        let gap = meters_between(prev[0], prev[1], next[0], next[1]);
+        if offset > ANOMALY_SPIKE_M && gap < offset {
+            issues.push(
+                Issue::new(
+                    "spike",
+                    format!(
+                        "vertex {} is {:.0} m off the line{}",
+                        i,
+                        offset,
+                        boundary_note(i)
+                    ),
+                )
+                .at(cur[0], cur[1]),
+            );
+            continue;
+        }
+
+        // U-turn: the line doubles back on itself
+        if prev == cur || cur == next {
+            continue;
+        }
+        let turn = (bearing(cur, next) - bearing(prev, cur)).rem_euclid(360.0);
+        let turn = turn.min(360.0 - turn);
+        if turn >= ANOMALY_UTURN_DEG {
+            issues.push(
+                Issue::new(
+                    "u_turn",
+                    format!(
+                        "line turns back {:.0}° at vertex {}{}",
+                        turn,
+                        i,
+                        boundary_note(i)
+                    ),
+                )
+                .at(cur[0], cur[1]),
+            );
+        }
+    }
+
+    issues.extend(self_intersections(line));
+    issues
+}
+
+/// Crossings between non-adjacent segments of the line.
+fn self_intersections(line: &[Vec<f64>]) -> Vec<Issue> {
+    let segments: Vec<GeomWithData<Rectangle<[f64; 2]>, usize>> = line
+        .windows(2)
+        .enumerate()
+        .map(|(i, s)| {
+            GeomWithData::new(
+                Rectangle::from_corners([s[0][0], s[0][1]], [s[1][0], s[1][1]]),
+                i,
+            )
+        })
+        .collect();
+    let tree = RTree::bulk_load(segments.clone());
+
+    let mut crossings = Vec::new();
+    for seg in &segments {
+        let i = seg.data;
+        let envelope = AABB::from_corners(seg.geom().lower(), seg.geom().upper());
+        let mut candidates: Vec<usize> = tree
+            .locate_in_envelope_intersecting(&envelope)
+            .map(|other| other.data)
+            .filter(|&j| j > i + 1)
+            .collect();
+        candidates.sort_unstable();
+
+        for j in candidates {
+            if let Some(p) = segment_crossing(&line[i], &line[i + 1], &line[j], &line[j + 1]) {
+                crossings.push((i, j, p));
+            }
+        }
+    }
+
+    let total = crossings.len();
+    let mut issues: Vec<Issue> = crossings
+        .into_iter()
+        .take(MAX_INTERSECTIONS)
+        .map(|(i, j, (x, y))| {
+            Issue::new(
+                "self_intersection",
+                format!("segments {} and {} cross", i, j),
+            )
+            .at(x, y)
+        })
+        .collect();
+    if total > MAX_INTERSECTIONS {
+        issues.push(Issue::new(
+            "self_intersection",
+            format!("{} more crossings not listed", total - MAX_INTERSECTIONS),
+        ));
+    }
+    issues
+}
+
+/// Point where segments `a1`-`a2` and `b1`-`b2` properly cross (collinear
+/// overlaps and shared endpoints do not count).
+fn segment_crossing(a1: &[f64], a2: &[f64], b1: &[f64], b2: &[f64]) -> Option<(f64, f64)> {
+    let (rx, ry) = (a2[0] - a1[0], a2[1] - a1[1]);
+    let (sx, sy) = (b2[0] - b1[0], b2[1] - b1[1]);
+    let denom = rx * sy - ry * sx;
+    if denom == 0.0 {
+        return None;
+    }
+
+    let (qx, qy) = (b1[0] - a1[0], b1[1] - a1[1]);
+    let t = (qx * sy - qy * sx) / denom;
+    let u = (qx * ry - qy * rx) / denom;
+
+    (t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0).then(|| (a1[0] + t * rx, a1[1] + t * ry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(points: &[[f64; 2]]) -> Vec<Vec<f64>> {
+        points.iter().map(|p| p.to_vec()).collect()
+    }
+
+    fn kinds(issues: &[Issue]) -> Vec<&str> {
+        issues.iter().map(|i| i.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn corners_are_not_anomalies() {
+        let line = line(&[
+            [127.900, 37.300],
+            [127.900, 37.302],
+            [127.902, 37.302],
+            [127.904, 37.302],
+        ]);
+        assert!(detect_anomalies(&line, &[]).is_empty());
+        assert!(detect_anomalies(&line[..2], &[]).is_empty());
+    }
+
+    #[test]
+    fn detects_spikes() {
+        // A single vertex about 530 m east of a line heading north
+        let line = line(&[
+            [127.900, 37.300],
+            [127.906, 37.3005],
+            [127.900, 37.301],
+            [127.900, 37.302],
+        ]);
+        let issues = detect_anomalies(&line, &[]);
+        assert_eq!(kinds(&issues), ["spike"]);
+        assert_eq!(issues[0].at, Some([127.906, 37.3005]));
+        assert!(!issues[0].detail.contains("chunk boundary"));
+
+        let issues = detect_anomalies(&line, &[2]);
+        assert!(issues[0].detail.ends_with("at a chunk boundary"));
+    }
+
+    #[test]
+    fn detects_u_turns() {
+        let line = line(&[[127.900, 37.300], [127.900, 37.302], [127.90001, 37.301]]);
+        let issues = detect_anomalies(&line, &[]);
+        assert_eq!(kinds(&issues), ["u_turn"]);
+        assert_eq!(issues[0].at, Some([127.900, 37.302]));
+    }
+
+    #[test]
+    fn detects_self_intersections() {
+        let line = line(&[
+            [127.900, 37.300],
+            [127.910, 37.302],
+            [127.910, 37.300],
+            [127.900, 37.302],
+        ]);
+        let issues = detect_anomalies(&line, &[]);
+        assert_eq!(kinds(&issues), ["self_intersection"]);
+        assert_eq!(issues[0].detail, "segments 0 and 2 cross");
+        let [x, y] = issues[0].at.unwrap();
+        assert!((x - 127.905).abs() < 1e-9 && (y - 37.301).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shared_endpoints_and_overlaps_do_not_cross() {
+        let (a, b, c) = ([0.0, 0.0], [1.0, 0.0], [2.0, 0.0]);
+        assert_eq!(segment_crossing(&a, &b, &b, &[1.0, 1.0]), None);
+        assert_eq!(segment_crossing(&a, &c, &b, &[3.0, 0.0]), None);
+        assert_eq!(
+            segment_crossing(&a, &[2.0, 2.0], &[0.0, 2.0], &c),
+            Some((1.0, 1.0))
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Geometry Pipeline Benchmark
+//!
+//! Runs Phase 2 (`process_raw_to_derived`) over the raw routes of a previous
+//! run several times and reports its throughput and allocations, so that
+//! changes to the geometry code can be measured without a routing server.
+//!
+//! Routing requests are answered by a stub backend that follows the straight
+//! lines between the stops with a vertex every [`STUB_SPACING_M`] meters, about
+//! as dense as OSRM geometry. Derived files go to a temporary directory, and
+//! log events of the pipeline are muted while it is measured.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use tracing::info;
+use tracing::instrument::WithSubscriber;
+use tracing::subscriber::NoSubscriber;
+
+use crate::route::model::{BusRouteProcessor, GeometryEncoding, RawStop, SnappedRoute, Snapper};
+use crate::route::snapper::{SnapChain, SnappingBackend};
+use crate::settings::Settings;
+use crate::tago::client::TagoClient;
+use crate::utils::alloc;
+use crate::utils::crs::Crs;
+use crate::utils::ensure_dir;
+use crate::utils::geo::meters_between;
+use crate::utils::http::HttpClient;
+
+/// Vertex spacing (meters) of the stub geometry
+const STUB_SPACING_M: f64 = 15.0;
+/// Bus speed (m/s) the stub derives leg durations from
+const STUB_SPEED_MPS: f64 = 8.0;
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// Route output directory of a previous run (containing `raw_routes/`)
+    #[arg(short, long, default_value = "./storage/processed_routes")]
+    input_dir: PathBuf,
+
+    /// Passes over all raw routes
+    #[arg(short = 'n', long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    iterations: u32,
+
+    /// Simplify the geometry with this Douglas-Peucker tolerance (meters)
+    #[arg(long)]
+    simplify_m: Option<f64>,
+
+    /// How the derived route line is written
+    #[arg(long, value_enum, default_value = "geojson")]
+    geometry_encoding: GeometryEncoding,
+
+    /// Skip drift correction of the stop coordinates
+    #[arg(long)]
+    no_sanitize: bool,
+}
+
+/// Measurements of one pass over all raw routes
+struct Pass {
+    elapsed: Duration,
+    routes: usize,
+    points: usize,
+    bytes: usize,
+    allocations: u64,
+    allocated: u64,
+    /// Heap growth at the peak, over the heap size before the pass
+    peak: u64,
+}
+
+pub async fn run(args: BenchArgs, settings: &Settings) -> Result<()> {
+    let raw_dir = args.input_dir.join("raw_routes");
+    let mut raw_files: Vec<PathBuf> = fs::read_dir(&raw_dir)
+        .with_context(|| format!("Reading raw routes {:?}", raw_dir))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    raw_files.sort();
+    if raw_files.is_empty() {
+        anyhow::bail!("No raw routes in {:?}; run `route` first", raw_dir);
+    }
+
+    let derived_dir = std::env::temp_dir().join(format!("polly-bench-{}", std::process::id()));
+    ensure_dir(&derived_dir)?;
+    let processor = BusRouteProcessor {
+        // Never called: the raw routes are already on disk.
+        tago: TagoClient::new(
+            HttpClient::new(&settings.http)?,
+            &settings.urls,
+            &settings.service_key,
+            0.0,
+        )?,
+        city_code: settings.route.city_code.clone(),
+        raw_dir,
+        derived_dir: derived_dir.clone(),
+        mapping_file: derived_dir.join("routeMap.json"),
+        snapper: SnapChain::new(vec![Box::new(StubRouter)]),
+        chunk_size: settings.route.osrm_chunk_size,
+        sanitize: !args.no_sanitize,
+        snap_threshold_m: settings.route.snap_threshold_m,
+        simplify_m: args.simplify_m,
+        geometry_encoding: args.geometry_encoding,
+        polyline_precision: 6,
+        compress: Vec::new(),
+        romanize: false,
+        route_info: false,
+        max_age: None,
+        conditional: false,
+        // Every pass must redo the work.
+        reprocess_all: true,
+        flatgeobuf: false,
+        split_map: false,
+        hashed_names: false,
+        crs: Crs::Wgs84,
+        provenance: settings.agency.provenance(),
+    };
+
+    info!(
+        count = raw_files.len(),
+        iterations = args.iterations,
+        "Benchmarking Phase 2 over {} raw routes, {} pass(es)",
+        raw_files.len(),
+        args.iterations
+    );
+    let mut passes = Vec::new();
+    for _ in 0..args.iterations {
+        let pass = run_pass(&processor, &raw_files)
+            .with_subscriber(NoSubscriber::default())
+            .await;
+        passes.push(pass?);
+    }
+    fs::remove_dir_all(&derived_dir).ok();
+
+    print_passes(&passes);
+    Ok(())
+}
+
+/// Processes every raw file once.
+async fn run_pass(processor: &BusRouteProcessor, raw_files: &[PathBuf]) -> Result<Pass> {
+    alloc::reset_peak();
+    let before = alloc::stats();
+    let started = Instant::now();
+
+    let mut pass = Pass {
+        elapsed: Duration::ZERO,
+        routes: 0,
+        points: 0,
+        bytes: 0,
+        allocations: 0,
+        allocated: 0,
+        peak: 0,
+    };
+    for path in raw_files {
+        let stats = processor.process_raw_to_derived(path).await?;
+        pass.routes += 1;
+        pass.points += stats.points_after;
+        pass.bytes += stats.output_bytes;
+    }
+
+    pass.elapsed = started.elapsed();
+    let after = alloc::stats();
+    pass.allocations = after.allocations - before.allocations;
+    pass.allocated = after.bytes - before.bytes;
+    pass.peak = after.peak.saturating_sub(before.live);
+    Ok(pass)
+}
+
+/// Prints one line per pass and the median pass.
+fn print_passes(passes: &[Pass]) {
+    println!(
+        "{:>4}  {:>9}  {:>9}  {:>11}  {:>11}  {:>10}  {:>10}  {:>8}",
+        "Pass",
+        "Time (ms)",
+        "Routes/s",
+        "Points/s",
+        "Allocs",
+        "Alloc (MB)",
+        "Peak (MB)",
+        "Out (MB)"
+    );
+    let row = |label: &str, p: &Pass| {
+        let secs = p.elapsed.as_secs_f64().max(f64::EPSILON);
+        println!(
+            "{:>4}  {:>9.1}  {:>9.1}  {:>11.0}  {:>11}  {:>10.1}  {:>10.1}  {:>8.1}",
+            label,
+            secs * 1000.0,
+            p.routes as f64 / secs,
+            p.points as f64 / secs,
+            p.allocations,
+            mib(p.allocated),
+            mib(p.peak),
+            mib(p.bytes as u64)
+        );
+    };
+    for (i, pass) in passes.iter().enumerate() {
+        row(&(i + 1).to_string(), pass);
+    }
+
+    let mut by_time: Vec<&Pass> = passes.iter().collect();
+    by_time.sort_by_key(|p| p.elapsed);
+    row("p50", by_time[(by_time.len() - 1) / 2]);
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+// ============================================================================
+// Stub Router
+// ============================================================================
+
+/// Stands in for a routing server: straight lines between the stops, densified.
+struct StubRouter;
+
+impl StubRouter {
+    fn route(stops: &[RawStop]) -> SnappedRoute {
+        let mut coordinates = Vec::new();
+        let mut leg_distances = Vec::with_capacity(stops.len().saturating_sub(1));
+        for pair in stops.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let meters = meters_between(a.gps_long, a.gps_lat, b.gps_long, b.gps_lat);
+            let steps = ((meters / STUB_SPACING_M).ceil() as usize).max(1);
+            // Each leg starts where the previous one ended.
+            let first = if coordinates.is_empty() { 0 } else { 1 };
+            for i in first..=steps {
+                let t = i as f64 / steps as f64;
+                coordinates.push(vec![
+                    a.gps_long + (b.gps_long - a.gps_long) * t,
+                    a.gps_lat + (b.gps_lat - a.gps_lat) * t,
+                ]);
+            }
+            leg_distances.push(Some(meters));
+        }
+        SnappedRoute {
+            coordinates,
+            leg_durations: leg_distances
+                .iter()
+                .map(|d| d.map(|d| d / STUB_SPEED_MPS))
+                .collect(),
+            leg_confidence: vec![Some(1.0); leg_distances.len()],
+            leg_distances,
+        }
+    }
+}
+
+impl SnappingBackend for StubRouter {
+    fn kind(&self) -> Snapper {
+        Snapper::Osrm
+    }
+
+    fn snap<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        async move { Ok(Self::route(stops)) }.boxed()
+    }
+
+    fn corridor<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        async move { Ok(Self::route(stops)) }.boxed()
+    }
+}
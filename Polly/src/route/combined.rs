@@ -0,0 +1,326 @@
+//! Combined Route Outputs
+//!
+//! Web maps showing the whole network at low zoom load one file rather than
+//! a request per route. `all_routes.geojson` holds every derived route as a
+//! Feature; `all_routes_simplified.geojson` keeps only the line and a few
+//! identifying properties, with coarser geometry. `stops.geojson` lists every
+//! station as a Point with the routes serving it. With `--flatgeobuf`, routes
+//! and stops are also written as `routes.fgb` and `stops.fgb` for GIS tools.
+//! With `--spatial-index`, a flatbush index over route bboxes and stop points
+//! lets the web client find what is near a tap without a server.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::config::ALL_ROUTES_SIMPLIFY_M;
+use crate::route::model::SnapQuality;
+use crate::route::{derived_route_files, load_derived_file, load_derived_routes};
+use crate::utils::compress::{Compression, compress_file};
+use crate::utils::crs::Crs;
+use crate::utils::flatgeobuf::{self, ColumnType, GeometryType, Property};
+use crate::utils::geo::simplify_line;
+use crate::utils::packed_rtree::{self, Bbox, NODE_SIZE};
+use crate::utils::{AtomicFile, write_atomic};
+
+pub const ALL_ROUTES_FILE: &str = "all_routes.geojson";
+pub const ALL_ROUTES_SIMPLIFIED_FILE: &str = "all_routes_simplified.geojson";
+pub const STOPS_FILE: &str = "stops.geojson";
+pub const ROUTES_FGB_FILE: &str = "routes.fgb";
+pub const STOPS_FGB_FILE: &str = "stops.fgb";
+pub const SPATIAL_INDEX_FILE: &str = "spatial_index.bin";
+pub const SPATIAL_INDEX_ITEMS_FILE: &str = "spatial_index.json";
+
+/// Route numbers and route IDs serving each stop
+pub type ServingRoutes<'a> = HashMap<&'a str, (BTreeSet<&'a str>, BTreeSet<&'a str>)>;
+
+/// A GeoJSON FeatureCollection streamed to disk one feature at a time, so
+/// combined outputs never hold every feature in memory.
+struct FeatureWriter {
+    file: AtomicFile,
+    count: usize,
+}
+
+impl FeatureWriter {
+    fn create(path: &Path, crs: Crs) -> Result<Self> {
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(br#"{"type":"FeatureCollection","#)?;
+        if let Some(member) = crs.geojson_member() {
+            write!(file, r#""crs":{},"#, member)?;
+        }
+        file.write_all(br#""features":["#)?;
+        Ok(Self { file, count: 0 })
+    }
+
+    fn push(&mut self, feature: &impl Serialize) -> Result<()> {
+        if self.count > 0 {
+            self.file.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.file, feature)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize> {
+        self.file.write_all(b"]}")?;
+        self.file.commit()?;
+        Ok(self.count)
+    }
+}
+
+/// Writes both combined files to `output_dir` from its derived routes, with
+/// coordinates in `crs`. Returns the number of routes written.
+pub fn write_all_routes(output_dir: &Path, compress: &[Compression], crs: Crs) -> Result<usize> {
+    let full_path = output_dir.join(ALL_ROUTES_FILE);
+    let simplified_path = output_dir.join(ALL_ROUTES_SIMPLIFIED_FILE);
+    let mut full = FeatureWriter::create(&full_path, crs)?;
+    let mut simplified = FeatureWriter::create(&simplified_path, crs)?;
+
+    // One route file at a time
+    for path in derived_route_files(output_dir)? {
+        for mut feature in load_derived_file(&path)? {
+            // Coordinates were decoded on load; plain GeoJSON is the point of this file.
+            feature.geometry.polyline = None;
+            feature.geometry.precision = None;
+
+            let line = &feature.geometry.coordinates;
+            let coordinates: Vec<Vec<f64>> = simplify_line(line, ALL_ROUTES_SIMPLIFY_M, &[])
+                .into_iter()
+                .map(|i| match crs {
+                    Crs::Wgs84 => line[i]
+                        .iter()
+                        .map(|c| (c * 100_000.0).round() / 100_000.0)
+                        .collect(),
+                    _ => crs.project(&line[i]),
+                })
+                .collect();
+            simplified.push(&json!({
+                "type": "Feature",
+                "id": feature.id,
+                "properties": {
+                    "route_id": feature.properties.route_id,
+                    "route_no": feature.properties.route_no,
+                    "is_circular": feature.properties.is_circular,
+                    "snap_quality": feature.properties.snap_quality,
+                },
+                "geometry": { "type": "LineString", "coordinates": coordinates },
+            }))?;
+
+            if crs != Crs::Wgs84 {
+                feature.reproject(|p| crs.project(p));
+            }
+            full.push(&feature)?;
+        }
+    }
+
+    let count = full.finish()?;
+    simplified.finish()?;
+    compress_file(&full_path, compress)?;
+    compress_file(&simplified_path, compress)?;
+
+    Ok(count)
+}
+
+/// Writes the aggregated station map as a Point FeatureCollection to `path`,
+/// with coordinates in `crs`. `details` holds the routeMap.json
+/// `route_details` entries (with `sequence`) and `stations` the `stations`
+/// entries, both keyed by ID.
+pub fn write_stops_geojson(
+    path: &Path,
+    details: &BTreeMap<String, Value>,
+    stations: &BTreeMap<String, Value>,
+    crs: Crs,
+    compress: &[Compression],
+) -> Result<()> {
+    let mut serving = serving_routes(details);
+
+    let mut writer = FeatureWriter::create(path, crs)?;
+    for (node_id, station) in stations {
+        let (numbers, ids) = serving.remove(node_id.as_str()).unwrap_or_default();
+        let mut properties = json!({
+            "id": node_id,
+            "name": station["nodenm"],
+            "nodeno": station["nodeno"],
+            "routes": numbers,
+            "route_ids": ids,
+        });
+        if let Some(name_en) = station.get("name_en") {
+            properties["name_en"] = name_en.clone();
+        }
+        let coordinates = match (station["gpslong"].as_f64(), station["gpslati"].as_f64()) {
+            (Some(lon), Some(lat)) if crs != Crs::Wgs84 => json!(crs.project(&[lon, lat])),
+            _ => json!([station["gpslong"], station["gpslati"]]),
+        };
+        writer.push(&json!({
+            "type": "Feature",
+            "id": node_id,
+            "properties": properties,
+            "geometry": {
+                "type": "Point",
+                "coordinates": coordinates,
+            },
+        }))?;
+    }
+    writer.finish()?;
+    compress_file(path, compress)?;
+
+    Ok(())
+}
+
+/// Writes every derived route in `output_dir` to `routes.fgb` there, with
+/// coordinates in `crs`.
+pub fn write_routes_fgb(output_dir: &Path, crs: Crs) -> Result<usize> {
+    let features: Vec<flatgeobuf::Feature> = load_derived_routes(output_dir)?
+        .into_iter()
+        .map(|f| flatgeobuf::Feature {
+            properties: vec![
+                Property::String(f.properties.route_id),
+                Property::String(f.properties.route_no),
+                Property::Bool(f.properties.is_circular),
+                Property::String(
+                    match f.properties.snap_quality {
+                        SnapQuality::Full => "full",
+                        SnapQuality::Degraded => "degraded",
+                    }
+                    .to_string(),
+                ),
+                Property::Double(f.properties.meta.total_dist),
+            ],
+            coordinates: f
+                .geometry
+                .coordinates
+                .iter()
+                .map(|p| crs.project(p))
+                .collect(),
+        })
+        .collect();
+
+    flatgeobuf::write(
+        &output_dir.join(ROUTES_FGB_FILE),
+        "routes",
+        crs,
+        GeometryType::LineString,
+        &[
+            ("route_id", ColumnType::String),
+            ("route_no", ColumnType::String),
+            ("is_circular", ColumnType::Bool),
+            ("snap_quality", ColumnType::String),
+            ("total_dist", ColumnType::Double),
+        ],
+        &features,
+    )?;
+    Ok(features.len())
+}
+
+/// Writes the aggregated station map to `path` as a FlatGeobuf Point layer
+/// in `crs`, with the same properties as `stops.geojson`.
+pub fn write_stops_fgb(
+    path: &Path,
+    details: &BTreeMap<String, Value>,
+    stations: &BTreeMap<String, Value>,
+    crs: Crs,
+) -> Result<()> {
+    let mut serving = serving_routes(details);
+    let text = |v: &Value| v.as_str().map_or_else(|| v.to_string(), str::to_string);
+
+    let features: Vec<flatgeobuf::Feature> = stations
+        .iter()
+        .filter_map(|(node_id, station)| {
+            let (numbers, ids) = serving.remove(node_id.as_str()).unwrap_or_default();
+            Some(flatgeobuf::Feature {
+                coordinates: vec![
+                    crs.project(&[station["gpslong"].as_f64()?, station["gpslati"].as_f64()?]),
+                ],
+                properties: vec![
+                    Property::String(node_id.clone()),
+                    Property::String(text(&station["nodenm"])),
+                    Property::String(text(&station["nodeno"])),
+                    Property::String(json!(numbers).to_string()),
+                    Property::String(json!(ids).to_string()),
+                ],
+            })
+        })
+        .collect();
+
+    flatgeobuf::write(
+        path,
+        "stops",
+        crs,
+        GeometryType::Point,
+        &[
+            ("id", ColumnType::String),
+            ("name", ColumnType::String),
+            ("nodeno", ColumnType::String),
+            ("routes", ColumnType::Json),
+            ("route_ids", ColumnType::Json),
+        ],
+        &features,
+    )
+}
+
+/// Writes a flatbush index over the bboxes of the derived routes in
+/// `output_dir` and the points of `stations` to `spatial_index.bin`, and the
+/// item IDs to `spatial_index.json`: results below the number of routes are
+/// indices into `routes`, the rest into `stops` after subtracting it.
+/// Coordinates are WGS84 regardless of `--crs`, like map taps. Returns the
+/// number of routes and stops indexed.
+pub fn write_spatial_index(
+    output_dir: &Path,
+    stations: &BTreeMap<String, Value>,
+) -> Result<(usize, usize)> {
+    let mut boxes: Vec<Bbox> = Vec::new();
+
+    let mut route_ids = Vec::new();
+    for feature in load_derived_routes(output_dir)? {
+        if let Some(bbox) = packed_rtree::bbox_of(&feature.geometry.coordinates) {
+            boxes.push(bbox);
+            route_ids.push(feature.id);
+        }
+    }
+
+    let mut stop_ids = Vec::new();
+    for (node_id, station) in stations {
+        if let (Some(lon), Some(lat)) = (station["gpslong"].as_f64(), station["gpslati"].as_f64()) {
+            boxes.push([lon, lat, lon, lat]);
+            stop_ids.push(node_id.as_str());
+        }
+    }
+
+    let Some(index) = packed_rtree::to_flatbush(&boxes, NODE_SIZE) else {
+        anyhow::bail!("No routes or stops to index in {:?}", output_dir);
+    };
+    write_atomic(&output_dir.join(SPATIAL_INDEX_FILE), index)?;
+    write_atomic(
+        &output_dir.join(SPATIAL_INDEX_ITEMS_FILE),
+        serde_json::to_string(&json!({
+            "routes": route_ids,
+            "stops": stop_ids,
+        }))?,
+    )?;
+
+    Ok((route_ids.len(), stop_ids.len()))
+}
+
+/// Route numbers and route IDs serving each stop of `details` (routeMap.json
+/// `route_details`), keyed by node ID.
+pub fn serving_routes(details: &BTreeMap<String, Value>) -> ServingRoutes<'_> {
+    let mut serving = ServingRoutes::new();
+    for (route_id, detail) in details {
+        let route_no = detail["routeno"].as_str().unwrap_or_default();
+        for node_id in detail["sequence"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|s| s["nodeid"].as_str())
+        {
+            let (numbers, ids) = serving.entry(node_id).or_default();
+            numbers.insert(route_no);
+            ids.insert(route_id);
+        }
+    }
+    serving
+}
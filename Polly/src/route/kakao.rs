@@ -0,0 +1,160 @@
+//! Kakao Mobility Snapping Backend
+//!
+//! Routes stop sequences with the Kakao Mobility multi-waypoint directions API,
+//! whose Korean road data is often fresher than public OSRM extracts. The API
+//! takes at most `KAKAO_MAX_WAYPOINTS` waypoints per request, so longer chunks
+//! are routed in overlapping pieces and joined. Each stop-to-stop section
+//! becomes one leg, matching the OSRM output.
+
+use anyhow::Result;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use reqwest::header;
+use serde_json::{Value, json};
+
+use crate::config::KAKAO_MAX_WAYPOINTS;
+use crate::error::PipelineError;
+use crate::route::model::{RawStop, SnappedRoute, Snapper};
+use crate::route::snapper::{SnapClient, SnappingBackend};
+
+pub struct KakaoBackend {
+    client: SnapClient,
+    /// Kakao Mobility directions endpoint
+    url: String,
+    /// Kakao Developers REST API key
+    api_key: String,
+}
+
+impl KakaoBackend {
+    pub fn new(client: SnapClient, url: String, api_key: String) -> Self {
+        Self {
+            client,
+            url,
+            api_key,
+        }
+    }
+
+    /// Routes a stop sequence through every stop with Kakao directions.
+    async fn fetch_route(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        // Origin and destination come on top of the waypoints
+        let piece_len = KAKAO_MAX_WAYPOINTS + 2;
+
+        let mut route = SnappedRoute {
+            coordinates: Vec::new(),
+            leg_durations: Vec::new(),
+            leg_distances: Vec::new(),
+            leg_confidence: Vec::new(),
+        };
+
+        let mut start = 0;
+        while start + 1 < stops.len() {
+            let end = (start + piece_len).min(stops.len());
+            let piece = self.call_kakao(&stops[start..end]).await?;
+
+            let skip = usize::from(
+                route
+                    .coordinates
+                    .last()
+                    .is_some_and(|l| piece.coordinates.first() == Some(l)),
+            );
+            route
+                .coordinates
+                .extend(piece.coordinates.into_iter().skip(skip));
+            route.leg_durations.extend(piece.leg_durations);
+            route.leg_distances.extend(piece.leg_distances);
+            route.leg_confidence.extend(piece.leg_confidence);
+
+            start = end - 1;
+        }
+
+        Ok(route)
+    }
+
+    async fn call_kakao(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        let point = |s: &RawStop| json!({ "x": s.gps_long, "y": s.gps_lat });
+        let body = json!({
+            "origin": point(&stops[0]),
+            "destination": point(&stops[stops.len() - 1]),
+            "waypoints": stops[1..stops.len() - 1].iter().map(point).collect::<Vec<_>>(),
+            "priority": "RECOMMEND",
+        })
+        .to_string();
+
+        let request = self
+            .client
+            .http
+            .post(&self.url)
+            .header(header::AUTHORIZATION, format!("KakaoAK {}", self.api_key))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+
+        // The API key travels in a header, so it never ends up in the cache key.
+        let key = format!("{}\n{}", self.url, body);
+        let json = self
+            .client
+            .cached_json(&key, request, |j| j["routes"][0]["result_code"] == 0)
+            .await?;
+
+        let result = &json["routes"][0];
+        if result["result_code"] != 0 {
+            return Err(PipelineError::snap(
+                "Kakao",
+                format!("directions returned {}", result["result_msg"]),
+            )
+            .into());
+        }
+
+        let sections = result["sections"].as_array().cloned().unwrap_or_default();
+
+        let mut coordinates: Vec<Vec<f64>> = Vec::new();
+        for road in sections.iter().flat_map(section_roads) {
+            // Vertexes are a flat [x1, y1, x2, y2, ...] list
+            let vertexes: Vec<f64> = road["vertexes"]
+                .as_array()
+                .map(|v| v.iter().filter_map(Value::as_f64).collect())
+                .unwrap_or_default();
+            for xy in vertexes.chunks_exact(2) {
+                let point = vec![xy[0], xy[1]];
+                if coordinates.last() != Some(&point) {
+                    coordinates.push(point);
+                }
+            }
+        }
+        if coordinates.is_empty() {
+            return Err(
+                PipelineError::snap("Kakao", "directions returned an empty geometry").into(),
+            );
+        }
+
+        let leg_durations: Vec<Option<f64>> =
+            sections.iter().map(|s| s["duration"].as_f64()).collect();
+        let leg_distances: Vec<Option<f64>> =
+            sections.iter().map(|s| s["distance"].as_f64()).collect();
+
+        Ok(SnappedRoute {
+            leg_confidence: vec![None; leg_durations.len()],
+            coordinates,
+            leg_durations,
+            leg_distances,
+        })
+    }
+}
+
+impl SnappingBackend for KakaoBackend {
+    fn kind(&self) -> Snapper {
+        Snapper::Kakao
+    }
+
+    /// Directions only; there is no map matching, so this is the corridor.
+    fn snap<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        self.fetch_route(stops).boxed()
+    }
+
+    fn corridor<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        self.fetch_route(stops).boxed()
+    }
+}
+
+fn section_roads(section: &Value) -> Vec<Value> {
+    section["roads"].as_array().cloned().unwrap_or_default()
+}
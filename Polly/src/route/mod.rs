@@ -4,28 +4,82 @@
 //! information. It fetches raw route data from a public API, saves it,
 //! and processes it into GeoJSON format suitable for frontend applications.
 
-mod model;
-
-use std::collections::{BTreeMap, HashMap};
+mod anomalies;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod combined;
+mod kakao;
+pub mod model;
+mod osrm;
+pub mod snapper;
+mod valhalla;
+pub mod variants;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use chrono::Local;
+use clap::builder::RangedU64ValueParser;
 use futures::stream::{self, StreamExt};
 use serde_json::{Value, json};
+use tracing::{debug, error, info, warn};
 
-use crate::config::{CONCURRENCY_FETCH, CONCURRENCY_SNAP, OSRM_CHUNK_SIZE, OSRM_URL, TAGO_URL};
+use crate::config::{
+    FRECHET_STEP_M, LOOP_CLOSURE_M, OSRM_LOW_CONFIDENCE, OSRM_MAX_COORDS_LEN, OSRM_MIN_CHUNK_SIZE,
+    ROUTE_MAP_SCHEMA_VERSION, STOP_BEARING_REACH_M, STOP_MAPPING_RADIUS_M,
+};
+use crate::error::{ExitError, ExitStatus, PipelineError, config_error};
+use crate::route::anomalies::detect_anomalies;
+use crate::route::combined::{
+    ALL_ROUTES_FILE, ROUTES_FGB_FILE, SPATIAL_INDEX_FILE, STOPS_FGB_FILE, STOPS_FILE,
+    serving_routes, write_all_routes, write_routes_fgb, write_spatial_index, write_stops_fgb,
+    write_stops_geojson,
+};
+use crate::route::kakao::KakaoBackend;
 use crate::route::model::{
-    BusRouteProcessor, FrontendMeta, FrontendStop, RawRouteFile, RawStop, RouteFeature,
-    RouteFeatureCollection, RouteGeometry, RouteIndices, RouteProcessData, RouteProperties,
+    BusRouteProcessor, FrontendMeta, FrontendStop, GeometryEncoding, RawRouteFile, RawStop,
+    RouteDetail, RouteFeature, RouteFeatureCollection, RouteGeometry, RouteIndices, RouteMapFile,
+    RouteProcessData, RouteProperties, RouteServiceInfo, SequenceStop, SnapMetrics, SnapQuality,
+    SnapStats, SnappedRoute, Snapper, Station, StopCorrection,
 };
+use crate::route::osrm::{OsrmBackend, stops_fitting_url};
+use crate::route::snapper::{SnapChain, SnapClient, SnappingBackend, StraightLine};
+use crate::route::valhalla::ValhallaBackend;
+use crate::route::variants::{RouteVariant, analyze_variants};
+use crate::schema::{OutputFormat, validate_files};
+use crate::settings::Settings;
+use crate::tago::client::{RouteInfoRequest, RouteListRequest, RouteStopsRequest, TagoClient};
+use crate::tago::error::TagoError;
+use crate::tago::model::{RouteInfo, RouteInfoItem};
 use crate::utils::{
-    ensure_dir, extract_items,
-    geo::{calculate_metrics, closest_point_on_polyline, find_nearest_coord_index},
-    get_env, parse_flexible_string, resolve_url,
+    cache::ResponseCache,
+    compress::{Compression, compress_file, write_compressed},
+    crs::Crs,
+    dry_run, ensure_dir, events,
+    failures::FailureManifest,
+    geo::{
+        bearing_along, calculate_metrics, closest_point_on_polyline, cumulative_distances,
+        densify_line, find_nearest_coord_index, frechet_distance, map_points_along_line,
+        meters_between, simplify_line,
+    },
+    http::HttpClient,
+    i18n::Msg,
+    interrupt::Interrupt,
+    lock, metrics, now, polyline, remove_file,
+    report::{Issue, REPORT_FILE, RunReport},
+    romanize::romanize,
+    routes_file::RoutesFile,
+    short_hash, state,
+    status::{self, STATUS_FILE},
+    write_atomic, write_json_atomic,
 };
+use crate::watch::Fingerprint;
+
+/// Index of hashed output files (`--hashed-names`) within the output directory
+const ASSETS_FILE: &str = "assets.json";
 
 // ============================================================================
 // Argument Structure
@@ -34,16 +88,16 @@ use crate::utils::{
 #[derive(clap::Args)]
 pub struct RouteArgs {
     /// City code to process (default: Wonju -> 32020)
-    #[arg(long, default_value = "32020")]
-    city_code: String,
+    #[arg(long)]
+    city_code: Option<String>,
 
     /// Specific route number (if not specified, all)
     #[arg(short, long)]
     route: Option<String>,
 
-    /// Output directory
-    #[arg(short, long, default_value = "./storage/processed_routes")]
-    output_dir: PathBuf,
+    /// Output directory (default: ./storage/processed_routes)
+    #[arg(short, long)]
+    output_dir: Option<PathBuf>,
 
     /// Update station map only and skip snapping
     #[arg(long)]
@@ -52,286 +106,1373 @@ pub struct RouteArgs {
     /// Snap route paths using OSRM only (skip Tago API)
     #[arg(long)]
     osrm_only: bool,
+
+    /// Produce reproducible output (stable ordering; honors SOURCE_DATE_EPOCH)
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Also write pre-compressed copies of large outputs (e.g., `gz,br`)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    compress: Vec<Compression>,
+
+    /// Add romanized `name_en` fields to stations in routeMap.json
+    #[arg(long)]
+    romanize: bool,
+
+    /// Skip fetching TAGO route info (type, first/last bus, headways) for
+    /// `route_details`, saving one request per route
+    #[arg(long)]
+    no_route_info: bool,
+
+    /// Maximum TAGO requests per second across all concurrent fetches
+    /// (0: unlimited; default: 10)
+    #[arg(long)]
+    tago_rps: Option<f64>,
+
+    /// Routes fetched from TAGO at the same time (default: 10)
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    fetch_concurrency: Option<usize>,
+
+    /// Routes snapped at the same time, each sending its chunks one by one
+    /// (default: 4)
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    snap_concurrency: Option<usize>,
+
+    /// Stops per routing request; lower it if the routing server rejects long
+    /// requests (osrm-routed allows at most 100 for /match by default; default: 100)
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(2..))]
+    osrm_chunk_size: Option<usize>,
+
+    /// Snapping backends tried in order for each chunk (e.g., `osrm,valhalla`);
+    /// straight lines are always the last resort
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "osrm")]
+    snapper: Vec<Snapper>,
+
+    /// Days to reuse cached routing responses from `osrm_cache/` (default: 7)
+    #[arg(long)]
+    osrm_cache_days: Option<u64>,
+
+    /// Always query the routing backend, bypassing the response cache
+    #[arg(long)]
+    no_osrm_cache: bool,
+
+    /// Max distance (meters) a drifted stop is moved onto its road corridor
+    /// (default: 90)
+    #[arg(long)]
+    snap_threshold_m: Option<f64>,
+
+    /// Simplify the snapped geometry with this Douglas-Peucker tolerance (meters),
+    /// keeping the vertices stops are mapped to
+    #[arg(long)]
+    simplify_m: Option<f64>,
+
+    /// How the derived route line is written
+    #[arg(long, value_enum, default_value = "geojson")]
+    geometry_encoding: GeometryEncoding,
+
+    /// Decimal places of encoded polylines (5 or 6)
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(5..=6))]
+    polyline_precision: u32,
+
+    /// Coordinate reference system of output geometries and stops (routeMap.json
+    /// always keeps TAGO's WGS84 coordinates)
+    #[arg(long, value_enum, default_value = "wgs84")]
+    crs: Crs,
+
+    /// Keep TAGO stop coordinates as-is (skip drift correction)
+    #[arg(long)]
+    no_sanitize: bool,
+
+    /// Also write every route into `all_routes.geojson` and a lightweight
+    /// `all_routes_simplified.geojson`
+    #[arg(long)]
+    all_routes: bool,
+
+    /// Also write routes and stops as FlatGeobuf (`routes.fgb`, `stops.fgb`)
+    #[arg(long)]
+    flatgeobuf: bool,
+
+    /// Also write a flatbush index over route bboxes and stops
+    /// (`spatial_index.bin`, with item IDs in `spatial_index.json`)
+    #[arg(long)]
+    spatial_index: bool,
+
+    /// Also write routeMap.json split into `routeMap/index.json`, per-route
+    /// detail shards, and a stations shard, for lazy loading
+    #[arg(long)]
+    split_map: bool,
+
+    /// Name derived files `<routeid>.<contenthash>.geojson` and list them in
+    /// `assets.json`, so they can be served with immutable cache headers
+    #[arg(long)]
+    hashed_names: bool,
+
+    /// Reuse raw route files fetched less than this many hours ago
+    #[arg(long)]
+    max_age: Option<f64>,
+
+    /// Re-fetch every route, ignoring `--max-age` and the validators of the
+    /// saved stop lists
+    #[arg(long)]
+    force: bool,
+
+    /// Re-snap every raw route, even those whose derived file is up to date
+    #[arg(long)]
+    reprocess_all: bool,
+
+    /// Only re-process the routes recorded in the last run's `failures.json`
+    #[arg(long, conflicts_with = "route")]
+    retry_failed: bool,
+
+    /// Only process the route numbers or IDs listed in this file, one per
+    /// line (`-` reads them from stdin)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["route", "retry_failed"])]
+    routes_file: Option<PathBuf>,
+
+    /// Fetch and snap as usual, but only report the files that would be
+    /// written (Phase 2 snaps the raw files already on disk)
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Wait for another run writing to the output directory to finish,
+    /// instead of failing
+    #[arg(long)]
+    wait_lock: bool,
+
+    /// Fail (exit code 8) if more than this percentage of the routes failed,
+    /// even though the others were written
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    fail_on_partial: Option<u8>,
 }
 
 // ============================================================================
 // Main Execution
 // ============================================================================
 
-pub async fn run(args: RouteArgs) -> Result<()> {
+pub async fn run(args: RouteArgs, settings: &Settings) -> Result<()> {
+    if args.dry_run {
+        dry_run::enable();
+    }
+
+    // Flags override the settings of the same names
+    let defaults = &settings.route;
+    let output_dir = args
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| defaults.output_dir.clone());
+    let city_code = args
+        .city_code
+        .clone()
+        .unwrap_or_else(|| defaults.city_code.clone());
+    let fetch_concurrency = args.fetch_concurrency.unwrap_or(defaults.fetch_concurrency);
+    let snap_concurrency = args.snap_concurrency.unwrap_or(defaults.snap_concurrency);
+
     // Setup Directories
-    let raw_dir = args.output_dir.join("raw_routes");
-    let derived_dir = args.output_dir.join("derived_routes");
+    let raw_dir = output_dir.join("raw_routes");
+    let derived_dir = output_dir.join("derived_routes");
 
     ensure_dir(&raw_dir)?;
     ensure_dir(&derived_dir)?;
+    let _lock = lock::acquire(&output_dir, "route", args.wait_lock).await?;
+    status::start("route", output_dir.join(STATUS_FILE));
+    state::open(&output_dir, "route")?;
 
-    let service_key = get_env("DATA_GO_KR_SERVICE_KEY");
+    let service_key = &settings.service_key;
     if service_key.is_empty() {
-        anyhow::bail!("DATA_GO_KR_SERVICE_KEY is missing!");
+        return Err(config_error("DATA_GO_KR_SERVICE_KEY is missing!"));
+    }
+
+    // Polylines encode degrees at a fixed precision; projected meters don't fit.
+    if args.crs != Crs::Wgs84 && args.geometry_encoding == GeometryEncoding::Polyline {
+        return Err(config_error(format!(
+            "--crs {:?} requires --geometry-encoding geojson",
+            args.crs
+        )));
+    }
+
+    let kakao_api_key = &settings.kakao_api_key;
+    if args.snapper.contains(&Snapper::Kakao) && kakao_api_key.is_empty() {
+        return Err(config_error(
+            "KAKAO_REST_API_KEY is required for --snapper kakao",
+        ));
     }
 
+    let http = HttpClient::new(&settings.http)?;
+    let snap_client = SnapClient {
+        http: http.clone(),
+        cache: if args.no_osrm_cache {
+            None
+        } else {
+            Some(Arc::new(ResponseCache::new(
+                output_dir.join("osrm_cache"),
+                Duration::from_secs(
+                    args.osrm_cache_days.unwrap_or(defaults.osrm_cache_days) * 24 * 60 * 60,
+                ),
+            )?))
+        },
+    };
+    let backends = args
+        .snapper
+        .iter()
+        .map(|kind| -> Box<dyn SnappingBackend> {
+            match kind {
+                Snapper::Osrm => Box::new(OsrmBackend::new(
+                    snap_client.clone(),
+                    settings.urls.osrm.clone(),
+                )),
+                Snapper::Valhalla => Box::new(ValhallaBackend::new(
+                    snap_client.clone(),
+                    settings.urls.valhalla.clone(),
+                )),
+                Snapper::Kakao => Box::new(KakaoBackend::new(
+                    snap_client.clone(),
+                    settings.urls.kakao_directions.clone(),
+                    kakao_api_key.clone(),
+                )),
+                Snapper::Straight => Box::new(StraightLine),
+            }
+        })
+        .collect();
+
     let processor = Arc::new(BusRouteProcessor {
-        service_key,
-        city_code: args.city_code.clone(),
+        tago: TagoClient::new(
+            http,
+            &settings.urls,
+            service_key,
+            args.tago_rps.unwrap_or(defaults.tago_rps),
+        )?,
+        city_code,
         raw_dir: raw_dir.clone(),
         derived_dir: derived_dir.clone(),
-        mapping_file: args.output_dir.join("routeMap.json"),
-        tago_base_url: resolve_url("TAGO_API_URL", TAGO_URL),
-        osrm_base_url: resolve_url("OSRM_API_URL", OSRM_URL),
+        mapping_file: output_dir.join("routeMap.json"),
+        snapper: SnapChain::new(backends),
+        chunk_size: args.osrm_chunk_size.unwrap_or(defaults.osrm_chunk_size),
+        sanitize: !args.no_sanitize,
+        snap_threshold_m: args.snap_threshold_m.unwrap_or(defaults.snap_threshold_m),
+        simplify_m: args.simplify_m,
+        geometry_encoding: args.geometry_encoding,
+        polyline_precision: args.polyline_precision,
+        compress: args.compress.clone(),
+        romanize: args.romanize,
+        route_info: !args.no_route_info,
+        max_age: args
+            .max_age
+            .filter(|_| !args.force)
+            .map(|hours| chrono::TimeDelta::seconds((hours * 3600.0) as i64)),
+        conditional: !args.force,
+        // Retried routes have a derived file, but a degraded one.
+        reprocess_all: args.reprocess_all || args.retry_failed,
+        flatgeobuf: args.flatgeobuf,
+        split_map: args.split_map,
+        hashed_names: args.hashed_names,
+        crs: args.crs,
+        provenance: settings.agency.provenance(),
     });
 
+    // Routes that failed last time, if only those should be re-processed.
+    let retry = if args.retry_failed {
+        let previous = FailureManifest::previous(&output_dir)?;
+        info!(
+            count = previous.ids().len(),
+            "{}",
+            Msg::RetryingRoutes(previous.ids().len())
+        );
+        Some(previous)
+    } else {
+        None
+    };
+    // Routes listed in `--routes-file`, if only those should be processed.
+    let listed = match &args.routes_file {
+        Some(path) => {
+            let listed = RoutesFile::read(path)?;
+            info!(count = listed.len(), "{}", Msg::ListedRoutes(listed.len()));
+            Some(listed)
+        }
+        None => None,
+    };
+    let mut failures = FailureManifest::default();
+    // Routes fetched or snapped this run, the base of the failure threshold
+    let mut route_ids: BTreeSet<String> = BTreeSet::new();
+    let mut report = RunReport::default();
+    let mut interrupt = Interrupt::install();
+
     // [Phase 1] Data Collection (Raw Save)
-    if !args.osrm_only {
-        println!("\n[Phase 1: Fetching Raw Data to {:?}]", raw_dir);
+    // When retrying, only routes whose raw data could not be fetched are re-fetched.
+    let fetch_retries = retry.as_ref().map(|r| r.ids_in_stage("fetch"));
+    let skip_fetch = fetch_retries.as_ref().is_some_and(|ids| ids.is_empty());
 
-        let routes = processor.get_all_routes().await?;
-        let target_routes: Vec<Value> = if let Some(target_no) = args.route.as_ref() {
+    if !args.osrm_only && !skip_fetch {
+        info!(phase = "fetch", "{}", Msg::FetchPhase(&raw_dir));
+        let started = Instant::now();
+
+        let routes = processor.get_all_routes(args.route.as_deref()).await?;
+        let target_routes: Vec<RouteInfo> = if let Some(target_no) = args.route.as_ref() {
+            routes
+                .into_iter()
+                .filter(|r| r.route_no == *target_no)
+                .collect()
+        } else if let Some(ids) = &fetch_retries {
+            routes
+                .into_iter()
+                .filter(|r| ids.contains(&r.route_id))
+                .collect()
+        } else if let Some(listed) = &listed {
             routes
                 .into_iter()
-                .filter(|r| parse_flexible_string(&r["routeno"]) == *target_no)
+                .filter(|r| listed.contains(&r.route_no, &r.route_id))
                 .collect()
         } else {
             routes
         };
 
-        println!(" Targeting {} routes...", target_routes.len());
+        info!(
+            phase = "fetch",
+            count = target_routes.len(),
+            "{}",
+            Msg::TargetingRoutes(target_routes.len())
+        );
+        let mut pending: BTreeSet<String> =
+            target_routes.iter().map(|r| r.route_id.clone()).collect();
+        let total = pending.len();
+        route_ids.extend(pending.iter().cloned());
 
         let mut route_stream = stream::iter(target_routes)
             .map(|route| {
                 let proc = Arc::clone(&processor);
-                async move { proc.fetch_and_save_raw(route).await }
+                let route_id = route.route_id.clone();
+                async move {
+                    let started = Instant::now();
+                    let result = proc.fetch_and_save_raw(route).await;
+                    (route_id, result, started.elapsed())
+                }
             })
-            .buffer_unordered(CONCURRENCY_FETCH);
+            .buffer_unordered(fetch_concurrency);
+
+        let mut results: Vec<RouteProcessData> = Vec::new();
+        let mut reused = 0;
+        loop {
+            let next = tokio::select! {
+                next = route_stream.next() => next,
+                _ = interrupt.wait() => None,
+            };
+            let Some((route_id, result, elapsed)) = next else {
+                break;
+            };
+            pending.remove(&route_id);
+            status::progress("fetch", total - pending.len(), total);
+            match result {
+                Ok(Some(data)) => {
+                    reused += usize::from(data.reused);
+                    if !data.reused {
+                        state::record_fetch(&route_id, &data.content_hash);
+                        report.record_timing("fetch", &route_id, elapsed, data.raw_bytes);
+                        metrics::observe_route("fetch", elapsed);
+                    }
+                    let outcome = if data.reused {
+                        "reused"
+                    } else if data.unchanged {
+                        "unchanged"
+                    } else {
+                        "ok"
+                    };
+                    metrics::route_processed("fetch", outcome);
+                    events::route_fetched(&route_id, outcome, elapsed.as_millis() as u64);
+                    debug!(
+                        route_id,
+                        phase = "fetch",
+                        outcome,
+                        elapsed_ms = elapsed.as_millis() as u64,
+                        "Fetched {} ({})",
+                        data.route_no,
+                        route_id
+                    );
+                    results.push(data);
+                    if results.len().is_multiple_of(10) {
+                        info!(
+                            phase = "fetch",
+                            count = results.len(),
+                            "{}",
+                            Msg::FetchedRoutes(results.len())
+                        );
+                    }
+                }
+                Ok(None) => {
+                    warn!(
+                        route_id,
+                        phase = "fetch",
+                        outcome = "failed",
+                        "{}",
+                        Msg::NoStops(&route_id)
+                    );
+                    failures.record(&route_id, "fetch", "no stops returned");
+                }
+                Err(e) => {
+                    // A rejected key, an exhausted quota, or an unwritable
+                    // output directory fails every route alike.
+                    if e.downcast_ref::<TagoError>()
+                        .is_some_and(TagoError::is_fatal)
+                        || matches!(PipelineError::find(&e), Some(PipelineError::Io { .. }))
+                    {
+                        return Err(e);
+                    }
+                    error!(
+                        route_id,
+                        phase = "fetch",
+                        outcome = "failed",
+                        "{}",
+                        Msg::FetchFailed(&route_id, &e)
+                    );
+                    failures.record_error(&route_id, "fetch", &e);
+                }
+            }
+        }
+        let count = results.len();
+        drop(route_stream);
+
+        // Routes still in flight are dropped; record them for `--retry-failed`.
+        let interrupted = interrupt.is_set();
+        if interrupted {
+            warn!(
+                phase = "fetch",
+                outcome = "interrupted",
+                count,
+                pending = pending.len(),
+                "{}",
+                Msg::FetchInterrupted {
+                    done: count,
+                    left: pending.len()
+                }
+            );
+            for route_id in &pending {
+                failures.record(route_id, "fetch", "interrupted");
+            }
+        }
+        if reused > 0 {
+            info!(
+                phase = "fetch",
+                count = reused,
+                "{}",
+                Msg::ReusedRawFiles(reused)
+            );
+        }
+
+        // Completion order of concurrent fetches is arbitrary; sort for stable output.
+        if args.deterministic {
+            results.sort_by(|a, b| a.route_id.cmp(&b.route_id));
+        }
 
         // Aggregation for routeMap.json
         let mut all_stops = BTreeMap::new();
-        let mut route_details_map = HashMap::new();
+        let mut route_details_map = BTreeMap::new();
         let mut route_mapping: BTreeMap<String, Vec<String>> = BTreeMap::new();
-        let mut count = 0usize;
 
-        while let Some(result) = route_stream.next().await {
-            match result {
-                Ok(Some(data)) => {
-                    count += 1;
-                    route_details_map.insert(data.route_id.clone(), data.details);
-                    route_mapping
-                        .entry(data.route_no)
-                        .or_default()
-                        .push(data.route_id);
-                    for (id, val) in data.stops_map {
-                        all_stops.insert(id, val);
-                    }
-                    if count % 10 == 0 {
-                        print!(".");
-                    }
+        // A retry, a route list (or an interrupted run) only fetched some
+        // routes; keep the rest of the existing map.
+        if retry.is_some() || listed.is_some() || interrupted {
+            load_route_map_into(
+                &processor.mapping_file,
+                &mut route_mapping,
+                &mut route_details_map,
+                &mut all_stops,
+            )?;
+        }
+
+        let flagged = results.iter().filter(|d| !d.issues.is_empty()).count();
+        if flagged > 0 {
+            warn!(
+                phase = "fetch",
+                count = flagged,
+                "{}",
+                Msg::SequenceIssues {
+                    count: flagged,
+                    file: REPORT_FILE
                 }
-                Ok(None) => {}
-                Err(e) => eprintln!("\n Error: {:?}", e),
+            );
+        }
+
+        for data in results {
+            report.extend(&data.route_id, data.issues);
+            route_details_map.insert(data.route_id.clone(), data.details);
+            let ids = route_mapping.entry(data.route_no).or_default();
+            if !ids.contains(&data.route_id) {
+                ids.push(data.route_id);
+            }
+            for (id, val) in data.stops_map {
+                all_stops.insert(id, val);
+            }
+        }
+        info!(
+            phase = "fetch",
+            count,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "{}",
+            Msg::FetchDone {
+                count,
+                secs: started.elapsed().as_secs_f64()
             }
+        );
+        if let Some(summary) = report.summarize("fetch") {
+            info!(phase = "fetch", "{}", Msg::FetchTimes(summary));
         }
-        println!("\n Processed {} raw routes.", count);
 
-        processor.save_route_map_json(&route_mapping, &route_details_map, &all_stops)?;
+        processor.save_route_map_json(&route_mapping, &route_details_map, &mut all_stops)?;
+        if !args.dry_run {
+            validate_files(
+                OutputFormat::RouteMap,
+                std::slice::from_ref(&processor.mapping_file),
+            )?;
+        }
 
+        if interrupted {
+            failures.save(&output_dir)?;
+            report.save(&output_dir)?;
+            return Err(interrupted_error());
+        }
         if args.station_map_only {
-            println!("✓ Station map generated.");
+            failures.save(&output_dir)?;
+            report.save(&output_dir)?;
+            failures.check_threshold(route_ids.len(), args.fail_on_partial)?;
+            info!(outcome = "ok", "{}", Msg::StationMapDone);
             return Ok(());
         }
     }
 
     // [Phase 2] Data Processing (Raw -> Derived)
-    println!(
-        "\n[Phase 2: Processing raw data to GeoJSON: {:?}]",
-        derived_dir
-    );
+    info!(phase = "snap", "{}", Msg::SnapPhase(&derived_dir));
+
+    let started = Instant::now();
+
+    // A dry run creates no directories, so a fresh output tree has nothing to snap.
+    if args.dry_run && !raw_dir.is_dir() {
+        info!(phase = "snap", "Dry run: no raw routes on disk to snap");
+        failures.check_threshold(route_ids.len(), args.fail_on_partial)?;
+        return Ok(());
+    }
 
     // Read all JSONs from `raw_routes/`
-    let raw_entries: Vec<_> = fs::read_dir(&raw_dir)?.filter_map(|e| e.ok()).collect();
+    let retry_ids = retry.as_ref().map(|r| r.ids());
+    let raw_files: Vec<(String, PathBuf)> = fs::read_dir(&raw_dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                return None;
+            }
 
-    // Process with concurrency
-    let mut snap_stream = stream::iter(raw_entries)
-        .map(|entry| {
-            let proc = Arc::clone(&processor);
-            let specific = args.route.clone();
+            let fname = path.file_name().unwrap().to_string_lossy().to_string();
 
-            async move {
-                let path = entry.path();
-                if path.extension().map_or(false, |ext| ext == "json") {
-                    let fname = path.file_name().unwrap().to_string_lossy();
+            // Raw files are named `{route_no}_{route_id}.json`.
+            let stem = path.file_stem().unwrap().to_string_lossy();
+            let (route_no, route_id) = stem.rsplit_once('_').unwrap_or(("", &*stem));
 
-                    // Filter check
-                    if let Some(ref target) = specific {
-                        if !fname.starts_with(target) && !fname.contains(target) {
-                            return Ok(());
-                        }
-                    }
+            // Filter check
+            if let Some(ref target) = args.route
+                && !fname.starts_with(target)
+                && !fname.contains(target)
+            {
+                return None;
+            }
+            if let Some(listed) = &listed
+                && !listed.contains(route_no, route_id)
+            {
+                return None;
+            }
+            let route_id = route_id.to_string();
+            if let Some(ids) = &retry_ids
+                && !ids.contains(&route_id)
+            {
+                return None;
+            }
 
-                    println!(" Processing {}...", fname);
+            Some((route_id, path))
+        })
+        .collect();
+    let mut pending: BTreeSet<String> = raw_files.iter().map(|(id, _)| id.clone()).collect();
+    let total = pending.len();
+    route_ids.extend(pending.iter().cloned());
 
-                    proc.process_raw_to_derived(&path).await
+    // Process with concurrency
+    let mut snap_stream = stream::iter(raw_files)
+        .map(|(route_id, path)| {
+            let proc = Arc::clone(&processor);
+            async move { (route_id, proc.process_raw_to_derived(&path).await) }
+        })
+        .buffer_unordered(snap_concurrency);
+
+    let (mut points_before, mut points_after) = (0usize, 0usize);
+    let mut skipped = 0;
+    loop {
+        let next = tokio::select! {
+            next = snap_stream.next() => next,
+            _ = interrupt.wait() => None,
+        };
+        let Some((route_id, result)) = next else {
+            break;
+        };
+        pending.remove(&route_id);
+        status::progress("snap", total - pending.len(), total);
+        match result {
+            Ok(stats) if stats.skipped => {
+                metrics::route_processed("snap", "skipped");
+                debug!(
+                    route_id,
+                    phase = "snap",
+                    outcome = "skipped",
+                    "{} is up to date",
+                    route_id
+                );
+                skipped += 1;
+            }
+            Ok(stats) => {
+                let outcome = if stats.failed_chunks > 0 {
+                    "degraded"
                 } else {
-                    Ok(())
+                    "ok"
+                };
+                metrics::route_processed("snap", outcome);
+                metrics::observe_route("snap", stats.snap_time);
+                debug!(
+                    route_id,
+                    phase = "snap",
+                    outcome,
+                    elapsed_ms = stats.snap_time.as_millis() as u64,
+                    "Snapped {}",
+                    route_id
+                );
+                report.record_timing("snap", &route_id, stats.snap_time, stats.output_bytes);
+                points_before += stats.points_before;
+                points_after += stats.points_after;
+                report.extend(&route_id, stats.issues);
+                if stats.failed_chunks > 0 {
+                    failures.record(
+                        &route_id,
+                        "snap",
+                        format!(
+                            "{} chunk(s) fell back to straight lines",
+                            stats.failed_chunks
+                        ),
+                    );
                 }
             }
-        })
-        .buffer_unordered(CONCURRENCY_SNAP);
-
-    while let Some(res) = snap_stream.next().await {
-        if let Err(e) = res {
-            eprintln!(" Processing failed: {:?}", e);
+            Err(e) => {
+                error!(
+                    route_id,
+                    phase = "snap",
+                    outcome = "failed",
+                    "{}",
+                    Msg::SnapFailed(&route_id, &e)
+                );
+                failures.record_error(&route_id, "snap", &e);
+            }
+        }
+    }
+    drop(snap_stream);
+
+    // Derived files are written atomically, so routes cut off mid-snap are
+    // simply left for `--retry-failed`; the combined outputs are not rebuilt.
+    if interrupt.is_set() {
+        warn!(
+            phase = "snap",
+            outcome = "interrupted",
+            pending = pending.len(),
+            "{}",
+            Msg::SnapInterrupted(pending.len())
+        );
+        for route_id in &pending {
+            failures.record(route_id, "snap", "interrupted");
         }
+        failures.save(&output_dir)?;
+        report.save(&output_dir)?;
+        return Err(interrupted_error());
+    }
+
+    if let Some(summary) = report.summarize("snap") {
+        info!(phase = "snap", "{}", Msg::SnapTimes(summary));
+    }
+    if skipped > 0 {
+        info!(
+            phase = "snap",
+            count = skipped,
+            "{}",
+            Msg::SkippedUpToDate(skipped)
+        );
+    }
+    if let Some(tolerance) = args.simplify_m {
+        info!(
+            phase = "snap",
+            points_before,
+            points_after,
+            "Simplified geometry ({} m): {} -> {} points",
+            tolerance,
+            points_before,
+            points_after
+        );
+    }
+
+    // The combined outputs and the validation read the derived files back from
+    // disk, which a dry run leaves as they were.
+    if args.dry_run {
+        info!("Dry run: skipping combined outputs and validation of derived files");
+        failures.save(&output_dir)?;
+        report.save(&output_dir)?;
+        failures.check_threshold(route_ids.len(), args.fail_on_partial)?;
+        return Ok(());
+    }
+    if args.hashed_names {
+        let count = write_asset_index(&output_dir)?;
+        info!(count, "Listed {} hashed files in {}", count, ASSETS_FILE);
+    }
+    if args.all_routes {
+        let count = write_all_routes(&output_dir, &args.compress, args.crs)?;
+        info!(count, "Combined {} routes into {}", count, ALL_ROUTES_FILE);
+    }
+    if args.flatgeobuf {
+        let count = write_routes_fgb(&output_dir, args.crs)?;
+        info!(count, "Wrote {} routes to {}", count, ROUTES_FGB_FILE);
+    }
+    if args.spatial_index {
+        let mut stations = BTreeMap::new();
+        load_route_map_into(
+            &processor.mapping_file,
+            &mut BTreeMap::new(),
+            &mut BTreeMap::new(),
+            &mut stations,
+        )?;
+        let (routes, stops) = write_spatial_index(&output_dir, &stations)?;
+        info!(
+            routes,
+            stops, "Indexed {} routes and {} stops in {}", routes, stops, SPATIAL_INDEX_FILE
+        );
+    }
+
+    // Record failed routes for `--retry-failed`.
+    failures.save(&output_dir)?;
+    report.save(&output_dir)?;
+    if !failures.failures.is_empty() {
+        warn!(
+            count = failures.failures.len(),
+            "{}",
+            Msg::RoutesFailed(failures.failures.len())
+        );
     }
 
-    println!("✓ Pipeline Complete.");
+    // Check everything Phase 2 left behind, not only the routes snapped this run.
+    let mut geojson_files: Vec<PathBuf> = fs::read_dir(&derived_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "geojson"))
+        .collect();
+    if args.all_routes {
+        geojson_files.push(output_dir.join(ALL_ROUTES_FILE));
+    }
+    validate_files(OutputFormat::DerivedRoute, &geojson_files)?;
+    failures.check_threshold(route_ids.len(), args.fail_on_partial)?;
+
+    info!(
+        outcome = "ok",
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "{}",
+        Msg::PipelineComplete(started.elapsed().as_secs_f64())
+    );
 
     Ok(())
 }
 
-// ============================================================================
-// Processor Implementation
-// ============================================================================
+/// Fetches only the route list of `city_code` and fingerprints it, for
+/// `watch` to tell whether routes were added, removed, or renumbered.
+pub async fn probe(settings: &Settings, city_code: Option<&str>) -> Result<Fingerprint> {
+    let defaults = &settings.route;
+    if settings.service_key.is_empty() {
+        return Err(config_error("DATA_GO_KR_SERVICE_KEY is missing!"));
+    }
+    let tago = TagoClient::new(
+        HttpClient::new(&settings.http)?,
+        &settings.urls,
+        &settings.service_key,
+        defaults.tago_rps,
+    )?;
+    let city_code = city_code.unwrap_or(&defaults.city_code);
+    let mut routes: Vec<(String, String)> = tago
+        .fetch_all(&RouteListRequest::new(city_code))
+        .await?
+        .into_iter()
+        .map(|r| (r.route_id, r.route_no))
+        .collect();
+    routes.sort();
+    Ok(Fingerprint::of(&routes, routes.len()))
+}
 
-impl BusRouteProcessor {
-    // Phase 1 Logic
+/// Error returned once an interrupted run has saved its progress.
+fn interrupted_error() -> anyhow::Error {
+    ExitError::new(
+        ExitStatus::Interrupted,
+        "Interrupted; progress saved, re-run with --retry-failed to finish",
+    )
+    .into()
+}
 
-    async fn get_all_routes(&self) -> Result<Vec<Value>> {
-        let params = [
-            ("cityCode", self.city_code.as_str()),
-            ("numOfRows", "2000"),
-            ("pageNo", "1"),
-            ("serviceKey", self.service_key.as_str()),
-            ("_type", "json"),
-        ];
+/// Moves a stop onto the part of `corridor` between `from` and `to`, if it
+/// lies within `threshold_m` of it. Returns the distance moved.
+fn snap_onto_corridor(
+    stop: &mut RawStop,
+    corridor: &[Vec<f64>],
+    from: (f64, f64),
+    to: (f64, f64),
+    threshold_m: f64,
+) -> Option<f64> {
+    let a = find_nearest_coord_index(from, corridor)?;
+    let b = find_nearest_coord_index(to, corridor)?;
+    let section = &corridor[a.min(b)..=a.max(b)];
+
+    let p = (stop.gps_long, stop.gps_lat);
+    let ((cx, cy), d) = closest_point_on_polyline(p, section)?;
+    if d > threshold_m {
+        return None;
+    }
+
+    stop.gps_long = cx;
+    stop.gps_lat = cy;
+    Some(d)
+}
 
-        let url = format!("{}/getRouteNoList", self.tago_base_url);
-        let resp: reqwest::Response = reqwest::Client::new()
-            .get(&url)
-            .query(&params)
-            .send()
-            .await?;
-        let json: Value = resp.json().await?;
+/// Loads the entries of an existing routeMap.json (if any) into the given maps.
+fn load_route_map_into(
+    path: &Path,
+    route_mapping: &mut BTreeMap<String, Vec<String>>,
+    route_details: &mut BTreeMap<String, Value>,
+    stations: &mut BTreeMap<String, Value>,
+) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let existing: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if let Some(map) = existing["route_numbers"].as_object() {
+        for (route_no, ids) in map {
+            route_mapping.insert(route_no.clone(), serde_json::from_value(ids.clone())?);
+        }
+    }
+    if let Some(map) = existing["route_details"].as_object() {
+        route_details.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    if let Some(map) = existing["stations"].as_object() {
+        stations.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
 
-        extract_items(&json)
+    Ok(())
+}
+
+/// Loads all derived route features from a route output directory.
+pub fn load_derived_routes(output_dir: &Path) -> Result<Vec<RouteFeature>> {
+    let mut features = Vec::new();
+    for path in derived_route_files(output_dir)? {
+        features.extend(load_derived_file(&path)?);
     }
+    Ok(features)
+}
 
-    async fn fetch_and_save_raw(&self, route_info: Value) -> Result<Option<RouteProcessData>> {
-        let route_id = route_info["routeid"]
-            .as_str()
-            .unwrap_or_default()
-            .to_string();
-        let route_no = parse_flexible_string(&route_info["routeno"]);
+/// Paths of the derived route files in a route output directory, sorted, for
+/// callers that process one route at a time.
+pub fn derived_route_files(output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(output_dir.join("derived_routes"))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "geojson"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
 
-        if route_no == "UNKNOWN" || route_id.is_empty() {
-            return Ok(None);
+/// Loads the features of one derived route file, decoded and in WGS84
+/// (consumers work in WGS84, whatever CRS the file was written in).
+pub fn load_derived_file(path: &Path) -> Result<Vec<RouteFeature>> {
+    let collection: RouteFeatureCollection = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| anyhow::anyhow!("Invalid derived route {:?}: {}", path, e))?;
+    let crs = Crs::from_geojson_member(collection.crs.as_ref())
+        .ok_or_else(|| anyhow::anyhow!("Unsupported CRS in derived route {:?}", path))?;
+
+    let mut features = collection.features;
+    for feature in &mut features {
+        feature.geometry.decode();
+        if crs != Crs::Wgs84 {
+            feature.reproject(|p| crs.unproject(p));
         }
+    }
+    Ok(features)
+}
 
-        // Fetch Stops
-        let params = [
-            ("cityCode", self.city_code.as_str()),
-            ("routeId", route_id.as_str()),
-            ("numOfRows", "1024"),
-            ("serviceKey", self.service_key.as_str()),
-            ("_type", "json"),
-        ];
+/// Whether the derived file at `path` was built from the raw data fetched at
+/// `fetched_at` (its `source_ver`), or from the same content if `unchanged`,
+/// with fully snapped geometry in `crs`. Degraded routes are never current,
+/// so they are retried.
+fn derived_is_current(path: &Path, fetched_at: &str, unchanged: bool, crs: Crs) -> bool {
+    let Some(collection) = fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<RouteFeatureCollection>(&s).ok())
+    else {
+        return false;
+    };
+    if Crs::from_geojson_member(collection.crs.as_ref()) != Some(crs) {
+        return false;
+    }
+    collection.features.first().is_some_and(|f| {
+        (unchanged || f.properties.meta.source_ver == fetched_at)
+            && f.properties.snap_quality == SnapQuality::Full
+    })
+}
 
-        let url = format!("{}/getRouteAcctoThrghSttnList", self.tago_base_url);
-        let resp: reqwest::Response = reqwest::Client::new()
-            .get(&url)
-            .query(&params)
-            .send()
-            .await?;
+/// Derived files of `route_id`, whether named `{route_id}.geojson` or
+/// `{route_id}.{hash}.geojson`.
+fn derived_files(derived_dir: &Path, route_id: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(derived_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension().is_some_and(|ext| ext == "geojson")
+                && p.file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .is_some_and(|s| s.split('.').next() == Some(route_id))
+        })
+        .collect()
+}
 
-        let json: Value = match resp.json().await {
-            Ok(v) => v,
-            Err(_) => return Ok(None),
-        };
+/// Whether a derived file name carries a content hash.
+fn is_hashed_name(path: &Path) -> bool {
+    path.file_stem()
+        .is_some_and(|s| s.to_string_lossy().contains('.'))
+}
 
-        let items = extract_items(&json)?;
-        if items.is_empty() {
-            return Ok(None);
+/// Writes `assets.json`, mapping the logical name of every derived file
+/// (`derived_routes/{route_id}.geojson`) to the file actually written.
+fn write_asset_index(output_dir: &Path) -> Result<usize> {
+    let mut assets = BTreeMap::new();
+    for entry in fs::read_dir(output_dir.join("derived_routes"))?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "geojson") {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let route_id = name.split('.').next().unwrap_or_default();
+        assets.insert(
+            format!("derived_routes/{}.geojson", route_id),
+            format!("derived_routes/{}", name),
+        );
+    }
+
+    let content = serde_json::to_string_pretty(&json!({
+        "lastUpdated": now().to_rfc3339(),
+        "assets": assets,
+    }))?;
+    write_atomic(&output_dir.join(ASSETS_FILE), content)?;
+    Ok(assets.len())
+}
+
+/// Reads a raw route file if its `fetched_at` is within `max_age` of now.
+/// Missing, unreadable, or stale files yield `None`, so the route is fetched.
+fn read_fresh_raw(path: &Path, max_age: chrono::TimeDelta) -> Option<RawRouteFile> {
+    let raw = read_raw(path)?;
+    let fetched_at = chrono::DateTime::parse_from_rfc3339(&raw.fetched_at).ok()?;
+    (now().signed_duration_since(fetched_at) < max_age).then_some(raw)
+}
+
+/// Reads a raw route file, if there is a readable one with stops.
+fn read_raw(path: &Path) -> Option<RawRouteFile> {
+    let raw: RawRouteFile = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    (!raw.stops.is_empty()).then_some(raw)
+}
+
+/// Checks a stop sequence (sorted by `node_ord`) for gaps and duplicates:
+/// missing or repeated ordinals, the same stop twice in a row, and different
+/// stops sharing identical coordinates.
+fn validate_stop_sequence(stops: &[RawStop]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    if let Some(first) = stops.first()
+        && first.node_ord > 1
+    {
+        issues.push(Issue::new(
+            "missing_ordinal",
+            format!("ordinals 1-{} are missing", first.node_ord - 1),
+        ));
+    }
+
+    for pair in stops.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if b.node_ord == a.node_ord {
+            issues.push(
+                Issue::new(
+                    "duplicate_ordinal",
+                    format!(
+                        "ordinal {} is used by {} and {}",
+                        a.node_ord, a.node_id, b.node_id
+                    ),
+                )
+                .at(b.gps_long, b.gps_lat),
+            );
+        } else if b.node_ord > a.node_ord + 1 {
+            issues.push(Issue::new(
+                "missing_ordinal",
+                format!("ordinals {}-{} are missing", a.node_ord + 1, b.node_ord - 1),
+            ));
         }
 
-        // Convert to internal RawStop
-        let mut stops: Vec<RawStop> = items
-            .iter()
-            .map(|item| RawStop {
-                node_id: item["nodeid"].as_str().unwrap_or("").to_string(),
-                node_nm: item["nodenm"].as_str().unwrap_or("").to_string(),
-                node_ord: item["nodeord"].as_i64().unwrap_or(0),
-                node_no: parse_flexible_string(&item["nodeno"]),
-                gps_lat: item["gpslati"].as_f64().unwrap_or(0.0),
-                gps_long: item["gpslong"].as_f64().unwrap_or(0.0),
-                up_down_cd: item["updowncd"]
-                    .as_i64()
-                    .or_else(|| item["updowncd"].as_str().and_then(|s| s.parse().ok()))
-                    .unwrap_or(0),
-            })
-            .collect();
+        if a.node_id == b.node_id {
+            issues.push(
+                Issue::new(
+                    "duplicate_stop",
+                    format!("{} ({}) appears twice in a row", a.node_nm, a.node_id),
+                )
+                .at(b.gps_long, b.gps_lat),
+            );
+        }
+    }
 
-        stops.sort_by_key(|s| s.node_ord);
+    // Distinct stops at exactly the same position
+    let mut seen: HashMap<(u64, u64), &RawStop> = HashMap::new();
+    for stop in stops {
+        let key = (stop.gps_long.to_bits(), stop.gps_lat.to_bits());
+        match seen.get(&key) {
+            Some(other) if other.node_id != stop.node_id => issues.push(
+                Issue::new(
+                    "identical_coordinates",
+                    format!("{} and {} share coordinates", other.node_id, stop.node_id),
+                )
+                .at(stop.gps_long, stop.gps_lat),
+            ),
+            Some(_) => {}
+            None => {
+                seen.insert(key, stop);
+            }
+        }
+    }
 
-        // Save RAW file
-        let raw_file = RawRouteFile {
-            route_id: route_id.clone(),
-            route_no: route_no.clone(),
-            fetched_at: Local::now().to_rfc3339(),
-            stops: stops.clone(),
-        };
+    issues
+}
+
+/// Infers the turning point of a round trip whose direction codes are unusable.
+///
+/// A round trip serves most stops on both legs, so stop names mirror around
+/// the terminus; the index with the most mirrored names wins, ties going to the
+/// stop closest to the one farthest from the start. Without any mirrored names,
+/// the farthest stop is the turn. A final stop that closes a loop back to the
+/// first one mirrors nothing, so it is left out of the comparison.
+fn infer_turn_idx(stops: &[RawStop]) -> usize {
+    let start = &stops[0];
+    let len = match stops.last() {
+        Some(last) if stops.len() > 2 && last.node_id == start.node_id => stops.len() - 1,
+        _ => stops.len(),
+    };
+    let distance_from_start =
+        |s: &RawStop| meters_between(start.gps_long, start.gps_lat, s.gps_long, s.gps_lat);
+    let farthest = (0..stops.len())
+        .max_by(|&a, &b| distance_from_start(&stops[a]).total_cmp(&distance_from_start(&stops[b])))
+        .unwrap_or(stops.len() - 1);
+
+    let name = |i: usize| stops[i].node_nm.trim();
+    let mirror_score = |t: usize| {
+        // Terminus served once (names mirror around t) or as two stops (t, t + 1)
+        let odd = (1..=t)
+            .take_while(|&k| t + k < len)
+            .filter(|&k| name(t - k) == name(t + k))
+            .count();
+        let even = (0..=t)
+            .take_while(|&k| t + 1 + k < len)
+            .filter(|&k| name(t - k) == name(t + 1 + k))
+            .count();
+        odd.max(even)
+    };
+
+    (0..stops.len())
+        .max_by_key(|&t| (mirror_score(t), std::cmp::Reverse(t.abs_diff(farthest))))
+        .filter(|&t| mirror_score(t) > 0)
+        .unwrap_or(farthest)
+}
+
+/// Formats a TAGO "HHMM" time (possibly sent as a number, e.g. `530`) as
+/// "HH:MM".
+fn format_hhmm(time: &str) -> Option<String> {
+    let value: u32 = time.trim().parse().ok()?;
+    let (hours, minutes) = (value / 100, value % 100);
+    (hours < 30 && minutes < 60).then(|| format!("{:02}:{:02}", hours, minutes))
+}
+
+/// Detects circular (순환) routes: either named as such, or running in a single
+/// direction (no up/down change) from and back to the same place.
+fn is_circular_route(route_no: &str, stops: &[RawStop]) -> bool {
+    if route_no.contains("순환") {
+        return true;
+    }
+
+    let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+        return false;
+    };
+
+    let single_direction = stops.iter().all(|s| s.up_down_cd == first.up_down_cd);
+    let closes_loop = first.node_id == last.node_id
+        || meters_between(first.gps_long, first.gps_lat, last.gps_long, last.gps_lat)
+            <= LOOP_CLOSURE_M;
+
+    stops.len() > 2 && single_direction && closes_loop
+}
+
+// ============================================================================
+// Processor Implementation
+// ============================================================================
+
+impl BusRouteProcessor {
+    // Phase 1 Logic
+
+    /// Fetches the city's route list, narrowed server-side when a route number is given.
+    async fn get_all_routes(&self, route_no: Option<&str>) -> Result<Vec<RouteInfo>> {
+        let mut request = RouteListRequest::new(&self.city_code);
+        if let Some(no) = route_no {
+            request = request.route_no(no);
+        }
+        self.tago.fetch_all(&request).await
+    }
+
+    async fn fetch_and_save_raw(&self, route_info: RouteInfo) -> Result<Option<RouteProcessData>> {
+        let RouteInfo {
+            route_id, route_no, ..
+        } = route_info;
+
+        if route_no.is_empty() || route_id.is_empty() {
+            return Ok(None);
+        }
 
         let file_path = self.raw_dir.join(format!("{}_{}.json", route_no, route_id));
-        fs::write(file_path, serde_json::to_string_pretty(&raw_file)?)?;
+        let fresh = self
+            .max_age
+            .and_then(|max_age| read_fresh_raw(&file_path, max_age));
+        let reused = fresh.is_some();
+        let mut unchanged = false;
+        let mut raw_bytes = 0;
+
+        let (stops, info, content_hash) = if let Some(raw_file) = fresh {
+            let content_hash = raw_file.content_hash();
+            (raw_file.stops, raw_file.info, content_hash)
+        } else {
+            // With the raw file saved last time, TAGO may answer that the
+            // stops are unchanged (304 Not Modified).
+            let saved = self.conditional.then(|| read_raw(&file_path)).flatten();
+            let stops_key = format!("stops:{}", route_id);
+            let validators = saved.as_ref().and_then(|_| state::validators(&stops_key));
+
+            // Fetch Stops
+            let fetched = self
+                .tago
+                .fetch_all_if_modified(
+                    &RouteStopsRequest::new(&self.city_code, &route_id),
+                    validators.as_ref(),
+                )
+                .await?;
+            let (stops, stops_validators) = match (fetched, &saved) {
+                (Some((items, new_validators)), _) => {
+                    if items.is_empty() {
+                        return Ok(None);
+                    }
+
+                    // Convert to internal RawStop
+                    let mut stops: Vec<RawStop> = items
+                        .into_iter()
+                        .map(|item| RawStop {
+                            node_id: item.node_id,
+                            node_nm: item.node_name,
+                            node_ord: item.node_ord,
+                            node_no: item.node_no,
+                            gps_lat: item.gps_lat,
+                            gps_long: item.gps_long,
+                            up_down_cd: item.up_down_cd,
+                        })
+                        .collect();
+                    stops.sort_by_key(|s| s.node_ord);
+                    (stops, new_validators)
+                }
+                (None, Some(saved)) => (saved.stops.clone(), validators),
+                // Only requests with the validators of a saved file are conditional
+                (None, None) => return Ok(None),
+            };
+
+            let info = if self.route_info {
+                self.fetch_route_info(&route_id).await?
+            } else {
+                None
+            };
+
+            // Save RAW file, unless it is unchanged: the saved file keeps its
+            // `fetched_at`, so the route is not snapped again.
+            let raw_file = RawRouteFile {
+                route_id: route_id.clone(),
+                route_no: route_no.clone(),
+                fetched_at: now().to_rfc3339(),
+                stops,
+                info,
+            };
+            let content_hash = raw_file.content_hash();
+            unchanged = saved.is_some_and(|s| s.content_hash() == content_hash);
+            if !unchanged {
+                let content = serde_json::to_string_pretty(&raw_file)?;
+                raw_bytes = content.len();
+                write_atomic(&file_path, content)?;
+            }
+            state::record_validators(&stops_key, stops_validators.as_ref());
+            (raw_file.stops, raw_file.info, content_hash)
+        };
+        let issues = validate_stop_sequence(&stops);
 
         // Generate Metadata for routeMap.json
-        let sequence_meta: Vec<Value> = stops
+        let sequence: Vec<SequenceStop> = stops
             .iter()
-            .map(|s| {
-                json!({
-                    "nodeid": s.node_id, "nodeord": s.node_ord, "updowncd": s.up_down_cd
-                })
+            .map(|s| SequenceStop {
+                nodeid: s.node_id.clone(),
+                nodeord: s.node_ord,
+                updowncd: s.up_down_cd,
             })
             .collect();
 
         let stops_map_data: Vec<(String, Value)> = stops
             .iter()
             .map(|s| {
-                (
-                    s.node_id.clone(),
-                    json!({
-                        "nodenm": s.node_nm, "nodeno": s.node_no,
-                        "gpslati": s.gps_lat, "gpslong": s.gps_long
-                    }),
-                )
+                let station = Station {
+                    nodenm: s.node_nm.clone(),
+                    nodeno: s.node_no.clone(),
+                    gpslati: s.gps_lat,
+                    gpslong: s.gps_long,
+                    name_en: self.romanize.then(|| romanize(&s.node_nm)),
+                    routes: Vec::new(),
+                    route_ids: Vec::new(),
+                };
+                (s.node_id.clone(), json!(station))
             })
             .collect();
 
         Ok(Some(RouteProcessData {
             route_id,
             route_no: route_no.clone(),
-            details: json!({ "routeno": route_no, "sequence": sequence_meta }),
+            details: json!(RouteDetail {
+                routeno: route_no.clone(),
+                sequence,
+                routetp: info
+                    .as_ref()
+                    .map(|i| i.route_type.clone())
+                    .filter(|t| !t.is_empty()),
+                startvehicletime: info.as_ref().and_then(|i| i.first_bus.clone()),
+                endvehicletime: info.as_ref().and_then(|i| i.last_bus.clone()),
+                intervaltime: info.as_ref().and_then(|i| i.interval_min),
+                intervalsattime: info.as_ref().and_then(|i| i.interval_sat_min),
+                intervalsuntime: info.as_ref().and_then(|i| i.interval_sun_min),
+            }),
             stops_map: stops_map_data,
+            issues,
+            reused,
+            unchanged,
+            raw_bytes,
+            content_hash,
+        }))
+    }
+
+    /// Fetches the route's service information. Missing or unusable info only
+    /// leaves the fields out; a rejected key or exhausted quota is an error.
+    async fn fetch_route_info(&self, route_id: &str) -> Result<Option<RouteServiceInfo>> {
+        let items = match self
+            .tago
+            .fetch_all(&RouteInfoRequest::new(&self.city_code, route_id))
+            .await
+        {
+            Ok(items) => items,
+            Err(e)
+                if e.downcast_ref::<TagoError>()
+                    .is_some_and(TagoError::is_fatal) =>
+            {
+                return Err(e);
+            }
+            Err(e) => {
+                warn!(
+                    route_id,
+                    phase = "fetch",
+                    "No route info for {}: {:#}",
+                    route_id,
+                    e
+                );
+                return Ok(None);
+            }
+        };
+
+        Ok(items.into_iter().next().map(|item: RouteInfoItem| {
+            let interval = |minutes: i64| (minutes > 0).then_some(minutes);
+            RouteServiceInfo {
+                first_bus: format_hhmm(&item.start_vehicle_time),
+                last_bus: format_hhmm(&item.end_vehicle_time),
+                interval_min: interval(item.interval),
+                interval_sat_min: interval(item.interval_sat),
+                interval_sun_min: interval(item.interval_sun),
+                route_type: item.route_type,
+            }
         }))
     }
 
     // Phase 2 Logic
-    async fn process_raw_to_derived(&self, raw_path: &Path) -> Result<()> {
+
+    /// Snaps a raw route file to a derived GeoJSON route.
+    async fn process_raw_to_derived(&self, raw_path: &Path) -> Result<SnapStats> {
         // Read Raw File
         let content = fs::read_to_string(raw_path)?;
         let raw_data: RawRouteFile = serde_json::from_str(&content)?;
 
+        let content_hash = raw_data.content_hash();
+        // Re-fetched, but the same data as snapped last time
+        let unchanged = state::snapped_hash(&raw_data.route_id).as_ref() == Some(&content_hash);
+        let existing = derived_files(&self.derived_dir, &raw_data.route_id);
+        let current = match existing.as_slice() {
+            // Only a file named the way this run names it can be kept
+            [path] => {
+                is_hashed_name(path) == self.hashed_names
+                    && derived_is_current(path, &raw_data.fetched_at, unchanged, self.crs)
+            }
+            _ => false,
+        };
+        if !self.reprocess_all && current {
+            return Ok(SnapStats {
+                skipped: true,
+                ..Default::default()
+            });
+        }
+        info!(
+            route_id = raw_data.route_id,
+            phase = "snap",
+            "{}",
+            Msg::Processing(&raw_path.file_name().unwrap_or_default().to_string_lossy())
+        );
+
         let mut stops = raw_data.stops;
+        let original_points: Vec<(f64, f64)> =
+            stops.iter().map(|s| (s.gps_long, s.gps_lat)).collect();
 
         // Sanitize coordinates (drift correction)
-        self.sanitize_stops_to_corridor(&mut stops).await;
+        let mut snap_time = Duration::ZERO;
+        let stop_corrections = if self.sanitize {
+            let started = Instant::now();
+            let corrections = self.sanitize_stops_to_corridor(&mut stops).await;
+            snap_time += started.elapsed();
+            corrections
+        } else {
+            Vec::new()
+        };
 
         if stops.len() < 2 {
-            return Ok(());
+            return Ok(SnapStats::default());
         }
 
         let route_id = raw_data.route_id;
         let route_no = raw_data.route_no;
 
+        let is_circular = is_circular_route(&route_no, &stops);
+
         // Identify Turning Point
+        let mut issues = Vec::new();
         let mut turn_idx = stops.len() - 1;
         for i in 0..stops.len() - 1 {
             if stops[i].up_down_cd != stops[i + 1].up_down_cd {
@@ -339,63 +1480,170 @@ impl BusRouteProcessor {
                 break;
             }
         }
+
+        // TAGO direction codes that flip more than once, or never change on a
+        // round trip, are replaced with ones split at the inferred turn.
+        let direction_changes = stops
+            .windows(2)
+            .filter(|p| p[0].up_down_cd != p[1].up_down_cd)
+            .count();
+        if !is_circular && direction_changes != 1 {
+            turn_idx = infer_turn_idx(&stops);
+            let first = stops[0].up_down_cd;
+            let second = if first == 0 { 1 } else { 0 };
+            for (i, stop) in stops.iter_mut().enumerate() {
+                stop.up_down_cd = if i <= turn_idx { first } else { second };
+            }
+            issues.push(
+                Issue::new(
+                    "updowncd_override",
+                    format!(
+                        "updowncd changes {} time(s); turn inferred at {} ({})",
+                        direction_changes, stops[turn_idx].node_nm, stops[turn_idx].node_id
+                    ),
+                )
+                .at(stops[turn_idx].gps_long, stops[turn_idx].gps_lat),
+            );
+        }
         let turn_node_id = stops[turn_idx].node_id.clone();
 
         // OSRM Logic (Merging)
         let mut full_coordinates: Vec<Vec<f64>> = Vec::new();
-        let mut stop_to_coord: Vec<usize> = Vec::with_capacity(stops.len());
+        let mut leg_durations: Vec<Option<f64>> = Vec::with_capacity(stops.len() - 1);
+        let mut leg_distances: Vec<Option<f64>> = Vec::with_capacity(stops.len() - 1);
+        let mut leg_confidence: Vec<Option<f64>> = Vec::with_capacity(stops.len() - 1);
+        let mut failed_requests = 0;
+        let mut snap_backends: Vec<Snapper> = Vec::new();
+        let mut chunk_starts: Vec<usize> = Vec::new();
         let mut start_idx = 0;
+        // Shrinks for the rest of the route whenever a chunk cannot be snapped
+        let mut chunk_size = self.chunk_size;
 
         while start_idx < stops.len() - 1 {
-            let end_idx = (start_idx + OSRM_CHUNK_SIZE).min(stops.len());
+            let fit = stops_fitting_url(&stops[start_idx..], OSRM_MAX_COORDS_LEN);
+            let end_idx = (start_idx + chunk_size.min(fit)).min(stops.len());
             let chunk = &stops[start_idx..end_idx];
 
             if chunk.len() < 2 {
                 break;
             }
 
-            if let Some(coords) = self.fetch_osrm_route(chunk).await {
-                let current_total = full_coordinates.len();
-
-                // Merge Geometry
-                let (to_append, _offset) = if current_total > 0 {
-                    (&coords[1..], 0)
-                } else {
-                    (&coords[..], 0)
-                };
-
-                // Map Stops to Geometry
-                for (i, stop) in chunk.iter().enumerate() {
-                    let global_stop_idx = start_idx + i;
-                    if global_stop_idx < stop_to_coord.len() {
-                        continue;
-                    }
+            // Snap the stops with the first backend in the chain that succeeds;
+            // straight lines between the stops are the last resort.
+            let started = Instant::now();
+            let (snapped, backend) = self.snapper.snap(&route_id, chunk).await;
+            snap_time += started.elapsed();
+            if backend == Snapper::Straight
+                && self.snapper.routes_roads()
+                && chunk.len() > OSRM_MIN_CHUNK_SIZE
+            {
+                // Too long or too odd for the backend; retry in halves rather
+                // than give up the road geometry of the whole chunk.
+                chunk_size = (chunk.len() / 2 + 1).max(OSRM_MIN_CHUNK_SIZE);
+                warn!(
+                    route_id,
+                    phase = "snap",
+                    outcome = "retry",
+                    "Chunk of {} stops of {} failed; retrying {} at a time",
+                    chunk.len(),
+                    route_id,
+                    chunk_size
+                );
+                continue;
+            }
+            // Straight lines are only a failure when roads were asked for.
+            if backend == Snapper::Straight && self.snapper.routes_roads() {
+                failed_requests += 1;
+            }
+            if !snap_backends.contains(&backend) {
+                snap_backends.push(backend);
+            }
 
-                    if let Some(local_idx) =
-                        find_nearest_coord_index((stop.gps_long, stop.gps_lat), &coords)
-                    {
-                        let global_coord_idx = if current_total > 0 {
-                            if local_idx == 0 {
-                                current_total - 1
-                            } else {
-                                current_total + local_idx - 1
-                            }
-                        } else {
-                            local_idx
-                        };
-                        stop_to_coord.push(global_coord_idx);
-                    } else {
-                        stop_to_coord.push(current_total);
-                    }
+            // Record per-leg travel times, distances, and match confidence
+            // (chunks overlap by one stop, so legs line up)
+            match &snapped {
+                r if r.leg_durations.len() == chunk.len() - 1 => {
+                    leg_durations.extend(
+                        r.leg_durations
+                            .iter()
+                            .map(|d| d.map(|d| (d * 10.0).round() / 10.0)),
+                    );
+                    leg_distances.extend(
+                        r.leg_distances
+                            .iter()
+                            .map(|d| d.map(|d| (d * 10.0).round() / 10.0)),
+                    );
+                    leg_confidence.extend(
+                        r.leg_confidence
+                            .iter()
+                            .map(|c| c.map(|c| (c * 1000.0).round() / 1000.0)),
+                    );
                 }
+                _ => {
+                    leg_durations.extend(std::iter::repeat_n(None, chunk.len() - 1));
+                    leg_distances.extend(std::iter::repeat_n(None, chunk.len() - 1));
+                    leg_confidence.extend(std::iter::repeat_n(None, chunk.len() - 1));
+                }
+            }
 
-                full_coordinates.extend_from_slice(to_append);
+            let SnappedRoute {
+                coordinates: coords,
+                ..
+            } = snapped;
+            let current_total = full_coordinates.len();
+
+            // Merge Geometry
+            let to_append = if current_total > 0 {
+                &coords[1..]
+            } else {
+                &coords[..]
+            };
+
+            if current_total > 0 {
+                chunk_starts.push(current_total - 1);
             }
+            full_coordinates.extend_from_slice(to_append);
             start_idx = end_idx - 1;
         }
 
-        while stop_to_coord.len() < stops.len() {
-            stop_to_coord.push(full_coordinates.len().saturating_sub(1));
+        issues.extend(detect_anomalies(&full_coordinates, &chunk_starts));
+
+        // Map stops onto the merged geometry in order, so indices never go
+        // backwards (even where the route passes the same point twice)
+        let stop_points: Vec<(f64, f64)> = stops.iter().map(|s| (s.gps_long, s.gps_lat)).collect();
+        let mut stop_to_coord: Vec<usize> =
+            map_points_along_line(&stop_points, &full_coordinates, STOP_MAPPING_RADIUS_M)
+                .iter()
+                .map(|p| p.coord_idx)
+                .collect();
+
+        // Simplify the line, keeping the vertices stops are mapped to
+        let points_before = full_coordinates.len();
+        if let Some(tolerance) = self.simplify_m {
+            let kept = simplify_line(&full_coordinates, tolerance, &stop_to_coord);
+            for idx in stop_to_coord.iter_mut() {
+                *idx = kept.binary_search(idx).unwrap_or_else(|pos| pos);
+            }
+            full_coordinates = kept
+                .into_iter()
+                .map(|i| full_coordinates[i].clone())
+                .collect();
+        }
+
+        // Flag legs whose map match is doubtful, so they can be reviewed.
+        let low_confidence = leg_confidence
+            .iter()
+            .filter(|c| c.is_some_and(|c| c < OSRM_LOW_CONFIDENCE))
+            .count();
+        if low_confidence > 0 {
+            warn!(
+                route_id,
+                phase = "snap",
+                "{} leg(s) of {} matched with confidence below {}",
+                low_confidence,
+                route_id,
+                OSRM_LOW_CONFIDENCE
+            );
         }
 
         // [OPTIMIZATION] Round coordinates to 6 decimal places to reduce file size
@@ -409,142 +1657,524 @@ impl BusRouteProcessor {
             })
             .collect();
 
-        // Derive Indices & Metrics
-        let turn_coord_idx = stops
-            .iter()
-            .position(|s| s.node_id == turn_node_id)
-            .and_then(|idx| stop_to_coord.get(idx).cloned())
-            .unwrap_or(optimized_coordinates.len() / 2);
+        // Derive Indices & Metrics (circular routes have no turning point)
+        let turn_coord_idx = (!is_circular).then(|| {
+            stops
+                .iter()
+                .position(|s| s.node_id == turn_node_id)
+                .and_then(|idx| stop_to_coord.get(idx).cloned())
+                .unwrap_or(optimized_coordinates.len() / 2)
+        });
 
         // Calculate BBox & Distance using optimized coordinates
         let (bbox, total_dist) = calculate_metrics(&optimized_coordinates);
+        let cum = cumulative_distances(&optimized_coordinates);
+
+        // Quality metrics against the stops as TAGO reported them
+        let max_stop_offset_m = map_points_along_line(
+            &original_points,
+            &optimized_coordinates,
+            STOP_MAPPING_RADIUS_M,
+        )
+        .iter()
+        .map(|p| p.offset_m)
+        .fold(0.0, f64::max);
+        let chain_dist: f64 = original_points
+            .windows(2)
+            .map(|w| meters_between(w[0].0, w[0].1, w[1].0, w[1].1))
+            .sum();
+        let chain: Vec<Vec<f64>> = original_points.iter().map(|&(x, y)| vec![x, y]).collect();
+        let frechet_m = frechet_distance(
+            &densify_line(&optimized_coordinates, FRECHET_STEP_M),
+            &densify_line(&chain, FRECHET_STEP_M),
+        )
+        .unwrap_or(0.0);
+        let quality = SnapMetrics {
+            max_stop_offset_m,
+            frechet_m,
+            length_ratio: if chain_dist > 0.0 {
+                (total_dist / chain_dist * 1000.0).round() / 1000.0
+            } else {
+                0.0
+            },
+            failed_chunks: failed_requests,
+        };
+        let stop_dist: Vec<f64> = stop_to_coord
+            .iter()
+            .map(|&c| (cum.get(c).copied().unwrap_or(total_dist) * 10.0).round() / 10.0)
+            .collect();
 
         // Build Frontend Data Structures
         let frontend_stops: Vec<FrontendStop> = stops
             .iter()
-            .map(|s| FrontendStop {
+            .zip(&stop_to_coord)
+            .map(|(s, &c)| FrontendStop {
                 id: s.node_id.clone(),
                 name: s.node_nm.clone(),
                 ord: s.node_ord,
                 up_down: s.up_down_cd,
+                bearing: bearing_along(
+                    &optimized_coordinates,
+                    &cum,
+                    cum.get(c).copied().unwrap_or(total_dist),
+                    STOP_BEARING_REACH_M,
+                )
+                .map(|b| b.round()),
             })
             .collect();
 
-        let derived_data = RouteFeatureCollection {
-            type_: "FeatureCollection".to_string(),
-            features: vec![RouteFeature {
-                type_: "Feature".to_string(),
-                id: route_id.clone(),
-                bbox: Some(bbox.to_vec()),
-                geometry: RouteGeometry {
-                    type_: "LineString".to_string(),
-                    coordinates: optimized_coordinates,
+        let snap_quality = if snap_backends.contains(&Snapper::Straight) {
+            SnapQuality::Degraded
+        } else {
+            SnapQuality::Full
+        };
+
+        let points_after = optimized_coordinates.len();
+        let geometry = match self.geometry_encoding {
+            GeometryEncoding::Geojson => RouteGeometry {
+                type_: "LineString".to_string(),
+                coordinates: optimized_coordinates,
+                polyline: None,
+                precision: None,
+            },
+            GeometryEncoding::Polyline => RouteGeometry {
+                type_: "LineString".to_string(),
+                coordinates: Vec::new(),
+                polyline: Some(polyline::encode(
+                    &optimized_coordinates,
+                    self.polyline_precision,
+                )),
+                precision: Some(self.polyline_precision),
+            },
+        };
+
+        let mut feature = RouteFeature {
+            type_: "Feature".to_string(),
+            id: route_id.clone(),
+            bbox: Some(bbox.to_vec()),
+            geometry,
+            properties: RouteProperties {
+                route_id: route_id.clone(),
+                route_no,
+                is_circular,
+                stops: frontend_stops,
+                stop_corrections,
+                leg_durations,
+                leg_distances,
+                leg_confidence,
+                snap_backends,
+                snap_quality,
+                indices: RouteIndices {
+                    turn_idx: turn_coord_idx,
+                    stop_to_coord,
+                    stop_dist,
                 },
-                properties: RouteProperties {
-                    route_id: route_id.clone(),
-                    route_no,
-                    stops: frontend_stops,
-                    indices: RouteIndices {
-                        turn_idx: turn_coord_idx,
-                        stop_to_coord,
-                    },
-                    meta: FrontendMeta {
-                        total_dist: (total_dist * 10.0).round() / 10.0,
-                        source_ver: raw_data.fetched_at,
-                    },
+                meta: FrontendMeta {
+                    total_dist: (total_dist * 10.0).round() / 10.0,
+                    source_ver: raw_data.fetched_at,
+                    quality,
                 },
-            }],
+            },
+        };
+        // Metrics above are computed in WGS84; only the written coordinates move.
+        if self.crs != Crs::Wgs84 {
+            feature.reproject(|p| self.crs.project(p));
+        }
+        let derived_data = RouteFeatureCollection {
+            type_: "FeatureCollection".to_string(),
+            crs: self.crs.geojson_member(),
+            provenance: Some(self.provenance.clone()),
+            features: vec![feature],
         };
 
-        // Save Derived File
-        let output_path = self.derived_dir.join(format!("{}.geojson", route_id));
-        fs::write(output_path, serde_json::to_string(&derived_data)?)?;
+        // Save Derived File, replacing any earlier version of the route
+        let content = serde_json::to_string(&derived_data)?;
+        let file_name = if self.hashed_names {
+            format!(
+                "{}.{}.geojson",
+                route_id,
+                short_hash(content.as_bytes(), 12)
+            )
+        } else {
+            format!("{}.geojson", route_id)
+        };
+        let output_path = self.derived_dir.join(file_name);
+        write_atomic(&output_path, &content)?;
+        for old in existing.iter().filter(|p| **p != output_path) {
+            remove_file(old)?;
+        }
+        if failed_requests == 0 {
+            let full = (snap_quality == SnapQuality::Full).then_some(content_hash.as_str());
+            state::record_snap(&route_id, full);
+        }
 
-        Ok(())
+        Ok(SnapStats {
+            failed_chunks: failed_requests,
+            points_before,
+            points_after,
+            issues,
+            skipped: false,
+            snap_time,
+            output_bytes: content.len(),
+        })
     }
 
     // Helpers (Sanitize, OSRM Fetch, Save Map)
-    async fn sanitize_stops_to_corridor(&self, stops: &mut [RawStop]) {
+    /// Corrects drifted stop coordinates by snapping each intermediate stop onto
+    /// the road corridor between its neighbours (routed without the stop itself).
+    ///
+    /// Routing every other stop in one request yields those corridors for all
+    /// stops of the opposite parity at once, so a route needs about two OSRM
+    /// requests per chunk instead of one per stop.
+    async fn sanitize_stops_to_corridor(&self, stops: &mut [RawStop]) -> Vec<StopCorrection> {
+        let mut corrections = Vec::new();
         if stops.len() < 3 {
-            return;
+            return corrections;
         }
 
-        for i in 1..stops.len() - 1 {
-            let prev = stops[i - 1].clone();
-            let next = stops[i + 1].clone();
-
-            if let Some(corr) = self.fetch_osrm_route_between(&prev, &next).await {
-                let p = (stops[i].gps_long, stops[i].gps_lat);
-                if let Some(((cx, cy), d)) = closest_point_on_polyline(p, &corr) {
-                    if d <= 90.0 {
-                        stops[i].gps_long = cx;
-                        stops[i].gps_lat = cy;
+        for parity in 0..2 {
+            let waypoints: Vec<usize> = (parity..stops.len()).step_by(2).collect();
+
+            let mut start = 0;
+            while start + 1 < waypoints.len() {
+                let fit = stops_fitting_url(
+                    waypoints[start..].iter().map(|&i| &stops[i]),
+                    OSRM_MAX_COORDS_LEN,
+                );
+                let end = (start + self.chunk_size.min(fit)).min(waypoints.len());
+                let chunk: Vec<RawStop> = waypoints[start..end]
+                    .iter()
+                    .map(|&i| stops[i].clone())
+                    .collect();
+
+                // Best effort: stops keep their coordinates if the corridor is unavailable.
+                if let Ok(SnappedRoute {
+                    coordinates: corr, ..
+                }) = self.snapper.corridor(&chunk).await
+                {
+                    // Waypoints are two apart; the stop in between is snapped.
+                    for pair in waypoints[start..end].windows(2) {
+                        let (prev, next) = (&stops[pair[0]], &stops[pair[1]]);
+                        let from = (prev.gps_long, prev.gps_lat);
+                        let to = (next.gps_long, next.gps_lat);
+
+                        let idx = pair[0] + 1;
+                        let original = [stops[idx].gps_long, stops[idx].gps_lat];
+                        let moved = snap_onto_corridor(
+                            &mut stops[idx],
+                            &corr,
+                            from,
+                            to,
+                            self.snap_threshold_m,
+                        );
+                        if let Some(moved_m) = moved.filter(|&m| m > 0.0) {
+                            corrections.push(StopCorrection {
+                                stop_idx: idx,
+                                stop_id: stops[idx].node_id.clone(),
+                                from: original,
+                                moved_m,
+                            });
+                        }
                     }
                 }
+
+                start = end - 1;
             }
         }
-    }
-
-    async fn fetch_osrm_route_between(&self, a: &RawStop, b: &RawStop) -> Option<Vec<Vec<f64>>> {
-        let coords = format!(
-            "{:.6},{:.6};{:.6},{:.6}",
-            a.gps_long, a.gps_lat, b.gps_long, b.gps_lat
-        );
 
-        self.call_osrm(&coords).await
+        corrections.sort_by_key(|c| c.stop_idx);
+        corrections
     }
 
-    async fn fetch_osrm_route(&self, stops: &[RawStop]) -> Option<Vec<Vec<f64>>> {
-        let coords = stops
-            .iter()
-            .map(|s| format!("{:.6},{:.6}", s.gps_long, s.gps_lat))
-            .collect::<Vec<_>>()
-            .join(";");
-
-        self.call_osrm(&coords).await
-    }
-
-    async fn call_osrm(&self, coords_param: &str) -> Option<Vec<Vec<f64>>> {
-        let url = format!(
-            "{}/{coords}?overview=full&geometries=geojson&steps=false&continue_straight=true",
-            self.osrm_base_url,
-            coords = coords_param
-        );
+    /// Writes routeMap.json and the station outputs, filling in the routes
+    /// serving each station of `stops` on the way. The files are streamed to
+    /// disk, so large cities are not held in memory twice.
+    fn save_route_map_json(
+        &self,
+        map: &BTreeMap<String, Vec<String>>,
+        details: &BTreeMap<String, Value>,
+        stops: &mut BTreeMap<String, Value>,
+    ) -> Result<()> {
+        let last_updated = now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let variants = analyze_variants(map, details);
+
+        // Reverse index, so stop popups can list services without joining routes
+        let mut serving = serving_routes(details);
+        for (node_id, station) in stops.iter_mut() {
+            let (numbers, ids) = serving.remove(node_id.as_str()).unwrap_or_default();
+            station["routes"] = json!(numbers);
+            station["route_ids"] = json!(ids);
+        }
+        let stops = &*stops;
 
-        let resp = reqwest::get(&url).await.ok()?;
-        if !resp.status().is_success() {
-            return None;
+        if self.split_map {
+            self.save_route_map_shards(map, details, stops, &last_updated, &variants)?;
         }
 
-        let json: Value = resp.json().await.ok()?;
-        let coords: Vec<Vec<f64>> =
-            serde_json::from_value(json["routes"][0]["geometry"]["coordinates"].clone()).ok()?;
+        let final_data = RouteMapFile {
+            schema_version: ROUTE_MAP_SCHEMA_VERSION,
+            last_updated,
+            route_numbers: map,
+            route_details: details,
+            variants: &variants,
+            stations: stops,
+            provenance: Some(&self.provenance),
+        };
 
-        if coords.is_empty() {
-            None
-        } else {
-            Some(coords)
+        write_json_atomic(&self.mapping_file, &final_data, true)?;
+        compress_file(&self.mapping_file, &self.compress)?;
+
+        write_stops_geojson(
+            &self.mapping_file.with_file_name(STOPS_FILE),
+            details,
+            stops,
+            self.crs,
+            &self.compress,
+        )?;
+        if self.flatgeobuf {
+            write_stops_fgb(
+                &self.mapping_file.with_file_name(STOPS_FGB_FILE),
+                details,
+                stops,
+                self.crs,
+            )?;
         }
+
+        Ok(())
     }
 
-    fn save_route_map_json(
+    /// Writes the route map as shards under `routeMap/`: an `index.json` with
+    /// the route numbers, variants, and shard paths, one `routes/{route_id}.json`
+    /// per route detail, and `stations.json`. Shards of routes no longer in the
+    /// map are removed.
+    fn save_route_map_shards(
         &self,
         map: &BTreeMap<String, Vec<String>>,
-        details: &HashMap<String, Value>,
+        details: &BTreeMap<String, Value>,
         stops: &BTreeMap<String, Value>,
+        last_updated: &str,
+        variants: &BTreeMap<String, Vec<RouteVariant>>,
     ) -> Result<()> {
-        let final_data = json!({
-            "lastUpdated": Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        let shard_dir = self.mapping_file.with_extension("");
+        let routes_dir = shard_dir.join("routes");
+        ensure_dir(&routes_dir)?;
+
+        // Absent only in a dry run, where `ensure_dir` creates nothing.
+        let entries = fs::read_dir(&routes_dir).into_iter().flatten();
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let stale = path.extension().is_some_and(|ext| ext == "json")
+                && path
+                    .file_stem()
+                    .is_some_and(|id| !details.contains_key(&*id.to_string_lossy()));
+            if stale {
+                remove_file(&path)?;
+            }
+        }
+
+        let mut route_files = BTreeMap::new();
+        for (route_id, detail) in details {
+            let name = format!("routes/{}.json", route_id);
+            write_atomic(&shard_dir.join(&name), serde_json::to_string(detail)?)?;
+            route_files.insert(route_id, name);
+        }
+
+        let stations_path = shard_dir.join("stations.json");
+        write_json_atomic(&stations_path, stops, false)?;
+        compress_file(&stations_path, &self.compress)?;
+
+        let index = json!({
+            "schemaVersion": ROUTE_MAP_SCHEMA_VERSION,
+            "lastUpdated": last_updated,
             "route_numbers": map,
-            "route_details": details,
-            "stations": stops
+            "variants": variants,
+            "route_files": route_files,
+            "stations_file": "stations.json",
         });
-
-        fs::write(
-            &self.mapping_file,
-            serde_json::to_string_pretty(&final_data)?,
-        )?;
+        let index_path = shard_dir.join("index.json");
+        let content = serde_json::to_string_pretty(&index)?;
+        write_atomic(&index_path, &content)?;
+        write_compressed(&index_path, content.as_bytes(), &self.compress)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(id: &str, name: &str, ord: i64, lon: f64, lat: f64) -> RawStop {
+        RawStop {
+            node_id: id.to_string(),
+            node_nm: name.to_string(),
+            node_ord: ord,
+            node_no: String::new(),
+            gps_lat: lat,
+            gps_long: lon,
+            up_down_cd: 0,
+        }
+    }
+
+    /// Stops `a`, `b`, `c`, ... with the given ordinals, 100 m or so apart
+    fn sequence(ords: &[i64]) -> Vec<RawStop> {
+        ords.iter()
+            .enumerate()
+            .map(|(i, &ord)| {
+                let id = ((b'a' + i as u8) as char).to_string();
+                stop(&id, &id, ord, 127.9, 37.3 + 0.001 * i as f64)
+            })
+            .collect()
+    }
+
+    fn kinds(issues: &[Issue]) -> Vec<&str> {
+        issues.iter().map(|i| i.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn clean_sequences_have_no_issues() {
+        assert!(validate_stop_sequence(&sequence(&[1, 2, 3, 4])).is_empty());
+        assert!(validate_stop_sequence(&[]).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_ordinals() {
+        let issues = validate_stop_sequence(&sequence(&[1, 2, 5]));
+        assert_eq!(kinds(&issues), ["missing_ordinal"]);
+        assert_eq!(issues[0].detail, "ordinals 3-4 are missing");
+
+        let issues = validate_stop_sequence(&sequence(&[3, 4]));
+        assert_eq!(kinds(&issues), ["missing_ordinal"]);
+        assert_eq!(issues[0].detail, "ordinals 1-2 are missing");
+    }
+
+    #[test]
+    fn reports_duplicate_ordinals() {
+        let issues = validate_stop_sequence(&sequence(&[1, 2, 2, 3]));
+        assert_eq!(kinds(&issues), ["duplicate_ordinal"]);
+        assert_eq!(issues[0].detail, "ordinal 2 is used by b and c");
+        assert_eq!(issues[0].at, Some([127.9, 37.302]));
+    }
+
+    #[test]
+    fn reports_the_same_stop_twice_in_a_row() {
+        let mut stops = sequence(&[1, 2, 3]);
+        stops[2] = RawStop {
+            node_ord: 3,
+            ..stop("b", "b", 0, stops[1].gps_long, stops[1].gps_lat)
+        };
+        let issues = validate_stop_sequence(&stops);
+        assert_eq!(kinds(&issues), ["duplicate_stop"]);
+    }
+
+    #[test]
+    fn reports_distinct_stops_at_identical_coordinates() {
+        let mut stops = sequence(&[1, 2, 3]);
+        stops[2].gps_lat = stops[0].gps_lat;
+        let issues = validate_stop_sequence(&stops);
+        assert_eq!(kinds(&issues), ["identical_coordinates"]);
+        assert_eq!(issues[0].detail, "a and c share coordinates");
+    }
+
+    /// Stops named `names` at `[lon, lat]` positions, numbered in order
+    fn named_stops(stops: &[(&str, [f64; 2])]) -> Vec<RawStop> {
+        stops
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, [lon, lat]))| stop(&format!("{}", i), name, i as i64 + 1, lon, lat))
+            .collect()
+    }
+
+    #[test]
+    fn infers_the_turn_of_an_out_and_back_route() {
+        // Opposite-side stops share their names but sit across the road.
+        let stops = named_stops(&[
+            ("시청", [127.9000, 37.3000]),
+            ("중앙시장", [127.9000, 37.3020]),
+            ("터미널", [127.9000, 37.3040]),
+            ("연세대", [127.9000, 37.3060]),
+            ("터미널", [127.9002, 37.3040]),
+            ("중앙시장", [127.9002, 37.3020]),
+            ("시청", [127.9002, 37.3000]),
+        ]);
+        assert_eq!(infer_turn_idx(&stops), 3);
+
+        // A terminus served as two stops turns at the first of them.
+        let mut two_stop_terminus = stops.clone();
+        two_stop_terminus.insert(4, stop("x", "연세대", 0, 127.9002, 37.3060));
+        assert_eq!(infer_turn_idx(&two_stop_terminus), 3);
+    }
+
+    #[test]
+    fn turns_a_loop_back_to_its_first_stop_at_the_farthest_stop() {
+        let mut stops = named_stops(&[
+            ("시청", [127.900, 37.300]),
+            ("중앙시장", [127.900, 37.302]),
+            ("터미널", [127.903, 37.303]),
+            ("연세대", [127.905, 37.301]),
+            ("원주역", [127.903, 37.299]),
+            ("시청", [127.900, 37.300]),
+        ]);
+        stops[5].node_id = stops[0].node_id.clone();
+        assert_eq!(infer_turn_idx(&stops), 3);
+    }
+
+    #[test]
+    fn without_mirrored_names_the_farthest_stop_is_the_turn() {
+        let stops = named_stops(&[
+            ("시청", [127.900, 37.300]),
+            ("중앙시장", [127.900, 37.302]),
+            ("터미널", [127.900, 37.306]),
+            ("연세대", [127.902, 37.304]),
+            ("원주역", [127.902, 37.302]),
+        ]);
+        assert_eq!(infer_turn_idx(&stops), 2);
+    }
+
+    /// Saves a routeMap.json built from `route_ids` (inserted in that order).
+    fn save_route_map(route_ids: &[&str], path: &Path) -> Vec<u8> {
+        let details: BTreeMap<String, Value> = route_ids
+            .iter()
+            .map(|id| (id.to_string(), json!({ "routeno": id, "sequence": [] })))
+            .collect();
+        let route_numbers: BTreeMap<String, Vec<String>> = route_ids
+            .iter()
+            .map(|id| (id.to_string(), vec![id.to_string()]))
+            .collect();
+        let file = RouteMapFile {
+            schema_version: ROUTE_MAP_SCHEMA_VERSION,
+            last_updated: "2026-01-01 00:00:00".to_string(),
+            route_numbers: &route_numbers,
+            route_details: &details,
+            variants: &BTreeMap::new(),
+            stations: &BTreeMap::new(),
+            provenance: None,
+        };
+        write_json_atomic(path, &file, true).unwrap();
+        let bytes = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn route_map_is_byte_identical_across_saves() {
+        let ids = [
+            "WJB251000001",
+            "WJB251000034",
+            "WJB251000100",
+            "WJB251000041",
+        ];
+        let path =
+            std::env::temp_dir().join(format!("polly-route-map-{}.json", std::process::id()));
+        let first = save_route_map(&ids, &path);
+        let reversed: Vec<&str> = ids.iter().rev().copied().collect();
+        assert_eq!(first, save_route_map(&reversed, &path));
+
+        // Keys come out sorted, whatever the insertion order.
+        let text = String::from_utf8(first).unwrap();
+        let mut sorted = ids;
+        sorted.sort();
+        let positions: Vec<usize> = sorted.iter().map(|id| text.find(id).unwrap()).collect();
+        assert!(positions.is_sorted());
+    }
+}
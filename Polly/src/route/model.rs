@@ -4,10 +4,25 @@
 //! raw and derived bus route information, including GeoJSON
 //! formats for frontend consumption.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, Serializer};
 use serde_json::Value;
+use ts_rs::TS;
+
+use crate::route::snapper::SnapChain;
+use crate::route::variants::RouteVariant;
+use crate::settings::Provenance;
+use crate::tago::client::TagoClient;
+use crate::utils::compress::Compression;
+use crate::utils::crs::Crs;
+use crate::utils::packed_rtree::bbox_of;
+use crate::utils::polyline;
+use crate::utils::report::Issue;
+use crate::utils::short_hash;
 
 // ============================================================================
 // Raw Data Models (Saved to raw_routes/)
@@ -32,6 +47,31 @@ pub struct RawRouteFile {
     pub route_no: String,
     pub fetched_at: String,
     pub stops: Vec<RawStop>,
+    /// TAGO route info, fetched with the stops (skipped with `--no-route-info`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<RouteServiceInfo>,
+}
+
+impl RawRouteFile {
+    /// Fingerprint of the fetched content (stops and route info), which,
+    /// unlike `fetched_at`, only changes when TAGO's data does.
+    pub fn content_hash(&self) -> String {
+        let content = serde_json::to_vec(&(&self.stops, &self.info)).unwrap_or_default();
+        short_hash(&content, 16)
+    }
+}
+
+/// Service information of a route from TAGO `getRouteInfoIem`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteServiceInfo {
+    pub route_type: String,
+    /// First and last departure ("HH:MM")
+    pub first_bus: Option<String>,
+    pub last_bus: Option<String>,
+    /// Headway in minutes on weekdays, Saturdays, and Sundays
+    pub interval_min: Option<i64>,
+    pub interval_sat_min: Option<i64>,
+    pub interval_sun_min: Option<i64>,
 }
 
 // ============================================================================
@@ -39,64 +79,198 @@ pub struct RawRouteFile {
 // ============================================================================
 
 /// GeoJSON FeatureCollection
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+#[ts(rename = "DerivedFeatureCollection")]
+#[schemars(rename = "DerivedFeatureCollection")]
 pub struct RouteFeatureCollection {
     #[serde(rename = "type")]
     pub type_: String, // "FeatureCollection"
+    /// Named CRS of the coordinates (`--crs`); omitted for WGS84
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub crs: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub provenance: Option<Provenance>,
     pub features: Vec<RouteFeature>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
 pub struct RouteFeature {
     #[serde(rename = "type")]
     pub type_: String, // "Feature"
     pub id: String, // Root ID (e.g., Route ID)
 
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
     pub bbox: Option<Vec<f64>>,
 
     pub properties: RouteProperties,
     pub geometry: RouteGeometry,
 }
 
-#[derive(Serialize)]
+impl RouteFeature {
+    /// Maps every coordinate (the line and the original positions of corrected
+    /// stops) through `f`, recomputing the bbox. Expects decoded geometry.
+    pub fn reproject(&mut self, f: impl Fn(&[f64]) -> Vec<f64>) {
+        let line = &mut self.geometry.coordinates;
+        *line = line.iter().map(|p| f(p)).collect();
+        if self.bbox.is_some() {
+            self.bbox = bbox_of(line).map(|b| b.to_vec());
+        }
+        for correction in &mut self.properties.stop_corrections {
+            let p = f(&correction.from);
+            correction.from = [p[0], p[1]];
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
 pub struct RouteGeometry {
     #[serde(rename = "type")]
     pub type_: String, // "LineString"
+    /// [lon, lat] pairs ([easting, northing] with `--crs epsg5179`); empty when
+    /// the line is written as `polyline` instead
+    #[serde(default)]
     pub coordinates: Vec<Vec<f64>>,
+    /// Google encoded polyline of the line (`--geometry-encoding polyline`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub polyline: Option<String>,
+    /// Decimal places of `polyline` (5 or 6)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub precision: Option<u32>,
 }
 
-#[derive(Serialize)]
+impl RouteGeometry {
+    /// Fills `coordinates` from `polyline`, if the line was written encoded.
+    pub fn decode(&mut self) {
+        if let Some(encoded) = &self.polyline
+            && self.coordinates.is_empty()
+        {
+            self.coordinates = polyline::decode(encoded, self.precision.unwrap_or(5));
+        }
+    }
+}
+
+/// How the derived route line is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GeometryEncoding {
+    /// GeoJSON coordinate array
+    Geojson,
+    /// Google encoded polyline (about 60% smaller)
+    Polyline,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
 pub struct RouteProperties {
     pub route_id: String,
     pub route_no: String,
+    /// Loop route with a single direction (no turning point)
+    #[serde(default)]
+    pub is_circular: bool,
     pub stops: Vec<FrontendStop>,
+    /// Stops moved by coordinate sanitization, and by how far
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[ts(as = "Option<Vec<StopCorrection>>", optional)]
+    pub stop_corrections: Vec<StopCorrection>,
+    /// OSRM travel time in seconds between consecutive stops (`None` if unavailable)
+    #[serde(default)]
+    pub leg_durations: Vec<Option<f64>>,
+    /// OSRM road distance in meters between consecutive stops (`None` if unavailable)
+    #[serde(default)]
+    pub leg_distances: Vec<Option<f64>>,
+    /// OSRM map-matching confidence (0-1) between consecutive stops (`None` where the
+    /// leg was routed as a shortest path instead of matched)
+    #[serde(default)]
+    pub leg_confidence: Vec<Option<f64>>,
+    /// Snapping backends that produced the geometry, in order of first use
+    #[serde(default)]
+    pub snap_backends: Vec<Snapper>,
+    /// `degraded` if any chunk fell back to straight lines between stops
+    #[serde(default)]
+    pub snap_quality: SnapQuality,
     #[serde(flatten)]
     pub indices: RouteIndices,
     #[serde(flatten)]
     pub meta: FrontendMeta,
 }
 
-#[derive(Serialize)]
+/// How well the route geometry follows the road network
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapQuality {
+    /// Every chunk was matched or routed by OSRM
+    #[default]
+    Full,
+    /// At least one chunk is straight segments between stops
+    Degraded,
+}
+
+/// A stop whose coordinates were moved onto its road corridor during sanitization
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+pub struct StopCorrection {
+    /// Index into `stops`
+    pub stop_idx: usize,
+    pub stop_id: String,
+    /// Original [lon, lat] as returned by TAGO (in the output CRS)
+    pub from: [f64; 2],
+    #[serde(serialize_with = "round_f64_1")]
+    pub moved_m: f64,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
 pub struct FrontendStop {
     pub id: String,
     pub name: String,
+    #[ts(type = "number")]
     pub ord: i64,
     #[serde(rename = "ud")]
+    #[ts(type = "number")]
     pub up_down: i64,
+    /// Direction of travel at the stop, in degrees clockwise from north
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub bearing: Option<f64>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
 pub struct RouteIndices {
-    pub turn_idx: usize,
+    /// Coordinate index of the turning point; omitted for circular routes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub turn_idx: Option<usize>,
     pub stop_to_coord: Vec<usize>,
+    /// Distance in meters along the line to each stop's mapped coordinate
+    #[serde(default)]
+    pub stop_dist: Vec<f64>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
 pub struct FrontendMeta {
     #[serde(serialize_with = "round_f64_1")]
     pub total_dist: f64,
     pub source_ver: String,
+    /// Snap quality metrics, for consumers that hide doubtful geometry
+    #[serde(default)]
+    pub quality: SnapMetrics,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, TS, JsonSchema)]
+pub struct SnapMetrics {
+    /// Largest distance (meters) from an original TAGO stop to the line
+    #[serde(serialize_with = "round_f64_1")]
+    pub max_stop_offset_m: f64,
+    /// Fréchet distance (meters) between the line and the stop-to-stop chain:
+    /// how far the line strays from the stops when followed in order, which
+    /// also catches detours between two stops that are each on the line
+    #[serde(default, serialize_with = "round_f64_1")]
+    pub frechet_m: f64,
+    /// Line length divided by the straight stop-to-stop chain length
+    pub length_ratio: f64,
+    /// Chunks no snapping backend could route (straight lines there)
+    pub failed_chunks: usize,
 }
 
 // --------------------------------------------------------
@@ -112,6 +286,95 @@ where
     serializer.serialize_f64(rounded)
 }
 
+// ============================================================================
+// Route Map Models (Saved to routeMap.json)
+// ============================================================================
+
+// Details and stations are kept as JSON while aggregating (earlier maps are
+// merged in on retries), but are built from `RouteDetail` and `Station`.
+/// Consolidated station and route metadata (routeMap.json)
+#[derive(Serialize, TS, JsonSchema)]
+pub struct RouteMapFile<'a> {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: u32,
+    #[serde(rename = "lastUpdated")]
+    pub last_updated: String,
+    /// Route number -> route IDs
+    pub route_numbers: &'a BTreeMap<String, Vec<String>>,
+    #[ts(as = "BTreeMap<String, RouteDetail>")]
+    #[schemars(with = "BTreeMap<String, RouteDetail>")]
+    pub route_details: &'a BTreeMap<String, Value>,
+    /// Route number -> variants, for numbers with several route IDs
+    pub variants: &'a BTreeMap<String, Vec<RouteVariant>>,
+    #[ts(as = "BTreeMap<String, Station>")]
+    #[schemars(with = "BTreeMap<String, Station>")]
+    pub stations: &'a BTreeMap<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub provenance: Option<&'a Provenance>,
+}
+
+/// One route in `route_details`
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct RouteDetail {
+    pub routeno: String,
+    pub sequence: Vec<SequenceStop>,
+    /// Route type (e.g., 일반버스, 좌석버스), from TAGO route info
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub routetp: Option<String>,
+    /// First departure ("HH:MM"), from TAGO route info
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub startvehicletime: Option<String>,
+    /// Last departure ("HH:MM"), from TAGO route info
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub endvehicletime: Option<String>,
+    /// Weekday headway in minutes, from TAGO route info
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub intervaltime: Option<i64>,
+    /// Saturday headway in minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub intervalsattime: Option<i64>,
+    /// Sunday headway in minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub intervalsuntime: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct SequenceStop {
+    pub nodeid: String,
+    #[ts(type = "number")]
+    pub nodeord: i64,
+    #[ts(type = "number")]
+    pub updowncd: i64,
+}
+
+/// One stop in `stations`
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct Station {
+    pub nodenm: String,
+    pub nodeno: String,
+    pub gpslati: f64,
+    pub gpslong: f64,
+    /// Romanized name (`--romanize`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub name_en: Option<String>,
+    /// Route numbers stopping here (filled when the map is saved)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub routes: Vec<String>,
+    /// Route IDs stopping here (filled when the map is saved)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub route_ids: Vec<String>,
+}
+
 // ============================================================================
 // Processing Structures
 // ============================================================================
@@ -122,15 +385,103 @@ pub struct RouteProcessData {
     pub route_no: String,
     pub details: Value,
     pub stops_map: Vec<(String, Value)>,
+    /// Stop sequence problems found in the TAGO data
+    pub issues: Vec<Issue>,
+    /// Read from a raw file fresher than `--max-age` instead of fetched
+    pub reused: bool,
+    /// Fetched, but the same as the saved raw file, which was kept
+    pub unchanged: bool,
+    /// Size of the raw file written (0 when reused)
+    pub raw_bytes: usize,
+    /// See [`RawRouteFile::content_hash`]
+    pub content_hash: String,
+}
+
+/// Outcome of snapping one route, summarized in the run output
+#[derive(Debug, Default)]
+pub struct SnapStats {
+    /// Chunks no snapping backend could route (straight lines there)
+    pub failed_chunks: usize,
+    /// Geometry points before and after simplification
+    pub points_before: usize,
+    pub points_after: usize,
+    /// Data quality issues found while snapping
+    pub issues: Vec<Issue>,
+    /// The derived file was already up to date and left as-is
+    pub skipped: bool,
+    /// Time spent waiting on the snapping backends (drift correction included)
+    pub snap_time: Duration,
+    /// Size of the derived file written
+    pub output_bytes: usize,
+}
+
+/// Road geometry returned by a snapping backend for a sequence of waypoints
+pub struct SnappedRoute {
+    pub coordinates: Vec<Vec<f64>>,
+    /// Travel time in seconds for each leg between consecutive waypoints
+    /// (`None` for legs a map match could not connect)
+    pub leg_durations: Vec<Option<f64>>,
+    /// Road distance in meters for each leg (`None` like `leg_durations`)
+    pub leg_distances: Vec<Option<f64>>,
+    /// Map-matching confidence (0-1) for each leg; `None` for shortest-path routes
+    pub leg_confidence: Vec<Option<f64>>,
+}
+
+/// Road network backend used to snap route geometry
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum, TS, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Snapper {
+    /// OSRM `/match`, falling back to `/route`
+    Osrm,
+    /// Valhalla `trace_route` with bus costing, falling back to `route`
+    Valhalla,
+    /// Kakao Mobility directions through every stop (requires `KAKAO_REST_API_KEY`)
+    Kakao,
+    /// Straight segments between stops (degraded)
+    Straight,
 }
 
 /// Main processor structure
 pub struct BusRouteProcessor {
-    pub service_key: String,
+    pub tago: TagoClient,
     pub city_code: String,
     pub raw_dir: PathBuf,
     pub derived_dir: PathBuf,
     pub mapping_file: PathBuf,
-    pub tago_base_url: String,
-    pub osrm_base_url: String,
+    /// Snapping backends tried in order for each chunk of stops
+    pub snapper: SnapChain,
+    /// Stops per routing request
+    pub chunk_size: usize,
+    /// Correct drifted stop coordinates onto their road corridor
+    pub sanitize: bool,
+    /// Max distance (meters) a stop may be moved by sanitization
+    pub snap_threshold_m: f64,
+    /// Douglas-Peucker tolerance (meters) for the derived geometry
+    pub simplify_m: Option<f64>,
+    pub geometry_encoding: GeometryEncoding,
+    /// Decimal places of encoded polylines
+    pub polyline_precision: u32,
+    pub compress: Vec<Compression>,
+    pub romanize: bool,
+    /// Fetch TAGO route info (type, first/last bus, headways) with the stops
+    pub route_info: bool,
+    /// Raw files fetched more recently than this are reused, not re-fetched
+    pub max_age: Option<chrono::TimeDelta>,
+    /// Send the validators of the saved stop lists, so TAGO can answer that
+    /// they are unchanged
+    pub conditional: bool,
+    /// Re-snap routes whose derived file is already up to date
+    pub reprocess_all: bool,
+    /// Also write the station map as FlatGeobuf
+    pub flatgeobuf: bool,
+    /// Also write routeMap.json as lazily loadable shards
+    pub split_map: bool,
+    /// Name derived files `{route_id}.{content_hash}.geojson`
+    pub hashed_names: bool,
+    /// Coordinate reference system of the written geometry
+    pub crs: Crs,
+    /// Embedded in routeMap.json and the derived files
+    pub provenance: Provenance,
 }
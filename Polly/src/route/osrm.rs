@@ -0,0 +1,214 @@
+//! OSRM Snapping Backend
+//!
+//! Map-matches stop sequences with OSRM `/match` so the geometry follows the
+//! bus corridor, falling back to the shortest path from `/route` for chunks
+//! that cannot be matched.
+
+use anyhow::Result;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::config::OSRM_MATCH_RADIUS_M;
+use crate::error::PipelineError;
+use crate::route::model::{RawStop, SnappedRoute, Snapper};
+use crate::route::snapper::{SnapClient, SnappingBackend};
+
+pub struct OsrmBackend {
+    client: SnapClient,
+    route_url: String,
+    match_url: String,
+}
+
+impl OsrmBackend {
+    /// `route_url` is the `/route/v1/{profile}` endpoint; `/match` is derived from it.
+    pub fn new(client: SnapClient, route_url: String) -> Self {
+        Self {
+            client,
+            match_url: route_url.replacen("/route/", "/match/", 1),
+            route_url,
+        }
+    }
+
+    async fn fetch_route(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        let url = format!(
+            "{}/{}?overview=full&geometries=geojson&steps=false&annotations=duration,distance&continue_straight=true",
+            self.route_url,
+            coords_param(stops)
+        );
+
+        let json = self
+            .client
+            .cached_get_json(&url, |j| j["code"] == "Ok")
+            .await?;
+        if json["code"] != "Ok" {
+            return Err(
+                PipelineError::snap("OSRM", format!("route returned {}", json["code"])).into(),
+            );
+        }
+
+        let coords: Vec<Vec<f64>> =
+            serde_json::from_value(json["routes"][0]["geometry"]["coordinates"].clone())
+                .map_err(|e| PipelineError::schema("OSRM", e))?;
+        let legs = json["routes"][0]["legs"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let leg_durations: Vec<Option<f64>> =
+            legs.iter().map(|l| leg_total(l, "duration")).collect();
+        let leg_distances: Vec<Option<f64>> =
+            legs.iter().map(|l| leg_total(l, "distance")).collect();
+
+        if coords.is_empty() {
+            return Err(PipelineError::snap("OSRM", "route returned an empty geometry").into());
+        }
+
+        Ok(SnappedRoute {
+            leg_confidence: vec![None; leg_durations.len()],
+            coordinates: coords,
+            leg_durations,
+            leg_distances,
+        })
+    }
+
+    /// Map-matches a stop sequence with OSRM `/match`. Each stop may be moved up
+    /// to `OSRM_MATCH_RADIUS_M` onto the road, so the geometry follows the actual
+    /// corridor instead of the shortest path between stops.
+    async fn fetch_match(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        let radiuses = vec![format!("{}", OSRM_MATCH_RADIUS_M); stops.len()].join(";");
+
+        let url = format!(
+            "{}/{}?overview=full&geometries=geojson&steps=false&annotations=duration,distance&gaps=ignore&tidy=false&radiuses={}",
+            self.match_url,
+            coords_param(stops),
+            radiuses
+        );
+
+        let json = self
+            .client
+            .cached_get_json(&url, |j| j["code"] == "Ok")
+            .await?;
+        if json["code"] != "Ok" {
+            return Err(
+                PipelineError::snap("OSRM", format!("match returned {}", json["code"])).into(),
+            );
+        }
+
+        let matchings = json["matchings"].as_array().cloned().unwrap_or_default();
+        let tracepoints = json["tracepoints"].as_array().cloned().unwrap_or_default();
+        if matchings.is_empty() || tracepoints.len() != stops.len() {
+            return Err(PipelineError::snap("OSRM", "match returned no usable matching").into());
+        }
+
+        // A match may be split into several matchings; join their geometries in order.
+        let mut coordinates: Vec<Vec<f64>> = Vec::new();
+        for matching in &matchings {
+            let coords: Vec<Vec<f64>> =
+                serde_json::from_value(matching["geometry"]["coordinates"].clone())
+                    .map_err(|e| PipelineError::schema("OSRM", e))?;
+            let skip = usize::from(
+                coordinates
+                    .last()
+                    .is_some_and(|l| coords.first() == Some(l)),
+            );
+            coordinates.extend(coords.into_iter().skip(skip));
+        }
+        if coordinates.is_empty() {
+            return Err(PipelineError::snap("OSRM", "match returned an empty geometry").into());
+        }
+
+        // A leg between two stops exists if both were matched into the same
+        // matching as consecutive waypoints; unmatched stops have a null tracepoint.
+        let position = |tp: &Value| {
+            Some((
+                tp["matchings_index"].as_u64()? as usize,
+                tp["waypoint_index"].as_u64()? as usize,
+            ))
+        };
+        let mut leg_durations = Vec::with_capacity(stops.len() - 1);
+        let mut leg_distances = Vec::with_capacity(stops.len() - 1);
+        let mut leg_confidence = Vec::with_capacity(stops.len() - 1);
+        for pair in tracepoints.windows(2) {
+            match (position(&pair[0]), position(&pair[1])) {
+                (Some((m, w)), Some((m2, w2))) if m == m2 && w2 == w + 1 => {
+                    let leg = &matchings[m]["legs"][w];
+                    leg_durations.push(leg_total(leg, "duration"));
+                    leg_distances.push(leg_total(leg, "distance"));
+                    leg_confidence.push(matchings[m]["confidence"].as_f64());
+                }
+                _ => {
+                    leg_durations.push(None);
+                    leg_distances.push(None);
+                    leg_confidence.push(None);
+                }
+            }
+        }
+
+        Ok(SnappedRoute {
+            coordinates,
+            leg_durations,
+            leg_distances,
+            leg_confidence,
+        })
+    }
+}
+
+impl SnappingBackend for OsrmBackend {
+    fn kind(&self) -> Snapper {
+        Snapper::Osrm
+    }
+
+    fn snap<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        async move {
+            match self.fetch_match(stops).await {
+                Ok(r) => Ok(r),
+                Err(match_err) => {
+                    warn!("OSRM match failed ({:#}), using shortest path", match_err);
+                    self.fetch_route(stops).await
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn corridor<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        self.fetch_route(stops).boxed()
+    }
+}
+
+/// Number of leading `stops` (at least two) whose coordinates and match
+/// radiuses fit in `max_len` bytes of a request URL.
+pub fn stops_fitting_url<'a>(
+    stops: impl IntoIterator<Item = &'a RawStop>,
+    max_len: usize,
+) -> usize {
+    let radius_len = format!("{};", OSRM_MATCH_RADIUS_M).len();
+    let mut len = 0;
+    let mut count = 0;
+    for stop in stops {
+        len += format!("{:.6},{:.6};", stop.gps_long, stop.gps_lat).len() + radius_len;
+        if len > max_len && count >= 2 {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
+
+fn coords_param(stops: &[RawStop]) -> String {
+    stops
+        .iter()
+        .map(|s| format!("{:.6},{:.6}", s.gps_long, s.gps_lat))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Total of an OSRM leg's `duration` or `distance`: the sum of its per-segment
+/// annotations when present, otherwise the leg summary value.
+fn leg_total(leg: &Value, key: &str) -> Option<f64> {
+    match leg["annotation"][key].as_array() {
+        Some(values) => Some(values.iter().filter_map(Value::as_f64).sum()),
+        None => leg[key].as_f64(),
+    }
+}
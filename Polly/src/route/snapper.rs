@@ -0,0 +1,171 @@
+//! Snapping Backends
+//!
+//! Route geometry is produced by a chain of snapping backends (OSRM, Valhalla,
+//! Kakao, straight lines). Each chunk of stops is tried against the backends in
+//! order until one returns a geometry; straight segments between the stops are
+//! the last resort and cannot fail.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use reqwest::RequestBuilder;
+use serde_json::Value;
+use tracing::{Instrument, info_span, warn};
+
+use crate::error::PipelineError;
+use crate::route::model::{RawStop, SnappedRoute, Snapper};
+use crate::utils::cache::ResponseCache;
+use crate::utils::http::HttpClient;
+
+// ============================================================================
+// Backend Trait
+// ============================================================================
+
+/// A road network backend that turns a stop sequence into road geometry.
+pub trait SnappingBackend: Send + Sync {
+    /// Backend identifier recorded in the derived properties
+    fn kind(&self) -> Snapper;
+
+    /// Geometry through the stops, map-matched where the backend supports it.
+    fn snap<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>>;
+
+    /// Shortest-path corridor through the stops, used for drift correction.
+    fn corridor<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>>;
+}
+
+/// HTTP client and response cache shared by the network backends.
+#[derive(Clone)]
+pub struct SnapClient {
+    pub http: HttpClient,
+    /// Cache of successful routing responses (disabled with `--no-osrm-cache`)
+    pub cache: Option<Arc<ResponseCache>>,
+}
+
+impl SnapClient {
+    /// Sends a routing GET request, answering from the response cache when possible.
+    pub async fn cached_get_json(&self, url: &str, is_ok: fn(&Value) -> bool) -> Result<Value> {
+        self.cached_json(url, self.http.get(url), is_ok).await
+    }
+
+    /// Sends a routing request cached under `key`, which must identify the
+    /// request (URL and body). Only responses accepted by `is_ok` are cached.
+    pub async fn cached_json(
+        &self,
+        key: &str,
+        request: RequestBuilder,
+        is_ok: fn(&Value) -> bool,
+    ) -> Result<Value> {
+        if let Some(body) = self.cache.as_ref().and_then(|c| c.get(key))
+            && let Ok(json) = serde_json::from_str::<Value>(&body)
+        {
+            return Ok(json);
+        }
+
+        let body = self.http.send_text(request).await?;
+        let json: Value = serde_json::from_str(&body)
+            .map_err(|e| PipelineError::schema("the routing server", e))?;
+        if is_ok(&json)
+            && let Some(cache) = &self.cache
+        {
+            cache.put(key, &body);
+        }
+
+        Ok(json)
+    }
+}
+
+// ============================================================================
+// Straight Lines
+// ============================================================================
+
+/// Degraded geometry: straight segments between consecutive stops, without
+/// travel times. Never fails, but offers no corridor for drift correction.
+pub struct StraightLine;
+
+impl SnappingBackend for StraightLine {
+    fn kind(&self) -> Snapper {
+        Snapper::Straight
+    }
+
+    fn snap<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        let legs = stops.len().saturating_sub(1);
+        let route = SnappedRoute {
+            coordinates: stops.iter().map(|s| vec![s.gps_long, s.gps_lat]).collect(),
+            leg_durations: vec![None; legs],
+            leg_distances: vec![None; legs],
+            leg_confidence: vec![None; legs],
+        };
+        async move { Ok(route) }.boxed()
+    }
+
+    fn corridor<'a>(&'a self, _stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        async { anyhow::bail!("Straight lines do not follow a road corridor") }.boxed()
+    }
+}
+
+// ============================================================================
+// Fallback Chain
+// ============================================================================
+
+/// Backends tried in order for every chunk of stops.
+pub struct SnapChain {
+    backends: Vec<Box<dyn SnappingBackend>>,
+}
+
+impl SnapChain {
+    /// Builds a chain from `backends`, appending straight lines as the last
+    /// resort if they are not already part of it.
+    pub fn new(mut backends: Vec<Box<dyn SnappingBackend>>) -> Self {
+        if !backends.iter().any(|b| b.kind() == Snapper::Straight) {
+            backends.push(Box::new(StraightLine));
+        }
+        Self { backends }
+    }
+
+    /// Whether a chunk can be snapped to roads at all, i.e. the chain does not
+    /// start with straight lines.
+    pub fn routes_roads(&self) -> bool {
+        self.backends[0].kind() != Snapper::Straight
+    }
+
+    /// Snaps the stops with the first backend that succeeds and reports which one it was.
+    /// Events logged by the backends carry the route ID in their span.
+    pub async fn snap(&self, route_id: &str, stops: &[RawStop]) -> (SnappedRoute, Snapper) {
+        for backend in &self.backends {
+            let span = info_span!("snap", route_id, backend = ?backend.kind());
+            match backend.snap(stops).instrument(span).await {
+                Ok(route) => return (route, backend.kind()),
+                Err(e) => warn!(
+                    route_id,
+                    phase = "snap",
+                    outcome = "fallback",
+                    "{:?} snapping failed for {}: {:#}",
+                    backend.kind(),
+                    route_id,
+                    e
+                ),
+            }
+        }
+        (
+            StraightLine
+                .snap(stops)
+                .await
+                .expect("straight lines never fail"),
+            Snapper::Straight,
+        )
+    }
+
+    /// Corridor through the stops from the first backend that can provide one.
+    pub async fn corridor(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        let mut last_err = anyhow::anyhow!("No snapping backend configured");
+        for backend in &self.backends {
+            match backend.corridor(stops).await {
+                Ok(route) => return Ok(route),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+}
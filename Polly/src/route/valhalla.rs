@@ -0,0 +1,139 @@
+//! Valhalla Snapping Backend
+//!
+//! Snaps stop sequences with Valhalla instead of OSRM. `trace_route` map-matches
+//! the stops with bus costing, which follows one-way restrictions and bus lanes
+//! more closely on some corridors; `route` gives the shortest bus path.
+//! Every stop is sent as a `break` location so the trip has one leg per pair
+//! of consecutive stops, matching the OSRM output.
+
+use anyhow::Result;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::{Value, json};
+use tracing::warn;
+
+use crate::config::OSRM_MATCH_RADIUS_M;
+use crate::error::PipelineError;
+use crate::route::model::{RawStop, SnappedRoute, Snapper};
+use crate::route::snapper::{SnapClient, SnappingBackend};
+use crate::utils::polyline;
+
+pub struct ValhallaBackend {
+    client: SnapClient,
+    url: String,
+}
+
+impl ValhallaBackend {
+    pub fn new(client: SnapClient, url: String) -> Self {
+        Self { client, url }
+    }
+
+    /// Map-matches a stop sequence with Valhalla `trace_route`.
+    async fn fetch_trace(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        let request = json!({
+            "shape": break_locations(stops),
+            "costing": "bus",
+            "shape_match": "map_snap",
+            "trace_options": { "search_radius": OSRM_MATCH_RADIUS_M },
+            "units": "kilometers",
+        });
+        self.call_valhalla("trace_route", &request).await
+    }
+
+    /// Routes a stop sequence with Valhalla `route`.
+    async fn fetch_route(&self, stops: &[RawStop]) -> Result<SnappedRoute> {
+        let request = json!({
+            "locations": break_locations(stops),
+            "costing": "bus",
+            "units": "kilometers",
+        });
+        self.call_valhalla("route", &request).await
+    }
+
+    /// Sends the request as a GET (`?json=`), so responses can be cached by URL.
+    async fn call_valhalla(&self, action: &str, request: &Value) -> Result<SnappedRoute> {
+        let url = format!(
+            "{}/{}?json={}",
+            self.url.trim_end_matches('/'),
+            action,
+            utf8_percent_encode(&request.to_string(), NON_ALPHANUMERIC)
+        );
+
+        let json = self
+            .client
+            .cached_get_json(&url, |j| j["trip"]["status"] == 0)
+            .await?;
+        if json["trip"]["status"] != 0 {
+            return Err(PipelineError::snap(
+                "Valhalla",
+                format!("{} returned {}", action, json["trip"]["status_message"]),
+            )
+            .into());
+        }
+
+        let legs = json["trip"]["legs"].as_array().cloned().unwrap_or_default();
+
+        // Consecutive legs share their boundary point; keep it once.
+        let mut coordinates: Vec<Vec<f64>> = Vec::new();
+        for leg in &legs {
+            let shape = polyline::decode(leg["shape"].as_str().unwrap_or_default(), 6);
+            let skip = usize::from(coordinates.last().is_some_and(|l| shape.first() == Some(l)));
+            coordinates.extend(shape.into_iter().skip(skip));
+        }
+        if coordinates.is_empty() {
+            return Err(PipelineError::snap(
+                "Valhalla",
+                format!("{} returned an empty geometry", action),
+            )
+            .into());
+        }
+
+        let leg_durations: Vec<Option<f64>> =
+            legs.iter().map(|l| l["summary"]["time"].as_f64()).collect();
+        let leg_distances: Vec<Option<f64>> = legs
+            .iter()
+            .map(|l| l["summary"]["length"].as_f64().map(|km| km * 1000.0))
+            .collect();
+
+        Ok(SnappedRoute {
+            leg_confidence: vec![None; leg_durations.len()],
+            coordinates,
+            leg_durations,
+            leg_distances,
+        })
+    }
+}
+
+impl SnappingBackend for ValhallaBackend {
+    fn kind(&self) -> Snapper {
+        Snapper::Valhalla
+    }
+
+    fn snap<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        async move {
+            match self.fetch_trace(stops).await {
+                Ok(r) => Ok(r),
+                Err(trace_err) => {
+                    warn!(
+                        "Valhalla trace failed ({:#}), using shortest path",
+                        trace_err
+                    );
+                    self.fetch_route(stops).await
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn corridor<'a>(&'a self, stops: &'a [RawStop]) -> BoxFuture<'a, Result<SnappedRoute>> {
+        self.fetch_route(stops).boxed()
+    }
+}
+
+fn break_locations(stops: &[RawStop]) -> Vec<Value> {
+    stops
+        .iter()
+        .map(|s| json!({ "lat": s.gps_lat, "lon": s.gps_long, "type": "break" }))
+        .collect()
+}
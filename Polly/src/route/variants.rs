@@ -0,0 +1,201 @@
+//! Route Variant Analysis
+//!
+//! A route number can map to several TAGO route IDs (branches, extensions,
+//! short turns). Their stop sequences are compared to pick the trunk route
+//! and name the others, e.g. "34 본선" and "34 지선 A".
+
+use std::collections::{BTreeMap, HashSet};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use ts_rs::TS;
+
+/// How a variant relates to the trunk route of its number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, TS, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VariantKind {
+    Trunk,
+    /// Serves every trunk stop plus others
+    Extension,
+    /// Serves only trunk stops, but not all of them
+    Short,
+    /// Leaves the trunk somewhere along the way
+    Branch,
+}
+
+#[derive(Debug, Serialize, TS, JsonSchema)]
+pub struct RouteVariant {
+    #[serde(rename = "routeid")]
+    pub route_id: String,
+    pub name: String,
+    pub kind: VariantKind,
+    pub stop_count: usize,
+    /// Stops also served by the trunk
+    pub shared_stops: usize,
+}
+
+/// Analyzes every route number with more than one route ID.
+/// `details` holds the routeMap.json `route_details` entries (with `sequence`).
+pub fn analyze_variants(
+    route_numbers: &BTreeMap<String, Vec<String>>,
+    details: &BTreeMap<String, Value>,
+) -> BTreeMap<String, Vec<RouteVariant>> {
+    route_numbers
+        .iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|(route_no, ids)| (route_no.clone(), name_variants(route_no, ids, details)))
+        .collect()
+}
+
+fn name_variants(
+    route_no: &str,
+    ids: &[String],
+    details: &BTreeMap<String, Value>,
+) -> Vec<RouteVariant> {
+    let mut ids = ids.to_vec();
+    ids.sort();
+
+    let stop_sets: Vec<HashSet<&str>> = ids
+        .iter()
+        .map(|id| {
+            details
+                .get(id)
+                .and_then(|d| d["sequence"].as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|s| s["nodeid"].as_str())
+                .collect()
+        })
+        .collect();
+
+    // The trunk shares the most stops with the other variants; ties go to the
+    // longer route, then the lower route ID.
+    let trunk = (0..ids.len())
+        .max_by_key(|&i| {
+            let shared: usize = (0..ids.len())
+                .filter(|&j| j != i)
+                .map(|j| stop_sets[i].intersection(&stop_sets[j]).count())
+                .sum();
+            (shared, stop_sets[i].len(), std::cmp::Reverse(i))
+        })
+        .unwrap_or(0);
+
+    let mut branch_letter = b'A';
+    ids.iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let shared = stop_sets[i].intersection(&stop_sets[trunk]).count();
+            let kind = if i == trunk {
+                VariantKind::Trunk
+            } else if shared == stop_sets[trunk].len() {
+                VariantKind::Extension
+            } else if shared == stop_sets[i].len() {
+                VariantKind::Short
+            } else {
+                VariantKind::Branch
+            };
+
+            let name = if kind == VariantKind::Trunk {
+                format!("{} 본선", route_no)
+            } else {
+                let letter = branch_letter as char;
+                branch_letter = branch_letter.saturating_add(1);
+                format!("{} 지선 {}", route_no, letter)
+            };
+
+            RouteVariant {
+                route_id: id.clone(),
+                name,
+                kind,
+                stop_count: stop_sets[i].len(),
+                shared_stops: shared,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn details(routes: &[(&str, &[&str])]) -> BTreeMap<String, Value> {
+        routes
+            .iter()
+            .map(|(id, stops)| {
+                let sequence: Vec<Value> = stops.iter().map(|s| json!({ "nodeid": s })).collect();
+                (id.to_string(), json!({ "sequence": sequence }))
+            })
+            .collect()
+    }
+
+    fn numbers(routes: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        routes
+            .iter()
+            .map(|(no, ids)| (no.to_string(), ids.iter().map(|s| s.to_string()).collect()))
+            .collect()
+    }
+
+    fn summary(variants: &[RouteVariant]) -> Vec<(&str, &str, VariantKind, usize, usize)> {
+        variants
+            .iter()
+            .map(|v| {
+                (
+                    v.route_id.as_str(),
+                    v.name.as_str(),
+                    v.kind,
+                    v.stop_count,
+                    v.shared_stops,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_the_trunk_and_lettered_branches() {
+        let details = details(&[
+            ("WJB34A", &["a", "b", "c", "d", "e", "f"]),
+            ("WJB34B", &["b", "c", "d"]),
+            ("WJB34C", &["a", "b", "c", "x", "y"]),
+            ("WJB41", &["a", "z"]),
+        ]);
+        let numbers = numbers(&[("34", &["WJB34C", "WJB34A", "WJB34B"]), ("41", &["WJB41"])]);
+
+        let variants = analyze_variants(&numbers, &details);
+        // Numbers with a single route have no variants.
+        assert_eq!(variants.keys().collect::<Vec<_>>(), ["34"]);
+        assert_eq!(
+            summary(&variants["34"]),
+            [
+                ("WJB34A", "34 본선", VariantKind::Trunk, 6, 6),
+                ("WJB34B", "34 지선 A", VariantKind::Short, 3, 3),
+                ("WJB34C", "34 지선 B", VariantKind::Branch, 5, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_longer_route_then_the_lower_id() {
+        let details = details(&[
+            ("WJB2A", &["a", "b", "c"]),
+            ("WJB2B", &["a", "b", "c", "d"]),
+            ("WJB3A", &["a", "b", "c"]),
+            ("WJB3B", &["a", "b", "c"]),
+        ]);
+        let numbers = numbers(&[("2", &["WJB2A", "WJB2B"]), ("3", &["WJB3B", "WJB3A"])]);
+
+        let variants = analyze_variants(&numbers, &details);
+        assert_eq!(variants["2"][1].route_id, "WJB2B");
+        assert_eq!(variants["2"][1].kind, VariantKind::Trunk);
+        assert_eq!(variants["2"][0].kind, VariantKind::Short);
+        // Identical stops: the lower ID is the trunk, the other serves all of it.
+        assert_eq!(
+            summary(&variants["3"]),
+            [
+                ("WJB3A", "3 본선", VariantKind::Trunk, 3, 3),
+                ("WJB3B", "3 지선 A", VariantKind::Extension, 3, 3),
+            ]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Headless Browser Fallback
+//!
+//! When the ITS site serves schedule pages that are rendered on the client,
+//! the static HTML fetched with reqwest contains no schedule table. This module
+//! drives a headless Chromium instance to render such pages and returns the
+//! resulting DOM as HTML, so it can be fed into the existing parser unchanged.
+//!
+//! Only compiled with the `browser` feature.
+
+use anyhow::{Context, Result};
+use chromiumoxide::{Browser, BrowserConfig};
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+
+use crate::config::BASE_URL;
+
+/// A lazily launched headless browser used to render JS-driven pages.
+pub struct BrowserRenderer {
+    browser: Browser,
+    handler: JoinHandle<()>,
+}
+
+impl BrowserRenderer {
+    /// Launches a headless Chromium instance, sending its traffic through
+    /// `proxy` if given. The executable can be overridden with the `CHROME` environment variable.
+    pub async fn launch(proxy: Option<&str>) -> Result<Self> {
+        let mut config = BrowserConfig::builder().no_sandbox();
+        if let Some(proxy) = proxy {
+            config = config.arg(format!("--proxy-server={}", proxy));
+        }
+        let config = config.build().map_err(anyhow::Error::msg)?;
+
+        let (browser, mut events) = Browser::launch(config)
+            .await
+            .context("Failed to launch headless browser")?;
+
+        // The CDP event handler must be polled for the browser to make progress.
+        let handler = tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { browser, handler })
+    }
+
+    /// Renders the main schedule page and returns its HTML.
+    pub async fn render_main(&self) -> Result<String> {
+        let page = self.browser.new_page(BASE_URL).await?;
+        page.wait_for_navigation().await?;
+
+        let html = page.content().await?;
+        page.close().await.ok();
+
+        Ok(html)
+    }
+
+    /// Renders the detail page for `route_id` by invoking the site's own
+    /// `goDetail(...)` handler from the main page, just like a user click.
+    pub async fn render_detail(&self, route_id: &str) -> Result<String> {
+        let page = self.browser.new_page(BASE_URL).await?;
+        page.wait_for_navigation().await?;
+
+        let escaped = route_id.replace('\\', "\\\\").replace('\'', "\\'");
+        page.evaluate(format!("goDetail('{}')", escaped))
+            .await
+            .context("goDetail() is not available on the rendered page")?;
+        page.wait_for_navigation().await?;
+
+        let html = page.content().await?;
+        page.close().await.ok();
+
+        Ok(html)
+    }
+
+    /// Closes the browser and stops the event handler.
+    pub async fn close(mut self) {
+        self.browser.close().await.ok();
+        self.handler.abort();
+    }
+}
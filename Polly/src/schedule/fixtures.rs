@@ -0,0 +1,233 @@
+//! Schedule Parser Fixtures
+//!
+//! Golden tests for the schedule parser. `fixtures add <route>` saves the
+//! detail pages of a route next to what the parser makes of them, and
+//! `fixtures check` re-parses every saved page and reports where the output
+//! no longer matches, so a parser or provider change can be checked against
+//! real upstream HTML without a crawl.
+//!
+//! Each route variant is stored as `{variant}.html` (the page as served) and
+//! `{variant}.json` (the route ID, the main-page metadata the parser was
+//! given, and its output).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::config::BASE_URL;
+use crate::schedule::model::RouteMeta;
+use crate::schedule::provider::Provider;
+use crate::schedule::{
+    build_client, detail_request, extract_route_info, http_error, parse_detail_schedule,
+    schedule_file_name,
+};
+use crate::settings::Settings;
+use crate::utils::{cassette, ensure_dir, write_atomic};
+
+#[derive(clap::Args)]
+pub struct FixturesArgs {
+    #[command(subcommand)]
+    action: FixturesAction,
+
+    /// Fixture directory
+    #[arg(long, global = true, default_value = "tests/fixtures/schedule")]
+    dir: PathBuf,
+
+    /// Provider config (TOML) to parse the pages with (default: built-in)
+    #[arg(long, global = true)]
+    provider: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand)]
+enum FixturesAction {
+    /// Fetch the detail pages of a route and store them with the parser's output
+    Add {
+        /// Route number (e.g., "34-1"); every day-type variant is added
+        route: String,
+    },
+    /// Re-parse all fixtures and compare with the stored output
+    Check {
+        /// Overwrite the stored output of mismatching fixtures instead of failing
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+/// Stored expectation of a fixture (`{variant}.json`)
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Golden {
+    route_id: String,
+    /// Route metadata from the main page, which the parser falls back on
+    meta: Option<RouteMeta>,
+    /// Parser output
+    parsed: Value,
+}
+
+pub async fn run(args: FixturesArgs, settings: &Settings) -> Result<()> {
+    let provider = Provider::load(args.provider.as_deref())?;
+    match args.action {
+        FixturesAction::Add { route } => add(&args.dir, &route, &provider, settings).await,
+        FixturesAction::Check { update } => check(&args.dir, update, &provider),
+    }
+}
+
+/// Fetches every variant of `route` and saves its page and parser output.
+async fn add(dir: &Path, route: &str, provider: &Provider, settings: &Settings) -> Result<()> {
+    let client = build_client(settings)?;
+    let main_html = cassette::send(client.get(BASE_URL)).await?.body;
+    let (route_meta_map, targets) =
+        extract_route_info(&Html::parse_document(&main_html), Some(route), provider)?;
+    if targets.is_empty() {
+        anyhow::bail!("No route matching {:?} on {}", route, BASE_URL);
+    }
+
+    ensure_dir(dir)?;
+    for route_id in &targets {
+        let resp = cassette::send(detail_request(&client, route_id)).await?;
+        if !resp.status.is_success() {
+            return Err(http_error(resp.status)).with_context(|| format!("Fetching {}", route_id));
+        }
+
+        let route_number = route_id.split('(').next().unwrap_or(route_id);
+        let meta = route_meta_map.get(route_number).cloned();
+        let parsed = parse_detail_schedule(&resp.body, route_id, meta.as_ref(), provider)
+            .with_context(|| format!("Parsing {}", route_id))?;
+        let golden = Golden {
+            route_id: route_id.clone(),
+            meta,
+            parsed: serde_json::to_value(&parsed)?,
+        };
+
+        let (html_path, golden_path) = fixture_paths(dir, route_id);
+        write_atomic(&html_path, &resp.body)?;
+        write_golden(&golden_path, &golden)?;
+        info!(route_id, "✓ Added fixture {:?}", golden_path);
+    }
+    Ok(())
+}
+
+/// Re-parses every fixture in `dir`, failing on (or, with `update`, rewriting)
+/// those whose output changed.
+fn check(dir: &Path, update: bool, provider: &Provider) -> Result<()> {
+    let mut golden_paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Reading fixture directory {:?}", dir))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    golden_paths.sort();
+
+    let mut mismatched = 0;
+    for golden_path in &golden_paths {
+        let mut golden: Golden = serde_json::from_str(&fs::read_to_string(golden_path)?)
+            .with_context(|| format!("Invalid fixture {:?}", golden_path))?;
+        let html_path = golden_path.with_extension("html");
+        let html = fs::read_to_string(&html_path)
+            .with_context(|| format!("Reading fixture page {:?}", html_path))?;
+
+        let parsed = parse_detail_schedule(&html, &golden.route_id, golden.meta.as_ref(), provider)
+            .map_or_else(
+                |e| Value::String(format!("error: {:#}", e)),
+                |p| serde_json::to_value(&p).unwrap_or_default(),
+            );
+
+        let mut differences = Vec::new();
+        diff_values("parsed", &golden.parsed, &parsed, &mut differences);
+        if differences.is_empty() {
+            info!(
+                route_id = golden.route_id,
+                outcome = "ok",
+                "✓ {}",
+                golden.route_id
+            );
+            continue;
+        }
+
+        mismatched += 1;
+        warn!(
+            route_id = golden.route_id,
+            outcome = "failed",
+            count = differences.len(),
+            "{}: output differs from {:?}\n  {}",
+            golden.route_id,
+            golden_path,
+            differences.join("\n  ")
+        );
+        if update {
+            golden.parsed = parsed;
+            write_golden(golden_path, &golden)?;
+            info!(route_id = golden.route_id, "Updated {:?}", golden_path);
+        }
+    }
+
+    info!(
+        count = golden_paths.len(),
+        "Checked {} fixture(s), {} mismatched",
+        golden_paths.len(),
+        mismatched
+    );
+    if mismatched > 0 && !update {
+        anyhow::bail!(
+            "{} fixture(s) no longer match the parser output (re-run with --update to accept)",
+            mismatched
+        );
+    }
+    Ok(())
+}
+
+/// Paths of the page and the expectation of a route variant, named like its
+/// schedule file (e.g., `34-1_평일_.html`, `34-1_평일_.json`).
+fn fixture_paths(dir: &Path, route_id: &str) -> (PathBuf, PathBuf) {
+    let golden_path = dir.join(schedule_file_name(route_id));
+    (golden_path.with_extension("html"), golden_path)
+}
+
+fn write_golden(path: &Path, golden: &Golden) -> Result<()> {
+    let mut json = serde_json::to_string_pretty(golden)?;
+    json.push('\n');
+    write_atomic(path, json)
+}
+
+/// Lists the paths (e.g., `parsed.timesByDirection.연세대[3].time`) where
+/// `actual` differs from `expected`.
+fn diff_values(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            let mut keys: Vec<&String> = e.keys().chain(a.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = format!("{}.{}", path, key);
+                match (e.get(key), a.get(key)) {
+                    (Some(ev), Some(av)) => diff_values(&child, ev, av, out),
+                    (Some(ev), None) => out.push(format!("{}: missing (expected {})", child, ev)),
+                    (None, Some(av)) => out.push(format!("{}: unexpected {}", child, av)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(e), Value::Array(a)) => {
+            for (i, (ev, av)) in e.iter().zip(a).enumerate() {
+                diff_values(&format!("{}[{}]", path, i), ev, av, out);
+            }
+            if e.len() != a.len() {
+                out.push(format!(
+                    "{}: {} item(s), expected {}",
+                    path,
+                    a.len(),
+                    e.len()
+                ));
+            }
+        }
+        _ if expected != actual => {
+            out.push(format!("{}: {} (expected {})", path, actual, expected))
+        }
+        _ => {}
+    }
+}
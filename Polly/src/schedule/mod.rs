@@ -5,24 +5,49 @@
 //! handle session cookies and parse HTML responses to extract schedule
 //! information. The extracted data is then organized and saved as JSON files.
 
-mod model;
-
-use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "browser")]
+mod browser;
+pub mod fixtures;
+pub mod model;
+mod provider;
+mod robots;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
-use regex::Regex;
-use reqwest::{Client, header};
-use scraper::{Html, Selector};
+use reqwest::{Client, RequestBuilder, StatusCode, header};
+use scraper::{ElementRef, Html};
 use serde_json::json;
 use tokio::time::sleep;
-
-use crate::config::{BASE_URL, DETAIL_URL};
-use crate::schedule::model::{ParsedSchedule, RouteMeta, TimeEntry};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{BASE_URL, DETAIL_URL, ITS_HOST, SCHEDULE_SCHEMA_VERSION};
+use crate::error::{ExitError, ExitStatus, PipelineError, config_error};
+use crate::schedule::model::{
+    CoverageGap, Departure, FeaturedStops, ParsedSchedule, RouteMeta, ScheduleFile, ScheduleReport,
+    TimeEntry,
+};
+use crate::schedule::provider::Provider;
+use crate::schema::{OutputFormat, validate_files, validate_values};
+use crate::settings::{Provenance, Settings};
 use crate::utils;
+use crate::utils::alert::Webhook;
+use crate::utils::cassette;
+use crate::utils::compress::{Compression, write_compressed};
+use crate::utils::events;
+use crate::utils::failures::FailureManifest;
+use crate::utils::http::Validators;
+use crate::utils::i18n::Msg;
+use crate::utils::lock;
+use crate::utils::metrics;
+use crate::utils::routes_file::RoutesFile;
+use crate::utils::state;
+use crate::utils::status::{self, STATUS_FILE};
+use crate::watch::Fingerprint;
 
 // ============================================================================
 // Schedule Arguments
@@ -31,10 +56,99 @@ use crate::utils;
 #[derive(clap::Args)]
 pub struct ScheduleArgs {
     /// Specific route number to crawl (e.g., "34-1"). If omitted, all routes are crawled.
+    #[arg(short, long)]
     pub route: Option<String>,
 
     /// Output directory for saving the schedule JSON files.
+    #[arg(short, long, default_value = "./storage")]
     pub output_dir: PathBuf,
+
+    /// Treat parse warnings (empty schedules, missing headers, fallbacks) as errors.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Also write a single `schedules.json` containing all routes and an index.
+    #[arg(long)]
+    pub combine: bool,
+
+    /// Write only the combined `schedules.json`, skipping per-route files.
+    #[arg(long, requires = "combine")]
+    pub combine_only: bool,
+
+    /// Also write pre-compressed copies of the combined output (e.g., `gz,br`)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub compress: Vec<Compression>,
+
+    /// Add romanized direction names (`directionsEn`) to each schedule
+    #[arg(long)]
+    pub romanize: bool,
+
+    /// Only crawl route variants of this day type; other day types already
+    /// saved for a route are kept
+    #[arg(long, value_enum)]
+    pub day_type: Option<DayTypeFilter>,
+
+    /// Provider config (TOML) overriding the selectors, regexes, and header
+    /// keywords used to parse the schedule pages
+    #[arg(long)]
+    pub provider: Option<PathBuf>,
+
+    /// Webhook URL to alert when the selector preflight check fails
+    /// (defaults to the `ALERT_WEBHOOK_URL` environment variable)
+    #[arg(long)]
+    pub alert_webhook: Option<String>,
+
+    /// Only re-crawl the route variants recorded in the last run's `failures.json`
+    #[arg(long, conflicts_with = "route")]
+    pub retry_failed: bool,
+
+    /// Only crawl the route numbers or variants (e.g., "34-1(평일)") listed in
+    /// this file, one per line (`-` reads them from stdin)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["route", "retry_failed"])]
+    pub routes_file: Option<PathBuf>,
+
+    /// Crawl and parse as usual, but only report the files that would be written
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print the schedule of the `--route` to stdout instead of writing any file
+    #[arg(long, requires = "route", conflicts_with = "combine")]
+    pub stdout: bool,
+
+    /// Wait for another run writing to the output directory to finish,
+    /// instead of failing
+    #[arg(long)]
+    pub wait_lock: bool,
+
+    /// Fail (exit code 8) if more than this percentage of the route variants failed,
+    /// even though the others were written
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub fail_on_partial: Option<u8>,
+
+    /// Crawl even if the site's robots.txt disallows it, and ignore its
+    /// `Crawl-delay`
+    #[arg(long)]
+    pub ignore_robots: bool,
+}
+
+/// Day-type variants that can be selected for a targeted crawl.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum DayTypeFilter {
+    Weekday,
+    Weekend,
+    Holiday,
+}
+
+impl DayTypeFilter {
+    /// Checks whether a route variant (e.g., "34-1(평일)") is of this day type.
+    fn matches(self, route_id: &str) -> bool {
+        let (_, raw_day_type) = split_route_id(route_id);
+        match self {
+            Self::Weekday => normalize_day_type(&raw_day_type) == "weekday",
+            Self::Weekend => normalize_day_type(&raw_day_type) == "weekend",
+            Self::Holiday => raw_day_type.contains("휴일") || raw_day_type.contains("공휴"),
+        }
+    }
 }
 
 /// Main entry point for the schedule crawler.
@@ -47,164 +161,604 @@ pub struct ScheduleArgs {
 /// 5. Merges the various schedules (e.g., weekday, weekend) for each route.
 /// 6. Saves the final, structured data as JSON files.
 ///
-pub async fn run(args: ScheduleArgs) -> Result<()> {
+pub async fn run(args: ScheduleArgs, settings: &Settings) -> Result<()> {
+    if args.stdout {
+        utils::dry_run::enable_quiet();
+    } else if args.dry_run {
+        utils::dry_run::enable();
+    }
+
     let schedule_dir = args.output_dir.join("schedules");
 
     utils::ensure_dir(&schedule_dir)?;
+    let _lock = lock::acquire(&args.output_dir, "schedule", args.wait_lock).await?;
+    status::start("schedule", args.output_dir.join(STATUS_FILE));
+    state::open(&args.output_dir, "schedule")?;
+
+    let provider =
+        Provider::load(args.provider.as_deref()).map_err(|e| config_error(format!("{:#}", e)))?;
+    let listed = args
+        .routes_file
+        .as_deref()
+        .map(RoutesFile::read)
+        .transpose()?;
+    let alert_webhook = args
+        .alert_webhook
+        .clone()
+        .or_else(|| settings.alert_webhook_url.clone());
+    let alert_webhooks = utils::alert::alert_webhooks(alert_webhook.as_deref(), &settings.notify);
+
+    info!("{}", Msg::ScheduleStart);
+
+    let client = build_client(settings)?;
+    let delay = politeness(&client, settings, args.ignore_robots).await?;
 
-    println!("\n============================================================");
-    println!("Starting Bus Schedule Crawler (Browser Mimic Mode)");
-    println!("============================================================\n");
+    // Fetch the main schedule page to acquire session cookies and the list of all routes.
+    info!("{}", Msg::FetchingMainPage);
 
-    // Initialize an HTTP client that mimics a web browser.
-    // Cookie store is enabled to automatically handle session cookies (JSESSIONID),
-    // which is crucial for making subsequent requests to the detail page.
-    let client = Client::builder()
-        .cookie_store(true)
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .timeout(Duration::from_secs(30))
-        .build()?;
+    let main_html = cassette::send(client.get(BASE_URL)).await?.body;
 
-    // Fetch the main schedule page to acquire session cookies and the list of all routes.
-    println!("Fetching main page (Initializing Session)...");
+    // The headless browser is only launched once a page turns out to need it.
+    #[cfg(feature = "browser")]
+    let mut renderer: Option<browser::BrowserRenderer> = None;
+
+    // If the static main page has no route table, it is likely rendered client-side.
+    #[cfg(feature = "browser")]
+    let main_html = if has_schedule_table(&main_html, &provider) {
+        main_html
+    } else {
+        info!("Main page has no table, rendering with headless browser...");
+        let r = renderer
+            .insert(browser::BrowserRenderer::launch(settings.http.proxy.as_deref()).await?);
+        r.render_main().await?
+    };
 
-    let resp = client.get(BASE_URL).send().await?.text().await?;
-    let document = Html::parse_document(&resp);
+    let document = Html::parse_document(&main_html);
+
+    // Preflight: if the route list selectors match nothing, the site has most
+    // likely changed, and crawling on would only produce empty output.
+    if count_route_rows(&document, &provider) == 0 {
+        return Err(selector_check_failed(
+            &client,
+            &alert_webhooks,
+            format!(
+                "no route rows on {} match the route selector/regex; the page layout may have changed",
+                BASE_URL
+            ),
+        )
+        .await);
+    }
 
     // Extract basic route information and the target route IDs to crawl.
-    let (route_meta_map, targets) = extract_route_info(&document, args.route.as_deref())?;
+    let (route_meta_map, targets) =
+        extract_route_info(&document, args.route.as_deref(), &provider)?;
+
+    // Restrict to the requested day-type variants.
+    let targets: Vec<String> = match args.day_type {
+        Some(filter) => targets
+            .into_iter()
+            .filter(|id| filter.matches(id))
+            .collect(),
+        None => targets,
+    };
 
-    println!("✓ Found info for {} routes", route_meta_map.len());
-    println!("✓ Found {} route schedules to process", targets.len());
+    // Restrict to the variants that failed last time.
+    let targets: Vec<String> = if args.retry_failed {
+        let failed = FailureManifest::previous(&args.output_dir)?.ids();
+        info!(
+            count = failed.len(),
+            "{}",
+            Msg::RetryingSchedules(failed.len())
+        );
+        targets
+            .into_iter()
+            .filter(|id| failed.contains(id))
+            .collect()
+    } else {
+        targets
+    };
+
+    // Restrict to the routes of the route list.
+    let targets: Vec<String> = match &listed {
+        Some(listed) => {
+            info!(count = listed.len(), "{}", Msg::ListedRoutes(listed.len()));
+            targets
+                .into_iter()
+                .filter(|id| listed.contains(&split_route_id(id).0, id))
+                .collect()
+        }
+        None => targets,
+    };
+
+    // Only one route goes to stdout, so "34" must not also match "34-1".
+    let targets: Vec<String> = match args.route.as_deref() {
+        Some(route) if args.stdout => targets
+            .into_iter()
+            .filter(|id| split_route_id(id).0 == route)
+            .collect(),
+        _ => targets,
+    };
+
+    info!(
+        count = route_meta_map.len(),
+        "{}",
+        Msg::FoundRouteInfo(route_meta_map.len())
+    );
+    info!(
+        count = targets.len(),
+        "{}",
+        Msg::FoundSchedules(targets.len())
+    );
 
     let mut collected_schedules: Vec<ParsedSchedule> = Vec::new();
+    let mut report = ScheduleReport::default();
+    let mut failures = FailureManifest::default();
+    let mut sample_checked = false;
+    // Route numbers with a variant the site answered 304 Not Modified for
+    let mut unchanged_routes: BTreeSet<String> = BTreeSet::new();
+    // Validators of the parsed pages, stored once the schedules are saved
+    let mut parsed_validators: Vec<(String, Option<Validators>)> = Vec::new();
 
     // Iterate through each target route and fetch its detailed schedule.
     for (i, route_id) in targets.iter().enumerate() {
-        print!(
-            "\r   [/{}/{}] Fetching {}... ",
-            i + 1,
-            targets.len(),
+        status::progress("schedule", i, targets.len());
+        let progress = format!("[{}/{}]", i + 1, targets.len());
+        debug!(
+            route_id,
+            phase = "fetch",
+            "{} Fetching {}...",
+            progress,
             route_id
         );
-        sleep(Duration::from_millis(300)).await; // Politeness delay.
-
-        // The website expects the route ID in the POST body to be percent-encoded UTF-8.
-        let encoded_val = percent_encode(route_id.as_bytes(), NON_ALPHANUMERIC).to_string();
-        let body_str = format!("no={}", encoded_val);
-
-        // Send a POST request to get the detailed schedule for the specific route_id.
-        // It's crucial to set the correct headers (Referer, Origin, Content-Type)
-        // to simulate a legitimate request originating from the website.
-        let detail_resp = match client
-            .post(DETAIL_URL)
-            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
-            .header(header::REFERER, BASE_URL)
-            .header(header::ORIGIN, "http://its.wonju.go.kr")
-            .body(body_str)
-            .send()
-            .await
+        sleep(delay).await; // Politeness delay.
+        let started = Instant::now();
+
+        // The route number is the part of the route_id before any parentheses.
+        let route_number = route_id.split('(').next().unwrap_or(route_id).to_string();
+
+        // With the route's schedule saved, the site may answer that the page
+        // is unchanged (304 Not Modified).
+        let validators_key = format!("detail:{}", route_id);
+        let mut request = detail_request(&client, route_id);
+        if !args.combine_only
+            && schedule_dir
+                .join(schedule_file_name(&route_number))
+                .exists()
+            && let Some(validators) = state::validators(&validators_key)
         {
+            request = validators.apply(request);
+        }
+
+        let detail_resp = match cassette::send(request).await {
             Ok(r) => r,
             Err(_) => {
-                println!("✗ Failed (Network)");
+                warn!(
+                    route_id,
+                    phase = "fetch",
+                    outcome = "failed",
+                    "{} {}: network error",
+                    progress,
+                    route_id
+                );
+                report.warnings.push(format!("{}: network error", route_id));
+                failures.record(route_id, "fetch", "network error");
                 continue;
             }
         };
 
-        if !detail_resp.status().is_success() {
-            println!("✗ Failed (Status: {})", detail_resp.status());
+        if detail_resp.is_not_modified() {
+            metrics::route_processed("schedule", "unchanged");
+            events::route_fetched(route_id, "unchanged", started.elapsed().as_millis() as u64);
+            info!(
+                route_id,
+                phase = "fetch",
+                outcome = "unchanged",
+                "{}",
+                Msg::ScheduleUnchanged {
+                    progress: &progress,
+                    route_id
+                }
+            );
+            report.variants_unchanged += 1;
+            unchanged_routes.insert(route_number);
             continue;
         }
 
-        let detail_html = detail_resp.text().await?;
+        if !detail_resp.status.is_success() {
+            let err = http_error(detail_resp.status);
+            warn!(
+                route_id,
+                phase = "fetch",
+                outcome = "failed",
+                status = detail_resp.status.as_u16(),
+                "{} {}: {}",
+                progress,
+                route_id,
+                err
+            );
+            report.warnings.push(format!("{}: {}", route_id, err));
+            failures.record_error(route_id, "fetch", &err.into());
+            continue;
+        }
+
+        let validators = Validators::of(&detail_resp);
+        let detail_html = detail_resp.body;
+
+        // A detail page without any table is likely rendered client-side,
+        // so render it through the headless browser instead.
+        #[cfg(feature = "browser")]
+        let detail_html = if has_schedule_table(&detail_html, &provider) {
+            detail_html
+        } else {
+            debug!(
+                route_id,
+                phase = "fetch",
+                "Rendering {} with headless browser",
+                route_id
+            );
+            if renderer.is_none() {
+                renderer =
+                    Some(browser::BrowserRenderer::launch(settings.http.proxy.as_deref()).await?);
+            }
+            match renderer.as_ref().unwrap().render_detail(route_id).await {
+                Ok(html) => html,
+                Err(e) => {
+                    warn!(
+                        route_id,
+                        phase = "fetch",
+                        "Browser rendering failed for {}: {}",
+                        route_id,
+                        e
+                    );
+                    detail_html
+                }
+            }
+        };
+
+        // Preflight (continued): the first detail page must still contain a
+        // departure table, otherwise every route would parse to nothing.
+        if !sample_checked {
+            sample_checked = true;
+            if find_departure_table(&Html::parse_document(&detail_html), &provider).is_none() {
+                return Err(selector_check_failed(
+                    &client,
+                    &alert_webhooks,
+                    format!(
+                        "sample detail page for {} has no table with '{}' headers; the page layout may have changed",
+                        route_id, provider.departure_marker
+                    ),
+                )
+                .await);
+            }
+        }
 
-        // The route number is the part of the route_id before any parentheses.
-        let route_number = route_id.split('(').next().unwrap_or(route_id).to_string();
         let meta = route_meta_map.get(&route_number);
 
         // Parse the returned HTML to extract the schedule.
-        match parse_detail_schedule(&detail_html, route_id, meta) {
-            Ok(parsed) => {
+        match parse_detail_schedule(&detail_html, route_id, meta, &provider) {
+            Ok(mut parsed) => {
+                for w in parsed.warnings.drain(..) {
+                    warn!(route_id, phase = "parse", "{}: {}", route_id, w);
+                    report.warnings.push(format!("{}: {}", route_id, w));
+                }
+
                 let count: usize = parsed.times_by_direction.values().map(|v| v.len()).sum();
                 if count > 0 {
-                    println!("✓ ({} times)", count);
+                    metrics::route_processed("schedule", "ok");
+                    metrics::observe_route("schedule", started.elapsed());
+                    events::route_fetched(route_id, "ok", started.elapsed().as_millis() as u64);
+                    state::record_fetch(route_id, &parsed.content_hash());
+                    parsed_validators.push((validators_key, validators));
+                    info!(
+                        route_id,
+                        phase = "parse",
+                        outcome = "ok",
+                        times = count,
+                        "{}",
+                        Msg::ScheduleParsed {
+                            progress: &progress,
+                            route_id,
+                            times: count
+                        }
+                    );
                     collected_schedules.push(parsed);
                 } else {
                     // If parsing yields no times, save the HTML for debugging.
-                    println!("Warning: 0 times. (HTML Check Saved)");
-                    fs::write(format!("debug_empty_{}.html", i), &detail_html).ok();
+                    warn!(
+                        route_id,
+                        phase = "parse",
+                        outcome = "failed",
+                        "{}",
+                        Msg::ScheduleEmpty {
+                            progress: &progress,
+                            route_id
+                        }
+                    );
+                    if !utils::dry_run::is_enabled() {
+                        fs::write(format!("debug_empty_{}.html", i), &detail_html).ok();
+                    }
+                    let err = PipelineError::ParseEmpty { what: "times" };
+                    report.warnings.push(format!("{}: {}", route_id, err));
+                    failures.record_error(route_id, "parse", &err.into());
                 }
             }
             Err(e) => {
-                println!("✗ Error: {}", e);
+                warn!(
+                    route_id,
+                    phase = "parse",
+                    outcome = "failed",
+                    "{} {}: {}",
+                    progress,
+                    route_id,
+                    e
+                );
+                report.warnings.push(format!("{}: {}", route_id, e));
+                failures.record_error(route_id, "parse", &e);
             }
         }
     }
 
+    status::progress("schedule", targets.len(), targets.len());
+
+    #[cfg(feature = "browser")]
+    if let Some(r) = renderer {
+        r.close().await;
+    }
+
     // Merge the collected schedules and save them to JSON files.
-    println!("\nOrganizing and saving schedules...");
+    info!("{}", Msg::SavingSchedules);
 
-    let merged_routes = merge_schedules(collected_schedules, &route_meta_map);
+    let mut merged_routes = merge_schedules(collected_schedules, &route_meta_map, args.romanize);
 
-    for (route_number, data) in merged_routes {
-        save_route_schedule(&schedule_dir, &route_number, &data)?;
+    // A day-type filtered, listed or retry crawl only refreshes some variants;
+    // keep the rest.
+    if args.day_type.is_some() || listed.is_some() || args.retry_failed {
+        merge_with_existing(&schedule_dir, &mut merged_routes)?;
+    }
+    keep_unchanged(&schedule_dir, &unchanged_routes, &mut merged_routes)?;
+
+    // A route with weekday but no weekend schedule (or vice versa) usually means
+    // one variant's detail page failed to parse.
+    report.coverage_gaps = check_day_type_coverage(&merged_routes);
+    for gap in &report.coverage_gaps {
+        warn!(
+            route_no = gap.route_number,
+            "{}",
+            Msg::CoverageGap {
+                route_no: &gap.route_number,
+                day_types: &gap.day_types.join("/"),
+                missing: &gap.missing.join("/")
+            }
+        );
+    }
+
+    let provenance = settings.agency.provenance();
+    for data in merged_routes.values_mut() {
+        data["provenance"] = json!(provenance);
     }
 
+    if let Some(route) = args.route.as_deref()
+        && args.stdout
+    {
+        let data = merged_routes
+            .get(route)
+            .with_context(|| format!("No schedule of route {} was parsed", route))?;
+        println!("{}", serde_json::to_string_pretty(data)?);
+    } else if !args.combine_only {
+        for (route_number, data) in &merged_routes {
+            save_route_schedule(&schedule_dir, route_number, data)?;
+        }
+    }
+
+    if args.combine {
+        save_combined_schedules(
+            &args.output_dir,
+            &merged_routes,
+            &provenance,
+            &args.compress,
+        )?;
+    }
+    for (key, validators) in &parsed_validators {
+        state::record_validators(key, validators.as_ref());
+    }
+    if !utils::dry_run::is_enabled() {
+        validate_saved_schedules(&args, &schedule_dir, &merged_routes)?;
+    }
+
+    // Write the run report alongside the schedules.
+    report.generated_at = utils::now().to_rfc3339();
+    report.routes_saved = merged_routes.len();
+    utils::write_atomic(
+        &args.output_dir.join("schedule_report.json"),
+        serde_json::to_string_pretty(&report)?,
+    )?;
+
+    // Record failed variants for `--retry-failed`.
+    failures.save(&args.output_dir)?;
+    if !failures.failures.is_empty() {
+        warn!(
+            count = failures.failures.len(),
+            "{}",
+            Msg::SchedulesFailed(failures.failures.len())
+        );
+    }
+
+    // In strict mode, any warning collected during the crawl fails the run.
+    if !report.warnings.is_empty() {
+        warn!(
+            count = report.warnings.len(),
+            "{}",
+            Msg::CrawlWarnings(report.warnings.len())
+        );
+        for w in &report.warnings {
+            info!("   - {}", w);
+        }
+
+        if args.strict {
+            anyhow::bail!(
+                "Strict mode: {} warning(s) during crawl",
+                report.warnings.len()
+            );
+        }
+    }
+
+    failures.check_threshold(targets.len(), args.fail_on_partial)?;
     Ok(())
 }
 
+/// The error for a page the ITS site answered with an error `status`.
+fn http_error(status: StatusCode) -> PipelineError {
+    PipelineError::UpstreamHttp {
+        upstream: ITS_HOST.to_string(),
+        status,
+    }
+}
+
+/// Builds an HTTP client that mimics a web browser.
+///
+/// Cookie store is enabled to automatically handle session cookies (JSESSIONID),
+/// which is crucial for making subsequent requests to the detail page.
+fn build_client(settings: &Settings) -> Result<Client> {
+    let client = Client::builder()
+        .cookie_store(true)
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .timeout(Duration::from_secs(30));
+    Ok(utils::http::configure(client, &settings.http)?.build()?)
+}
+
+/// Checks that the site's robots.txt allows crawling the schedule pages, and
+/// returns the pause between detail page requests: the configured one, or
+/// the site's `Crawl-delay` if longer. robots.txt is not read with
+/// `ignore_robots`, nor when replaying a cassette.
+async fn politeness(client: &Client, settings: &Settings, ignore_robots: bool) -> Result<Duration> {
+    let configured = Duration::from_millis(settings.schedule.delay_ms);
+    let (robots, crawl_delay) = if ignore_robots {
+        ("ignored", None)
+    } else if cassette::is_replaying() {
+        ("replay", None)
+    } else {
+        ("honored", check_robots(client).await?.crawl_delay)
+    };
+    let delay = crawl_delay
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .map_or(configured, |d| d.max(configured));
+    info!(
+        delay_ms = delay.as_millis() as u64,
+        crawl_delay,
+        robots,
+        "{}",
+        Msg::Politeness {
+            delay_ms: delay.as_millis() as u64,
+            crawl_delay,
+            robots_read: robots == "honored",
+        }
+    );
+    Ok(delay)
+}
+
+/// Reads the site's robots.txt, failing if it disallows the main or the
+/// detail page.
+async fn check_robots(client: &Client) -> Result<robots::Robots> {
+    let robots = robots::fetch(client).await?;
+    for url in [BASE_URL, DETAIL_URL] {
+        let path = url::Url::parse(url)?.path().to_string();
+        if !robots.is_allowed(&path) {
+            return Err(config_error(format!(
+                "robots.txt of {} disallows {}; pass --ignore-robots to crawl anyway",
+                ITS_HOST, path
+            )));
+        }
+    }
+    Ok(robots)
+}
+
+/// Fetches only the main schedule page and fingerprints its route list (the
+/// route variants and their termini), for `watch` to tell whether the
+/// schedules changed. Like a crawl, it honors robots.txt unless `ignore_robots`.
+pub async fn probe(settings: &Settings, ignore_robots: bool) -> Result<Fingerprint> {
+    let provider = Provider::load(None).map_err(|e| config_error(format!("{:#}", e)))?;
+    let client = build_client(settings)?;
+    if !ignore_robots && !cassette::is_replaying() {
+        check_robots(&client).await?;
+    }
+    let main_html = cassette::send(client.get(BASE_URL)).await?.body;
+    let (route_meta_map, mut targets) =
+        extract_route_info(&Html::parse_document(&main_html), None, &provider)?;
+    if targets.is_empty() {
+        return Err(PipelineError::schema(ITS_HOST, "no route rows on the main page").into());
+    }
+    targets.sort();
+    let meta: BTreeMap<_, _> = route_meta_map.iter().collect();
+    Ok(Fingerprint::of(&(&targets, meta), targets.len()))
+}
+
+/// Builds the POST request for the detailed schedule of a specific route_id.
+///
+/// It's crucial to set the correct headers (Referer, Origin, Content-Type)
+/// to simulate a legitimate request originating from the website.
+fn detail_request(client: &Client, route_id: &str) -> RequestBuilder {
+    // The website expects the route ID in the POST body to be percent-encoded UTF-8.
+    let encoded_val = percent_encode(route_id.as_bytes(), NON_ALPHANUMERIC).to_string();
+    client
+        .post(DETAIL_URL)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::REFERER, BASE_URL)
+        .header(header::ORIGIN, format!("http://{}", ITS_HOST))
+        .body(format!("no={}", encoded_val))
+}
+
 /// Parses the main schedule page to extract a list of all available routes.
 /// It creates a map of route metadata and a list of `route_id`s used for fetching details.
 fn extract_route_info(
     document: &Html,
     filter: Option<&str>,
+    provider: &Provider,
 ) -> Result<(HashMap<String, RouteMeta>, Vec<String>)> {
     let mut route_meta_map = HashMap::new();
     let mut targets = Vec::new();
 
-    let row_selector = Selector::parse("table tr").unwrap();
-    let cell_selector = Selector::parse("td").unwrap();
-    let onclick_re = Regex::new(r"goDetail\('([^']+)'\)").unwrap();
-
     let mut temp_directions: HashMap<String, HashSet<String>> = HashMap::new();
 
     // Iterate over each row in the main schedule table.
-    for row in document.select(&row_selector) {
-        let cells: Vec<_> = row.select(&cell_selector).collect();
+    for row in document.select(&provider.route_row) {
+        let cells: Vec<_> = row.select(&provider.route_cell).collect();
         if cells.len() >= 6 {
             let route_element = cells[0];
 
             // The route_id required for the POST request is in an `onclick` attribute.
-            if let Some(onclick) = route_element.value().attr("onclick") {
-                if let Some(caps) = onclick_re.captures(onclick) {
-                    let route_id = caps.get(1).unwrap().as_str().to_string();
-
-                    // If a specific route is requested, filter out all others.
-                    if let Some(f) = filter {
-                        if !route_id.starts_with(f) {
-                            continue;
-                        }
-                    }
+            if let Some(onclick) = route_element.value().attr("onclick")
+                && let Some(caps) = provider.route_onclick.captures(onclick)
+            {
+                let route_id = caps.get(1).unwrap().as_str().to_string();
 
-                    targets.push(route_id.clone());
+                // If a specific route is requested, filter out all others.
+                if let Some(f) = filter
+                    && !route_id.starts_with(f)
+                {
+                    continue;
+                }
 
-                    let route_no = route_id.split('(').next().unwrap_or(&route_id).to_string();
-                    let origin = cells[1].text().collect::<String>().trim().to_string();
-                    let dest = cells[2].text().collect::<String>().trim().to_string();
+                targets.push(route_id.clone());
 
-                    // Collect all unique termini for this route number.
-                    let entry = temp_directions.entry(route_no.clone()).or_default();
-                    entry.insert(origin.clone());
-                    entry.insert(dest.clone());
+                let route_no = route_id.split('(').next().unwrap_or(&route_id).to_string();
+                let origin = cells[1].text().collect::<String>().trim().to_string();
+                let dest = cells[2].text().collect::<String>().trim().to_string();
 
-                    // Store metadata for the route.
-                    route_meta_map.entry(route_no).or_insert(RouteMeta {
-                        origin,
-                        destination: dest,
-                        directions: Vec::new(),
-                    });
-                }
+                // Collect all unique termini for this route number.
+                let entry = temp_directions.entry(route_no.clone()).or_default();
+                entry.insert(origin.clone());
+                entry.insert(dest.clone());
+
+                // A single terminus or an explicit "순환" marks a circular route.
+                let circular = origin == dest || route_id.contains("순환");
+
+                // Store metadata for the route.
+                route_meta_map.entry(route_no).or_insert(RouteMeta {
+                    origin,
+                    destination: dest,
+                    directions: Vec::new(),
+                    circular,
+                });
             }
         }
     }
@@ -221,6 +775,53 @@ fn extract_route_info(
     Ok((route_meta_map, targets))
 }
 
+/// Counts the rows of the route list whose route cell yields a route ID.
+fn count_route_rows(document: &Html, provider: &Provider) -> usize {
+    document
+        .select(&provider.route_row)
+        .filter(|row| {
+            row.select(&provider.route_cell)
+                .next()
+                .and_then(|cell| cell.value().attr("onclick"))
+                .is_some_and(|onclick| provider.route_onclick.is_match(onclick))
+        })
+        .count()
+}
+
+/// Reports a failed selector preflight check (and alerts the webhooks, if
+/// configured), returning the error that aborts the crawl.
+async fn selector_check_failed(
+    client: &Client,
+    webhooks: &[Webhook],
+    reason: String,
+) -> anyhow::Error {
+    let message = format!("Polly schedule preflight failed: {}", reason);
+    error!("{}", message);
+    utils::alert::notify(client, webhooks, &message).await;
+    ExitError::new(ExitStatus::SelectorCheck, message).into()
+}
+
+/// Splits a route ID such as "34-1(평일)" into the route number ("34-1")
+/// and the raw day type ("평일"). IDs without a day type yield "general".
+fn split_route_id(route_id: &str) -> (String, String) {
+    match route_id.split_once('(') {
+        Some((number, rest)) => (
+            number.trim().to_string(),
+            rest.trim_end_matches(')').trim().to_string(),
+        ),
+        None => (route_id.trim().to_string(), "general".to_string()),
+    }
+}
+
+/// Returns true if the HTML contains any table the schedule parser could work with.
+#[cfg(feature = "browser")]
+fn has_schedule_table(html: &str, provider: &Provider) -> bool {
+    Html::parse_document(html)
+        .select(&provider.table)
+        .next()
+        .is_some()
+}
+
 /// Normalizes Korean day type strings into a standard English identifier.
 fn normalize_day_type(raw: &str) -> String {
     let lower = raw.to_lowercase();
@@ -241,64 +842,58 @@ fn normalize_day_type(raw: &str) -> String {
     }
 }
 
+/// Finds the schedule table by looking for a header cell containing the
+/// departure marker ("발").
+fn find_departure_table<'a>(document: &'a Html, provider: &Provider) -> Option<ElementRef<'a>> {
+    document.select(&provider.table).find(|table| {
+        table.select(&provider.header_cell).any(|th| {
+            th.text()
+                .collect::<String>()
+                .contains(&provider.departure_marker)
+        })
+    })
+}
+
 /// Parses the HTML of a schedule detail page for a single route.
 fn parse_detail_schedule(
     html: &str,
     route_id: &str,
     meta: Option<&RouteMeta>,
+    provider: &Provider,
 ) -> Result<ParsedSchedule> {
     let document = Html::parse_document(html);
 
     // Extract the route number and raw day type from the route_id string (e.g., "34-1(평일)").
-    let route_match_re = Regex::new(r"^(\S+?)(.*)?$").unwrap();
-    let (route_number, raw_day_type) = if let Some(caps) = route_match_re.captures(route_id) {
-        (
-            caps.get(1).map_or("", |m| m.as_str()).to_string(),
-            caps.get(2)
-                .map_or("general", |m| {
-                    m.as_str().trim_matches(|c| c == '(' || c == ')')
-                })
-                .to_string(),
-        )
-    } else {
-        (route_id.to_string(), "general".to_string())
-    };
+    let (route_number, raw_day_type) = split_route_id(route_id);
 
     let day_type = normalize_day_type(&raw_day_type);
+    let mut warnings = Vec::new();
 
-    let table_selector = Selector::parse("table").unwrap();
-    let th_selector = Selector::parse("th").unwrap();
-
-    // Find the correct schedule table by looking for a `th` element containing "발" (departure).
-    let mut target_table = None;
-    for table in document.select(&table_selector) {
-        let headers: Vec<String> = table
-            .select(&th_selector)
-            .map(|th| th.text().collect::<String>())
-            .collect();
-        if headers.iter().any(|h| h.contains("발")) {
-            target_table = Some(table);
-            break;
-        }
-    }
+    let mut target_table = find_departure_table(&document, provider);
 
     // If the specific table isn't found, fall back to the first table on the page.
     if target_table.is_none() {
-        target_table = document.select(&table_selector).next();
+        target_table = document.select(&provider.table).next();
+        if target_table.is_some() {
+            warnings.push(format!(
+                "No departure ('{}') table found, fell back to first table",
+                provider.departure_marker
+            ));
+        }
     }
 
-    let table = target_table.context("No schedule table found in the HTML")?;
+    let table = target_table
+        .ok_or_else(|| PipelineError::schema(ITS_HOST, "no schedule table found in the HTML"))?;
 
     let mut col_map: HashMap<usize, String> = HashMap::new(); // Maps column index to direction name.
     let mut directions: Vec<String> = Vec::new();
     let mut note_col_idx = None;
 
-    let tr_selector = Selector::parse("tr").unwrap();
-    let header_rows: Vec<_> = table.select(&tr_selector).collect();
+    let header_rows: Vec<_> = table.select(&provider.row).collect();
 
     // Parse table headers to identify directions.
     for row in &header_rows {
-        let ths: Vec<_> = row.select(&th_selector).collect();
+        let ths: Vec<_> = row.select(&provider.header_cell).collect();
         if ths.is_empty() {
             continue;
         }
@@ -306,7 +901,7 @@ fn parse_detail_schedule(
         for (idx, th) in ths.iter().enumerate() {
             let text = th.text().collect::<String>().trim().to_string();
 
-            if text == "비고" {
+            if provider.note_headers.contains(&text) {
                 // "비고" means "Notes".
                 note_col_idx = Some(idx);
                 continue;
@@ -314,10 +909,10 @@ fn parse_detail_schedule(
 
             // Extract direction names from headers. Headers for times often end with "발" (departure).
             // We ignore irrelevant headers like "운행순번" (run order), "시" (hour), "분" (minute), etc.
-            let clean_text = text.trim_end_matches('발').to_string();
+            let clean_text = provider.strip_departure_marker(&text).to_string();
             if !clean_text.is_empty()
-                && !["운행순번", "시", "분", "", "구분"].contains(&clean_text.as_str())
-                && !Regex::new(r"^\d+시$").unwrap().is_match(&clean_text)
+                && !provider.ignore_headers.contains(&clean_text)
+                && !provider.hour_header.is_match(&clean_text)
             {
                 if !directions.contains(&clean_text) {
                     directions.push(clean_text.clone());
@@ -330,6 +925,7 @@ fn parse_detail_schedule(
     // If directions could not be determined from the table headers,
     // fall back to the metadata extracted from the main page.
     if directions.is_empty() {
+        warnings.push("No direction headers found in schedule table".to_string());
         if let Some(m) = meta {
             directions = m.directions.clone();
         }
@@ -341,17 +937,14 @@ fn parse_detail_schedule(
         }
     }
 
-    let td_selector = Selector::parse("td").unwrap();
-    let time_re = Regex::new(r"^(\d{1,2}:\d{2})").unwrap();
-
     let mut times_by_direction: HashMap<String, Vec<TimeEntry>> = HashMap::new();
     for dir in &directions {
         times_by_direction.insert(dir.clone(), Vec::new());
     }
 
     // Iterate through table rows to extract departure times.
-    for row in table.select(&tr_selector) {
-        let cells: Vec<_> = row.select(&td_selector).collect();
+    for row in table.select(&provider.row) {
+        let cells: Vec<_> = row.select(&provider.data_cell).collect();
         if cells.is_empty() {
             // Skip header rows.
             continue;
@@ -373,7 +966,7 @@ fn parse_detail_schedule(
         for (col_idx, cell) in cells.iter().enumerate() {
             if let Some(dir_name) = col_map.get(&col_idx) {
                 let text = cell.text().collect::<String>().trim().to_string();
-                if let Some(caps) = time_re.captures(&text) {
+                if let Some(caps) = provider.time.captures(&text) {
                     let clean_time = caps.get(1).unwrap().as_str().to_string();
 
                     if let Some(list) = times_by_direction.get_mut(dir_name) {
@@ -392,6 +985,7 @@ fn parse_detail_schedule(
         day_type,
         directions,
         times_by_direction,
+        warnings,
     })
 }
 
@@ -400,10 +994,12 @@ fn parse_detail_schedule(
 fn merge_schedules(
     schedules: Vec<ParsedSchedule>,
     route_meta_map: &HashMap<String, RouteMeta>,
+    romanize: bool,
 ) -> HashMap<String, serde_json::Value> {
     let mut merged_routes: HashMap<String, serde_json::Value> = HashMap::new();
-    let mut route_note_maps: HashMap<String, HashMap<String, String>> = HashMap::new();
-    let mut route_note_counters: HashMap<String, usize> = HashMap::new();
+
+    // Assign note IDs per route up front, across all day types.
+    let mut route_note_maps = assign_note_ids(&schedules);
 
     for schedule in schedules {
         let r_no = schedule.route_number.clone();
@@ -411,7 +1007,7 @@ fn merge_schedules(
         // If this is the first time seeing this route, create the base JSON structure.
         if !merged_routes.contains_key(&r_no) {
             let meta = route_meta_map.get(&r_no);
-            let (origin, dest, dirs) = match meta {
+            let (origin, dest, mut dirs) = match meta {
                 Some(m) => (
                     m.origin.clone(),
                     m.destination.clone(),
@@ -420,48 +1016,72 @@ fn merge_schedules(
                 None => (String::new(), String::new(), schedule.directions.clone()),
             };
 
-            let initial_json = json!({
-                "routeId": r_no,
-                "routeName": format!("{}번", r_no),
-                "description": format!("{} ↔ {}", origin, dest),
-                "lastUpdated": chrono::Local::now().format("%Y-%m-%d").to_string(),
-                "directions": dirs,
-                "routeDetails": [],
-                "featuredStops": { "general": [] },
-                "schedule": {},
-                "notes": {}
-            });
-            merged_routes.insert(r_no.clone(), initial_json);
-            route_note_maps.insert(r_no.clone(), HashMap::new());
-            route_note_counters.insert(r_no.clone(), 1);
+            // Circular routes are modeled with a single direction named after the terminus.
+            let circular = meta.is_some_and(|m| m.circular)
+                || schedule.directions.iter().any(|d| d.contains("순환"));
+            let description = if circular {
+                dirs = vec![if origin.is_empty() {
+                    dirs.first().cloned().unwrap_or_default()
+                } else {
+                    origin.clone()
+                }];
+                format!("{} 순환", dirs[0])
+            } else {
+                format!("{} ↔ {}", origin, dest)
+            };
+
+            let notes: BTreeMap<String, String> = route_note_maps
+                .entry(r_no.clone())
+                .or_default()
+                .iter()
+                .map(|(text, id)| (id.clone(), text.clone()))
+                .collect();
+
+            let dirs_en: Option<Vec<String>> =
+                romanize.then(|| dirs.iter().map(|d| utils::romanize::romanize(d)).collect());
+
+            let initial = ScheduleFile {
+                schema_version: SCHEDULE_SCHEMA_VERSION,
+                route_id: r_no.clone(),
+                route_name: format!("{}번", r_no),
+                description,
+                last_updated: utils::now().format("%Y-%m-%d").to_string(),
+                circular,
+                directions: dirs,
+                directions_en: dirs_en,
+                route_details: Vec::new(),
+                featured_stops: FeaturedStops::default(),
+                schedule: BTreeMap::new(),
+                notes,
+                provenance: None,
+            };
+            merged_routes.insert(r_no.clone(), json!(initial));
         }
 
         let route_json = merged_routes.get_mut(&r_no).unwrap();
-        let note_map = route_note_maps.get_mut(&r_no).unwrap();
-        let note_counter = route_note_counters.get_mut(&r_no).unwrap();
+        let note_map = &route_note_maps[&r_no];
 
         // Create a schedule object for the current day type (e.g., "weekday").
         let day_type_schedule = json!({});
         route_json["schedule"][&schedule.day_type] = day_type_schedule;
 
-        for (direction, entries) in schedule.times_by_direction {
+        // Collapse all departure columns of a circular route into its single direction.
+        let mut times_by_direction = schedule.times_by_direction;
+        if route_json["circular"].as_bool() == Some(true) {
+            let direction = route_json["directions"][0]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let mut entries: Vec<TimeEntry> = times_by_direction.into_values().flatten().collect();
+            entries.sort_by_key(|e| format!("{:0>5}", e.time));
+            times_by_direction = HashMap::from([(direction, entries)]);
+        }
+
+        for (direction, entries) in times_by_direction {
             let mut times_by_hour: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
 
             for entry in entries {
-                // Handle notes: assign a unique ID to each note text.
-                let note_id = if let Some(note_text) = entry.note {
-                    if !note_map.contains_key(&note_text) {
-                        let new_id = note_counter.to_string();
-                        note_map.insert(note_text.clone(), new_id.clone());
-                        *note_counter += 1;
-                        route_json["notes"][&new_id] = json!(note_text);
-                        Some(new_id)
-                    } else {
-                        Some(note_map[&note_text].clone())
-                    }
-                } else {
-                    None
-                };
+                let note_id = entry.note.map(|text| note_map[&text].clone());
 
                 // Group times by the hour.
                 let parts: Vec<&str> = entry.time.split(':').collect();
@@ -469,12 +1089,10 @@ fn merge_schedules(
                     let hour = format!("{:0>2}", parts[0]);
                     let minute = format!("{:0>2}", parts[1]);
 
-                    let mut minute_obj = json!({ "minute": minute });
-                    if let Some(nid) = note_id {
-                        minute_obj["noteId"] = json!(nid);
-                    }
-
-                    times_by_hour.entry(hour).or_default().push(minute_obj);
+                    times_by_hour
+                        .entry(hour)
+                        .or_default()
+                        .push(json!(Departure { minute, note_id }));
                 }
             }
 
@@ -491,24 +1109,458 @@ fn merge_schedules(
     merged_routes
 }
 
+/// Finds routes that have a weekday schedule but no weekend schedule, or vice versa.
+/// Routes with only a "general" (unspecified) schedule are not flagged.
+fn check_day_type_coverage(merged_routes: &HashMap<String, serde_json::Value>) -> Vec<CoverageGap> {
+    let mut gaps = Vec::new();
+
+    for (route_number, data) in merged_routes {
+        let day_types: Vec<String> = data["schedule"]
+            .as_object()
+            .map(|m| m.keys().cloned().collect())
+            .unwrap_or_default();
+
+        let has = |dt: &str| day_types.iter().any(|d| d == dt);
+        let missing = match (has("weekday"), has("weekend")) {
+            (true, false) => "weekend",
+            (false, true) => "weekday",
+            _ => continue,
+        };
+
+        gaps.push(CoverageGap {
+            route_number: route_number.clone(),
+            day_types,
+            missing: vec![missing.to_string()],
+        });
+    }
+
+    gaps.sort_by(|a, b| a.route_number.cmp(&b.route_number));
+    gaps
+}
+
+/// Fills in day types missing from the freshly merged routes with those from
+/// the schedule files already on disk, so that a day-type filtered crawl
+/// doesn't drop the variants it didn't fetch.
+fn merge_with_existing(
+    schedule_dir: &Path,
+    merged_routes: &mut HashMap<String, serde_json::Value>,
+) -> Result<()> {
+    for (route_number, data) in merged_routes.iter_mut() {
+        let path = schedule_dir.join(schedule_file_name(route_number));
+        if !path.exists() {
+            continue;
+        }
+
+        let existing: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Invalid schedule file {:?}", path))?;
+
+        if let Some(day_types) = existing["schedule"].as_object() {
+            for (day_type, hours) in day_types {
+                if data["schedule"][day_type].is_null() {
+                    data["schedule"][day_type] = hours.clone();
+                }
+            }
+        }
+
+        // Note IDs are content hashes, so notes from both files can be unioned.
+        if let Some(notes) = existing["notes"].as_object() {
+            for (id, text) in notes {
+                if data["notes"][id].is_null() {
+                    data["notes"][id] = text.clone();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps the saved schedules of the variants the site answered 304 Not
+/// Modified for: routes with no other variant crawled are taken as saved,
+/// the others get the day types they lack from the saved file.
+fn keep_unchanged(
+    schedule_dir: &Path,
+    unchanged_routes: &BTreeSet<String>,
+    merged_routes: &mut HashMap<String, serde_json::Value>,
+) -> Result<()> {
+    let mut partial: HashMap<String, serde_json::Value> = HashMap::new();
+    for route_number in unchanged_routes {
+        if let Some(data) = merged_routes.remove(route_number) {
+            partial.insert(route_number.clone(), data);
+            continue;
+        }
+        let path = schedule_dir.join(schedule_file_name(route_number));
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Invalid schedule file {:?}", path))?;
+        merged_routes.insert(route_number.clone(), saved);
+    }
+    merge_with_existing(schedule_dir, &mut partial)?;
+    merged_routes.extend(partial);
+    Ok(())
+}
+
+/// Assigns a stable ID to every distinct note text of each route.
+///
+/// IDs are derived from a hash of the note text, so the same note keeps its ID
+/// across day types and re-crawls. Texts are processed in sorted order, so the
+/// (rare) prefix collisions are also resolved independently of page order.
+fn assign_note_ids(schedules: &[ParsedSchedule]) -> HashMap<String, HashMap<String, String>> {
+    let mut texts_by_route: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for schedule in schedules {
+        let texts = texts_by_route.entry(&schedule.route_number).or_default();
+        for entries in schedule.times_by_direction.values() {
+            texts.extend(entries.iter().filter_map(|e| e.note.as_deref()));
+        }
+    }
+
+    let mut result = HashMap::new();
+    for (route_number, texts) in texts_by_route {
+        let mut note_map: HashMap<String, String> = HashMap::new();
+        let mut used: HashSet<String> = HashSet::new();
+
+        for text in texts {
+            // Lengthen the hash prefix until it no longer collides.
+            let mut len = 8;
+            let mut id = utils::short_hash(text.as_bytes(), len);
+            while used.contains(&id) {
+                len += 4;
+                id = utils::short_hash(text.as_bytes(), len);
+            }
+            used.insert(id.clone());
+            note_map.insert(text.to_string(), id);
+        }
+
+        result.insert(route_number.to_string(), note_map);
+    }
+
+    result
+}
+
+/// Loads previously saved merged schedules from an output directory, keyed by route number.
+///
+/// Reads the per-route files under `schedules/`, falling back to the combined
+/// `schedules.json` when no per-route files exist.
+pub fn load_merged_schedules(output_dir: &Path) -> Result<BTreeMap<String, serde_json::Value>> {
+    let mut routes = BTreeMap::new();
+
+    let schedule_dir = output_dir.join("schedules");
+    if schedule_dir.is_dir() {
+        for entry in fs::read_dir(&schedule_dir)?.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let data: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)
+                .with_context(|| format!("Invalid schedule file {:?}", path))?;
+            if let Some(route_id) = data["routeId"].as_str() {
+                routes.insert(route_id.to_string(), data);
+            }
+        }
+    }
+
+    let combined_path = output_dir.join("schedules.json");
+    if routes.is_empty() && combined_path.exists() {
+        let combined: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&combined_path)?)
+                .with_context(|| format!("Invalid combined schedule file {:?}", combined_path))?;
+        if let Some(map) = combined["routes"].as_object() {
+            routes.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+
+    Ok(routes)
+}
+
+/// File name of a route's schedule, with the route number sanitized.
+fn schedule_file_name(route_number: &str) -> String {
+    let safe_name = route_number.replace(|c: char| !c.is_alphanumeric() && c != '-', "_");
+    format!("{}.json", safe_name)
+}
+
+/// Validates the schedule files written by this run against their schema.
+fn validate_saved_schedules(
+    args: &ScheduleArgs,
+    schedule_dir: &Path,
+    merged_routes: &HashMap<String, serde_json::Value>,
+) -> Result<()> {
+    if !args.combine_only {
+        let paths: Vec<PathBuf> = merged_routes
+            .keys()
+            .map(|r| schedule_dir.join(schedule_file_name(r)))
+            .collect();
+        validate_files(OutputFormat::Schedule, &paths)?;
+    }
+
+    if args.combine {
+        let path = args.output_dir.join("schedules.json");
+        let combined: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        let routes = combined["routes"].as_object().into_iter().flatten();
+        validate_values(
+            OutputFormat::Schedule,
+            routes.map(|(route, doc)| (format!("{:?} route {}", path, route), doc)),
+        )?;
+    }
+    Ok(())
+}
+
 /// Saves the final merged schedule data for a route to a JSON file.
 fn save_route_schedule(
-    base_dir: &PathBuf,
+    base_dir: &Path,
     route_number: &str,
     data: &serde_json::Value,
 ) -> Result<()> {
-    // Sanitize the route number to create a valid filename.
-    let safe_name = route_number.replace(|c: char| !c.is_alphanumeric() && c != '-', "_");
-    let filename = format!("{}.json", safe_name);
-    let path = base_dir.join(filename);
+    let path = base_dir.join(schedule_file_name(route_number));
 
     let json_str = serde_json::to_string_pretty(data)?;
-    fs::write(&path, json_str)?;
+    utils::write_atomic(&path, json_str)?;
 
-    println!(
+    info!(
+        route_no = route_number,
         "   ✓ Saved {} to {:?}",
         route_number,
         path.file_name().unwrap()
     );
     Ok(())
 }
+
+/// Saves all merged routes into a single `schedules.json`, with a lightweight
+/// index (route, name, description, day types) so clients can list routes
+/// without walking the full schedule data.
+fn save_combined_schedules(
+    output_dir: &Path,
+    merged_routes: &HashMap<String, serde_json::Value>,
+    provenance: &Provenance,
+    compress: &[Compression],
+) -> Result<()> {
+    let routes: BTreeMap<&String, &serde_json::Value> = merged_routes.iter().collect();
+
+    let index: Vec<serde_json::Value> = routes
+        .iter()
+        .map(|(route_number, data)| {
+            let day_types: Vec<&String> = data["schedule"]
+                .as_object()
+                .map(|m| m.keys().collect())
+                .unwrap_or_default();
+            json!({
+                "routeId": route_number,
+                "routeName": data["routeName"],
+                "description": data["description"],
+                "dayTypes": day_types,
+            })
+        })
+        .collect();
+
+    let combined = json!({
+        "schemaVersion": SCHEDULE_SCHEMA_VERSION,
+        "lastUpdated": utils::now().format("%Y-%m-%d").to_string(),
+        "provenance": provenance,
+        "index": index,
+        "routes": routes,
+    });
+
+    let path = output_dir.join("schedules.json");
+    let content = serde_json::to_string(&combined)?;
+    utils::write_atomic(&path, &content)?;
+    write_compressed(&path, content.as_bytes(), compress)?;
+
+    info!(
+        count = routes.len(),
+        "   ✓ Saved {} routes to {:?}",
+        routes.len(),
+        path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DETAIL: &str = r#"
+        <table>
+            <tr><th>운행순번</th><th>연세대발</th><th>터미널발</th><th>비고</th></tr>
+            <tr><td>1</td><td>06:00</td><td>06:30</td><td></td></tr>
+            <tr><td>2</td><td>7:05</td><td>-</td><td>학기중</td></tr>
+        </table>"#;
+
+    fn parse(html: &str, route_id: &str) -> ParsedSchedule {
+        let provider = Provider::load(None).unwrap();
+        parse_detail_schedule(html, route_id, None, &provider).unwrap()
+    }
+
+    fn times(schedule: &ParsedSchedule, direction: &str) -> Vec<(String, Option<String>)> {
+        schedule.times_by_direction[direction]
+            .iter()
+            .map(|e| (e.time.clone(), e.note.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_route_ids_into_number_and_day_type() {
+        assert_eq!(
+            split_route_id("34-1(평일)"),
+            ("34-1".to_string(), "평일".to_string())
+        );
+        assert_eq!(
+            split_route_id("2 (토,일요일)"),
+            ("2".to_string(), "토,일요일".to_string())
+        );
+        assert_eq!(
+            split_route_id("41"),
+            ("41".to_string(), "general".to_string())
+        );
+    }
+
+    #[test]
+    fn normalizes_day_types() {
+        assert_eq!(normalize_day_type("평일"), "weekday");
+        assert_eq!(normalize_day_type("주중"), "weekday");
+        assert_eq!(normalize_day_type("토,일요일"), "weekend");
+        assert_eq!(normalize_day_type("공휴일"), "weekend");
+        assert_eq!(normalize_day_type("방학"), "weekend");
+        assert_eq!(normalize_day_type("general"), "general");
+    }
+
+    #[test]
+    fn parses_departures_per_direction_with_notes() {
+        let schedule = parse(DETAIL, "34(평일)");
+        assert_eq!(schedule.route_number, "34");
+        assert_eq!(schedule.day_type, "weekday");
+        assert_eq!(schedule.directions, ["연세대", "터미널"]);
+        assert_eq!(
+            times(&schedule, "연세대"),
+            [
+                ("06:00".to_string(), None),
+                ("7:05".to_string(), Some("학기중".to_string()))
+            ]
+        );
+        assert_eq!(times(&schedule, "터미널"), [("06:30".to_string(), None)]);
+        assert!(schedule.warnings.is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_the_first_table() {
+        let html = "<table><tr><th>시</th><th>분</th></tr><tr><td>6</td><td>00</td></tr></table>";
+        let schedule = parse(html, "34");
+        assert_eq!(schedule.day_type, "general");
+        assert_eq!(schedule.warnings.len(), 2, "{:?}", schedule.warnings);
+        assert!(schedule.times_by_direction.is_empty());
+    }
+
+    #[test]
+    fn pages_without_a_table_are_an_error() {
+        let provider = Provider::load(None).unwrap();
+        assert!(parse_detail_schedule("<p>점검 중</p>", "34", None, &provider).is_err());
+    }
+
+    #[test]
+    fn circular_routes_merge_into_one_direction() {
+        let meta = RouteMeta {
+            origin: "터미널".to_string(),
+            destination: "터미널".to_string(),
+            directions: vec!["터미널".to_string()],
+            circular: true,
+        };
+        let merged = merge_schedules(
+            vec![parse(DETAIL, "34(평일)")],
+            &HashMap::from([("34".to_string(), meta)]),
+            false,
+        );
+        let route = &merged["34"];
+        assert_eq!(route["circular"], true);
+        assert_eq!(route["description"], "터미널 순환");
+        assert_eq!(route["directions"], json!(["터미널"]));
+        assert_eq!(
+            route["schedule"]["weekday"]["06"]["터미널"],
+            json!([{ "minute": "00" }, { "minute": "30" }])
+        );
+    }
+
+    #[test]
+    fn merges_day_types_into_one_route() {
+        let weekday = parse(DETAIL, "34(평일)");
+        let weekend = parse(
+            &DETAIL.replace("7:05", "08:15").replace("학기중", ""),
+            "34(주말)",
+        );
+        let merged = merge_schedules(vec![weekday, weekend], &HashMap::new(), false);
+
+        assert_eq!(merged.len(), 1);
+        let route = &merged["34"];
+        assert_eq!(route["routeName"], "34번");
+        assert_eq!(route["directions"], json!(["연세대", "터미널"]));
+        let schedule = &route["schedule"];
+        assert_eq!(
+            schedule["weekday"]["06"]["연세대"],
+            json!([{ "minute": "00" }])
+        );
+        assert_eq!(
+            schedule["weekday"]["06"]["터미널"],
+            json!([{ "minute": "30" }])
+        );
+        assert_eq!(
+            schedule["weekend"]["08"]["연세대"],
+            json!([{ "minute": "15" }])
+        );
+
+        // Notes are referenced by ID and listed once per route.
+        let note_id = schedule["weekday"]["07"]["연세대"][0]["noteId"]
+            .as_str()
+            .unwrap();
+        assert_eq!(route["notes"][note_id], "학기중");
+        assert_eq!(route["notes"].as_object().unwrap().len(), 1);
+    }
+
+    /// A schedule of route 34 with one departure per note on a single direction
+    fn with_notes(day_type: &str, notes: &[&str]) -> ParsedSchedule {
+        let entries = notes
+            .iter()
+            .map(|note| TimeEntry {
+                time: "06:00".to_string(),
+                note: Some(note.to_string()),
+            })
+            .collect();
+        ParsedSchedule {
+            route_number: "34".to_string(),
+            day_type: day_type.to_string(),
+            directions: vec!["연세대".to_string()],
+            times_by_direction: HashMap::from([("연세대".to_string(), entries)]),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn note_ids_are_stable_across_day_types_and_page_orders() {
+        let ids = assign_note_ids(&[
+            with_notes("weekday", &["학기중", "방학중 미운행"]),
+            with_notes("weekend", &["학기중"]),
+        ]);
+        let reordered = assign_note_ids(&[
+            with_notes("weekend", &["학기중"]),
+            with_notes("weekday", &["방학중 미운행", "학기중"]),
+        ]);
+        assert_eq!(ids, reordered);
+        assert_eq!(ids["34"].len(), 2);
+        assert_eq!(
+            ids["34"]["학기중"],
+            utils::short_hash("학기중".as_bytes(), 8)
+        );
+    }
+
+    #[test]
+    fn note_id_collisions_are_lengthened_independently_of_order() {
+        // The SHA-256 digests of these texts share their first 8 hex digits.
+        let (a, b) = ("비고 42278", "비고 50010");
+        assert_eq!(
+            utils::short_hash(a.as_bytes(), 8),
+            utils::short_hash(b.as_bytes(), 8)
+        );
+
+        for notes in [[a, b], [b, a]] {
+            let ids = assign_note_ids(&[with_notes("weekday", &notes)]);
+            assert_eq!(ids["34"][a], "2fe86e0b");
+            assert_eq!(ids["34"][b], "2fe86e0b6398");
+        }
+    }
+}
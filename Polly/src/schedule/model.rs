@@ -3,29 +3,113 @@
 //! This module defines the data structures used to represent
 //! bus route metadata and parsed schedule information.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::settings::Provenance;
+use crate::utils::short_hash;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ts_rs::TS;
 
 /// Holds metadata for a bus route, such as its start and end points
 /// and a list of all unique directions (termini) it serves.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteMeta {
     pub origin: String,
     pub destination: String,
     pub directions: Vec<String>,
+    /// Circular (순환) route with a single terminus
+    pub circular: bool,
 }
 
 /// Represents a single departure time entry in the schedule.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TimeEntry {
     pub time: String,
     pub note: Option<String>,
 }
 
 /// Represents the fully parsed schedule for a specific route on a specific day type.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ParsedSchedule {
     pub route_number: String,
     pub day_type: String,
     pub directions: Vec<String>,
     pub times_by_direction: HashMap<String, Vec<TimeEntry>>,
+    /// Non-fatal issues noticed while parsing (e.g., fallback table selection).
+    pub warnings: Vec<String>,
+}
+
+impl ParsedSchedule {
+    /// Fingerprint of the parsed directions and departure times.
+    pub fn content_hash(&self) -> String {
+        let times: BTreeMap<_, _> = self.times_by_direction.iter().collect();
+        let content = serde_json::to_vec(&(&self.directions, times)).unwrap_or_default();
+        short_hash(&content, 16)
+    }
+}
+
+/// A route whose schedules cover only some of the expected day types.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageGap {
+    pub route_number: String,
+    pub day_types: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+/// Summary of a crawl run, saved next to the schedules as `schedule_report.json`.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleReport {
+    pub generated_at: String,
+    pub routes_saved: usize,
+    /// Route variants the site answered 304 Not Modified for, kept as saved
+    pub variants_unchanged: usize,
+    pub warnings: Vec<String>,
+    pub coverage_gaps: Vec<CoverageGap>,
+}
+
+/// A merged route schedule, saved as `{route}.json` (and within `schedules.json`).
+#[derive(Debug, Serialize, Deserialize, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleFile {
+    pub schema_version: u32,
+    pub route_id: String,
+    pub route_name: String,
+    pub description: String,
+    pub last_updated: String,
+    pub circular: bool,
+    pub directions: Vec<String>,
+    /// Romanized directions (`--romanize`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub directions_en: Option<Vec<String>>,
+    pub route_details: Vec<Value>,
+    pub featured_stops: FeaturedStops,
+    /// Day type -> hour ("06") -> direction -> departures in that hour
+    pub schedule: BTreeMap<String, BTreeMap<String, BTreeMap<String, Vec<Departure>>>>,
+    /// Note ID -> note text
+    pub notes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub provenance: Option<Provenance>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, TS, JsonSchema)]
+pub struct FeaturedStops {
+    pub general: Vec<Value>,
+}
+
+/// A departure within an hour of the schedule
+#[derive(Debug, Serialize, Deserialize, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Departure {
+    pub minute: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub note_id: Option<String>,
 }
@@ -0,0 +1,177 @@
+//! Schedule Provider Configuration
+//!
+//! The CSS selectors, regexes, and header keywords the schedule parser relies
+//! on are read from a provider config (TOML) instead of being hardcoded, so
+//! minor HTML changes on the upstream site can be fixed by editing the config.
+//! Any key left out of the file falls back to the built-in Wonju defaults.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use scraper::Selector;
+use serde::Deserialize;
+
+// ============================================================================
+// Raw Config (TOML)
+// ============================================================================
+
+/// Provider config as written in the TOML file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    pub selectors: SelectorConfig,
+    pub patterns: PatternConfig,
+    pub headers: HeaderConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SelectorConfig {
+    /// Rows of the route list on the main page
+    pub route_row: String,
+    /// Cells within a route list row
+    pub route_cell: String,
+    /// Candidate schedule tables on a detail page
+    pub table: String,
+    /// Rows within a schedule table
+    pub row: String,
+    /// Header cells within a schedule table
+    pub header_cell: String,
+    /// Data cells within a schedule table
+    pub data_cell: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PatternConfig {
+    /// Extracts the route ID from a route cell's `onclick` (first capture group)
+    pub route_onclick: String,
+    /// Matches hour headers (e.g., "6시") that are not directions
+    pub hour_header: String,
+    /// Extracts a departure time from a data cell (first capture group)
+    pub time: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderConfig {
+    /// Suffix marking departure columns (e.g., "연세대발"); also identifies the schedule table
+    pub departure_marker: String,
+    /// Header texts of the notes column
+    pub note: Vec<String>,
+    /// Header texts that are neither directions nor notes
+    pub ignore: Vec<String>,
+}
+
+impl Default for SelectorConfig {
+    fn default() -> Self {
+        Self {
+            route_row: "table tr".to_string(),
+            route_cell: "td".to_string(),
+            table: "table".to_string(),
+            row: "tr".to_string(),
+            header_cell: "th".to_string(),
+            data_cell: "td".to_string(),
+        }
+    }
+}
+
+impl Default for PatternConfig {
+    fn default() -> Self {
+        Self {
+            route_onclick: r"goDetail\('([^']+)'\)".to_string(),
+            hour_header: r"^\d+시$".to_string(),
+            time: r"^(\d{1,2}:\d{2})".to_string(),
+        }
+    }
+}
+
+impl Default for HeaderConfig {
+    fn default() -> Self {
+        Self {
+            departure_marker: "발".to_string(),
+            note: vec!["비고".to_string()],
+            ignore: ["운행순번", "시", "분", "구분"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+// ============================================================================
+// Compiled Provider
+// ============================================================================
+
+/// Provider config with selectors and regexes compiled, ready for parsing.
+pub struct Provider {
+    pub route_row: Selector,
+    pub route_cell: Selector,
+    pub table: Selector,
+    pub row: Selector,
+    pub header_cell: Selector,
+    pub data_cell: Selector,
+    pub route_onclick: Regex,
+    pub hour_header: Regex,
+    pub time: Regex,
+    pub departure_marker: String,
+    pub note_headers: Vec<String>,
+    pub ignore_headers: Vec<String>,
+}
+
+impl Provider {
+    /// Loads the provider config from `path`, or the built-in defaults if none is given.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let config = match path {
+            Some(p) => {
+                let content = fs::read_to_string(p)
+                    .with_context(|| format!("Failed to read provider config {:?}", p))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("Invalid provider config {:?}", p))?
+            }
+            None => ProviderConfig::default(),
+        };
+        Self::compile(config)
+    }
+
+    fn compile(config: ProviderConfig) -> Result<Self> {
+        let ProviderConfig {
+            selectors: s,
+            patterns: p,
+            headers: h,
+        } = config;
+
+        Ok(Self {
+            route_row: selector(&s.route_row)?,
+            route_cell: selector(&s.route_cell)?,
+            table: selector(&s.table)?,
+            row: selector(&s.row)?,
+            header_cell: selector(&s.header_cell)?,
+            data_cell: selector(&s.data_cell)?,
+            route_onclick: regex(&p.route_onclick)?,
+            hour_header: regex(&p.hour_header)?,
+            time: regex(&p.time)?,
+            departure_marker: h.departure_marker,
+            note_headers: h.note,
+            ignore_headers: h.ignore,
+        })
+    }
+
+    /// Strips the departure marker from a header (e.g., "연세대발" -> "연세대").
+    pub fn strip_departure_marker<'a>(&self, header: &'a str) -> &'a str {
+        if self.departure_marker.is_empty() {
+            return header;
+        }
+        header.trim_end_matches(self.departure_marker.as_str())
+    }
+}
+
+fn selector(css: &str) -> Result<Selector> {
+    Selector::parse(css).map_err(|e| anyhow!("Invalid selector {:?}: {}", css, e))
+}
+
+fn regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).with_context(|| format!("Invalid regex {:?}", pattern))
+}
@@ -0,0 +1,193 @@
+//! robots.txt of the ITS Site
+//!
+//! Before crawling, the schedule crawler reads the site's robots.txt
+//! (RFC 9309): the pages it fetches must not be disallowed for it, and a
+//! `Crawl-delay` longer than the configured pause between detail pages is
+//! honored. Groups for Polly's product token apply, else those for `*`.
+
+use anyhow::Result;
+use reqwest::Client;
+
+use crate::config::{ITS_HOST, ITS_ROBOTS_URL, ROBOTS_AGENT};
+use crate::error::{ExitError, ExitStatus};
+use crate::utils::cassette;
+
+/// The rules of robots.txt that apply to Polly.
+#[derive(Debug, Default)]
+pub struct Robots {
+    /// `Allow` (true) and `Disallow` (false) path patterns
+    rules: Vec<(bool, String)>,
+    /// `Crawl-delay`, in seconds
+    pub crawl_delay: Option<f64>,
+}
+
+/// Fetches and parses the robots.txt of the ITS site. A missing file (4xx)
+/// allows everything; a server error means the site cannot say, so crawling
+/// is assumed disallowed, as RFC 9309 asks.
+pub async fn fetch(client: &Client) -> Result<Robots> {
+    let resp = cassette::send(client.get(ITS_ROBOTS_URL)).await?;
+    if resp.status.is_server_error() {
+        return Err(ExitError::new(
+            ExitStatus::Unreachable,
+            format!(
+                "robots.txt of {} returned HTTP status {}, so crawling is assumed \
+                 disallowed; pass --ignore-robots to crawl anyway",
+                ITS_HOST, resp.status
+            ),
+        )
+        .into());
+    }
+    if !resp.status.is_success() {
+        return Ok(Robots::default());
+    }
+    Ok(Robots::parse(&resp.body, ROBOTS_AGENT))
+}
+
+impl Robots {
+    /// Parses robots.txt, keeping the groups for `agent` (or, without any,
+    /// those for `*`). Lines that are not directives, such as an HTML error
+    /// page served in place of the file, are ignored.
+    pub fn parse(text: &str, agent: &str) -> Self {
+        let mut own = Robots::default();
+        let mut any = Robots::default();
+        let mut has_own = false;
+        // Which of the two the current group's rules go to
+        let (mut to_own, mut to_any) = (false, false);
+        let mut in_agents = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        (to_own, to_any) = (false, false);
+                        in_agents = true;
+                    }
+                    if value.eq_ignore_ascii_case(agent) {
+                        to_own = true;
+                        has_own = true;
+                    } else if value == "*" {
+                        to_any = true;
+                    }
+                }
+                directive => {
+                    in_agents = false;
+                    for (robots, applies) in [(&mut own, to_own), (&mut any, to_any)] {
+                        if applies {
+                            robots.add(directive, value);
+                        }
+                    }
+                }
+            }
+        }
+        if has_own { own } else { any }
+    }
+
+    fn add(&mut self, directive: &str, value: &str) {
+        match directive {
+            "allow" if !value.is_empty() => self.rules.push((true, value.to_string())),
+            // An empty `Disallow` allows everything.
+            "disallow" if !value.is_empty() => self.rules.push((false, value.to_string())),
+            "crawl-delay" => {
+                if let Ok(secs) = value.parse::<f64>()
+                    && secs.is_finite()
+                    && secs >= 0.0
+                {
+                    self.crawl_delay = Some(self.crawl_delay.map_or(secs, |d| d.max(secs)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `path` may be crawled: the longest matching rule decides, and
+    /// `Allow` wins a tie. Paths no rule matches are allowed.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Matches a robots.txt path pattern, where `*` stands for any characters and
+/// a trailing `$` anchors the end of the path. Patterns are prefixes otherwise.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        // The last part of an anchored pattern must end the path.
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_group_overrides_the_wildcard() {
+        let text =
+            "User-agent: *\nDisallow: /\n\nUser-agent: Polly\nDisallow: /admin\nCrawl-delay: 2\n";
+        let robots = Robots::parse(text, "polly");
+        assert!(robots.is_allowed("/bus/schedule"));
+        assert!(!robots.is_allowed("/admin/login"));
+        assert_eq!(robots.crawl_delay, Some(2.0));
+
+        let others = Robots::parse(text, "OtherBot");
+        assert!(!others.is_allowed("/bus/schedule"));
+        assert_eq!(others.crawl_delay, None);
+    }
+
+    #[test]
+    fn longest_match_decides_and_allow_wins_ties() {
+        let text = "User-agent: *\nDisallow: /bus\nAllow: /bus/schedule\nDisallow: /x\nAllow: /x\n";
+        let robots = Robots::parse(text, "polly");
+        assert!(robots.is_allowed("/bus/schedule?route=34"));
+        assert!(!robots.is_allowed("/bus/live"));
+        assert!(robots.is_allowed("/x"));
+    }
+
+    #[test]
+    fn wildcards_and_anchors() {
+        let text = "User-agent: *\nDisallow: /*.php$\nDisallow: /tmp*/cache\n";
+        let robots = Robots::parse(text, "polly");
+        assert!(!robots.is_allowed("/detail.php"));
+        assert!(robots.is_allowed("/detail.php?id=1"));
+        assert!(!robots.is_allowed("/tmp1/cache/a"));
+        assert!(robots.is_allowed("/tmp1/other"));
+    }
+
+    #[test]
+    fn consecutive_agents_share_a_group() {
+        let text = "User-agent: Polly\nUser-agent: Other\nDisallow: /private\n";
+        assert!(!Robots::parse(text, "Polly").is_allowed("/private"));
+    }
+
+    #[test]
+    fn empty_disallow_and_junk_allow_everything() {
+        assert!(Robots::parse("User-agent: *\nDisallow:\n", "polly").is_allowed("/"));
+        let html = "<html><body>Not Found</body></html>";
+        assert!(Robots::parse(html, "polly").is_allowed("/bus"));
+    }
+}
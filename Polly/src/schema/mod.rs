@@ -0,0 +1,135 @@
+//! JSON Schema Module
+//!
+//! This module derives JSON Schemas of the output files from the Rust models
+//! that write them, ships them via the `schema` command, and validates freshly
+//! written files against them, so a run that produced malformed output fails
+//! instead of reporting success.
+
+mod validate;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use schemars::{Schema, schema_for};
+use serde_json::Value;
+use tracing::info;
+
+use crate::route::model::{RouteFeatureCollection, RouteMapFile};
+use crate::schedule::model::ScheduleFile;
+use crate::utils::{ensure_dir, write_atomic};
+
+/// Validation errors listed per file before the rest are summarized
+const MAX_ERRORS: usize = 10;
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct SchemaArgs {
+    /// Directory to write the schemas to
+    #[arg(short, long, default_value = "./schemas")]
+    output_dir: PathBuf,
+}
+
+// ============================================================================
+// Output Formats
+// ============================================================================
+
+/// An output file format with a schema
+#[derive(Debug, Clone, Copy)]
+pub enum OutputFormat {
+    /// `derived_routes/*.geojson` and `all_routes.geojson`
+    DerivedRoute,
+    /// `routeMap.json`
+    RouteMap,
+    /// `schedules/{route}.json`, and each route in `schedules.json`
+    Schedule,
+}
+
+impl OutputFormat {
+    const ALL: [OutputFormat; 3] = [Self::DerivedRoute, Self::RouteMap, Self::Schedule];
+
+    pub fn schema(self) -> Schema {
+        match self {
+            Self::DerivedRoute => schema_for!(RouteFeatureCollection),
+            Self::RouteMap => schema_for!(RouteMapFile<'static>),
+            Self::Schedule => schema_for!(ScheduleFile),
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::DerivedRoute => "derived-route.schema.json",
+            Self::RouteMap => "route-map.schema.json",
+            Self::Schedule => "schedule.schema.json",
+        }
+    }
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: SchemaArgs) -> Result<()> {
+    ensure_dir(&args.output_dir)?;
+
+    for format in OutputFormat::ALL {
+        let path = args.output_dir.join(format.file_name());
+        write_atomic(&path, serde_json::to_string_pretty(&format.schema())?)?;
+        info!("✓ Wrote {:?}", path);
+    }
+
+    Ok(())
+}
+
+/// Validates JSON documents against the schema of `format`. `label` names
+/// each document in the error (usually its path).
+pub fn validate_values<'a>(
+    format: OutputFormat,
+    docs: impl IntoIterator<Item = (String, &'a Value)>,
+) -> Result<()> {
+    let schema = format.schema();
+    let schema = schema.as_value();
+
+    let mut failures = Vec::new();
+    for (label, doc) in docs {
+        let errors = validate::validate(schema, doc);
+        if errors.is_empty() {
+            continue;
+        }
+        let mut message = format!("{}:", label);
+        for error in errors.iter().take(MAX_ERRORS) {
+            message.push_str(&format!("\n    {}", error));
+        }
+        if errors.len() > MAX_ERRORS {
+            message.push_str(&format!("\n    ... {} more", errors.len() - MAX_ERRORS));
+        }
+        failures.push(message);
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} file(s) do not match the {:?} schema:\n  {}",
+            failures.len(),
+            format,
+            failures.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Validates output files against the schema of `format`.
+pub fn validate_files(format: OutputFormat, paths: &[PathBuf]) -> Result<()> {
+    let docs = paths
+        .iter()
+        .map(|path| read_json(path).map(|doc| (format!("{:?}", path), doc)))
+        .collect::<Result<Vec<_>>>()?;
+    validate_values(format, docs.iter().map(|(label, doc)| (label.clone(), doc)))
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path).with_context(|| format!("Reading {:?}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("Parsing {:?}", path))
+}
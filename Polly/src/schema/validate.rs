@@ -0,0 +1,202 @@
+//! Minimal JSON Schema validator.
+//!
+//! Covers the keywords `schemars` emits for the output models (types,
+//! properties, required fields, arrays, enums, `oneOf`/`anyOf`/`allOf`, and
+//! local `$ref`s). Annotation keywords such as `format` and `default` are
+//! ignored.
+
+use serde_json::Value;
+
+/// Checks `doc` against `schema`, returning one message per violation,
+/// each prefixed with the JSON pointer of the offending value.
+pub fn validate(schema: &Value, doc: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, schema, doc, "", &mut errors);
+    errors
+}
+
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            errors.push(format!("{}: not allowed", display(path)));
+            return;
+        }
+        Value::Object(map) => map,
+        _ => return,
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+        {
+            Some(target) => check(root, target, value, path, errors),
+            None => errors.push(format!("{}: unresolved $ref {}", display(path), reference)),
+        }
+    }
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.iter().any(|t| has_type(value, t)) {
+            errors.push(format!(
+                "{}: expected {}, found {}",
+                display(path),
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(expected) = schema.get("const")
+        && value != expected
+    {
+        errors.push(format!("{}: expected {}", display(path), expected));
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(value)
+    {
+        errors.push(format!(
+            "{}: {} is not one of {:?}",
+            display(path),
+            value,
+            options
+        ));
+    }
+
+    if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+        && value.as_f64().is_some_and(|v| v < min)
+    {
+        errors.push(format!("{}: {} is below {}", display(path), value, min));
+    }
+    if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+        && value.as_f64().is_some_and(|v| v > max)
+    {
+        errors.push(format!("{}: {} is above {}", display(path), value, max));
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for key in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(key) {
+                errors.push(format!(
+                    "{}: missing required field `{}`",
+                    display(path),
+                    key
+                ));
+            }
+        }
+        for (key, item) in object {
+            let item_path = format!("{}/{}", path, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(item_schema) => check(root, item_schema, item, &item_path, errors),
+                None => {
+                    if let Some(extra) = schema.get("additionalProperties") {
+                        check(root, extra, item, &item_path, errors);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Value::Array(items) = value {
+        let prefix = schema.get("prefixItems").and_then(Value::as_array);
+        for (i, item) in items.iter().enumerate() {
+            let item_schema = prefix
+                .and_then(|p| p.get(i))
+                .or_else(|| schema.get("items"));
+            if let Some(item_schema) = item_schema {
+                check(root, item_schema, item, &format!("{}/{}", path, i), errors);
+            }
+        }
+        let len = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+            && len < min
+        {
+            errors.push(format!(
+                "{}: {} items, expected at least {}",
+                display(path),
+                len,
+                min
+            ));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+            && len > max
+        {
+            errors.push(format!(
+                "{}: {} items, expected at most {}",
+                display(path),
+                len,
+                max
+            ));
+        }
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all {
+            check(root, sub, value, path, errors);
+        }
+    }
+    for (keyword, exactly_one) in [("anyOf", false), ("oneOf", true)] {
+        if let Some(options) = schema.get(keyword).and_then(Value::as_array) {
+            let matches = options
+                .iter()
+                .filter(|sub| {
+                    let mut sub_errors = Vec::new();
+                    check(root, sub, value, path, &mut sub_errors);
+                    sub_errors.is_empty()
+                })
+                .count();
+            if matches == 0 || (exactly_one && matches > 1) {
+                errors.push(format!(
+                    "{}: {} matches {} of the {} options",
+                    display(path),
+                    value,
+                    matches,
+                    keyword
+                ));
+            }
+        }
+    }
+}
+
+fn has_type(value: &Value, type_: &str) -> bool {
+    match type_ {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|v| v.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn display(path: &str) -> &str {
+    if path.is_empty() { "/" } else { path }
+}
@@ -0,0 +1,331 @@
+//! Layered Settings
+//!
+//! Endpoints, keys, and tuning knobs are resolved once at startup from, in
+//! increasing priority: the defaults in `config`, a `polly.toml` file, the
+//! environment (including `.env`), and command-line flags. Commands read them
+//! from [`Settings`] instead of looking up environment variables themselves.
+//!
+//! The file is `./polly.toml` if present, or the path given with `--config`
+//! or `POLLY_CONFIG`. Environment variables keep their established names
+//! (e.g., `OSRM_API_URL`); see [`ENV_KEYS`] and [`ENV_NUMBER_KEYS`].
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use figment::Figment;
+use figment::providers::{Format, Serialized, Toml};
+use figment::value::Value;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::config::{
+    CONCURRENCY_FETCH, CONCURRENCY_SNAP, KAKAO_DIRECTIONS_URL, OSRM_CACHE_TTL_DAYS,
+    OSRM_CHUNK_SIZE, OSRM_FOOT_TABLE_URL, OSRM_URL, SANITIZE_SNAP_M, SCHEDULE_DELAY_MS,
+    TAGO_ARRIVAL_URL, TAGO_LOCATION_URL, TAGO_REQUESTS_PER_SEC, TAGO_URL, VALHALLA_URL,
+};
+use crate::utils::i18n::Lang;
+
+/// Config file read from the working directory when no path is given
+pub const DEFAULT_CONFIG_FILE: &str = "polly.toml";
+
+/// Environment variables and the text settings they set
+pub const ENV_KEYS: &[(&str, &str)] = &[
+    ("DATA_GO_KR_SERVICE_KEY", "service_key"),
+    ("KAKAO_REST_API_KEY", "kakao_api_key"),
+    ("ALERT_WEBHOOK_URL", "alert_webhook_url"),
+    ("POLLY_LANG", "lang"),
+    ("SLACK_WEBHOOK_URL", "notify.slack_webhook"),
+    ("DISCORD_WEBHOOK_URL", "notify.discord_webhook"),
+    ("TAGO_API_URL", "urls.tago"),
+    ("TAGO_ARRIVAL_API_URL", "urls.tago_arrival"),
+    ("TAGO_LOCATION_API_URL", "urls.tago_location"),
+    ("OSRM_API_URL", "urls.osrm"),
+    ("OSRM_FOOT_API_URL", "urls.osrm_foot_table"),
+    ("VALHALLA_API_URL", "urls.valhalla"),
+    ("KAKAO_DIRECTIONS_URL", "urls.kakao_directions"),
+];
+
+/// Environment variables and the numeric settings they set
+pub const ENV_NUMBER_KEYS: &[(&str, &str)] = &[
+    ("FETCH_CONCURRENCY", "route.fetch_concurrency"),
+    ("SNAP_CONCURRENCY", "route.snap_concurrency"),
+    ("OSRM_CHUNK_SIZE", "route.osrm_chunk_size"),
+    ("SCHEDULE_DELAY_MS", "schedule.delay_ms"),
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Decoded TAGO service key
+    pub service_key: String,
+    /// Kakao Developers REST API key (`--snapper kakao`)
+    pub kakao_api_key: String,
+    /// Webhook alerted when the schedule preflight check fails
+    pub alert_webhook_url: Option<String>,
+    /// Language of progress messages and run summaries (default: the locale)
+    pub lang: Option<Lang>,
+    pub urls: Urls,
+    pub http: HttpSettings,
+    pub route: RouteSettings,
+    pub schedule: ScheduleSettings,
+    pub notify: NotifySettings,
+    pub agency: AgencySettings,
+}
+
+/// Upstream API endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Urls {
+    pub tago: String,
+    pub tago_arrival: String,
+    pub tago_location: String,
+    /// OSRM `/route/v1/{profile}` endpoint; `/match` is derived from it
+    pub osrm: String,
+    /// OSRM `/table/v1/foot` endpoint for walking transfers
+    pub osrm_foot_table: String,
+    pub valhalla: String,
+    pub kakao_directions: String,
+}
+
+/// Outbound connections of every HTTP client
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSettings {
+    /// Proxy for all requests (`http://`, `https://`, or `socks5://` URL),
+    /// replacing `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY`; `NO_PROXY` still applies
+    pub proxy: Option<String>,
+    /// PEM file of extra trusted certificates, e.g., of a TLS-intercepting
+    /// proxy or an intermediate a server fails to send
+    pub ca_cert: Option<PathBuf>,
+    pub tls: TlsBackend,
+}
+
+/// TLS implementation of the HTTP clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsBackend {
+    /// rustls, verifying with the system certificate store
+    #[default]
+    Rustls,
+    /// The platform TLS library (OpenSSL on Linux); needs the `native-tls` feature
+    Native,
+}
+
+/// Defaults of the `route` command flags of the same names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteSettings {
+    pub city_code: String,
+    pub output_dir: PathBuf,
+    pub fetch_concurrency: usize,
+    pub snap_concurrency: usize,
+    pub osrm_chunk_size: usize,
+    pub tago_rps: f64,
+    pub osrm_cache_days: u64,
+    pub snap_threshold_m: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleSettings {
+    /// Pause between detail page requests, in milliseconds
+    pub delay_ms: u64,
+}
+
+/// Chat notifications: run summaries and selector-health alerts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifySettings {
+    /// Slack incoming webhook URL
+    pub slack_webhook: Option<String>,
+    /// Discord webhook URL
+    pub discord_webhook: Option<String>,
+    /// Which `route` and `schedule` runs post a summary
+    pub summary: SummaryPolicy,
+}
+
+/// Runs that post a summary to the notification webhooks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryPolicy {
+    /// Every run
+    #[default]
+    Always,
+    /// Failed runs and runs with failed items
+    Failure,
+    /// None; alerts are still sent
+    Never,
+}
+
+/// Operator and publisher of the data, written to GTFS `agency.txt` and
+/// `feed_info.txt` and embedded in the JSON outputs as `provenance`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgencySettings {
+    pub name: String,
+    pub url: String,
+    /// IANA time zone of the timetables
+    pub timezone: String,
+    /// Language of stop and route names (ISO 639-1)
+    pub lang: String,
+    /// Publisher of the feed (default: the agency)
+    pub publisher: Option<String>,
+    pub publisher_url: Option<String>,
+    /// License of the published data (e.g., "KOGL Type 1")
+    pub license: Option<String>,
+}
+
+/// Origin of an output file
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    pub agency: String,
+    pub agency_url: String,
+    pub publisher: String,
+    pub publisher_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub license: Option<String>,
+    /// Name and version of the program that wrote the file
+    pub generator: String,
+}
+
+impl AgencySettings {
+    pub fn provenance(&self) -> Provenance {
+        Provenance {
+            agency: self.name.clone(),
+            agency_url: self.url.clone(),
+            publisher: self.publisher.clone().unwrap_or_else(|| self.name.clone()),
+            publisher_url: self
+                .publisher_url
+                .clone()
+                .unwrap_or_else(|| self.url.clone()),
+            license: self.license.clone(),
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl Default for Urls {
+    fn default() -> Self {
+        Self {
+            tago: TAGO_URL.to_string(),
+            tago_arrival: TAGO_ARRIVAL_URL.to_string(),
+            tago_location: TAGO_LOCATION_URL.to_string(),
+            osrm: OSRM_URL.to_string(),
+            osrm_foot_table: OSRM_FOOT_TABLE_URL.to_string(),
+            valhalla: VALHALLA_URL.to_string(),
+            kakao_directions: KAKAO_DIRECTIONS_URL.to_string(),
+        }
+    }
+}
+
+impl Default for RouteSettings {
+    fn default() -> Self {
+        Self {
+            city_code: "32020".to_string(),
+            output_dir: PathBuf::from("./storage/processed_routes"),
+            fetch_concurrency: CONCURRENCY_FETCH,
+            snap_concurrency: CONCURRENCY_SNAP,
+            osrm_chunk_size: OSRM_CHUNK_SIZE,
+            tago_rps: TAGO_REQUESTS_PER_SEC,
+            osrm_cache_days: OSRM_CACHE_TTL_DAYS,
+            snap_threshold_m: SANITIZE_SNAP_M,
+        }
+    }
+}
+
+impl Default for AgencySettings {
+    fn default() -> Self {
+        Self {
+            name: "원주시".to_string(),
+            url: "https://www.wonju.go.kr".to_string(),
+            timezone: "Asia/Seoul".to_string(),
+            lang: "ko".to_string(),
+            publisher: None,
+            publisher_url: None,
+            license: None,
+        }
+    }
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        Self {
+            delay_ms: SCHEDULE_DELAY_MS,
+        }
+    }
+}
+
+impl Settings {
+    /// Resolves the settings from the defaults, the config file, and the
+    /// environment. `path` is the config file given on the command line;
+    /// without one, `POLLY_CONFIG` or `./polly.toml` is read if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os("POLLY_CONFIG").map(PathBuf::from));
+        let file = match &path {
+            Some(path) if !path.is_file() => {
+                anyhow::bail!("Config file {:?} not found", path);
+            }
+            Some(path) => Some(path.clone()),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|p| p.is_file()),
+        };
+
+        let mut figment = Figment::from(Serialized::defaults(Settings::default()));
+        if let Some(file) = &file {
+            figment = figment.merge(Toml::file(file));
+        }
+        // Text settings are taken verbatim, so an all-digit API key stays a
+        // string. Empty variables (e.g., `KAKAO_REST_API_KEY=` in .env) mean
+        // "unset".
+        let env_var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        for (name, setting) in ENV_KEYS {
+            if let Some(value) = env_var(name) {
+                figment = figment.merge(Serialized::default(setting, value));
+            }
+        }
+        for (name, setting) in ENV_NUMBER_KEYS {
+            if let Some(value) = env_var(name) {
+                let Ok(value) = value.parse::<Value>();
+                figment = figment.merge(Serialized::default(setting, value));
+            }
+        }
+
+        let settings: Self = figment.extract().with_context(|| match &file {
+            Some(file) => format!("Invalid settings (config file {:?})", file),
+            None => "Invalid settings".to_string(),
+        })?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Checks the ranges the matching command-line flags enforce.
+    fn validate(&self) -> Result<()> {
+        let route = &self.route;
+        if route.fetch_concurrency == 0 || route.snap_concurrency == 0 {
+            anyhow::bail!("route.fetch_concurrency and route.snap_concurrency must be at least 1");
+        }
+        if route.osrm_chunk_size < 2 {
+            anyhow::bail!("route.osrm_chunk_size must be at least 2");
+        }
+        if route.tago_rps < 0.0 || route.snap_threshold_m < 0.0 {
+            anyhow::bail!("route.tago_rps and route.snap_threshold_m must not be negative");
+        }
+        let agency = &self.agency;
+        if agency.name.trim().is_empty() || agency.timezone.trim().is_empty() {
+            anyhow::bail!("agency.name and agency.timezone must not be empty");
+        }
+        for url in [Some(&agency.url), agency.publisher_url.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                anyhow::bail!("agency URL {:?} must be an http(s) URL", url);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+//! TAGO API Client
+//!
+//! Typed requests for the TAGO list endpoints and a client that sends them
+//! with paging, error-envelope detection, and conversion into the response
+//! models. The client is cheap to clone and shares its connection pool.
+
+// Arrival and bus location requests are provided for the realtime subsystems.
+#![allow(dead_code)]
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::config::TAGO_PAGE_SIZE;
+use crate::error::PipelineError;
+use crate::settings::Urls;
+use crate::tago::error::check_envelope;
+use crate::tago::model::{Arrival, BusLocation, RouteInfo, RouteInfoItem, RouteStop};
+use crate::utils::http::{HttpClient, Validators};
+
+// ============================================================================
+// Requests
+// ============================================================================
+
+/// TAGO services; each has its own base URL.
+#[derive(Debug, Clone, Copy)]
+pub enum Service {
+    RouteInfo,
+    Arrival,
+    Location,
+}
+
+/// A typed request to a TAGO list endpoint.
+pub trait TagoRequest {
+    type Item: DeserializeOwned;
+
+    const SERVICE: Service;
+    const OPERATION: &'static str;
+
+    /// Query parameters, excluding paging, the service key, and `_type`.
+    fn params(&self) -> Vec<(&'static str, String)>;
+}
+
+/// All routes of a city, optionally filtered by route number.
+pub struct RouteListRequest {
+    city_code: String,
+    route_no: Option<String>,
+}
+
+impl RouteListRequest {
+    pub fn new(city_code: &str) -> Self {
+        Self {
+            city_code: city_code.to_string(),
+            route_no: None,
+        }
+    }
+
+    /// Restricts the list to route numbers containing `route_no`.
+    pub fn route_no(mut self, route_no: &str) -> Self {
+        self.route_no = Some(route_no.to_string());
+        self
+    }
+}
+
+impl TagoRequest for RouteListRequest {
+    type Item = RouteInfo;
+
+    const SERVICE: Service = Service::RouteInfo;
+    const OPERATION: &'static str = "getRouteNoList";
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("cityCode", self.city_code.clone())];
+        if let Some(route_no) = &self.route_no {
+            params.push(("routeNo", route_no.clone()));
+        }
+        params
+    }
+}
+
+/// Service information of a route (type, first/last bus, headways).
+pub struct RouteInfoRequest {
+    city_code: String,
+    route_id: String,
+}
+
+impl RouteInfoRequest {
+    pub fn new(city_code: &str, route_id: &str) -> Self {
+        Self {
+            city_code: city_code.to_string(),
+            route_id: route_id.to_string(),
+        }
+    }
+}
+
+impl TagoRequest for RouteInfoRequest {
+    type Item = RouteInfoItem;
+
+    const SERVICE: Service = Service::RouteInfo;
+    const OPERATION: &'static str = "getRouteInfoIem";
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("cityCode", self.city_code.clone()),
+            ("routeId", self.route_id.clone()),
+        ]
+    }
+}
+
+/// Stops of a route, in order.
+pub struct RouteStopsRequest {
+    city_code: String,
+    route_id: String,
+}
+
+impl RouteStopsRequest {
+    pub fn new(city_code: &str, route_id: &str) -> Self {
+        Self {
+            city_code: city_code.to_string(),
+            route_id: route_id.to_string(),
+        }
+    }
+}
+
+impl TagoRequest for RouteStopsRequest {
+    type Item = RouteStop;
+
+    const SERVICE: Service = Service::RouteInfo;
+    const OPERATION: &'static str = "getRouteAcctoThrghSttnList";
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("cityCode", self.city_code.clone()),
+            ("routeId", self.route_id.clone()),
+        ]
+    }
+}
+
+/// Arrival predictions at a stop.
+pub struct ArrivalsRequest {
+    city_code: String,
+    node_id: String,
+}
+
+impl ArrivalsRequest {
+    pub fn new(city_code: &str, node_id: &str) -> Self {
+        Self {
+            city_code: city_code.to_string(),
+            node_id: node_id.to_string(),
+        }
+    }
+}
+
+impl TagoRequest for ArrivalsRequest {
+    type Item = Arrival;
+
+    const SERVICE: Service = Service::Arrival;
+    const OPERATION: &'static str = "getSttnAcctoArvlPrearngeInfoList";
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("cityCode", self.city_code.clone()),
+            ("nodeId", self.node_id.clone()),
+        ]
+    }
+}
+
+/// Current positions of the buses on a route.
+pub struct BusLocationsRequest {
+    city_code: String,
+    route_id: String,
+}
+
+impl BusLocationsRequest {
+    pub fn new(city_code: &str, route_id: &str) -> Self {
+        Self {
+            city_code: city_code.to_string(),
+            route_id: route_id.to_string(),
+        }
+    }
+}
+
+impl TagoRequest for BusLocationsRequest {
+    type Item = BusLocation;
+
+    const SERVICE: Service = Service::Location;
+    const OPERATION: &'static str = "getRouteAcctoBusLcList";
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("cityCode", self.city_code.clone()),
+            ("routeId", self.route_id.clone()),
+        ]
+    }
+}
+
+// ============================================================================
+// Client
+// ============================================================================
+
+#[derive(Clone)]
+pub struct TagoClient {
+    http: HttpClient,
+    service_key: String,
+    route_info_url: String,
+    arrival_url: String,
+    location_url: String,
+}
+
+impl TagoClient {
+    /// Creates a client for the TAGO base URLs in `urls`.
+    ///
+    /// Requests to each TAGO host are limited to `requests_per_sec` across all
+    /// clones of the client, to stay within the per-key quota.
+    pub fn new(
+        http: HttpClient,
+        urls: &Urls,
+        service_key: &str,
+        requests_per_sec: f64,
+    ) -> Result<Self> {
+        let route_info_url = urls.tago.clone();
+        let arrival_url = urls.tago_arrival.clone();
+        let location_url = urls.tago_location.clone();
+
+        // Limits are per host, so services on the same host share one limiter.
+        let mut http = http;
+        for url in [&route_info_url, &arrival_url, &location_url] {
+            http = http.with_rate_limit(url, requests_per_sec)?;
+        }
+
+        Ok(Self {
+            http,
+            service_key: service_key.to_string(),
+            route_info_url,
+            arrival_url,
+            location_url,
+        })
+    }
+
+    fn base_url(&self, service: Service) -> &str {
+        match service {
+            Service::RouteInfo => &self.route_info_url,
+            Service::Arrival => &self.arrival_url,
+            Service::Location => &self.location_url,
+        }
+    }
+
+    /// Fetches every page of a list endpoint, following `totalCount`.
+    pub async fn fetch_all<R: TagoRequest>(&self, request: &R) -> Result<Vec<R::Item>> {
+        let fetched = self.fetch_all_if_modified(request, None).await?;
+        Ok(fetched.map(|(items, _)| items).unwrap_or_default())
+    }
+
+    /// Like [`fetch_all`](Self::fetch_all), but conditional on the
+    /// `validators` of the response saved last time: `None` if TAGO answered
+    /// `304 Not Modified`. A list that fits in one page comes with the
+    /// validators of its response, to be stored once it was saved.
+    pub async fn fetch_all_if_modified<R: TagoRequest>(
+        &self,
+        request: &R,
+        validators: Option<&Validators>,
+    ) -> Result<Option<(Vec<R::Item>, Option<Validators>)>> {
+        let url = format!("{}/{}", self.base_url(R::SERVICE), R::OPERATION);
+        let params = request.params();
+        let page_size = TAGO_PAGE_SIZE.to_string();
+
+        let mut items = Vec::new();
+        let mut new_validators = None;
+        for page_no in 1.. {
+            let page = page_no.to_string();
+            let mut req = self.http.get(&url).query(&params).query(&[
+                ("numOfRows", page_size.as_str()),
+                ("pageNo", page.as_str()),
+                ("serviceKey", self.service_key.as_str()),
+                ("_type", "json"),
+            ]);
+            // Only the first page is conditional; a list spanning several
+            // pages is never treated as unchanged.
+            if page_no == 1
+                && let Some(v) = validators
+            {
+                req = v.apply(req);
+            }
+
+            let response = self.http.send(req).await?;
+            if response.is_not_modified() {
+                return Ok(None);
+            }
+            new_validators = if page_no == 1 {
+                Validators::of(&response)
+            } else {
+                None
+            };
+            let body = response.body;
+            check_envelope(&body)?;
+
+            let json: Value = serde_json::from_str(&body).map_err(|e| {
+                PipelineError::schema("TAGO", format!("invalid {} response: {}", R::OPERATION, e))
+            })?;
+
+            let page_items = page_items(&json);
+            let fetched = page_items.len();
+            for item in page_items {
+                items.push(serde_json::from_value(item).map_err(|e| {
+                    PipelineError::schema("TAGO", format!("invalid {} item: {}", R::OPERATION, e))
+                })?);
+            }
+
+            // Without a `totalCount`, a short page marks the last one.
+            let body = &json["response"]["body"];
+            let total = body["totalCount"]
+                .as_u64()
+                .or_else(|| body["totalCount"].as_str()?.parse().ok());
+            let done = match total {
+                Some(total) => items.len() as u64 >= total,
+                None => fetched < TAGO_PAGE_SIZE,
+            };
+            if done || fetched == 0 {
+                break;
+            }
+        }
+
+        Ok(Some((items, new_validators)))
+    }
+}
+
+/// Items of one page. TAGO returns a single object instead of an array when
+/// there is exactly one item, and an empty string when there are none.
+fn page_items(json: &Value) -> Vec<Value> {
+    match &json["response"]["body"]["items"]["item"] {
+        Value::Array(arr) => arr.clone(),
+        obj @ Value::Object(_) => vec![obj.clone()],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,126 @@
+//! TAGO Error Envelopes
+//!
+//! TAGO reports most failures with HTTP 200 and an error body instead of a
+//! status code: either a regular response whose `header.resultCode` is not
+//! "00", or (for gateway errors such as an invalid service key) an
+//! `OpenAPI_ServiceResponse` XML document, even when JSON was requested.
+//! This module detects both and turns them into actionable errors.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::error::ExitStatus;
+
+/// An error reported by the TAGO API in its response body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagoError {
+    /// The service key was rejected (not registered, expired, disabled, or wrong IP)
+    Auth { code: String, message: String },
+    /// The daily request quota of the service key is used up
+    QuotaExceeded { code: String, message: String },
+    /// Too many requests per second (transient)
+    RateLimited { code: String, message: String },
+    /// Any other error result
+    Service { code: String, message: String },
+}
+
+impl TagoError {
+    fn from_code(code: &str, message: &str) -> Self {
+        let (code, message) = (code.to_string(), message.to_string());
+        match code.as_str() {
+            "20" | "21" | "30" | "31" | "32" | "33" => Self::Auth { code, message },
+            "22" => Self::QuotaExceeded { code, message },
+            "23" => Self::RateLimited { code, message },
+            _ => Self::Service { code, message },
+        }
+    }
+
+    /// Distinct exit status for errors that need a different operator response.
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        match self {
+            Self::Auth { .. } => Some(ExitStatus::TagoAuth),
+            Self::QuotaExceeded { .. } => Some(ExitStatus::TagoQuota),
+            _ => None,
+        }
+    }
+
+    /// Errors that fail every request alike, so the run should stop at once.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Auth { .. } | Self::QuotaExceeded { .. })
+    }
+}
+
+impl fmt::Display for TagoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auth { code, message } => write!(
+                f,
+                "TAGO rejected the service key ({} {}). Check DATA_GO_KR_SERVICE_KEY: it must be \
+                 the decoded key, and the bus route API must be approved for it on data.go.kr \
+                 (new approvals can take an hour to become active).",
+                code, message
+            ),
+            Self::QuotaExceeded { code, message } => write!(
+                f,
+                "TAGO daily request quota exceeded ({} {}). Wait for the quota to reset at \
+                 midnight (KST) or request a higher limit on data.go.kr.",
+                code, message
+            ),
+            Self::RateLimited { code, message } => write!(
+                f,
+                "TAGO per-second request limit exceeded ({} {}). Lower the request concurrency.",
+                code, message
+            ),
+            Self::Service { code, message } => {
+                write!(f, "TAGO returned an error ({} {})", code, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TagoError {}
+
+/// Checks a TAGO response body for an error envelope.
+/// "No data" results are not errors; they simply yield no items.
+pub fn check_envelope(body: &str) -> Result<(), TagoError> {
+    let (code, message) = if body.trim_start().starts_with('<') {
+        // Gateway errors carry a reason code, service errors a result code.
+        match xml_tag(body, "returnReasonCode").or_else(|| xml_tag(body, "resultCode")) {
+            Some(code) => (
+                code,
+                xml_tag(body, "returnAuthMsg")
+                    .or_else(|| xml_tag(body, "resultMsg"))
+                    .or_else(|| xml_tag(body, "errMsg"))
+                    .unwrap_or_default(),
+            ),
+            None => return Ok(()),
+        }
+    } else {
+        let Ok(json) = serde_json::from_str::<Value>(body) else {
+            return Ok(());
+        };
+        let header = &json["response"]["header"];
+        match header["resultCode"].as_str() {
+            Some(code) => (
+                code.to_string(),
+                header["resultMsg"].as_str().unwrap_or_default().to_string(),
+            ),
+            None => return Ok(()),
+        }
+    };
+
+    match code.as_str() {
+        "00" | "0" | "03" => Ok(()),
+        _ => Err(TagoError::from_code(&code, &message)),
+    }
+}
+
+/// Text content of the first `<tag>...</tag>` element, if any.
+fn xml_tag(body: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = body.find(&open)? + open.len();
+    let end = start + body[start..].find(&close)?;
+    Some(body[start..end].trim().to_string())
+}
@@ -0,0 +1,8 @@
+//! TAGO API Module
+//!
+//! Typed client for the TAGO (data.go.kr) bus information API, shared by
+//! every subsystem that talks to it.
+
+pub mod client;
+pub mod error;
+pub mod model;
@@ -0,0 +1,168 @@
+//! TAGO Response Models
+//!
+//! Typed items of the TAGO list endpoints. Field names follow the API
+//! (all lowercase), and numeric fields accept both numbers and numeric
+//! strings, since TAGO returns either depending on the value.
+
+// The models mirror the full API responses; not every subsystem reads every field.
+#![allow(dead_code)]
+
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+// ============================================================================
+// Route Information Service
+// ============================================================================
+
+/// Item of `getRouteNoList`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RouteInfo {
+    #[serde(rename = "routeid")]
+    pub route_id: String,
+    #[serde(rename = "routeno", deserialize_with = "flex_string")]
+    pub route_no: String,
+    #[serde(rename = "routetp")]
+    pub route_type: String,
+    #[serde(rename = "startnodenm")]
+    pub start_node_name: String,
+    #[serde(rename = "endnodenm")]
+    pub end_node_name: String,
+    /// First departure (HHMM)
+    #[serde(rename = "startvehicletime", deserialize_with = "flex_string")]
+    pub start_vehicle_time: String,
+    /// Last departure (HHMM)
+    #[serde(rename = "endvehicletime", deserialize_with = "flex_string")]
+    pub end_vehicle_time: String,
+}
+
+/// Item of `getRouteInfoIem`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RouteInfoItem {
+    #[serde(rename = "routeid")]
+    pub route_id: String,
+    #[serde(rename = "routeno", deserialize_with = "flex_string")]
+    pub route_no: String,
+    /// Route type (e.g., 일반버스, 좌석버스)
+    #[serde(rename = "routetp")]
+    pub route_type: String,
+    /// First departure (HHMM)
+    #[serde(rename = "startvehicletime", deserialize_with = "flex_string")]
+    pub start_vehicle_time: String,
+    /// Last departure (HHMM)
+    #[serde(rename = "endvehicletime", deserialize_with = "flex_string")]
+    pub end_vehicle_time: String,
+    /// Headway in minutes on weekdays, Saturdays, and Sundays (0 if unknown)
+    #[serde(rename = "intervaltime", deserialize_with = "flex_i64")]
+    pub interval: i64,
+    #[serde(rename = "intervalsattime", deserialize_with = "flex_i64")]
+    pub interval_sat: i64,
+    #[serde(rename = "intervalsuntime", deserialize_with = "flex_i64")]
+    pub interval_sun: i64,
+}
+
+/// Item of `getRouteAcctoThrghSttnList`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RouteStop {
+    #[serde(rename = "routeid")]
+    pub route_id: String,
+    #[serde(rename = "nodeid")]
+    pub node_id: String,
+    #[serde(rename = "nodenm")]
+    pub node_name: String,
+    #[serde(rename = "nodeord", deserialize_with = "flex_i64")]
+    pub node_ord: i64,
+    #[serde(rename = "nodeno", deserialize_with = "flex_string")]
+    pub node_no: String,
+    #[serde(rename = "gpslati", deserialize_with = "flex_f64")]
+    pub gps_lat: f64,
+    #[serde(rename = "gpslong", deserialize_with = "flex_f64")]
+    pub gps_long: f64,
+    /// 0: up (outbound), 1: down (inbound)
+    #[serde(rename = "updowncd", deserialize_with = "flex_i64")]
+    pub up_down_cd: i64,
+}
+
+// ============================================================================
+// Arrival Information Service
+// ============================================================================
+
+/// Item of `getSttnAcctoArvlPrearngeInfoList`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Arrival {
+    #[serde(rename = "nodeid")]
+    pub node_id: String,
+    #[serde(rename = "nodenm")]
+    pub node_name: String,
+    #[serde(rename = "routeid")]
+    pub route_id: String,
+    #[serde(rename = "routeno", deserialize_with = "flex_string")]
+    pub route_no: String,
+    #[serde(rename = "routetp")]
+    pub route_type: String,
+    /// Number of stops the bus is away
+    #[serde(rename = "arrprevstationcnt", deserialize_with = "flex_i64")]
+    pub stops_away: i64,
+    /// Estimated seconds until arrival
+    #[serde(rename = "arrtime", deserialize_with = "flex_i64")]
+    pub arrival_secs: i64,
+    #[serde(rename = "vehicletp")]
+    pub vehicle_type: String,
+}
+
+// ============================================================================
+// Bus Location Service
+// ============================================================================
+
+/// Item of `getRouteAcctoBusLcList`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BusLocation {
+    #[serde(rename = "vehicleno")]
+    pub vehicle_no: String,
+    #[serde(rename = "routenm", deserialize_with = "flex_string")]
+    pub route_no: String,
+    #[serde(rename = "routetp")]
+    pub route_type: String,
+    #[serde(rename = "nodeid")]
+    pub node_id: String,
+    #[serde(rename = "nodenm")]
+    pub node_name: String,
+    #[serde(rename = "nodeord", deserialize_with = "flex_i64")]
+    pub node_ord: i64,
+    #[serde(rename = "gpslati", deserialize_with = "flex_f64")]
+    pub gps_lat: f64,
+    #[serde(rename = "gpslong", deserialize_with = "flex_f64")]
+    pub gps_long: f64,
+}
+
+// ============================================================================
+// Flexible Field Deserializers
+// ============================================================================
+
+fn flex_string<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::String(s) => s,
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    })
+}
+
+fn flex_i64<'de, D: Deserializer<'de>>(d: D) -> Result<i64, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::Number(n) => n.as_i64().unwrap_or_default(),
+        Value::String(s) => s.trim().parse().unwrap_or_default(),
+        _ => 0,
+    })
+}
+
+fn flex_f64<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::Number(n) => n.as_f64().unwrap_or_default(),
+        Value::String(s) => s.trim().parse().unwrap_or_default(),
+        _ => 0.0,
+    })
+}
@@ -0,0 +1,82 @@
+//! TypeScript Definitions Module
+//!
+//! This module generates `.d.ts` declarations of the output files (derived
+//! route GeoJSON, routeMap.json, and merged schedules) from the Rust models
+//! that write them, so the web frontend can type-check against the current
+//! format instead of a hand-maintained copy.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tracing::info;
+use ts_rs::TS;
+
+use crate::route::model::{
+    FrontendMeta, FrontendStop, RouteDetail, RouteFeature, RouteFeatureCollection, RouteGeometry,
+    RouteIndices, RouteMapFile, RouteProperties, SequenceStop, SnapMetrics, SnapQuality, Snapper,
+    Station, StopCorrection,
+};
+use crate::route::variants::{RouteVariant, VariantKind};
+use crate::schedule::model::{Departure, FeaturedStops, ScheduleFile};
+use crate::settings::Provenance;
+use crate::utils::write_atomic;
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct TypesArgs {
+    /// Output file for the declarations
+    #[arg(short, long, default_value = "polly.d.ts")]
+    output: PathBuf,
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: TypesArgs) -> Result<()> {
+    let declarations = [
+        // Derived route GeoJSON (derived_routes/*.geojson)
+        RouteFeatureCollection::decl(),
+        RouteFeature::decl(),
+        RouteGeometry::decl(),
+        RouteProperties::decl(),
+        FrontendStop::decl(),
+        StopCorrection::decl(),
+        RouteIndices::decl(),
+        FrontendMeta::decl(),
+        SnapMetrics::decl(),
+        SnapQuality::decl(),
+        Snapper::decl(),
+        // routeMap.json
+        RouteMapFile::decl(),
+        RouteDetail::decl(),
+        SequenceStop::decl(),
+        Station::decl(),
+        RouteVariant::decl(),
+        VariantKind::decl(),
+        // Merged schedules ({route}.json)
+        ScheduleFile::decl(),
+        FeaturedStops::decl(),
+        Departure::decl(),
+        // All of the above
+        Provenance::decl(),
+        serde_json::Value::decl(),
+    ];
+
+    let mut content = String::from(
+        "// Generated by `polly types`; do not edit.\n// Output file formats of Polly.\n",
+    );
+    for decl in declarations {
+        content.push_str("\nexport ");
+        content.push_str(&decl);
+        content.push('\n');
+    }
+
+    write_atomic(&args.output, &content)?;
+    info!("✓ Wrote TypeScript definitions to {:?}", args.output);
+
+    Ok(())
+}
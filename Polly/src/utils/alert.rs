@@ -0,0 +1,170 @@
+//! Webhook alerts and run summaries.
+//!
+//! Posts short messages to chat webhooks: alerts when an unattended run fails
+//! in a way that needs a human (e.g., the schedule preflight check), and,
+//! with `[notify]` configured, a summary at the end of each `route` and
+//! `schedule` run. Slack and generic webhooks get a `{"text": ...}` payload,
+//! Discord webhooks `{"content": ...}`.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::DateTime;
+use reqwest::Client;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::settings::{HttpSettings, NotifySettings, SummaryPolicy};
+use crate::utils::i18n::Msg;
+use crate::utils::status::{self, State};
+use crate::utils::{cassette, http};
+
+/// Longest message Discord accepts, in characters
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// Payload format of a webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `{"text": ...}`, understood by Slack and most chat tools
+    Slack,
+    /// `{"content": ...}`
+    Discord,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    pub format: Format,
+}
+
+impl Webhook {
+    fn new(url: Option<&str>, format: Format) -> Option<Self> {
+        url.filter(|u| !u.is_empty()).map(|url| Self {
+            url: url.to_string(),
+            format,
+        })
+    }
+}
+
+/// The webhooks alerts go to: `alert_webhook` (Slack-compatible) and the
+/// Slack and Discord webhooks of `[notify]`, each URL once.
+pub fn alert_webhooks(alert_webhook: Option<&str>, notify: &NotifySettings) -> Vec<Webhook> {
+    let mut webhooks: Vec<Webhook> = Vec::new();
+    for webhook in [
+        Webhook::new(alert_webhook, Format::Slack),
+        Webhook::new(notify.slack_webhook.as_deref(), Format::Slack),
+        Webhook::new(notify.discord_webhook.as_deref(), Format::Discord),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if !webhooks.iter().any(|w| w.url == webhook.url) {
+            webhooks.push(webhook);
+        }
+    }
+    webhooks
+}
+
+/// Sends `message` to `webhook`.
+pub async fn send_webhook(client: &Client, webhook: &Webhook, message: &str) -> Result<()> {
+    let payload = match webhook.format {
+        Format::Slack => json!({ "text": message }),
+        Format::Discord => {
+            json!({ "content": message.chars().take(DISCORD_MAX_CHARS).collect::<String>() })
+        }
+    };
+    client
+        .post(&webhook.url)
+        .timeout(Duration::from_secs(10))
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Sends `message` to every webhook. Delivery failures are only reported, so
+/// an unreachable webhook never masks the original error.
+pub async fn notify(client: &Client, webhooks: &[Webhook], message: &str) {
+    if webhooks.is_empty() {
+        return;
+    }
+    // A replayed run only re-enacts the original one.
+    if cassette::is_replaying() {
+        info!("Replaying a cassette; not sending the webhook alert");
+        return;
+    }
+
+    for webhook in webhooks {
+        match send_webhook(client, webhook, message).await {
+            Ok(()) => info!("✓ Message sent to {:?} webhook", webhook.format),
+            Err(e) => warn!("Failed to send {:?} webhook message: {}", webhook.format, e),
+        }
+    }
+}
+
+/// Posts a summary of the finished run (see [`status`]) to the `[notify]`
+/// webhooks, as far as its `summary` policy asks for one. Commands that do
+/// not track their status post nothing.
+pub async fn send_summary(notify_settings: &NotifySettings, http_settings: &HttpSettings) {
+    let Some(run) = status::current() else {
+        return;
+    };
+    let wanted = match notify_settings.summary {
+        SummaryPolicy::Always => true,
+        SummaryPolicy::Failure => run.state == State::Failed || run.errors > 0,
+        SummaryPolicy::Never => false,
+    };
+    let webhooks = alert_webhooks(None, notify_settings);
+    if !wanted || webhooks.is_empty() {
+        return;
+    }
+
+    let client = match build_client(http_settings) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to build the webhook client: {:#}", e);
+            return;
+        }
+    };
+    notify(&client, &webhooks, &summary_message(&run)).await;
+}
+
+fn build_client(settings: &HttpSettings) -> Result<Client> {
+    Ok(http::configure(Client::builder(), settings)?.build()?)
+}
+
+/// E.g., "✅ Polly route ok in 12m 03s · snap 240/240 · 2 failed item(s)",
+/// followed by the last error, if any.
+fn summary_message(run: &status::RunStatus) -> String {
+    let (icon, ok) = match (run.state, run.errors) {
+        (State::Failed, _) => ("❌", false),
+        (_, 0) => ("✅", true),
+        _ => ("⚠️", true),
+    };
+    let outcome = Msg::SummaryOutcome {
+        command: &run.command,
+        ok,
+    };
+    let mut message = format!("{} {}", icon, outcome);
+
+    let started = DateTime::parse_from_rfc3339(&run.started_at);
+    let finished = run.finished_at.as_deref().map(DateTime::parse_from_rfc3339);
+    if let (Ok(started), Some(Ok(finished))) = (started, finished) {
+        let secs = (finished - started).num_seconds().max(0);
+        message.push_str(&format!(" {}", Msg::SummaryDuration(secs)));
+    }
+    if let Some(progress) = &run.progress {
+        message.push_str(&format!(
+            " · {} {}/{}",
+            progress.phase, progress.done, progress.total
+        ));
+    }
+    if run.errors > 0 {
+        message.push_str(&format!(" · {}", Msg::SummaryFailedItems(run.errors)));
+    }
+    if let Some(error) = &run.last_error {
+        message.push_str(&format!("\n{}", Msg::SummaryLastError(error)));
+    }
+    message
+}
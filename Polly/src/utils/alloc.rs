@@ -0,0 +1,90 @@
+//! Allocation counting.
+//!
+//! The global allocator wraps the system allocator and counts allocations,
+//! bytes allocated, and the peak of live heap bytes, so `bench` can report
+//! the allocation cost of the geometry pipeline. Counting is a few relaxed
+//! atomic operations per allocation.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static LIVE: AtomicU64 = AtomicU64::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+/// System allocator that keeps allocation statistics
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn grow(&self, bytes: usize) {
+        let bytes = bytes as u64;
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(bytes, Ordering::Relaxed);
+        let live = LIVE.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        PEAK.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn shrink(&self, bytes: usize) {
+        LIVE.fetch_sub(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            self.grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            self.grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        self.shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            // Counted as freeing the old block and allocating the new one.
+            self.shrink(layout.size());
+            self.grow(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Allocation counters since the start of the process
+#[derive(Debug, Clone, Copy)]
+pub struct AllocStats {
+    pub allocations: u64,
+    /// Bytes allocated in total (freed ones included)
+    pub bytes: u64,
+    /// Most heap bytes live at once since the last [`reset_peak`]
+    pub peak: u64,
+    /// Heap bytes live now
+    pub live: u64,
+}
+
+pub fn stats() -> AllocStats {
+    AllocStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        bytes: ALLOCATED.load(Ordering::Relaxed),
+        peak: PEAK.load(Ordering::Relaxed),
+        live: LIVE.load(Ordering::Relaxed),
+    }
+}
+
+/// Restarts peak tracking from the current live heap size.
+pub fn reset_peak() {
+    PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+}
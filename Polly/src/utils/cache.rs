@@ -0,0 +1,55 @@
+//! On-disk response cache.
+//!
+//! A content-addressed file cache: each entry is stored under the SHA-256 of
+//! its key (e.g., a request URL) and expires after a TTL based on its mtime.
+//! Used to avoid re-issuing identical routing requests across re-runs.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+
+use crate::utils::{cassette, ensure_dir, short_hash, write_atomic};
+
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Result<Self> {
+        ensure_dir(&dir)?;
+        Ok(Self { dir, ttl })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", short_hash(key.as_bytes(), 64)))
+    }
+
+    /// Returns the cached value for `key`, unless missing or expired.
+    /// Always misses while an HTTP cassette is recorded or replayed.
+    pub fn get(&self, key: &str) -> Option<String> {
+        if cassette::is_active() {
+            return None;
+        }
+        let path = self.path(key);
+        let age = SystemTime::now()
+            .duration_since(fs::metadata(&path).ok()?.modified().ok()?)
+            .unwrap_or_default();
+        if age > self.ttl {
+            return None;
+        }
+        fs::read_to_string(path).ok()
+    }
+
+    /// Stores `value` for `key`. Failures are ignored; the cache is only an optimization.
+    pub fn put(&self, key: &str, value: &str) {
+        if cassette::is_active() {
+            return;
+        }
+        // Written atomically so concurrent readers never see a partial entry.
+        write_atomic(&self.path(key), value).ok();
+    }
+}
@@ -0,0 +1,275 @@
+//! HTTP cassettes.
+//!
+//! With `--record <FILE>`, every HTTP exchange (TAGO, OSRM, Kakao, and the
+//! ITS schedule pages) is saved to a JSON cassette at the end of the run.
+//! With `--replay <FILE>`, requests are answered from the cassette instead of
+//! the network, so a whole pipeline run can be repeated offline against the
+//! exact upstream responses of a bug report or an integration test.
+//!
+//! Requests are matched by method, URL, and body. Repeated requests get the
+//! recorded responses in order, the last one once they run out. Service keys
+//! are redacted from recorded URLs, so cassettes can be shared.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{Client, Request, RequestBuilder, StatusCode, header};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use url::Url;
+
+use crate::utils::metrics;
+
+/// Query parameters whose values are not written to cassettes.
+const REDACTED_PARAMS: &[&str] = &["serviceKey"];
+
+static CASSETTE: OnceLock<Cassette> = OnceLock::new();
+
+enum Cassette {
+    Record {
+        path: PathBuf,
+        interactions: Mutex<Vec<Interaction>>,
+    },
+    Replay {
+        path: PathBuf,
+        /// Recorded responses per request, with the index of the next one
+        responses: Mutex<HashMap<RequestKey, (Vec<Response>, usize)>>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    request: RequestKey,
+    response: Response,
+}
+
+/// What a request is matched by.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+struct RequestKey {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+/// An HTTP response, as far as Polly looks at it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Response {
+    #[serde(with = "status_code")]
+    pub status: StatusCode,
+    /// `Retry-After` header, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    /// `ETag` and `Last-Modified` headers, for conditional requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after.map(Duration::from_secs)
+    }
+
+    /// Whether a conditional request was answered `304 Not Modified`.
+    pub fn is_not_modified(&self) -> bool {
+        self.status == StatusCode::NOT_MODIFIED
+    }
+}
+
+/// Records every exchange of this run, to be saved to `path` by [`save`].
+pub fn record(path: &Path) {
+    let cassette = Cassette::Record {
+        path: path.to_path_buf(),
+        interactions: Mutex::default(),
+    };
+    CASSETTE.set(cassette).ok();
+}
+
+/// Answers the requests of this run from the cassette at `path`.
+pub fn replay(path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("Reading cassette {:?}", path))?;
+    let file: CassetteFile =
+        serde_json::from_str(&text).with_context(|| format!("Invalid cassette {:?}", path))?;
+
+    let mut responses: HashMap<RequestKey, (Vec<Response>, usize)> = HashMap::new();
+    for interaction in file.interactions {
+        responses
+            .entry(interaction.request)
+            .or_default()
+            .0
+            .push(interaction.response);
+    }
+    info!(
+        count = responses.len(),
+        "Replaying {} recorded request(s) from {:?}",
+        responses.len(),
+        path
+    );
+    let cassette = Cassette::Replay {
+        path: path.to_path_buf(),
+        responses: Mutex::new(responses),
+    };
+    CASSETTE.set(cassette).ok();
+    Ok(())
+}
+
+/// Whether requests are being recorded or replayed. Local caches of
+/// responses are bypassed then, so that the cassette sees every request.
+pub fn is_active() -> bool {
+    CASSETTE.get().is_some()
+}
+
+pub fn is_replaying() -> bool {
+    matches!(CASSETTE.get(), Some(Cassette::Replay { .. }))
+}
+
+/// Sends the request, or answers it from the cassette when replaying, and
+/// reads the whole response. Errors from the network are `reqwest::Error`s.
+pub async fn send(request: RequestBuilder) -> Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let key = RequestKey {
+        method: request.method().to_string(),
+        url: redact(request.url().clone()),
+        body: request
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| String::from_utf8_lossy(b).into_owned()),
+    };
+
+    if let Some(Cassette::Replay { path, responses }) = CASSETTE.get() {
+        let mut responses = responses.lock().unwrap();
+        let (recorded, next) = responses.get_mut(&key).ok_or_else(|| {
+            anyhow!(
+                "No recorded response for {} {} in cassette {:?}",
+                key.method,
+                key.url,
+                path
+            )
+        })?;
+        let response = recorded[(*next).min(recorded.len() - 1)].clone();
+        *next += 1;
+        debug!(method = key.method, url = key.url, "Replayed {}", key.url);
+        return Ok(response);
+    }
+
+    let url = request.url().clone();
+    let started = Instant::now();
+    let result = execute(&client, request).await;
+    let status = match &result {
+        Ok(response) => response.status.as_str().to_string(),
+        Err(_) => "error".to_string(),
+    };
+    metrics::observe_request(&url, &status, started.elapsed());
+    let response = result?;
+
+    if let Some(Cassette::Record { interactions, .. }) = CASSETTE.get() {
+        interactions.lock().unwrap().push(Interaction {
+            request: key,
+            response: response.clone(),
+        });
+    }
+    Ok(response)
+}
+
+/// Sends the request over the network and reads the whole response.
+async fn execute(client: &Client, request: Request) -> Result<Response> {
+    let resp = client.execute(request).await?;
+    let status = resp.status();
+    let header_value = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let retry_after = header_value(header::RETRY_AFTER).and_then(|v| v.parse::<u64>().ok());
+    let etag = header_value(header::ETAG);
+    let last_modified = header_value(header::LAST_MODIFIED);
+    Ok(Response {
+        status,
+        retry_after,
+        etag,
+        last_modified,
+        body: resp.text().await?,
+    })
+}
+
+/// Writes the recorded exchanges to the cassette file, if recording.
+/// They are grouped by request, so recordings of the same crawl diff cleanly.
+pub fn save() -> Result<()> {
+    let Some(Cassette::Record { path, interactions }) = CASSETTE.get() else {
+        return Ok(());
+    };
+
+    let mut grouped: BTreeMap<RequestKey, Vec<Response>> = BTreeMap::new();
+    for interaction in interactions.lock().unwrap().drain(..) {
+        grouped
+            .entry(interaction.request)
+            .or_default()
+            .push(interaction.response);
+    }
+    let interactions: Vec<Interaction> = grouped
+        .into_iter()
+        .flat_map(|(request, responses)| {
+            responses.into_iter().map(move |response| Interaction {
+                request: request.clone(),
+                response,
+            })
+        })
+        .collect();
+
+    let count = interactions.len();
+    // Written even in a dry run: the cassette is what the user asked for.
+    let json = serde_json::to_string_pretty(&CassetteFile { interactions })?;
+    fs::write(path, json).with_context(|| format!("Writing cassette {:?}", path))?;
+    info!(count, "Recorded {} HTTP exchange(s) to {:?}", count, path);
+    Ok(())
+}
+
+/// Replaces the values of [`REDACTED_PARAMS`] in the query of `url`.
+fn redact(mut url: Url) -> String {
+    if url
+        .query_pairs()
+        .any(|(k, _)| REDACTED_PARAMS.contains(&k.as_ref()))
+    {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let v = if REDACTED_PARAMS.contains(&k.as_ref()) {
+                    "REDACTED".into()
+                } else {
+                    v.into_owned()
+                };
+                (k.into_owned(), v)
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+/// Status codes are stored as plain numbers.
+mod status_code {
+    use reqwest::StatusCode;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(status: &StatusCode, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u16(status.as_u16())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<StatusCode, D::Error> {
+        StatusCode::from_u16(u16::deserialize(d)?).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,81 @@
+//! Output pre-compression.
+//!
+//! Writes `.gz` / `.br` siblings of output files so they can be served by a
+//! static host with the matching `Content-Encoding` header.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use flate2::{Compression as GzLevel, write::GzEncoder};
+
+use crate::utils::AtomicFile;
+
+/// Supported pre-compression formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    /// gzip (`.gz`)
+    Gz,
+    /// Brotli (`.br`)
+    Br,
+}
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Gz => "gz",
+            Self::Br => "br",
+        }
+    }
+
+    /// Compresses everything read from `input` into `output`.
+    fn encode(self, input: &mut impl Read, output: &mut impl Write) -> Result<()> {
+        match self {
+            Self::Gz => {
+                let mut encoder = GzEncoder::new(output, GzLevel::best());
+                io::copy(input, &mut encoder)?;
+                encoder.finish()?;
+            }
+            Self::Br => {
+                // Quality 11 and a 4 MiB window: slow, but outputs are written once.
+                let mut writer = brotli::CompressorWriter::new(output, 4096, 11, 22);
+                io::copy(input, &mut writer)?;
+                // Finishes the stream
+                writer.into_inner();
+            }
+        }
+        Ok(())
+    }
+
+    /// Path of the compressed sibling of `path` (e.g., `routeMap.json.gz`)
+    fn sibling(self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+}
+
+/// Writes a compressed sibling of `path` (e.g., `routeMap.json.gz`) for each format.
+/// The plain file itself is left untouched.
+pub fn write_compressed(path: &Path, data: &[u8], formats: &[Compression]) -> Result<()> {
+    for format in formats {
+        let mut file = AtomicFile::create(&format.sibling(path))?;
+        format.encode(&mut &data[..], &mut file)?;
+        file.commit()?;
+    }
+    Ok(())
+}
+
+/// Like [`write_compressed`], but reads the data back from the plain file at
+/// `path`, for outputs streamed to disk rather than held in memory.
+pub fn compress_file(path: &Path, formats: &[Compression]) -> Result<()> {
+    for format in formats {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut file = AtomicFile::create(&format.sibling(path))?;
+        format.encode(&mut input, &mut file)?;
+        file.commit()?;
+    }
+    Ok(())
+}
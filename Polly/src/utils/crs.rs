@@ -0,0 +1,219 @@
+//! Output coordinate reference systems.
+//!
+//! Outputs are in WGS84 by default. Korean government GIS systems expect the
+//! national grid, EPSG:5179 (Korea 2000 / Unified CS): a Transverse Mercator
+//! projection on GRS80 centered at 127.5°E, 38°N, in meters. The projection
+//! uses the series expansions from Snyder, "Map Projections: A Working Manual"
+//! (USGS PP 1395), accurate to well below a millimeter across Korea.
+
+use serde_json::{Value, json};
+
+// GRS80 ellipsoid
+const A: f64 = 6_378_137.0;
+const F: f64 = 1.0 / 298.257_222_101;
+
+// EPSG:5179 projection parameters
+const LAT0: f64 = 38.0;
+const LON0: f64 = 127.5;
+const K0: f64 = 0.9996;
+const FALSE_EASTING: f64 = 1_000_000.0;
+const FALSE_NORTHING: f64 = 2_000_000.0;
+
+/// Coordinate reference system of the output geometry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Crs {
+    /// WGS84 longitude/latitude (EPSG:4326)
+    #[default]
+    Wgs84,
+    /// Korea 2000 / Unified CS easting/northing in meters (EPSG:5179)
+    Epsg5179,
+}
+
+impl Crs {
+    pub fn epsg(self) -> i32 {
+        match self {
+            Self::Wgs84 => 4326,
+            Self::Epsg5179 => 5179,
+        }
+    }
+
+    /// The GeoJSON `crs` member naming this CRS, or `None` for WGS84, the
+    /// GeoJSON default (RFC 7946 dropped the member; GDAL and QGIS still
+    /// read it).
+    pub fn geojson_member(self) -> Option<Value> {
+        match self {
+            Self::Wgs84 => None,
+            Self::Epsg5179 => Some(json!({
+                "type": "name",
+                "properties": { "name": format!("urn:ogc:def:crs:EPSG::{}", self.epsg()) },
+            })),
+        }
+    }
+
+    /// Parses a GeoJSON `crs` member; a missing one means WGS84.
+    pub fn from_geojson_member(member: Option<&Value>) -> Option<Self> {
+        let Some(member) = member else {
+            return Some(Self::Wgs84);
+        };
+        let name = member["properties"]["name"].as_str()?;
+        match name.rsplit(':').next()? {
+            "4326" | "CRS84" => Some(Self::Wgs84),
+            "5179" => Some(Self::Epsg5179),
+            _ => None,
+        }
+    }
+
+    /// Projects a WGS84 `[lon, lat]` into this CRS, rounded to 1 cm.
+    pub fn project(self, point: &[f64]) -> Vec<f64> {
+        match self {
+            Self::Wgs84 => point.to_vec(),
+            Self::Epsg5179 => {
+                let (x, y) = tm_forward(point[0], point[1]);
+                vec![(x * 100.0).round() / 100.0, (y * 100.0).round() / 100.0]
+            }
+        }
+    }
+
+    /// Converts a point of this CRS back to WGS84 `[lon, lat]`, rounded to
+    /// 6 decimal places like the rest of the output.
+    pub fn unproject(self, point: &[f64]) -> Vec<f64> {
+        match self {
+            Self::Wgs84 => point.to_vec(),
+            Self::Epsg5179 => {
+                let (lon, lat) = tm_inverse(point[0], point[1]);
+                vec![
+                    (lon * 1_000_000.0).round() / 1_000_000.0,
+                    (lat * 1_000_000.0).round() / 1_000_000.0,
+                ]
+            }
+        }
+    }
+}
+
+fn e2() -> f64 {
+    2.0 * F - F * F
+}
+
+/// Meridian arc length from the equator to latitude `phi` (radians)
+fn meridian_arc(phi: f64) -> f64 {
+    let e2 = e2();
+    let (e4, e6) = (e2 * e2, e2 * e2 * e2);
+    A * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+        - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+        + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+        - (35.0 * e6 / 3072.0) * (6.0 * phi).sin())
+}
+
+fn tm_forward(lon: f64, lat: f64) -> (f64, f64) {
+    let e2 = e2();
+    let ep2 = e2 / (1.0 - e2);
+    let phi = lat.to_radians();
+
+    let n = A / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+    let t = phi.tan().powi(2);
+    let c = ep2 * phi.cos().powi(2);
+    let a = (lon - LON0).to_radians() * phi.cos();
+
+    let x = K0
+        * n
+        * (a + (1.0 - t + c) * a.powi(3) / 6.0
+            + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0);
+    let y = K0
+        * (meridian_arc(phi) - meridian_arc(LAT0.to_radians())
+            + n * phi.tan()
+                * (a * a / 2.0
+                    + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                    + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+
+    (x + FALSE_EASTING, y + FALSE_NORTHING)
+}
+
+fn tm_inverse(x: f64, y: f64) -> (f64, f64) {
+    let e2 = e2();
+    let ep2 = e2 / (1.0 - e2);
+    let (e4, e6) = (e2 * e2, e2 * e2 * e2);
+    let x = x - FALSE_EASTING;
+
+    // Footpoint latitude
+    let m = meridian_arc(LAT0.to_radians()) + (y - FALSE_NORTHING) / K0;
+    let mu = m / (A * (1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0));
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+    let phi1 = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1 * e1 / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+    let sin2 = phi1.sin().powi(2);
+    let c1 = ep2 * phi1.cos().powi(2);
+    let t1 = phi1.tan().powi(2);
+    let n1 = A / (1.0 - e2 * sin2).sqrt();
+    let r1 = A * (1.0 - e2) / (1.0 - e2 * sin2).powf(1.5);
+    let d = x / (n1 * K0);
+
+    let phi = phi1
+        - (n1 * phi1.tan() / r1)
+            * (d * d / 2.0
+                - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * ep2) * d.powi(4) / 24.0
+                + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1 - 252.0 * ep2 - 3.0 * c1 * c1)
+                    * d.powi(6)
+                    / 720.0);
+    let lambda = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+        + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * ep2 + 24.0 * t1 * t1) * d.powi(5)
+            / 120.0)
+        / phi1.cos();
+
+    (LON0 + lambda.to_degrees(), phi.to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// WGS84 `[lon, lat]` and EPSG:5179 `[x, y]` pairs, the latter computed
+    /// independently with the Krüger series PROJ uses
+    const REFERENCE: [([f64; 2], [f64; 2]); 5] = [
+        ([127.5, 38.0], [1_000_000.0, 2_000_000.0]),
+        // Seoul City Hall
+        ([126.978, 37.5665], [953_901.165, 1_952_032.081]),
+        // Wonju, Busan and Jeju, up to 180 km off the central meridian
+        ([127.92, 37.342], [1_037_202.000, 1_927_080.328]),
+        ([129.0756, 35.1796], [1_143_467.380, 1_688_281.982]),
+        ([126.5312, 33.4996], [910_010.546, 1_501_279.789]),
+    ];
+
+    #[test]
+    fn projects_to_korea_2000_within_centimeters() {
+        for ([lon, lat], [x, y]) in REFERENCE {
+            let (px, py) = tm_forward(lon, lat);
+            assert!((px - x).abs() < 0.03, "x of {lon},{lat}: {px} vs {x}");
+            assert!((py - y).abs() < 0.03, "y of {lon},{lat}: {py} vs {y}");
+            assert_eq!(Crs::Epsg5179.project(&[lon, lat]).len(), 2);
+        }
+    }
+
+    #[test]
+    fn unprojects_back_to_wgs84() {
+        for ([lon, lat], [x, y]) in REFERENCE {
+            assert_eq!(Crs::Epsg5179.unproject(&[x, y]), [lon, lat]);
+            let projected = Crs::Epsg5179.project(&[lon, lat]);
+            assert_eq!(Crs::Epsg5179.unproject(&projected), [lon, lat]);
+        }
+        assert_eq!(Crs::Wgs84.project(&[127.9, 37.3]), [127.9, 37.3]);
+    }
+
+    #[test]
+    fn geojson_members_round_trip() {
+        for crs in [Crs::Wgs84, Crs::Epsg5179] {
+            assert_eq!(
+                Crs::from_geojson_member(crs.geojson_member().as_ref()),
+                Some(crs)
+            );
+        }
+        let crs84 =
+            json!({ "type": "name", "properties": { "name": "urn:ogc:def:crs:OGC:1.3:CRS84" } });
+        assert_eq!(Crs::from_geojson_member(Some(&crs84)), Some(Crs::Wgs84));
+        let other = json!({ "type": "name", "properties": { "name": "EPSG:3857" } });
+        assert_eq!(Crs::from_geojson_member(Some(&other)), None);
+    }
+}
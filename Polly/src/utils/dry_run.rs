@@ -0,0 +1,125 @@
+//! Dry Runs
+//!
+//! With `--dry-run`, the output helpers in `utils` ([`write_atomic`],
+//! [`AtomicFile`], [`ensure_dir`], and [`remove_file`]) leave the disk
+//! untouched. Each file they would have written is compared with the one on
+//! disk and reported as new, changed, or unchanged instead.
+//!
+//! [`write_atomic`]: super::write_atomic
+//! [`AtomicFile`]: super::AtomicFile
+//! [`ensure_dir`]: super::ensure_dir
+//! [`remove_file`]: super::remove_file
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Whether the files are left unreported (output going to stdout instead)
+static QUIET: AtomicBool = AtomicBool::new(false);
+static SUMMARY: Mutex<Summary> = Mutex::new(Summary {
+    new: 0,
+    changed: 0,
+    unchanged: 0,
+    removed: 0,
+    bytes: 0,
+});
+
+/// Files the run would have written or removed
+struct Summary {
+    new: usize,
+    changed: usize,
+    unchanged: usize,
+    removed: usize,
+    /// Total size of the new and changed files
+    bytes: u64,
+}
+
+/// Turns dry-run mode on for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Turns dry-run mode on without reporting the files, for runs that print
+/// their output to stdout instead of writing it.
+pub fn enable_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+    enable();
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Reports a file that would be written: `bytes` long, with SHA-256 `digest`.
+pub(super) fn record_write(path: &Path, bytes: u64, digest: &[u8]) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut summary = SUMMARY.lock().unwrap();
+    match fs::read(path) {
+        Ok(old) if Sha256::digest(&old).as_slice() == digest => {
+            summary.unchanged += 1;
+            debug!(path = %path.display(), bytes, change = "unchanged", "Would keep {} (unchanged)", path.display());
+        }
+        Ok(old) => {
+            summary.changed += 1;
+            summary.bytes += bytes;
+            info!(
+                path = %path.display(),
+                bytes,
+                change = "changed",
+                "Would write {} ({} -> {} KB)",
+                path.display(),
+                (old.len() as u64).div_ceil(1024),
+                bytes.div_ceil(1024)
+            );
+        }
+        Err(_) => {
+            summary.new += 1;
+            summary.bytes += bytes;
+            info!(
+                path = %path.display(),
+                bytes,
+                change = "new",
+                "Would write {} (new, {} KB)",
+                path.display(),
+                bytes.div_ceil(1024)
+            );
+        }
+    }
+}
+
+/// Reports a file that would be removed.
+pub(super) fn record_removal(path: &Path) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    SUMMARY.lock().unwrap().removed += 1;
+    info!(path = %path.display(), change = "removed", "Would remove {}", path.display());
+}
+
+/// Logs the totals of the run.
+pub fn log_summary() {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    let summary = SUMMARY.lock().unwrap();
+    info!(
+        new = summary.new,
+        changed = summary.changed,
+        unchanged = summary.unchanged,
+        removed = summary.removed,
+        bytes = summary.bytes,
+        "Dry run: {} new, {} changed, {} unchanged, {} removed file(s); {} KB to write. Nothing was written.",
+        summary.new,
+        summary.changed,
+        summary.unchanged,
+        summary.removed,
+        summary.bytes.div_ceil(1024)
+    );
+}
@@ -0,0 +1,183 @@
+//! Event stream for automation.
+//!
+//! With `--events ndjson` (or `--events-file <PATH>`), `route` and `schedule`
+//! write one JSON object per line as things happen, so wrapper scripts can
+//! react to them without parsing the console output:
+//!
+//! ```text
+//! {"time":"...","event":"route_fetched","id":"WJB251000123","outcome":"ok","elapsedMs":412}
+//! {"time":"...","event":"route_failed","id":"WJB251000124","stage":"snap","reason":"...","kind":"snapFailed"}
+//! {"time":"...","event":"file_written","path":"storage/routeMap.json","bytes":18231}
+//! {"time":"...","event":"warning","message":"...","routeId":"WJB251000124"}
+//! ```
+//!
+//! Events go to stdout (the console log is on stderr) or are appended to a
+//! file. Updates of `status.json` are not reported, and service keys in
+//! messages are redacted as in `status.json`.
+
+use std::fmt::{self as std_fmt, Write as _};
+use std::fs::OpenOptions;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context as LayerContext;
+
+use crate::utils::now;
+use crate::utils::status::{self, STATUS_FILE};
+
+static SINK: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Format of the event stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum EventFormat {
+    /// One JSON object per line
+    Ndjson,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    #[serde(rename_all = "camelCase")]
+    RouteFetched {
+        id: &'a str,
+        outcome: &'a str,
+        elapsed_ms: u64,
+    },
+    RouteFailed {
+        id: &'a str,
+        stage: &'a str,
+        reason: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kind: Option<&'a str>,
+    },
+    FileWritten {
+        path: &'a Path,
+        bytes: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Warning {
+        message: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        route_id: Option<&'a str>,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: String,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+/// Starts the event stream, to `file` (appended) or else stdout.
+pub fn init(file: Option<&Path>) -> Result<()> {
+    let sink: Box<dyn Write + Send> = match file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Opening event file {:?}", path))?;
+            Box::new(LineWriter::new(file))
+        }
+        None => Box::new(LineWriter::new(io::stdout())),
+    };
+    *SINK.lock().unwrap() = Some(sink);
+    Ok(())
+}
+
+fn emit(event: Event<'_>) {
+    let mut sink = SINK.lock().unwrap();
+    let Some(sink) = sink.as_mut() else {
+        return;
+    };
+    let line = Line {
+        time: now().to_rfc3339(),
+        event,
+    };
+    if let Ok(json) = serde_json::to_string(&line) {
+        // A closed pipe must not fail the run.
+        let _ = writeln!(sink, "{}", json);
+    }
+}
+
+/// An item was fetched (and, for `schedule`, parsed); `outcome` as in the
+/// log events (`ok`, `reused`, `unchanged`).
+pub fn route_fetched(id: &str, outcome: &str, elapsed_ms: u64) {
+    emit(Event::RouteFetched {
+        id,
+        outcome,
+        elapsed_ms,
+    });
+}
+
+/// An item was recorded in `failures.json`.
+pub fn route_failed(id: &str, stage: &str, reason: &str, kind: Option<&str>) {
+    emit(Event::RouteFailed {
+        id,
+        stage,
+        reason: &status::redact(reason),
+        kind,
+    });
+}
+
+/// A file was written (not in a dry run).
+pub fn file_written(path: &Path, bytes: u64) {
+    if path.file_name().is_some_and(|name| name == STATUS_FILE) {
+        return;
+    }
+    emit(Event::FileWritten { path, bytes });
+}
+
+/// Layer of the log subscriber that reports warnings as events.
+pub fn layer<S: Subscriber>() -> impl Layer<S> {
+    Warnings
+}
+
+struct Warnings;
+
+impl<S: Subscriber> Layer<S> for Warnings {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: LayerContext<'_, S>) {
+        if *event.metadata().level() != Level::WARN || SINK.lock().unwrap().is_none() {
+            return;
+        }
+        let mut fields = WarningFields::default();
+        event.record(&mut fields);
+        emit(Event::Warning {
+            message: &status::redact(&fields.message),
+            route_id: fields.route_id.as_deref(),
+        });
+    }
+}
+
+#[derive(Default)]
+struct WarningFields {
+    message: String,
+    route_id: Option<String>,
+}
+
+impl Visit for WarningFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            "route_id" => self.route_id = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std_fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            "route_id" => self.route_id = Some(format!("{:?}", value)),
+            _ => {}
+        }
+    }
+}
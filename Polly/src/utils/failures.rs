@@ -0,0 +1,140 @@
+//! Failure manifests.
+//!
+//! Each run records the items it failed to process (route IDs, with the stage
+//! and reason) in a `failures.json` next to its output, so that a later run
+//! with `--retry-failed` can re-process only those instead of everything.
+//! Failures also go into the crawl state database (see [`state`]), which
+//! `--retry-failed` prefers, as it also remembers items that failed in an
+//! earlier run and were not attempted since.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ExitError, ExitStatus, PipelineError};
+use crate::utils::{events, metrics, now, state, status, write_atomic};
+
+/// File name of the failure manifest within an output directory.
+pub const FAILURES_FILE: &str = "failures.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailureManifest {
+    pub generated_at: String,
+    pub failures: Vec<Failure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    /// Item that failed (e.g., a route ID)
+    pub id: String,
+    /// Pipeline stage that failed (e.g., "fetch", "parse", "snap")
+    pub stage: String,
+    pub reason: String,
+    /// Error class (see [`PipelineError::kind`]), when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+impl FailureManifest {
+    /// Loads the manifest from `dir`, failing if none was written there.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(FAILURES_FILE);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("No failure manifest at {:?}, nothing to retry", path))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid failure manifest {:?}", path))
+    }
+
+    /// The items to retry: those whose last attempt failed, per the crawl
+    /// state database, or the manifest in `dir` if the database has no
+    /// record yet (e.g., it was written by an older version).
+    pub fn previous(dir: &Path) -> Result<Self> {
+        match state::failed_items() {
+            Some(manifest) => Ok(manifest),
+            None => Self::load(dir),
+        }
+    }
+
+    /// Writes the manifest to `dir`, replacing the previous one.
+    pub fn save(&mut self, dir: &Path) -> Result<()> {
+        self.generated_at = now().to_rfc3339();
+        write_atomic(
+            &dir.join(FAILURES_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Also counted in the `polly_failures_total` metric and reported as the
+    /// last error of the run status.
+    pub fn record(&mut self, id: &str, stage: &str, reason: impl Into<String>) {
+        self.push(id, stage, reason.into(), None);
+    }
+
+    /// Records `err`, along with its error class if it is a [`PipelineError`].
+    pub fn record_error(&mut self, id: &str, stage: &str, err: &anyhow::Error) {
+        let kind = PipelineError::find(err).map(|e| e.kind().to_string());
+        self.push(id, stage, err.to_string(), kind);
+    }
+
+    fn push(&mut self, id: &str, stage: &str, reason: String, kind: Option<String>) {
+        metrics::failure(stage);
+        status::error(format!("{} ({}): {}", id, stage, reason));
+        events::route_failed(id, stage, &reason, kind.as_deref());
+        state::record_failure(id, stage, &reason, kind.as_deref());
+        self.failures.push(Failure {
+            id: id.to_string(),
+            stage: stage.to_string(),
+            reason,
+            kind,
+        });
+    }
+
+    /// IDs of all failed items.
+    pub fn ids(&self) -> BTreeSet<String> {
+        self.failures.iter().map(|f| f.id.clone()).collect()
+    }
+
+    /// Fails the run if all of its `total` items failed, or, with
+    /// `--fail-on-partial`, more than `max_percent` of them.
+    pub fn check_threshold(&self, total: usize, max_percent: Option<u8>) -> Result<()> {
+        let failed = self.ids().len();
+        if total == 0 || failed == 0 {
+            return Ok(());
+        }
+        if failed >= total {
+            return Err(ExitError::new(
+                ExitStatus::TotalFailure,
+                format!("All {} item(s) failed; see {}", total, FAILURES_FILE),
+            )
+            .into());
+        }
+        let percent = failed as f64 * 100.0 / total as f64;
+        if let Some(max) = max_percent
+            && percent > f64::from(max)
+        {
+            return Err(ExitError::new(
+                ExitStatus::PartialFailure,
+                format!(
+                    "{} of {} item(s) failed ({:.1}%), more than --fail-on-partial {}% allows; see {}",
+                    failed, total, percent, max, FAILURES_FILE
+                ),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// IDs of the items that failed in `stage`.
+    pub fn ids_in_stage(&self, stage: &str) -> BTreeSet<String> {
+        self.failures
+            .iter()
+            .filter(|f| f.stage == stage)
+            .map(|f| f.id.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,195 @@
+//! FlatGeobuf writer.
+//!
+//! Writes Point and LineString layers with a packed Hilbert R-tree,
+//! so GIS tools (QGIS, GDAL, the `flatgeobuf` JS reader) can stream just the
+//! features in a bbox instead of loading a whole GeoJSON file.
+//!
+//! Layout: magic bytes, the size-prefixed header table, the index, then each
+//! feature as a size-prefixed table in index order. The tables are built by
+//! hand with `flatbuffers`, following the upstream `header.fbs` and
+//! `feature.fbs` schemas (field ids noted at each slot).
+
+use std::path::Path;
+
+use anyhow::Result;
+use flatbuffers::FlatBufferBuilder;
+
+use crate::utils::crs::Crs;
+use crate::utils::packed_rtree::{self, Bbox, NODE_SIZE};
+use crate::utils::write_atomic;
+
+const MAGIC: [u8; 8] = [b'f', b'g', b'b', 3, b'f', b'g', b'b', 0];
+
+/// Vtable offset of the schema field with the given id
+const fn slot(id: u16) -> u16 {
+    4 + 2 * id
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeometryType {
+    Point = 1,
+    LineString = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Bool = 2,
+    Double = 10,
+    String = 11,
+    Json = 12,
+}
+
+/// A property value, matching its column's type
+pub enum Property {
+    Bool(bool),
+    Double(f64),
+    /// Also used for `Json` columns
+    String(String),
+}
+
+pub struct Feature {
+    /// `[x, y]` pairs; a single one for points
+    pub coordinates: Vec<Vec<f64>>,
+    /// One value per column, in column order
+    pub properties: Vec<Property>,
+}
+
+/// Writes `features` as a FlatGeobuf layer named `name` to `path`, with
+/// coordinates in `crs`.
+pub fn write(
+    path: &Path,
+    name: &str,
+    crs: Crs,
+    geometry_type: GeometryType,
+    columns: &[(&str, ColumnType)],
+    features: &[Feature],
+) -> Result<()> {
+    let features: Vec<&Feature> = features
+        .iter()
+        .filter(|f| !f.coordinates.is_empty())
+        .collect();
+    let boxes: Vec<Bbox> = features
+        .iter()
+        .filter_map(|f| packed_rtree::bbox_of(&f.coordinates))
+        .collect();
+    let order = packed_rtree::hilbert_order(&boxes);
+
+    // Features in index order, and the byte offset of each
+    let mut feature_data = Vec::new();
+    let mut leaves = Vec::with_capacity(order.len());
+    for &i in &order {
+        leaves.push((boxes[i], feature_data.len() as u64));
+        feature_data.extend_from_slice(&encode_feature(features[i]));
+    }
+
+    let extent = boxes
+        .iter()
+        .copied()
+        .reduce(|a, b| packed_rtree::union(&a, &b));
+    let index = if leaves.is_empty() {
+        Vec::new()
+    } else {
+        packed_rtree::to_bytes(&packed_rtree::build(&leaves, NODE_SIZE))
+    };
+
+    let mut out = Vec::with_capacity(MAGIC.len() + index.len() + feature_data.len() + 1024);
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&encode_header(
+        name,
+        crs,
+        geometry_type,
+        columns,
+        extent,
+        leaves.len(),
+    ));
+    out.extend_from_slice(&index);
+    out.extend_from_slice(&feature_data);
+
+    write_atomic(path, out)
+}
+
+fn encode_header(
+    name: &str,
+    crs: Crs,
+    geometry_type: GeometryType,
+    columns: &[(&str, ColumnType)],
+    extent: Option<Bbox>,
+    features_count: usize,
+) -> Vec<u8> {
+    let mut fbb = FlatBufferBuilder::new();
+
+    let name = fbb.create_string(name);
+    let envelope = extent.map(|e| fbb.create_vector(&e));
+
+    let columns: Vec<_> = columns
+        .iter()
+        .map(|&(col_name, col_type)| {
+            let col_name = fbb.create_string(col_name);
+            let table = fbb.start_table();
+            fbb.push_slot_always(slot(0), col_name);
+            fbb.push_slot(slot(1), col_type as u8, 0);
+            fbb.end_table(table)
+        })
+        .collect();
+    let columns = fbb.create_vector(&columns);
+
+    let org = fbb.create_string("EPSG");
+    let crs_table = fbb.start_table();
+    fbb.push_slot_always(slot(0), org);
+    fbb.push_slot(slot(1), crs.epsg(), 0);
+    let crs_table = fbb.end_table(crs_table);
+
+    let header = fbb.start_table();
+    fbb.push_slot_always(slot(0), name);
+    if let Some(envelope) = envelope {
+        fbb.push_slot_always(slot(1), envelope);
+    }
+    fbb.push_slot(slot(2), geometry_type as u8, 0);
+    fbb.push_slot_always(slot(7), columns);
+    fbb.push_slot(slot(8), features_count as u64, 0);
+    // Without features there is no index (node size 0)
+    let node_size = if features_count == 0 { 0 } else { NODE_SIZE };
+    fbb.push_slot(slot(9), node_size as u16, 16);
+    fbb.push_slot_always(slot(10), crs_table);
+    let header = fbb.end_table(header);
+
+    fbb.finish_size_prefixed(header, None);
+    fbb.finished_data().to_vec()
+}
+
+fn encode_feature(feature: &Feature) -> Vec<u8> {
+    let mut fbb = FlatBufferBuilder::new();
+
+    let xy: Vec<f64> = feature
+        .coordinates
+        .iter()
+        .flat_map(|c| [c[0], c[1]])
+        .collect();
+    let xy = fbb.create_vector(&xy);
+    let geometry = fbb.start_table();
+    fbb.push_slot_always(slot(1), xy);
+    let geometry = fbb.end_table(geometry);
+
+    // Properties: each value prefixed with its u16 column index
+    let mut props = Vec::new();
+    for (i, value) in feature.properties.iter().enumerate() {
+        props.extend_from_slice(&(i as u16).to_le_bytes());
+        match value {
+            Property::Bool(b) => props.push(u8::from(*b)),
+            Property::Double(d) => props.extend_from_slice(&d.to_le_bytes()),
+            Property::String(s) => {
+                props.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                props.extend_from_slice(s.as_bytes());
+            }
+        }
+    }
+    let props = fbb.create_vector(&props);
+
+    let table = fbb.start_table();
+    fbb.push_slot_always(slot(0), geometry);
+    fbb.push_slot_always(slot(1), props);
+    let table = fbb.end_table(table);
+
+    fbb.finish_size_prefixed(table, None);
+    fbb.finished_data().to_vec()
+}
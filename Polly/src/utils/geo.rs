@@ -1,22 +1,52 @@
 //! Geospatial utility functions.
 //!
 //! Functions for calculating distances, finding nearest points, and computing bounding boxes.
+//!
+//! Distances are great-circle (haversine) distances on the mean Earth sphere.
+//! Points are projected onto segments in a local frame scaled to meters, so
+//! a projection is the ground-nearest point at any latitude.
+
+use rstar::RTree;
+use rstar::primitives::GeomWithData;
+
+/// Approximate length of one degree of latitude, in meters
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Mean Earth radius (IUGG), in meters
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
 
-/// Calculate distance in meters between two GPS coordinates using Equirectangular approximation
+/// Great-circle distance in meters between two GPS coordinates (haversine formula)
 pub fn meters_between(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
-    // Equirectangular approximation
-    let r = 6371000.0;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = phi2 - phi1;
+    let dlambda = (lon2 - lon1).to_radians();
 
-    let x = (lon2 - lon1).to_radians() * ((lat1 + lat2) * 0.5).to_radians().cos();
-    let y = (lat2 - lat1).to_radians();
+    let h = (dphi * 0.5).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda * 0.5).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// Position (0 to 1) along the segment `a`-`b` ([lon, lat]) nearest to `p`,
+/// in a local frame where a degree of longitude is shortened by its latitude.
+fn segment_fraction(p: (f64, f64), a: &[f64], b: &[f64]) -> f64 {
+    let lon_scale = ((a[1] + b[1]) * 0.5).to_radians().cos();
+    let dx = (b[0] - a[0]) * lon_scale;
+    let dy = b[1] - a[1];
+    let denom = dx * dx + dy * dy;
+    if denom == 0.0 {
+        return 0.0;
+    }
+    ((((p.0 - a[0]) * lon_scale) * dx + (p.1 - a[1]) * dy) / denom).clamp(0.0, 1.0)
+}
 
-    (x * x + y * y).sqrt() * r
+/// Point at `t` (0 to 1) along the segment `a`-`b`.
+fn lerp(a: &[f64], b: &[f64], t: f64) -> (f64, f64) {
+    (a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1]))
 }
 
 /// Find the closest point on a polyline to a given point
 pub fn closest_point_on_polyline(
     point: (f64, f64),
-    line: &Vec<Vec<f64>>,
+    line: &[Vec<f64>],
 ) -> Option<((f64, f64), f64)> {
     if line.len() < 2 {
         return None;
@@ -26,22 +56,11 @@ pub fn closest_point_on_polyline(
     let mut best = None;
 
     for seg in line.windows(2) {
-        let (x1, y1) = (seg[0][0], seg[0][1]);
-        let (x2, y2) = (seg[1][0], seg[1][1]);
-
-        let dx = x2 - x1;
-        let dy = y2 - y1;
-
-        let denom = dx * dx + dy * dy;
-        if denom == 0.0 {
+        if seg[0] == seg[1] {
             continue;
         }
 
-        let t = ((px - x1) * dx + (py - y1) * dy) / denom;
-
-        let cx = x1 + t.clamp(0.0, 1.0) * dx;
-        let cy = y1 + t.clamp(0.0, 1.0) * dy;
-
+        let (cx, cy) = lerp(&seg[0], &seg[1], segment_fraction(point, &seg[0], &seg[1]));
         let d = meters_between(px, py, cx, cy);
 
         match best {
@@ -55,7 +74,7 @@ pub fn closest_point_on_polyline(
 }
 
 /// Find the index of the coordinate in `line` closest to `point`
-pub fn find_nearest_coord_index(point: (f64, f64), line: &Vec<Vec<f64>>) -> Option<usize> {
+pub fn find_nearest_coord_index(point: (f64, f64), line: &[Vec<f64>]) -> Option<usize> {
     if line.is_empty() {
         return None;
     }
@@ -77,8 +96,335 @@ pub fn find_nearest_coord_index(point: (f64, f64), line: &Vec<Vec<f64>>) -> Opti
     Some(best_idx)
 }
 
+/// R-tree over the coordinates of a line, for nearest-coordinate lookups
+/// without scanning the whole line for every stop.
+pub struct CoordIndex {
+    tree: RTree<GeomWithData<[f64; 2], usize>>,
+    /// Longitude scale of the local equirectangular projection
+    lon_scale: f64,
+}
+
+impl CoordIndex {
+    pub fn new(line: &[Vec<f64>]) -> Self {
+        // Scale longitudes so planar distances in the tree match ground distances
+        let lat0 = line.first().map_or(0.0, |c| c[1]);
+        let lon_scale = lat0.to_radians().cos();
+
+        let points = line
+            .iter()
+            .enumerate()
+            .map(|(i, c)| GeomWithData::new([c[0] * lon_scale, c[1]], i))
+            .collect();
+
+        Self {
+            tree: RTree::bulk_load(points),
+            lon_scale,
+        }
+    }
+
+    /// Indices of the coordinates within `radius_m` of `point`, in line order.
+    pub fn within(&self, point: (f64, f64), radius_m: f64) -> Vec<usize> {
+        let query = [point.0 * self.lon_scale, point.1];
+        let radius_deg = radius_m / METERS_PER_DEGREE;
+        let mut idx: Vec<usize> = self
+            .tree
+            .locate_within_distance(query, radius_deg * radius_deg)
+            .map(|p| p.data)
+            .collect();
+        idx.sort_unstable();
+        idx
+    }
+}
+
+/// A point projected onto a line
+#[derive(Debug, Clone, Copy)]
+pub struct LinePosition {
+    /// Line coordinate nearest to the projection
+    pub coord_idx: usize,
+    /// Distance along the line from its start, in meters
+    pub along_m: f64,
+    /// Distance from the point to the line, in meters
+    pub offset_m: f64,
+}
+
+/// Projects `point` onto the segment starting at `line[i]`.
+fn project_onto_segment(
+    point: (f64, f64),
+    line: &[Vec<f64>],
+    cum: &[f64],
+    i: usize,
+) -> LinePosition {
+    let (px, py) = point;
+    let t = segment_fraction(point, &line[i], &line[i + 1]);
+    let (cx, cy) = lerp(&line[i], &line[i + 1], t);
+
+    LinePosition {
+        coord_idx: if t < 0.5 { i } else { i + 1 },
+        along_m: cum[i] + t * (cum[i + 1] - cum[i]),
+        offset_m: meters_between(px, py, cx, cy),
+    }
+}
+
+/// Best projection onto segments `first..=last` of the line.
+fn best_projection(
+    point: (f64, f64),
+    line: &[Vec<f64>],
+    cum: &[f64],
+    first: usize,
+    last: usize,
+) -> LinePosition {
+    (first..=last)
+        .map(|i| project_onto_segment(point, line, cum, i))
+        .min_by(|a, b| a.offset_m.total_cmp(&b.offset_m))
+        .expect("segment range is never empty")
+}
+
+/// Maps points (e.g., stops in order) onto a line so their positions never go
+/// backwards along it.
+///
+/// Every pass of the line near a point (a run of consecutive coordinates within
+/// `radius_m`) is a candidate, so a route crossing the same intersection twice
+/// offers both passes. The ordered assignment with the smallest total offset is
+/// chosen; points with no coordinate in range fall back to the closest segment.
+pub fn map_points_along_line(
+    points: &[(f64, f64)],
+    line: &[Vec<f64>],
+    radius_m: f64,
+) -> Vec<LinePosition> {
+    if line.len() < 2 {
+        let at_start = LinePosition {
+            coord_idx: 0,
+            along_m: 0.0,
+            offset_m: 0.0,
+        };
+        return vec![at_start; points.len()];
+    }
+
+    let cum = cumulative_distances(line);
+    let index = CoordIndex::new(line);
+    let last_segment = line.len() - 2;
+
+    // Candidate positions per point: the best projection on each nearby pass
+    let candidates: Vec<Vec<LinePosition>> = points
+        .iter()
+        .map(|&p| {
+            let near = index.within(p, radius_m);
+            if near.is_empty() {
+                return vec![best_projection(p, line, &cum, 0, last_segment)];
+            }
+
+            let mut runs: Vec<(usize, usize)> = Vec::new();
+            for i in near {
+                match runs.last_mut() {
+                    Some((_, end)) if i <= *end + 1 => *end = i,
+                    _ => runs.push((i, i)),
+                }
+            }
+            runs.into_iter()
+                .map(|(start, end)| {
+                    // Segments touching the run's coordinates
+                    let first = start.saturating_sub(1).min(last_segment);
+                    let last = end.min(last_segment);
+                    best_projection(p, line, &cum, first, last)
+                })
+                .collect()
+        })
+        .collect();
+
+    // Dynamic programming over the candidates; going backwards is allowed only
+    // at a prohibitive cost, so a solution always exists.
+    const BACKWARDS_PENALTY: f64 = 1e9;
+    let mut cost: Vec<Vec<f64>> = Vec::with_capacity(points.len());
+    let mut from: Vec<Vec<usize>> = Vec::with_capacity(points.len());
+    for (j, cands) in candidates.iter().enumerate() {
+        let mut row_cost = Vec::with_capacity(cands.len());
+        let mut row_from = Vec::with_capacity(cands.len());
+        for c in cands {
+            let (prev, prev_cost) = match j {
+                0 => (0, 0.0),
+                _ => candidates[j - 1]
+                    .iter()
+                    .enumerate()
+                    .map(|(k, p)| {
+                        let penalty = if p.along_m > c.along_m {
+                            BACKWARDS_PENALTY
+                        } else {
+                            0.0
+                        };
+                        (k, cost[j - 1][k] + penalty)
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .expect("every point has a candidate"),
+            };
+            row_cost.push(prev_cost + c.offset_m);
+            row_from.push(prev);
+        }
+        cost.push(row_cost);
+        from.push(row_from);
+    }
+
+    // Trace back the cheapest assignment
+    let mut positions = Vec::with_capacity(points.len());
+    let Some(last_row) = cost.last() else {
+        return positions;
+    };
+    let mut k = (0..last_row.len())
+        .min_by(|&a, &b| last_row[a].total_cmp(&last_row[b]))
+        .unwrap_or(0);
+    for j in (0..points.len()).rev() {
+        positions.push(candidates[j][k]);
+        k = from[j][k];
+    }
+    positions.reverse();
+
+    // Clamp any unavoidable backward step so positions are non-decreasing
+    for j in 1..positions.len() {
+        if positions[j].along_m < positions[j - 1].along_m {
+            positions[j] = LinePosition {
+                offset_m: positions[j].offset_m,
+                ..positions[j - 1]
+            };
+        }
+    }
+
+    positions
+}
+
+/// Simplifies a line with Douglas-Peucker, keeping every vertex within
+/// `tolerance_m` of the result. Vertices listed in `keep` (and both ends) are
+/// always retained. Returns the indices of the kept vertices in order.
+pub fn simplify_line(line: &[Vec<f64>], tolerance_m: f64, keep: &[usize]) -> Vec<usize> {
+    if line.len() < 3 {
+        return (0..line.len()).collect();
+    }
+
+    // Local planar coordinates in meters
+    let lon_scale = line[0][1].to_radians().cos() * METERS_PER_DEGREE;
+    let xy: Vec<(f64, f64)> = line
+        .iter()
+        .map(|c| (c[0] * lon_scale, c[1] * METERS_PER_DEGREE))
+        .collect();
+
+    let mut anchors: Vec<usize> = keep.iter().copied().filter(|&i| i < line.len()).collect();
+    anchors.extend([0, line.len() - 1]);
+    anchors.sort_unstable();
+    anchors.dedup();
+
+    let mut kept = vec![false; line.len()];
+    for &a in &anchors {
+        kept[a] = true;
+    }
+
+    // Simplify each stretch between anchors independently
+    let mut stack: Vec<(usize, usize)> = anchors.windows(2).map(|w| (w[0], w[1])).collect();
+    while let Some((first, last)) = stack.pop() {
+        if last <= first + 1 {
+            continue;
+        }
+
+        let (farthest, dist) = (first + 1..last)
+            .map(|i| (i, segment_distance(xy[i], xy[first], xy[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("stretch has inner vertices");
+
+        if dist > tolerance_m {
+            kept[farthest] = true;
+            stack.push((first, farthest));
+            stack.push((farthest, last));
+        }
+    }
+
+    (0..line.len()).filter(|&i| kept[i]).collect()
+}
+
+/// Planar distance from `p` to the segment `a`-`b`.
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let denom = dx * dx + dy * dy;
+    let t = if denom == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / denom).clamp(0.0, 1.0)
+    };
+    ((p.0 - a.0 - t * dx).powi(2) + (p.1 - a.1 - t * dy).powi(2)).sqrt()
+}
+
+/// Initial bearing from `from` to `to` ([lon, lat]), in degrees clockwise from north
+pub fn bearing(from: &[f64], to: &[f64]) -> f64 {
+    let (lat1, lat2) = (from[1].to_radians(), to[1].to_radians());
+    let dlon = (to[0] - from[0]).to_radians();
+
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Point `along_m` meters along a line from its start, clamped to the line.
+/// `cum` holds the [`cumulative_distances`] of the line.
+pub fn point_along_line(line: &[Vec<f64>], cum: &[f64], along_m: f64) -> Option<Vec<f64>> {
+    if line.len() < 2 {
+        return line.first().cloned();
+    }
+    let total = *cum.last()?;
+    let along_m = along_m.clamp(0.0, total);
+
+    // First segment ending at or beyond the distance
+    let end = cum
+        .partition_point(|&d| d < along_m)
+        .clamp(1, line.len() - 1);
+    let length = cum[end] - cum[end - 1];
+    let t = if length > 0.0 {
+        (along_m - cum[end - 1]) / length
+    } else {
+        0.0
+    };
+    let (x, y) = lerp(&line[end - 1], &line[end], t);
+    Some(vec![x, y])
+}
+
+/// Direction of travel along a line `along_m` meters from its start: the
+/// bearing from `reach_m` before to `reach_m` after that point, so it does
+/// not depend on how the vertices happen to be spaced. `None` if the line has
+/// no length there.
+pub fn bearing_along(line: &[Vec<f64>], cum: &[f64], along_m: f64, reach_m: f64) -> Option<f64> {
+    let before = point_along_line(line, cum, along_m - reach_m)?;
+    let after = point_along_line(line, cum, along_m + reach_m)?;
+    (before != after).then(|| bearing(&before, &after))
+}
+
+/// Discrete Fréchet distance between two lines, in meters: the shortest
+/// leash that lets one walker follow each line from start to end without
+/// backtracking. Unlike the largest offset, it catches a line that passes
+/// near every point of the other but in the wrong order (e.g., a detour that
+/// comes back). Only vertices are compared, so densify sparse lines first.
+pub fn frechet_distance(a: &[Vec<f64>], b: &[Vec<f64>]) -> Option<f64> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Rolling rows of the coupling table over `b`
+    let mut prev: Vec<f64> = Vec::with_capacity(b.len());
+    let mut row: Vec<f64> = Vec::with_capacity(b.len());
+    for (i, p) in a.iter().enumerate() {
+        row.clear();
+        for (j, q) in b.iter().enumerate() {
+            let d = meters_between(p[0], p[1], q[0], q[1]);
+            let reach = match (i, j) {
+                (0, 0) => 0.0,
+                (0, _) => row[j - 1],
+                (_, 0) => prev[0],
+                _ => prev[j].min(prev[j - 1]).min(row[j - 1]),
+            };
+            row.push(d.max(reach));
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev.last().copied()
+}
+
 /// Calculate bounding box and total distance of a series of coordinates
-pub fn calculate_metrics(coords: &Vec<Vec<f64>>) -> ([f64; 4], f64) {
+pub fn calculate_metrics(coords: &[Vec<f64>]) -> ([f64; 4], f64) {
     let mut min_lon = 180.0;
     let mut min_lat = 90.0;
 
@@ -111,3 +457,187 @@ pub fn calculate_metrics(coords: &Vec<Vec<f64>>) -> ([f64; 4], f64) {
 
     ([min_lon, min_lat, max_lon, max_lat], dist)
 }
+
+/// Inserts evenly spaced vertices so no segment of `line` is longer than
+/// `max_step_m`. Existing vertices are kept.
+pub fn densify_line(line: &[Vec<f64>], max_step_m: f64) -> Vec<Vec<f64>> {
+    let mut out = Vec::with_capacity(line.len());
+    for seg in line.windows(2) {
+        let d = meters_between(seg[0][0], seg[0][1], seg[1][0], seg[1][1]);
+        let steps = (d / max_step_m).ceil().max(1.0) as usize;
+        for k in 0..steps {
+            let t = k as f64 / steps as f64;
+            out.push(vec![
+                seg[0][0] + t * (seg[1][0] - seg[0][0]),
+                seg[0][1] + t * (seg[1][1] - seg[0][1]),
+            ]);
+        }
+    }
+    out.extend(line.last().cloned());
+    out
+}
+
+/// Calculate the cumulative distance in meters at each coordinate along a line
+pub fn cumulative_distances(coords: &[Vec<f64>]) -> Vec<f64> {
+    let mut dists = Vec::with_capacity(coords.len());
+    let mut total = 0.0;
+
+    for (i, c) in coords.iter().enumerate() {
+        if i > 0 {
+            total += meters_between(coords[i - 1][0], coords[i - 1][1], c[0], c[1]);
+        }
+        dists.push(total);
+    }
+
+    dists
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn measures_great_circle_distances() {
+        // One degree of latitude is about 111.2 km on the mean sphere.
+        assert_close(meters_between(127.9, 37.0, 127.9, 38.0), 111_195.0, 1.0);
+        // A degree of longitude shrinks with the latitude.
+        assert_close(
+            meters_between(127.0, 37.3, 128.0, 37.3),
+            111_195.0 * 37.3f64.to_radians().cos(),
+            1.0,
+        );
+        assert_eq!(meters_between(127.9, 37.3, 127.9, 37.3), 0.0);
+    }
+
+    #[test]
+    fn accumulates_distances_along_a_line() {
+        let line = vec![vec![127.9, 37.30], vec![127.9, 37.31], vec![127.9, 37.32]];
+        let cum = cumulative_distances(&line);
+        assert_eq!(cum[0], 0.0);
+        assert_close(cum[2], 2.0 * cum[1], 1e-6);
+        assert_close(cum[2], 2223.9, 0.1);
+    }
+
+    #[test]
+    fn finds_points_along_a_line() {
+        let line = vec![vec![127.9, 37.30], vec![127.9, 37.32]];
+        let cum = cumulative_distances(&line);
+        let middle = point_along_line(&line, &cum, cum[1] / 2.0).unwrap();
+        assert_close(middle[1], 37.31, 1e-9);
+        // Distances past the ends are clamped.
+        assert_eq!(point_along_line(&line, &cum, -5.0).unwrap(), line[0]);
+        assert_eq!(point_along_line(&line, &cum, 1e9).unwrap(), line[1]);
+    }
+
+    #[test]
+    fn densify_and_simplify_round_trip() {
+        let line = vec![
+            vec![127.90, 37.30],
+            vec![127.90, 37.31],
+            vec![127.91, 37.31],
+        ];
+        let dense = densify_line(&line, 100.0);
+        assert!(dense.len() > 20);
+        assert_eq!(dense.first(), line.first());
+        assert_eq!(dense.last(), line.last());
+        assert!(
+            cumulative_distances(&dense)
+                .windows(2)
+                .all(|w| w[1] - w[0] <= 100.0 + 1e-6)
+        );
+
+        // The added vertices lie on the line, so simplifying drops them all.
+        let kept = simplify_line(&dense, 1.0, &[]);
+        let simplified: Vec<&Vec<f64>> = kept.iter().map(|&i| &dense[i]).collect();
+        assert_eq!(simplified, line.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn simplify_keeps_requested_vertices() {
+        let line = vec![
+            vec![127.90, 37.30],
+            vec![127.90, 37.305],
+            vec![127.90, 37.31],
+        ];
+        assert_eq!(simplify_line(&line, 1.0, &[]), [0, 2]);
+        assert_eq!(simplify_line(&line, 1.0, &[1]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn maps_both_passes_of_an_out_and_back_line_in_order() {
+        let line: Vec<Vec<f64>> = [37.300, 37.301, 37.302, 37.303, 37.302, 37.301, 37.300]
+            .iter()
+            .map(|&lat| vec![127.9, lat])
+            .collect();
+        let cum = cumulative_distances(&line);
+        // The first and last stop are the same place, passed on the way out and back.
+        let stops = [(127.9, 37.301), (127.9, 37.303), (127.9, 37.301)];
+
+        let positions = map_points_along_line(&stops, &line, 30.0);
+        let coords: Vec<usize> = positions.iter().map(|p| p.coord_idx).collect();
+        assert_eq!(coords, [1, 3, 5]);
+        assert_close(positions[0].along_m, cum[1], 1e-6);
+        assert_close(positions[2].along_m, cum[5], 1e-6);
+        assert!(positions.iter().all(|p| p.offset_m < 1e-6));
+    }
+
+    #[test]
+    fn maps_the_start_of_a_loop_to_both_ends() {
+        let line = vec![
+            vec![127.900, 37.300],
+            vec![127.900, 37.302],
+            vec![127.902, 37.302],
+            vec![127.902, 37.300],
+            vec![127.900, 37.300],
+        ];
+        let total = *cumulative_distances(&line).last().unwrap();
+        let stops = [(127.900, 37.300), (127.902, 37.302), (127.900, 37.300)];
+
+        let positions = map_points_along_line(&stops, &line, 30.0);
+        assert_eq!(positions[0].coord_idx, 0);
+        assert_eq!(positions[1].coord_idx, 2);
+        assert_eq!(positions[2].coord_idx, 4);
+        assert_close(positions[2].along_m, total, 1e-6);
+        assert!(positions.windows(2).all(|w| w[0].along_m <= w[1].along_m));
+    }
+
+    #[test]
+    fn points_out_of_range_fall_back_to_the_closest_segment() {
+        let line = vec![vec![127.9, 37.300], vec![127.9, 37.302]];
+        let cum = cumulative_distances(&line);
+        // About 88 m east of the line, beyond the 30 m radius
+        let positions = map_points_along_line(&[(127.901, 37.301)], &line, 30.0);
+
+        assert_eq!(positions.len(), 1);
+        assert_close(positions[0].along_m, cum[1] / 2.0, 1.0);
+        assert_close(
+            positions[0].offset_m,
+            meters_between(127.901, 37.301, 127.9, 37.301),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn lines_shorter_than_a_segment_map_everything_to_the_start() {
+        let stops = [(127.9, 37.3), (127.91, 37.31)];
+        for line in [vec![], vec![vec![127.9, 37.3]]] {
+            let positions = map_points_along_line(&stops, &line, 30.0);
+            assert_eq!(positions.len(), 2);
+            assert!(
+                positions
+                    .iter()
+                    .all(|p| p.coord_idx == 0 && p.along_m == 0.0 && p.offset_m == 0.0)
+            );
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Korean public holidays.
+//!
+//! Fixed-date holidays follow the solar calendar; Seollal, Chuseok, and
+//! Buddha's Birthday follow the lunar calendar and are listed per year, along
+//! with one-off designated holidays such as election days. Substitute
+//! holidays (대체공휴일) are derived from the rules in force each year. Years
+//! missing from the table, or holidays designated later, can be supplied
+//! with a holidays file; a period with years neither covers is an error, as
+//! its lunar holidays would be missing.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+/// Holidays of the solar calendar: month, day, name, and the first year a
+/// weekend occurrence earns a substitute holiday
+const FIXED: &[(u32, u32, &str, Option<i32>)] = &[
+    (1, 1, "신정", None),
+    (3, 1, "삼일절", Some(2021)),
+    (5, 5, "어린이날", Some(2014)),
+    (6, 6, "현충일", None),
+    (8, 15, "광복절", Some(2021)),
+    (10, 3, "개천절", Some(2021)),
+    (10, 9, "한글날", Some(2021)),
+    (12, 25, "기독탄신일", Some(2023)),
+];
+
+type MonthDay = (u32, u32);
+
+/// Lunar holidays per year: Seollal and Chuseok (the day itself, observed
+/// with the days before and after) and Buddha's Birthday
+const LUNAR: &[(i32, MonthDay, MonthDay, MonthDay)] = &[
+    (2024, (2, 10), (9, 17), (5, 15)),
+    (2025, (1, 29), (10, 6), (5, 5)),
+    (2026, (2, 17), (9, 25), (5, 24)),
+    (2027, (2, 7), (9, 15), (5, 13)),
+];
+
+/// One-off holidays: election days and temporary holidays
+const DESIGNATED: &[(i32, u32, u32, &str)] = &[
+    (2024, 4, 10, "국회의원 선거일"),
+    (2024, 10, 1, "임시공휴일"),
+    (2025, 1, 27, "임시공휴일"),
+    (2025, 6, 3, "대통령 선거일"),
+    (2026, 6, 3, "지방선거일"),
+];
+
+/// Public holidays by date, with their names.
+#[derive(Debug, Default)]
+pub struct Holidays {
+    dates: BTreeMap<NaiveDate, String>,
+    /// Years of the built-in table or the holidays file
+    years: Vec<i32>,
+}
+
+impl Holidays {
+    /// The holidays of the years from `start` to `end`: the built-in ones,
+    /// plus those of `file` if given. Fails if a year is in neither.
+    pub fn between(start: NaiveDate, end: NaiveDate, file: Option<&Path>) -> Result<Self> {
+        let mut holidays = Self::default();
+        for year in start.year()..=end.year() {
+            holidays.add_year(year);
+        }
+        if let Some(path) = file {
+            holidays.read_file(path)?;
+        }
+
+        let missing: Vec<String> = (start.year()..=end.year())
+            .filter(|year| !holidays.covers(*year))
+            .map(|year| year.to_string())
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "No holidays known for {} (built in: {}-{})",
+                missing.join(", "),
+                LUNAR[0].0,
+                LUNAR[LUNAR.len() - 1].0
+            );
+        }
+        Ok(holidays)
+    }
+
+    /// Adds the holidays of a file with one `YYYY-MM-DD [name]` per line.
+    /// Blank lines and `#` comments are skipped. Each year listed counts as
+    /// known, so the file can cover years the built-in table does not.
+    fn read_file(&mut self, path: &Path) -> Result<()> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Reading holidays {:?}", path))?;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (date, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+                bail!("{:?} line {}: expected a YYYY-MM-DD date", path, i + 1);
+            };
+            let name = match name.trim() {
+                "" => "공휴일",
+                name => name,
+            };
+            self.dates.insert(date, name.to_string());
+            if !self.years.contains(&date.year()) {
+                self.years.push(date.year());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the holidays of `year` are known.
+    fn covers(&self, year: i32) -> bool {
+        self.years.contains(&year)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NaiveDate, &str)> {
+        self.dates.iter().map(|(date, name)| (*date, name.as_str()))
+    }
+
+    fn add_year(&mut self, year: i32) {
+        let Some(&(_, seollal, chuseok, buddha)) = LUNAR.iter().find(|(y, ..)| *y == year) else {
+            return;
+        };
+        let date = |(month, day): MonthDay| NaiveDate::from_ymd_opt(year, month, day);
+
+        // (dates, name, whether Saturdays earn a substitute), for the
+        // holidays that get one
+        let mut substituted: Vec<(Vec<NaiveDate>, String, bool)> = Vec::new();
+        let mut base: Vec<(NaiveDate, String)> = Vec::new();
+        for &(month, day, name, since) in FIXED {
+            let Some(date) = date((month, day)) else {
+                continue;
+            };
+            base.push((date, name.to_string()));
+            if since.is_some_and(|since| year >= since) {
+                substituted.push((vec![date], name.to_string(), true));
+            }
+        }
+        for (day, name) in [(seollal, "설날"), (chuseok, "추석")] {
+            let Some(day) = date(day) else {
+                continue;
+            };
+            let days: Vec<NaiveDate> = [day.pred_opt(), Some(day), day.succ_opt()]
+                .into_iter()
+                .flatten()
+                .collect();
+            for date in &days {
+                base.push((*date, name.to_string()));
+            }
+            // Seollal and Chuseok only count Sundays, not Saturdays.
+            substituted.push((days, name.to_string(), false));
+        }
+        if let Some(day) = date(buddha) {
+            base.push((day, "부처님오신날".to_string()));
+            if year >= 2023 {
+                substituted.push((vec![day], "부처님오신날".to_string(), true));
+            }
+        }
+        for &(_, month, day, name) in DESIGNATED.iter().filter(|(y, ..)| *y == year) {
+            if let Some(date) = date((month, day)) {
+                base.push((date, name.to_string()));
+            }
+        }
+
+        let mut overlaps: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+        for (date, name) in base {
+            *overlaps.entry(date).or_default() += 1;
+            self.dates.entry(date).or_insert(name);
+        }
+
+        // A holiday on a weekend, or on another holiday, moves to the next
+        // working day after it. Two holidays on the same day earn one.
+        substituted.sort_by_key(|(days, ..)| days[0]);
+        let mut compensated: Vec<NaiveDate> = Vec::new();
+        for (days, name, saturdays) in substituted {
+            let lost = days.iter().any(|d| {
+                d.weekday() == Weekday::Sun
+                    || (saturdays && d.weekday() == Weekday::Sat)
+                    || (overlaps[d] > 1 && !compensated.contains(d))
+            });
+            if !lost {
+                continue;
+            }
+            compensated.extend(&days);
+            let mut date = days[days.len() - 1];
+            loop {
+                date = date + Days::new(1);
+                if !is_weekend(date) && !self.dates.contains_key(&date) {
+                    break;
+                }
+            }
+            self.dates.insert(date, format!("{} 대체공휴일", name));
+        }
+        self.years.push(year);
+    }
+}
+
+pub fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn holidays(from: &str, to: &str) -> Holidays {
+        Holidays::between(date(from), date(to), None).unwrap()
+    }
+
+    fn name<'a>(holidays: &'a Holidays, day: &str) -> Option<&'a str> {
+        holidays.dates.get(&date(day)).map(String::as_str)
+    }
+
+    #[test]
+    fn weekend_holidays_earn_a_substitute() {
+        let holidays = holidays("2024-01-01", "2026-12-31");
+        for (day, holiday) in [
+            // Seollal ran Friday to Sunday
+            ("2024-02-12", "설날 대체공휴일"),
+            // Independence Movement Day on a Saturday
+            ("2025-03-03", "삼일절 대체공휴일"),
+            // Chuseok started on a Sunday
+            ("2025-10-08", "추석 대체공휴일"),
+            // Liberation Day on a Saturday
+            ("2026-08-17", "광복절 대체공휴일"),
+        ] {
+            assert_eq!(name(&holidays, day), Some(holiday), "{}", day);
+        }
+    }
+
+    #[test]
+    fn holidays_on_the_same_day_earn_one_substitute() {
+        // Children's Day and Buddha's Birthday were both on Monday 2025-05-05.
+        let holidays = holidays("2025-01-01", "2025-12-31");
+        assert_eq!(name(&holidays, "2025-05-05"), Some("어린이날"));
+        assert!(name(&holidays, "2025-05-06").is_some_and(|n| n.ends_with("대체공휴일")));
+        assert_eq!(name(&holidays, "2025-05-07"), None);
+    }
+
+    #[test]
+    fn saturdays_do_not_count_for_seollal_and_chuseok() {
+        // Chuseok 2026 ran Thursday to Saturday.
+        let holidays = holidays("2026-01-01", "2026-12-31");
+        assert_eq!(name(&holidays, "2026-09-26"), Some("추석"));
+        assert_eq!(name(&holidays, "2026-09-28"), None);
+    }
+
+    #[test]
+    fn years_outside_the_table_are_an_error() {
+        let error = Holidays::between(date("2027-06-01"), date("2028-05-31"), None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No holidays known for 2028 (built in: 2024-2027)"
+        );
+    }
+
+    #[test]
+    fn holidays_file_covers_more_years() {
+        let path = std::env::temp_dir().join(format!("polly-holidays-{}.txt", std::process::id()));
+        fs::write(&path, "# 2028\n2028-01-26 설날\n\n2028-10-03\n").unwrap();
+        let holidays = Holidays::between(date("2027-06-01"), date("2028-05-31"), Some(&path));
+        fs::remove_file(&path).ok();
+
+        let holidays = holidays.unwrap();
+        assert_eq!(name(&holidays, "2028-01-26"), Some("설날"));
+        assert_eq!(name(&holidays, "2028-10-03"), Some("공휴일"));
+    }
+
+    #[test]
+    fn holidays_file_rejects_bad_dates() {
+        let path =
+            std::env::temp_dir().join(format!("polly-bad-holidays-{}.txt", std::process::id()));
+        fs::write(&path, "2028-13-01 설날\n").unwrap();
+        let result = Holidays::between(date("2028-01-01"), date("2028-12-31"), Some(&path));
+        fs::remove_file(&path).ok();
+        assert!(result.unwrap_err().to_string().contains("line 1"));
+    }
+}
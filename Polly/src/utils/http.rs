@@ -0,0 +1,289 @@
+//! Shared HTTP client with retry/backoff.
+//!
+//! A single `reqwest::Client` keeps a connection pool, so consecutive requests
+//! to the same host (TAGO, OSRM) reuse keep-alive connections instead of
+//! paying a new TCP/TLS handshake each time. Build it once per run and share it.
+//!
+//! Transient failures (timeouts, connection errors, 5xx, 429, and TAGO
+//! rate-limit envelopes) are retried with exponential backoff and jitter.
+//! A run-wide retry budget stops a failing upstream from being hammered.
+//!
+//! Hosts with a request quota can be given a rate limit; it is shared by all
+//! clones of the client, so concurrent tasks are spaced out together.
+//!
+//! Where an upstream sends `ETag` or `Last-Modified` headers, callers keep
+//! them as [`Validators`] (in the crawl state database) and send them back
+//! with the next run's request; a `304 Not Modified` answer then means the
+//! item is unchanged since it was saved, and is neither downloaded nor parsed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy, RequestBuilder, header};
+use tokio::time::sleep;
+use url::Url;
+
+use crate::config::{
+    HTTP_CONNECT_TIMEOUT_SECS, HTTP_MAX_RETRIES, HTTP_POOL_IDLE_SECS, HTTP_RETRY_BASE_MS,
+    HTTP_RETRY_BUDGET, HTTP_RETRY_MAX_MS, HTTP_TIMEOUT_SECS,
+};
+use crate::error::PipelineError;
+use crate::settings::{HttpSettings, TlsBackend};
+use crate::utils::cassette::{self, Response};
+
+/// Applies the connection settings shared by every client: proxy, extra
+/// root certificates, and TLS backend. Without a configured proxy, reqwest
+/// reads `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`, and `NO_PROXY` itself.
+pub fn configure(builder: ClientBuilder, settings: &HttpSettings) -> Result<ClientBuilder> {
+    let mut builder = builder;
+    if let Some(url) = &settings.proxy {
+        let proxy = Proxy::all(url)
+            .with_context(|| format!("Invalid proxy URL {:?}", url))?
+            .no_proxy(NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &settings.ca_cert {
+        let pem = fs::read(path).with_context(|| format!("Reading CA certificate {:?}", path))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Parsing CA certificate {:?}", path))?;
+        if certs.is_empty() {
+            anyhow::bail!("No PEM certificates in {:?}", path);
+        }
+        builder = builder.tls_certs_merge(certs);
+    }
+
+    builder = match settings.tls {
+        TlsBackend::Rustls => builder.tls_backend_rustls(),
+        #[cfg(feature = "native-tls")]
+        TlsBackend::Native => builder.tls_backend_native(),
+        #[cfg(not(feature = "native-tls"))]
+        TlsBackend::Native => {
+            anyhow::bail!("--tls native requires building with the `native-tls` feature")
+        }
+    };
+    Ok(builder)
+}
+
+/// Builds the pooled API client (keep-alive, HTTP/2 where the server offers it).
+fn build_client(settings: &HttpSettings) -> Result<Client> {
+    let builder = Client::builder()
+        .user_agent(concat!("Polly/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
+        .connect_timeout(Duration::from_secs(HTTP_CONNECT_TIMEOUT_SECS))
+        .pool_idle_timeout(Duration::from_secs(HTTP_POOL_IDLE_SECS))
+        .tcp_keepalive(Duration::from_secs(HTTP_POOL_IDLE_SECS))
+        .http2_adaptive_window(true);
+    let client = configure(builder, settings)?.build()?;
+    Ok(client)
+}
+
+/// Pooled client that retries transient failures within a run-wide budget.
+/// Clones share the connection pool and the budget.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    retries_left: Arc<AtomicU32>,
+    rate_limits: Arc<HashMap<String, Arc<RateLimiter>>>,
+}
+
+/// Spaces requests to one host at least `interval` apart.
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_sec: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_sec),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for the next free slot and reserves it.
+    async fn acquire(&self) {
+        let wait = {
+            let mut next = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = (*next).max(now);
+            *next = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+/// Cache validators of a response, sent back to make a request conditional
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// The validators of `response`, if it has any.
+    pub fn of(response: &Response) -> Option<Self> {
+        let validators = Self {
+            etag: response.etag.clone(),
+            last_modified: response.last_modified.clone(),
+        };
+        (validators.etag.is_some() || validators.last_modified.is_some()).then_some(validators)
+    }
+
+    /// Adds `If-None-Match` and `If-Modified-Since` to `request`.
+    pub fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        let mut request = request;
+        if let Some(etag) = &self.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// Outcome of a single failed attempt.
+enum Failure {
+    /// Worth retrying, optionally not before the server-requested delay
+    Transient(anyhow::Error, Option<Duration>),
+    Permanent(anyhow::Error),
+}
+
+impl HttpClient {
+    pub fn new(settings: &HttpSettings) -> Result<Self> {
+        Ok(Self {
+            client: build_client(settings)?,
+            retries_left: Arc::new(AtomicU32::new(HTTP_RETRY_BUDGET)),
+            rate_limits: Arc::default(),
+        })
+    }
+
+    /// Limits requests to the host of `url` to `requests_per_sec` (0 disables the limit).
+    /// Applies to this client and its clones made afterwards.
+    pub fn with_rate_limit(mut self, url: &str, requests_per_sec: f64) -> Result<Self> {
+        if requests_per_sec > 0.0 {
+            let host = Url::parse(url)?
+                .host_str()
+                .with_context(|| format!("No host in {}", url))?
+                .to_string();
+            Arc::make_mut(&mut self.rate_limits)
+                .insert(host, Arc::new(RateLimiter::new(requests_per_sec)));
+        }
+        Ok(self)
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Sends the request and returns the response body, retrying transient failures.
+    pub async fn send_text(&self, request: RequestBuilder) -> Result<String> {
+        Ok(self.send(request).await?.body)
+    }
+
+    /// Sends the request, retrying transient failures. Besides successful
+    /// responses, only `304 Not Modified` (to a conditional request) is
+    /// returned; other error statuses are errors.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let host = match request.try_clone().map(|r| r.build()) {
+            Some(Ok(req)) => req.url().host_str().map(str::to_string),
+            _ => None,
+        };
+        let limiter = host.as_ref().and_then(|host| self.rate_limits.get(host));
+        let upstream = host.as_deref().unwrap_or("upstream");
+
+        let mut attempt = 0;
+        loop {
+            let req = request
+                .try_clone()
+                .context("Request with a streaming body cannot be retried")?;
+
+            // Replayed responses need no spacing.
+            if let Some(limiter) = limiter.filter(|_| !cassette::is_replaying()) {
+                limiter.acquire().await;
+            }
+
+            let (err, retry_after) = match attempt_once(req, upstream).await {
+                Ok(response) => return Ok(response),
+                Err(Failure::Permanent(e)) => return Err(e),
+                Err(Failure::Transient(e, retry_after)) => (e, retry_after),
+            };
+
+            if attempt >= HTTP_MAX_RETRIES || !self.take_retry() {
+                return Err(err.context(format!("Giving up after {} attempt(s)", attempt + 1)));
+            }
+
+            let server_delay = retry_after
+                .unwrap_or_default()
+                .min(Duration::from_millis(HTTP_RETRY_MAX_MS));
+            sleep(backoff(attempt).max(server_delay)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Takes one retry from the run-wide budget; false once it is used up.
+    fn take_retry(&self) -> bool {
+        self.retries_left
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+async fn attempt_once(request: RequestBuilder, upstream: &str) -> Result<Response, Failure> {
+    let resp = cassette::send(request).await.map_err(|e| {
+        let transient = e.downcast_ref::<reqwest::Error>().is_some_and(|e| {
+            e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() || e.is_decode()
+        });
+        if transient {
+            Failure::Transient(e, None)
+        } else {
+            Failure::Permanent(e)
+        }
+    })?;
+
+    if !resp.status.is_success() && !resp.is_not_modified() {
+        let err = PipelineError::UpstreamHttp {
+            upstream: upstream.to_string(),
+            status: resp.status,
+        };
+        return Err(if err.is_transient() {
+            Failure::Transient(err.into(), resp.retry_after())
+        } else {
+            Failure::Permanent(err.into())
+        });
+    }
+
+    if is_rate_limited(&resp.body) {
+        return Err(Failure::Transient(
+            anyhow!("Request rate limit exceeded"),
+            None,
+        ));
+    }
+
+    Ok(resp)
+}
+
+/// Detects the data.go.kr rate-limit envelope, which is served with HTTP 200.
+fn is_rate_limited(body: &str) -> bool {
+    body.contains("LIMITED_NUMBER_OF_SERVICE_REQUESTS_PER_SECOND_EXCEEDS_ERROR")
+}
+
+/// Exponential backoff with jitter (50-100% of the step), capped at `HTTP_RETRY_MAX_MS`.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = HTTP_RETRY_BASE_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(HTTP_RETRY_MAX_MS);
+    Duration::from_millis(fastrand::u64(ceiling / 2..=ceiling))
+}
@@ -0,0 +1,513 @@
+//! Message catalogs.
+//!
+//! Progress messages, warnings, and run summaries are shown in English (for
+//! developers) or Korean (for municipal staff): `--lang`, else `lang` in the
+//! settings (`POLLY_LANG`), else the locale (`LC_ALL`, `LC_MESSAGES`, `LANG`),
+//! else English. Structured log fields, error messages, and file contents stay
+//! in English, so scripts and log queries do not depend on the language.
+
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::report::MetricsSummary;
+
+/// Language of the messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Ko,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Sets the language of the run; without one, it follows the locale.
+pub fn init(lang: Option<Lang>) {
+    LANG.set(lang.unwrap_or_else(detect)).ok();
+}
+
+/// The language of the run (English until [`init`] is called).
+pub fn current() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+/// Korean for a `ko` locale (e.g., `ko_KR.UTF-8`), English otherwise. As in
+/// POSIX, the first of `LC_ALL`, `LC_MESSAGES`, and `LANG` that is set wins.
+fn detect() -> Lang {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()));
+    match locale {
+        Some(locale) if locale.starts_with("ko") => Lang::Ko,
+        _ => Lang::En,
+    }
+}
+
+/// A message of the catalog, displayed in the language of the run.
+pub enum Msg<'a> {
+    // route
+    RetryingRoutes(usize),
+    ListedRoutes(usize),
+    FetchPhase(&'a Path),
+    TargetingRoutes(usize),
+    FetchedRoutes(usize),
+    NoStops(&'a str),
+    FetchFailed(&'a str, &'a anyhow::Error),
+    FetchInterrupted {
+        done: usize,
+        left: usize,
+    },
+    ReusedRawFiles(usize),
+    SequenceIssues {
+        count: usize,
+        file: &'a str,
+    },
+    FetchDone {
+        count: usize,
+        secs: f64,
+    },
+    FetchTimes(&'a MetricsSummary),
+    StationMapDone,
+    SnapPhase(&'a Path),
+    Processing(&'a str),
+    SnapFailed(&'a str, &'a anyhow::Error),
+    SnapInterrupted(usize),
+    SnapTimes(&'a MetricsSummary),
+    SkippedUpToDate(usize),
+    RoutesFailed(usize),
+    PipelineComplete(f64),
+
+    // schedule
+    ScheduleStart,
+    FetchingMainPage,
+    RetryingSchedules(usize),
+    FoundRouteInfo(usize),
+    FoundSchedules(usize),
+    ScheduleParsed {
+        progress: &'a str,
+        route_id: &'a str,
+        times: usize,
+    },
+    ScheduleEmpty {
+        progress: &'a str,
+        route_id: &'a str,
+    },
+    ScheduleUnchanged {
+        progress: &'a str,
+        route_id: &'a str,
+    },
+    Politeness {
+        delay_ms: u64,
+        crawl_delay: Option<f64>,
+        robots_read: bool,
+    },
+    SavingSchedules,
+    CoverageGap {
+        route_no: &'a str,
+        day_types: &'a str,
+        missing: &'a str,
+    },
+    SchedulesFailed(usize),
+    CrawlWarnings(usize),
+
+    // pipeline
+    StepStart(&'a str),
+    StepSkipped(&'a str),
+    StepFailedItems {
+        step: &'a str,
+        count: usize,
+    },
+    Validated {
+        routes: usize,
+        schedules: usize,
+    },
+    PipelineFinished,
+
+    // watch
+    WatchStart(u64),
+    UpstreamUnchanged(&'a str),
+    UpstreamChanged {
+        name: &'a str,
+        before: Option<usize>,
+        after: usize,
+    },
+    ProbeFailed(&'a str, &'a anyhow::Error),
+    WatchCrawlFailed(&'a anyhow::Error),
+    WatchStopped,
+
+    // run summaries
+    SummaryOutcome {
+        command: &'a str,
+        ok: bool,
+    },
+    SummaryDuration(i64),
+    SummaryFailedItems(usize),
+    SummaryLastError(&'a str),
+}
+
+impl fmt::Display for Msg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Msg::*;
+
+        match (current(), self) {
+            (Lang::En, RetryingRoutes(n)) => write!(f, "Retrying {} failed routes", n),
+            (Lang::Ko, RetryingRoutes(n)) => write!(f, "실패한 노선 {}개를 다시 시도합니다", n),
+            (Lang::En, ListedRoutes(n)) => write!(f, "Restricting to {} listed routes", n),
+            (Lang::Ko, ListedRoutes(n)) => write!(f, "노선 목록의 노선 {}개만 처리합니다", n),
+            (Lang::En, FetchPhase(dir)) => write!(f, "[Phase 1] Fetching raw data to {:?}", dir),
+            (Lang::Ko, FetchPhase(dir)) => {
+                write!(f, "[1단계] 원본 데이터를 {:?}에 수집합니다", dir)
+            }
+            (Lang::En, TargetingRoutes(n)) => write!(f, "Targeting {} routes", n),
+            (Lang::Ko, TargetingRoutes(n)) => write!(f, "대상 노선 {}개", n),
+            (Lang::En, FetchedRoutes(n)) => write!(f, "Fetched {} routes", n),
+            (Lang::Ko, FetchedRoutes(n)) => write!(f, "노선 {}개 수집", n),
+            (Lang::En, NoStops(id)) => write!(f, "No stops returned for {}", id),
+            (Lang::Ko, NoStops(id)) => write!(f, "{}: 정류장 정보가 없습니다", id),
+            (Lang::En, FetchFailed(id, e)) => write!(f, "Fetching {} failed: {:#}", id, e),
+            (Lang::Ko, FetchFailed(id, e)) => write!(f, "{} 수집 실패: {:#}", id, e),
+            (Lang::En, FetchInterrupted { done, left }) => write!(
+                f,
+                "Interrupted after {} routes; {} left for --retry-failed",
+                done, left
+            ),
+            (Lang::Ko, FetchInterrupted { done, left }) => write!(
+                f,
+                "노선 {}개 처리 후 중단됨; 남은 {}개는 --retry-failed로 이어서 처리하세요",
+                done, left
+            ),
+            (Lang::En, ReusedRawFiles(n)) => {
+                write!(f, "Reused {} raw files newer than --max-age", n)
+            }
+            (Lang::Ko, ReusedRawFiles(n)) => {
+                write!(f, "--max-age보다 최근인 원본 파일 {}개를 재사용했습니다", n)
+            }
+            (Lang::En, SequenceIssues { count, file }) => write!(
+                f,
+                "{} route(s) have stop sequence issues (see {})",
+                count, file
+            ),
+            (Lang::Ko, SequenceIssues { count, file }) => write!(
+                f,
+                "노선 {}개에 정류장 순서 문제가 있습니다 ({} 참고)",
+                count, file
+            ),
+            (Lang::En, FetchDone { count, secs }) => {
+                write!(f, "Processed {} raw routes in {:.1}s", count, secs)
+            }
+            (Lang::Ko, FetchDone { count, secs }) => {
+                write!(f, "원본 노선 {}개를 {:.1}초 만에 처리했습니다", count, secs)
+            }
+            (Lang::En, FetchTimes(s)) => write!(f, "Fetch time per route: {}", Timings(s)),
+            (Lang::Ko, FetchTimes(s)) => write!(f, "노선별 수집 시간: {}", Timings(s)),
+            (Lang::En, StationMapDone) => write!(f, "✓ Station map generated."),
+            (Lang::Ko, StationMapDone) => write!(f, "✓ 정류장 지도를 생성했습니다."),
+            (Lang::En, SnapPhase(dir)) => {
+                write!(f, "[Phase 2] Processing raw data to GeoJSON: {:?}", dir)
+            }
+            (Lang::Ko, SnapPhase(dir)) => {
+                write!(f, "[2단계] 원본 데이터를 GeoJSON으로 변환합니다: {:?}", dir)
+            }
+            (Lang::En, Processing(file)) => write!(f, "Processing {}...", file),
+            (Lang::Ko, Processing(file)) => write!(f, "{} 처리 중...", file),
+            (Lang::En, SnapFailed(id, e)) => write!(f, "Processing {} failed: {:#}", id, e),
+            (Lang::Ko, SnapFailed(id, e)) => write!(f, "{} 처리 실패: {:#}", id, e),
+            (Lang::En, SnapInterrupted(n)) => {
+                write!(f, "Interrupted; {} route(s) left for --retry-failed", n)
+            }
+            (Lang::Ko, SnapInterrupted(n)) => write!(
+                f,
+                "중단됨; 남은 노선 {}개는 --retry-failed로 이어서 처리하세요",
+                n
+            ),
+            (Lang::En, SnapTimes(s)) => write!(f, "Snapping time per route: {}", Timings(s)),
+            (Lang::Ko, SnapTimes(s)) => write!(f, "노선별 변환 시간: {}", Timings(s)),
+            (Lang::En, SkippedUpToDate(n)) => write!(
+                f,
+                "Skipped {} up-to-date routes (use --reprocess-all to re-snap them)",
+                n
+            ),
+            (Lang::Ko, SkippedUpToDate(n)) => write!(
+                f,
+                "최신 상태인 노선 {}개를 건너뛰었습니다 (다시 변환하려면 --reprocess-all)",
+                n
+            ),
+            (Lang::En, RoutesFailed(n)) => write!(
+                f,
+                "{} route(s) failed; re-run with --retry-failed to retry them",
+                n
+            ),
+            (Lang::Ko, RoutesFailed(n)) => write!(
+                f,
+                "노선 {}개 실패; --retry-failed로 다시 실행하면 재시도합니다",
+                n
+            ),
+            (Lang::En, PipelineComplete(secs)) => {
+                write!(f, "✓ Pipeline Complete. (Phase 2: {:.1}s)", secs)
+            }
+            (Lang::Ko, PipelineComplete(secs)) => {
+                write!(f, "✓ 파이프라인 완료. (2단계: {:.1}초)", secs)
+            }
+
+            (Lang::En, ScheduleStart) => {
+                write!(f, "Starting Bus Schedule Crawler (Browser Mimic Mode)")
+            }
+            (Lang::Ko, ScheduleStart) => {
+                write!(f, "버스 시간표 수집을 시작합니다 (브라우저 모방 모드)")
+            }
+            (Lang::En, FetchingMainPage) => {
+                write!(f, "Fetching main page (Initializing Session)...")
+            }
+            (Lang::Ko, FetchingMainPage) => write!(f, "메인 페이지를 가져오는 중 (세션 초기화)..."),
+            (Lang::En, RetryingSchedules(n)) => {
+                write!(f, "Retrying {} failed route schedules...", n)
+            }
+            (Lang::Ko, RetryingSchedules(n)) => {
+                write!(f, "실패한 노선 시간표 {}개를 다시 시도합니다...", n)
+            }
+            (Lang::En, FoundRouteInfo(n)) => write!(f, "✓ Found info for {} routes", n),
+            (Lang::Ko, FoundRouteInfo(n)) => write!(f, "✓ 노선 {}개의 정보를 찾았습니다", n),
+            (Lang::En, FoundSchedules(n)) => {
+                write!(f, "✓ Found {} route schedules to process", n)
+            }
+            (Lang::Ko, FoundSchedules(n)) => {
+                write!(f, "✓ 처리할 노선 시간표 {}개를 찾았습니다", n)
+            }
+            (
+                Lang::En,
+                ScheduleParsed {
+                    progress,
+                    route_id,
+                    times,
+                },
+            ) => {
+                write!(f, "{} ✓ {} ({} times)", progress, route_id, times)
+            }
+            (
+                Lang::Ko,
+                ScheduleParsed {
+                    progress,
+                    route_id,
+                    times,
+                },
+            ) => {
+                write!(f, "{} ✓ {} (시각 {}개)", progress, route_id, times)
+            }
+            (Lang::En, ScheduleEmpty { progress, route_id }) => {
+                write!(f, "{} {}: 0 times. (HTML Check Saved)", progress, route_id)
+            }
+            (Lang::Ko, ScheduleEmpty { progress, route_id }) => {
+                write!(f, "{} {}: 시각 0개. (확인용 HTML 저장)", progress, route_id)
+            }
+            (Lang::En, ScheduleUnchanged { progress, route_id }) => {
+                write!(f, "{} {}: unchanged, kept as saved", progress, route_id)
+            }
+            (Lang::Ko, ScheduleUnchanged { progress, route_id }) => {
+                write!(
+                    f,
+                    "{} {}: 변경 없음, 저장된 시간표 유지",
+                    progress, route_id
+                )
+            }
+            (
+                Lang::En,
+                Politeness {
+                    delay_ms,
+                    crawl_delay,
+                    robots_read,
+                },
+            ) => {
+                write!(f, "Pausing {} ms between detail pages ", delay_ms)?;
+                match (robots_read, crawl_delay) {
+                    (false, _) => write!(f, "(robots.txt not read)"),
+                    (true, Some(secs)) => write!(f, "(robots.txt Crawl-delay: {} s)", secs),
+                    (true, None) => write!(f, "(robots.txt allows crawling, no Crawl-delay)"),
+                }
+            }
+            (
+                Lang::Ko,
+                Politeness {
+                    delay_ms,
+                    crawl_delay,
+                    robots_read,
+                },
+            ) => {
+                write!(f, "상세 페이지 요청 간격 {} ms ", delay_ms)?;
+                match (robots_read, crawl_delay) {
+                    (false, _) => write!(f, "(robots.txt 확인 안 함)"),
+                    (true, Some(secs)) => write!(f, "(robots.txt Crawl-delay: {}초)", secs),
+                    (true, None) => write!(f, "(robots.txt 수집 허용, Crawl-delay 없음)"),
+                }
+            }
+            (Lang::En, SavingSchedules) => write!(f, "Organizing and saving schedules..."),
+            (Lang::Ko, SavingSchedules) => write!(f, "시간표를 정리해 저장하는 중..."),
+            (
+                Lang::En,
+                CoverageGap {
+                    route_no,
+                    day_types,
+                    missing,
+                },
+            ) => write!(
+                f,
+                "Route {} has {} schedule(s) but none for {}",
+                route_no, day_types, missing
+            ),
+            (
+                Lang::Ko,
+                CoverageGap {
+                    route_no,
+                    day_types,
+                    missing,
+                },
+            ) => write!(
+                f,
+                "{}번 노선: {} 시간표는 있지만 {} 시간표가 없습니다",
+                route_no, day_types, missing
+            ),
+            (Lang::En, SchedulesFailed(n)) => write!(
+                f,
+                "{} route schedule(s) failed; re-run with --retry-failed to retry them",
+                n
+            ),
+            (Lang::Ko, SchedulesFailed(n)) => write!(
+                f,
+                "노선 시간표 {}개 실패; --retry-failed로 다시 실행하면 재시도합니다",
+                n
+            ),
+            (Lang::En, CrawlWarnings(n)) => write!(f, "{} warning(s) during crawl:", n),
+            (Lang::Ko, CrawlWarnings(n)) => write!(f, "수집 중 경고 {}개:", n),
+
+            (Lang::En, StepStart(step)) => write!(f, "▶ Step: {}", step),
+            (Lang::Ko, StepStart(step)) => write!(f, "▶ 단계: {}", step),
+            (Lang::En, StepSkipped(step)) => write!(f, "Skipping the {} step", step),
+            (Lang::Ko, StepSkipped(step)) => write!(f, "{} 단계를 건너뜁니다", step),
+            (Lang::En, StepFailedItems { step, count }) => {
+                write!(f, "Step {} finished with {} failed item(s)", step, count)
+            }
+            (Lang::Ko, StepFailedItems { step, count }) => {
+                write!(f, "{} 단계 완료, 실패 항목 {}개", step, count)
+            }
+            (Lang::En, Validated { routes, schedules }) => {
+                write!(
+                    f,
+                    "✓ Validated {} routes and {} schedules",
+                    routes, schedules
+                )
+            }
+            (Lang::Ko, Validated { routes, schedules }) => {
+                write!(
+                    f,
+                    "✓ 노선 {}개와 시간표 {}개를 검증했습니다",
+                    routes, schedules
+                )
+            }
+            (Lang::En, PipelineFinished) => write!(f, "✓ Pipeline finished"),
+            (Lang::Ko, PipelineFinished) => write!(f, "✓ 파이프라인 완료"),
+
+            (Lang::En, WatchStart(minutes)) => {
+                write!(f, "Watching the upstreams every {} min", minutes)
+            }
+            (Lang::Ko, WatchStart(minutes)) => {
+                write!(f, "{}분마다 원본 데이터의 변경을 확인합니다", minutes)
+            }
+            (Lang::En, UpstreamUnchanged(name)) => write!(f, "No changes in the {}", name),
+            (Lang::Ko, UpstreamUnchanged(name)) => write!(f, "{}: 변경 없음", name),
+            (
+                Lang::En,
+                UpstreamChanged {
+                    name,
+                    before,
+                    after,
+                },
+            ) => match before {
+                Some(before) => write!(
+                    f,
+                    "The {} changed ({} -> {} items), re-crawling",
+                    name, before, after
+                ),
+                None => write!(f, "First check of the {} ({} items), crawling", name, after),
+            },
+            (
+                Lang::Ko,
+                UpstreamChanged {
+                    name,
+                    before,
+                    after,
+                },
+            ) => match before {
+                Some(before) => write!(
+                    f,
+                    "{} 변경 감지 (항목 {} -> {}개), 다시 수집합니다",
+                    name, before, after
+                ),
+                None => write!(f, "{} 첫 확인 (항목 {}개), 수집합니다", name, after),
+            },
+            (Lang::En, ProbeFailed(name, e)) => {
+                write!(
+                    f,
+                    "Could not check the {}, retrying next time: {:#}",
+                    name, e
+                )
+            }
+            (Lang::Ko, ProbeFailed(name, e)) => {
+                write!(f, "{} 확인 실패, 다음 확인 때 재시도합니다: {:#}", name, e)
+            }
+            (Lang::En, WatchCrawlFailed(e)) => {
+                write!(f, "Crawl failed, retrying at the next check: {:#}", e)
+            }
+            (Lang::Ko, WatchCrawlFailed(e)) => {
+                write!(f, "수집 실패, 다음 확인 때 재시도합니다: {:#}", e)
+            }
+            (Lang::En, WatchStopped) => write!(f, "Stopped watching"),
+            (Lang::Ko, WatchStopped) => write!(f, "변경 확인을 중단했습니다"),
+
+            (Lang::En, SummaryOutcome { command, ok }) => {
+                write!(f, "Polly {} {}", command, if *ok { "ok" } else { "failed" })
+            }
+            (Lang::Ko, SummaryOutcome { command, ok }) => {
+                write!(f, "Polly {} {}", command, if *ok { "성공" } else { "실패" })
+            }
+            (Lang::En, SummaryDuration(secs)) => {
+                write!(f, "in {}m {:02}s", secs / 60, secs % 60)
+            }
+            (Lang::Ko, SummaryDuration(secs)) => {
+                write!(f, "{}분 {:02}초 소요", secs / 60, secs % 60)
+            }
+            (Lang::En, SummaryFailedItems(n)) => write!(f, "{} failed item(s)", n),
+            (Lang::Ko, SummaryFailedItems(n)) => write!(f, "실패 항목 {}개", n),
+            (Lang::En, SummaryLastError(e)) => write!(f, "Last error: {}", e),
+            (Lang::Ko, SummaryLastError(e)) => write!(f, "마지막 오류: {}", e),
+        }
+    }
+}
+
+/// E.g., "p50 0.4s, p90 1.2s, p99 2.0s, max 2.3s (WJB251000123); 840 KB written".
+struct Timings<'a>(&'a MetricsSummary);
+
+impl fmt::Display for Timings<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = self.0;
+        let secs = |ms: u64| ms as f64 / 1000.0;
+        let written = match current() {
+            Lang::En => "written",
+            Lang::Ko => "기록",
+        };
+        write!(
+            f,
+            "p50 {:.1}s, p90 {:.1}s, p99 {:.1}s, max {:.1}s ({}); {} KB {}",
+            secs(s.p50_ms),
+            secs(s.p90_ms),
+            secs(s.p99_ms),
+            secs(s.max_ms),
+            s.slowest,
+            s.total_bytes.div_ceil(1024),
+            written
+        )
+    }
+}
@@ -0,0 +1,46 @@
+//! Ctrl+C handling.
+//!
+//! The first Ctrl+C is only recorded, so a long-running phase can stop taking
+//! new work and save what it has collected so far; a second one exits at once.
+
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Exit status of a process killed by SIGINT, by shell convention
+const SIGINT_EXIT_CODE: i32 = 130;
+
+/// Whether Ctrl+C has been pressed since [`Interrupt::install`].
+#[derive(Clone)]
+pub struct Interrupt(watch::Receiver<bool>);
+
+impl Interrupt {
+    /// Installs the Ctrl+C handler. Must be called within the Tokio runtime.
+    pub fn install() -> Self {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_err() {
+                // No handler; the default one still terminates the process. Keep
+                // the sender alive so waiting never resolves.
+                return std::future::pending::<()>().await;
+            }
+            warn!("Interrupted; saving progress (press Ctrl+C again to exit now)");
+            tx.send_replace(true);
+
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(SIGINT_EXIT_CODE);
+            }
+            std::future::pending::<()>().await
+        });
+        Self(rx)
+    }
+
+    pub fn is_set(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once Ctrl+C has been pressed.
+    pub async fn wait(&mut self) {
+        // The sender lives as long as the runtime, so this only fails on shutdown.
+        let _ = self.0.wait_for(|&set| set).await;
+    }
+}
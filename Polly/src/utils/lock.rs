@@ -0,0 +1,113 @@
+//! Run locks.
+//!
+//! `route` and `schedule` hold an exclusive lock on `.polly.lock` in their
+//! output directory while they run, so two crawls (e.g., overlapping cron
+//! jobs) never interleave their writes to `routeMap.json` and the other
+//! merged outputs. A second run fails at once, or waits with `--wait-lock`.
+//!
+//! The lock is an OS file lock, released when the process exits, however it
+//! exits; a lock file left behind by a crashed run does not block anything.
+//! The file also names its holder, for the error message of the next run.
+
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::info;
+
+use crate::error::{ExitError, ExitStatus};
+use crate::utils::dry_run;
+
+/// File name of the lock within an output directory.
+pub const LOCK_FILE: &str = ".polly.lock";
+
+/// How often a waiting run retries the lock
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Held for the duration of a run; dropping it releases the lock.
+pub struct RunLock {
+    _file: Option<File>,
+}
+
+/// The run holding a lock, as written into the lock file
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Holder {
+    pid: u32,
+    command: String,
+    started_at: String,
+}
+
+/// Locks `dir` for `command`. If another run holds the lock, fails with
+/// [`ExitStatus::Locked`], or with `wait`, waits until it is released.
+pub async fn acquire(dir: &Path, command: &str, wait: bool) -> Result<RunLock> {
+    // A dry run writes nothing, so it neither takes nor waits for the lock.
+    if dry_run::is_enabled() {
+        return Ok(RunLock { _file: None });
+    }
+
+    let path = dir.join(LOCK_FILE);
+    let mut file = File::options()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Opening lock file {:?}", path))?;
+
+    let mut waiting = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {
+                let holder = describe_holder(&path);
+                if !wait {
+                    return Err(ExitError::new(
+                        ExitStatus::Locked,
+                        format!(
+                            "{:?} is locked by another run ({}); wait for it to finish, or pass --wait-lock",
+                            dir, holder
+                        ),
+                    )
+                    .into());
+                }
+                if !waiting {
+                    waiting = true;
+                    info!("Waiting for the lock on {:?}, held by {}...", dir, holder);
+                }
+                sleep(POLL_INTERVAL).await;
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Locking {:?}", path));
+            }
+        }
+    }
+
+    let holder = Holder {
+        pid: std::process::id(),
+        command: command.to_string(),
+        started_at: Local::now().to_rfc3339(),
+    };
+    file.set_len(0)?;
+    file.write_all(serde_json::to_string(&holder)?.as_bytes())?;
+    if waiting {
+        info!("✓ Acquired the lock on {:?}", dir);
+    }
+    Ok(RunLock { _file: Some(file) })
+}
+
+/// E.g., "route, pid 4242, since 2026-10-16T03:00:00+09:00".
+fn describe_holder(path: &Path) -> String {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Holder>(&text).ok())
+        .map_or_else(
+            || "unknown process".to_string(),
+            |h| format!("{}, pid {}, since {}", h.command, h.pid, h.started_at),
+        )
+}
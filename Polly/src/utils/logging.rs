@@ -0,0 +1,144 @@
+//! Log Output
+//!
+//! Sets up the tracing subscriber. Progress and warnings are tracing events
+//! with fields such as `route_id`, `phase`, and `outcome`; the console shows
+//! just their messages, while `--log-format json` or `--log-file` keep the
+//! fields for log pipelines. Events are filtered per module with `RUST_LOG`
+//! directives (e.g., `RUST_LOG=info,reqwest=warn`), or coarsely with `-v`/`-q`.
+
+use std::fmt::{self as std_fmt, Write as _};
+use std::fs::OpenOptions;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::{self, FormatEvent, FormatFields};
+use tracing_subscriber::fmt::{self, FmtContext};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::utils::events;
+
+/// Filter used when `RUST_LOG` is unset
+const DEFAULT_FILTER: &str = "info";
+
+/// Filter for the `-v`/`-q` flags, or `None` without either. `-v` shows the
+/// per-route events of Polly itself; `-vv` adds its traces and the debug
+/// events of the HTTP stack.
+pub fn verbosity_filter(verbose: u8, quiet: bool) -> Option<&'static str> {
+    match (quiet, verbose) {
+        (true, _) => Some("warn"),
+        (false, 0) => None,
+        (false, 1) => Some("info,Polly=debug"),
+        (false, _) => Some("debug,Polly=trace"),
+    }
+}
+
+/// How log events are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// One human-readable line per event
+    Pretty,
+    /// One JSON object per line, for journald, Loki, and other log pipelines
+    Json,
+}
+
+/// Installs the global subscriber. `verbosity` (see [`verbosity_filter`])
+/// takes precedence over `RUST_LOG`.
+///
+/// Without `file`, events go to stderr in `format`, where `pretty` is the
+/// console format. With `file`, the console format stays on stderr and the
+/// file gets every event in `format`, with timestamps and fields.
+pub fn init(format: LogFormat, file: Option<&Path>, verbosity: Option<&str>) -> Result<()> {
+    let filter = match (verbosity, std::env::var(EnvFilter::DEFAULT_ENV)) {
+        (Some(directives), _) => EnvFilter::new(directives),
+        (None, Ok(directives)) if !directives.is_empty() => EnvFilter::try_new(&directives)
+            .with_context(|| format!("Invalid RUST_LOG filter {:?}", directives))?,
+        _ => EnvFilter::new(DEFAULT_FILTER),
+    };
+
+    let console = (file.is_some() || format == LogFormat::Pretty).then(|| {
+        fmt::layer()
+            .event_format(Console)
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::stderr().is_terminal())
+    });
+
+    let structured = match file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Opening log file {:?}", path))?;
+            let layer = fmt::layer().with_writer(Mutex::new(file)).with_ansi(false);
+            Some(match format {
+                LogFormat::Pretty => layer.boxed(),
+                LogFormat::Json => layer.json().flatten_event(true).boxed(),
+            })
+        }
+        None => (format == LogFormat::Json).then(|| {
+            fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_writer(std::io::stderr)
+                .boxed()
+        }),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(structured)
+        .with(events::layer())
+        .try_init()
+        .context("Installing the log subscriber")
+}
+
+/// Console format: the message alone, prefixed for warnings and errors.
+struct Console;
+
+impl<S, N> FormatEvent<S, N> for Console
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std_fmt::Result {
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => Some(("Error: ", "\x1b[31m")),
+            Level::WARN => Some(("Warning: ", "\x1b[33m")),
+            _ => None,
+        };
+        if let Some((label, color)) = prefix {
+            if writer.has_ansi_escapes() {
+                write!(writer, "{}{}\x1b[0m", color, label)?;
+            } else {
+                writer.write_str(label)?;
+            }
+        }
+
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        writeln!(writer, "{}", message.0)
+    }
+}
+
+/// Collects the `message` field of an event.
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std_fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        }
+    }
+}
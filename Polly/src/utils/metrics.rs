@@ -0,0 +1,191 @@
+//! Prometheus metrics.
+//!
+//! Counters and histograms of the crawl, served in the Prometheus text format
+//! on `/metrics` while a command runs with `--metrics-addr`:
+//!
+//! - `polly_http_requests_total{upstream, status}`: requests by upstream host
+//!   and HTTP status (`error` when no response arrived)
+//! - `polly_http_request_duration_seconds{upstream}`: request latency
+//! - `polly_routes_processed_total{phase, outcome}`: routes (or schedule
+//!   variants) done, by phase and outcome, as in the log events
+//! - `polly_route_duration_seconds{phase}`: time spent per route
+//! - `polly_failures_total{stage}`: items recorded in `failures.json`
+
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+use url::Url;
+
+use crate::utils::status;
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    routes: IntCounterVec,
+    route_duration: HistogramVec,
+    failures: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new(
+                "polly_http_requests_total",
+                "HTTP requests by upstream and status",
+            ),
+            &["upstream", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "polly_http_request_duration_seconds",
+                "HTTP request latency",
+            )
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["upstream"],
+        )
+        .unwrap();
+        let routes = IntCounterVec::new(
+            Opts::new(
+                "polly_routes_processed_total",
+                "Routes processed by phase and outcome",
+            ),
+            &["phase", "outcome"],
+        )
+        .unwrap();
+        let route_duration = HistogramVec::new(
+            HistogramOpts::new("polly_route_duration_seconds", "Processing time per route")
+                .buckets(vec![
+                    0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+                ]),
+            &["phase"],
+        )
+        .unwrap();
+        let failures = IntCounterVec::new(
+            Opts::new("polly_failures_total", "Failed items by stage"),
+            &["stage"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(routes.clone())).unwrap();
+        registry.register(Box::new(route_duration.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            routes,
+            route_duration,
+            failures,
+        }
+    }
+}
+
+/// Counts a request to `url` that ended with `status` (a status code, or `error`).
+pub fn observe_request(url: &Url, status: &str, elapsed: Duration) {
+    let upstream = url.host_str().unwrap_or("unknown");
+    METRICS
+        .http_requests
+        .with_label_values(&[upstream, status])
+        .inc();
+    METRICS
+        .http_duration
+        .with_label_values(&[upstream])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Counts a route done in `phase` with `outcome` (e.g., `ok`, `reused`, `degraded`).
+pub fn route_processed(phase: &str, outcome: &str) {
+    METRICS.routes.with_label_values(&[phase, outcome]).inc();
+}
+
+/// Records how long a route took in `phase`.
+pub fn observe_route(phase: &str, elapsed: Duration) {
+    METRICS
+        .route_duration
+        .with_label_values(&[phase])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Counts an item that failed in `stage`.
+pub fn failure(stage: &str) {
+    METRICS.failures.with_label_values(&[stage]).inc();
+}
+
+/// Serves `/metrics` on `addr` in the background for the rest of the run.
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Binding metrics endpoint {}", addr))?;
+    info!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(respond(stream));
+                }
+                Err(e) => debug!("Metrics connection failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answers one HTTP request: the metrics for `GET /metrics`, the run status
+/// (`status.json`) for `GET /status`, 404 otherwise.
+async fn respond(mut stream: TcpStream) {
+    let mut buf = [0u8; 1024];
+    let Ok(n) = stream.read(&mut buf).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let mut request_line = request.split_whitespace();
+
+    let encoder = TextEncoder::new();
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let mut body = Vec::new();
+            encoder.encode(&METRICS.registry.gather(), &mut body).ok();
+            ("200 OK", encoder.format_type(), body)
+        }
+        (Some("GET"), Some("/status")) => match status::current() {
+            Some(current) => (
+                "200 OK",
+                "application/json",
+                serde_json::to_vec_pretty(&current).unwrap_or_default(),
+            ),
+            None => ("404 Not Found", "text/plain", b"No run status\n".to_vec()),
+        },
+        _ => ("404 Not Found", "text/plain", b"Not found\n".to_vec()),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    if stream.write_all(head.as_bytes()).await.is_ok() {
+        stream.write_all(&body).await.ok();
+    }
+    stream.shutdown().await.ok();
+}
@@ -3,47 +3,212 @@
 //! This module itself contains general utility functions, while specific utilities
 //! are organized into submodules.
 
+pub mod alert;
+#[cfg(feature = "bench")]
+pub mod alloc;
+pub mod cache;
+pub mod cassette;
+pub mod compress;
+pub mod crs;
+pub mod dry_run;
+pub mod events;
+pub mod failures;
+pub mod flatgeobuf;
 pub mod geo;
+pub mod holidays;
+pub mod http;
+pub mod i18n;
+pub mod interrupt;
+pub mod lock;
+pub mod logging;
+pub mod metrics;
+pub mod packed_rtree;
+pub mod polyline;
+pub mod report;
+pub mod romanize;
+pub mod routes_file;
+pub mod state;
+pub mod status;
 
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
-use serde_json::Value;
+use chrono::{DateTime, Local, TimeZone};
+use sha2::{Digest, Sha256};
+
+use crate::error::PipelineError;
 
 pub fn ensure_dir(path: &Path) -> Result<()> {
-    if !path.exists() {
-        fs::create_dir_all(path)?;
+    if !path.exists() && !dry_run::is_enabled() {
+        fs::create_dir_all(path).map_err(|e| PipelineError::io("Creating", path, e))?;
     }
     Ok(())
 }
 
-pub fn get_env(key: &str) -> String {
-    std::env::var(key).unwrap_or_else(|_| "".to_string())
+/// Writes `contents` to `path` atomically: the data goes to a temporary file
+/// in the same directory, which is then renamed over `path`. An interrupted
+/// run leaves either the previous file or the new one, never a truncated one.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if dry_run::is_enabled() {
+        let contents = contents.as_ref();
+        dry_run::record_write(path, contents.len() as u64, &Sha256::digest(contents));
+        return Ok(());
+    }
+    let tmp = temp_path(path);
+
+    let contents = contents.as_ref();
+    let result = fs::write(&tmp, contents).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    result.map_err(|e| PipelineError::io("Writing", path, e))?;
+    events::file_written(path, contents.len() as u64);
+    Ok(())
 }
 
-pub fn resolve_url(key: &str, default: &str) -> String {
-    let v = get_env(key);
-    if v.is_empty() { default.to_string() } else { v }
+/// Removes a file this tool wrote earlier (only reported in a dry run).
+pub fn remove_file(path: &Path) -> Result<()> {
+    if dry_run::is_enabled() {
+        dry_run::record_removal(path);
+        return Ok(());
+    }
+    Ok(fs::remove_file(path).map_err(|e| PipelineError::io("Removing", path, e))?)
 }
 
-pub fn extract_items(json: &Value) -> Result<Vec<Value>> {
-    let items = &json["response"]["body"]["items"]["item"];
-    if let Some(arr) = items.as_array() {
-        Ok(arr.clone())
-    } else if let Some(obj) = items.as_object() {
-        Ok(vec![Value::Object(obj.clone())])
+/// Serializes `value` as JSON straight into `path`, atomically like
+/// [`write_atomic`], without building the whole text in memory first.
+pub fn write_json_atomic(path: &Path, value: &impl serde::Serialize, pretty: bool) -> Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    if pretty {
+        serde_json::to_writer_pretty(&mut file, value)?;
     } else {
-        Ok(vec![])
+        serde_json::to_writer(&mut file, value)?;
     }
+    file.commit()
 }
 
-pub fn parse_flexible_string(v: &Value) -> String {
-    if let Some(s) = v.as_str() {
-        s.to_string()
-    } else if let Some(n) = v.as_i64() {
-        n.to_string()
-    } else {
-        "UNKNOWN".to_string()
+/// A buffered file written in pieces, then moved into place atomically like
+/// [`write_atomic`]. Dropped without [`AtomicFile::commit`], it leaves the
+/// previous file untouched.
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp: PathBuf,
+    writer: Option<Sink>,
+}
+
+/// Where the data of an [`AtomicFile`] goes
+enum Sink {
+    File(BufWriter<File>),
+    /// Dry run: only measured, to report what would be written
+    DryRun {
+        bytes: u64,
+        hasher: Sha256,
+    },
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let tmp = temp_path(path);
+        let writer = if dry_run::is_enabled() {
+            Sink::DryRun {
+                bytes: 0,
+                hasher: Sha256::new(),
+            }
+        } else {
+            Sink::File(BufWriter::new(
+                File::create(&tmp).map_err(|e| PipelineError::io("Writing", path, e))?,
+            ))
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            tmp,
+            writer: Some(writer),
+        })
     }
+
+    /// Flushes the data and renames the file over the target path.
+    pub fn commit(mut self) -> Result<()> {
+        let writer = match self.writer.take().expect("not committed") {
+            Sink::File(writer) => writer,
+            Sink::DryRun { bytes, hasher } => {
+                dry_run::record_write(&self.path, bytes, &hasher.finalize());
+                return Ok(());
+            }
+        };
+        let result = writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|_| fs::rename(&self.tmp, &self.path));
+        if result.is_err() {
+            fs::remove_file(&self.tmp).ok();
+        }
+        result.map_err(|e| PipelineError::io("Writing", &self.path, e))?;
+        let bytes = fs::metadata(&self.path).map_or(0, |m| m.len());
+        events::file_written(&self.path, bytes);
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.writer.as_mut().expect("not committed") {
+            Sink::File(writer) => writer.write(buf),
+            Sink::DryRun { bytes, hasher } => {
+                *bytes += buf.len() as u64;
+                hasher.update(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut().expect("not committed") {
+            Sink::File(writer) => writer.flush(),
+            Sink::DryRun { .. } => Ok(()),
+        }
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Still open: not committed, so discard the partial file.
+        if let Some(Sink::File(_)) = self.writer.take() {
+            fs::remove_file(&self.tmp).ok();
+        }
+    }
+}
+
+/// Hidden temporary file next to `path`
+fn temp_path(path: &Path) -> PathBuf {
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(format!(".tmp{}", fastrand::u32(..)));
+    path.with_file_name(tmp_name)
+}
+
+pub fn get_env(key: &str) -> String {
+    std::env::var(key).unwrap_or_else(|_| "".to_string())
+}
+
+/// Current time used for output timestamps (`lastUpdated`, `fetched_at`).
+///
+/// Honors `SOURCE_DATE_EPOCH` (seconds since the Unix epoch) so that repeated
+/// runs over identical upstream data can produce byte-identical files.
+pub fn now() -> DateTime<Local> {
+    get_env("SOURCE_DATE_EPOCH")
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| Local.timestamp_opt(secs, 0).single())
+        .unwrap_or_else(Local::now)
+}
+
+/// Hex-encoded SHA-256 digest of `data`, truncated to `len` characters.
+/// Stable across runs and platforms, for IDs and content fingerprints.
+pub fn short_hash(data: &[u8], len: usize) -> String {
+    let digest = Sha256::digest(data);
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    hex[..len.min(hex.len())].to_string()
 }
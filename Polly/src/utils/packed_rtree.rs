@@ -0,0 +1,317 @@
+//! Packed Hilbert R-trees.
+//!
+//! Static R-trees as used by FlatGeobuf and the `flatbush` JS library: items
+//! are sorted along a Hilbert curve and packed bottom-up into nodes of a fixed
+//! size, so the whole tree is one flat array that readers can search with
+//! range requests, or load into memory without parsing.
+
+use std::ops::Range;
+
+/// `[min_x, min_y, max_x, max_y]`
+pub type Bbox = [f64; 4];
+
+/// Default number of children per node
+pub const NODE_SIZE: usize = 16;
+
+/// Smallest box containing both `a` and `b`.
+pub fn union(a: &Bbox, b: &Bbox) -> Bbox {
+    [
+        a[0].min(b[0]),
+        a[1].min(b[1]),
+        a[2].max(b[2]),
+        a[3].max(b[3]),
+    ]
+}
+
+/// Bounding box of `[x, y]` points, or `None` if there are none.
+pub fn bbox_of(points: &[Vec<f64>]) -> Option<Bbox> {
+    points
+        .iter()
+        .map(|p| [p[0], p[1], p[0], p[1]])
+        .reduce(|a, b| union(&a, &b))
+}
+
+/// Item indices sorted by the Hilbert value of their box centers, so that
+/// items close on the curve (and on the map) end up in the same nodes.
+pub fn hilbert_order(boxes: &[Bbox]) -> Vec<usize> {
+    let Some(extent) = boxes.iter().copied().reduce(|a, b| union(&a, &b)) else {
+        return Vec::new();
+    };
+    let width = (extent[2] - extent[0]).max(f64::EPSILON);
+    let height = (extent[3] - extent[1]).max(f64::EPSILON);
+    let max = f64::from(u16::MAX);
+
+    let values: Vec<u32> = boxes
+        .iter()
+        .map(|b| {
+            let x = (max * ((b[0] + b[2]) / 2.0 - extent[0]) / width).floor() as u32;
+            let y = (max * ((b[1] + b[3]) / 2.0 - extent[1]) / height).floor() as u32;
+            hilbert(x, y)
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..boxes.len()).collect();
+    order.sort_by_key(|&i| values[i]);
+    order
+}
+
+/// Node index ranges of each tree level, leaves first. Nodes are stored root
+/// first, so the leaves occupy the end of the array.
+pub fn level_bounds(num_items: usize, node_size: usize) -> Vec<Range<usize>> {
+    let mut n = num_items;
+    let mut level_sizes = vec![n];
+    loop {
+        n = n.div_ceil(node_size);
+        level_sizes.push(n);
+        if n <= 1 {
+            break;
+        }
+    }
+
+    let mut end: usize = level_sizes.iter().sum();
+    level_sizes
+        .into_iter()
+        .map(|size| {
+            let start = end - size;
+            let range = start..end;
+            end = start;
+            range
+        })
+        .collect()
+}
+
+/// Builds a FlatGeobuf packed R-tree over `leaves` (already in Hilbert order),
+/// each a box and the byte offset of its feature. Parent nodes hold the index
+/// of their first child node. Returns all nodes, root first.
+pub fn build(leaves: &[(Bbox, u64)], node_size: usize) -> Vec<(Bbox, u64)> {
+    let bounds = level_bounds(leaves.len(), node_size);
+    let total = bounds.iter().map(|r| r.len()).sum();
+    let mut nodes = vec![([0.0; 4], 0u64); total];
+    nodes[bounds[0].clone()].copy_from_slice(leaves);
+
+    for level in 0..bounds.len() - 1 {
+        let parents = bounds[level + 1].clone();
+        for (parent, first) in parents.zip(bounds[level].clone().step_by(node_size)) {
+            let last = (first + node_size).min(bounds[level].end);
+            let bbox = nodes[first..last]
+                .iter()
+                .map(|(b, _)| *b)
+                .reduce(|a, b| union(&a, &b))
+                .unwrap_or([0.0; 4]);
+            nodes[parent] = (bbox, first as u64);
+        }
+    }
+
+    nodes
+}
+
+/// Serializes nodes as FlatGeobuf index entries: the box as four f64 followed
+/// by the u64 offset, all little-endian.
+pub fn to_bytes(nodes: &[(Bbox, u64)]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(nodes.len() * 40);
+    for (bbox, offset) in nodes {
+        for v in bbox {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+    out
+}
+
+/// Serializes a `flatbush` (v4) index over `boxes`, loadable with
+/// `Flatbush.from(buffer)`. Search results are indices into `boxes`. Returns
+/// `None` without boxes, which flatbush cannot represent.
+///
+/// Layout: an 8-byte header (magic `0xfb`, format version 3 with the
+/// Float64Array type, u16 node size, u32 item count), the boxes of all nodes
+/// as f64, leaves first, then one index per node (u16 below 16384 nodes, else
+/// u32): the item index for leaves, and for parents the position of their first
+/// child in the box array. All little-endian.
+pub fn to_flatbush(boxes: &[Bbox], node_size: usize) -> Option<Vec<u8>> {
+    if boxes.is_empty() {
+        return None;
+    }
+
+    let mut nodes: Vec<(Bbox, usize)> = hilbert_order(boxes)
+        .into_iter()
+        .map(|i| (boxes[i], i))
+        .collect();
+    let mut level = 0..nodes.len();
+    loop {
+        let start = nodes.len();
+        for first in level.clone().step_by(node_size) {
+            let last = (first + node_size).min(level.end);
+            let bbox = nodes[first..last]
+                .iter()
+                .map(|(b, _)| *b)
+                .reduce(|a, b| union(&a, &b))
+                .unwrap_or([0.0; 4]);
+            nodes.push((bbox, first * 4));
+        }
+        level = start..nodes.len();
+        if level.len() == 1 {
+            break;
+        }
+    }
+
+    let wide_indices = nodes.len() >= 16384;
+    let mut out = Vec::with_capacity(8 + nodes.len() * (32 + 4));
+    out.push(0xfb);
+    out.push((3 << 4) | 8);
+    out.extend_from_slice(&(node_size as u16).to_le_bytes());
+    out.extend_from_slice(&(boxes.len() as u32).to_le_bytes());
+    for (bbox, _) in &nodes {
+        for v in bbox {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    for &(_, index) in &nodes {
+        if wide_indices {
+            out.extend_from_slice(&(index as u32).to_le_bytes());
+        } else {
+            out.extend_from_slice(&(index as u16).to_le_bytes());
+        }
+    }
+    Some(out)
+}
+
+/// Position of (x, y) on a 16-bit Hilbert curve (the flatbush algorithm,
+/// after "Fast Hilbert curve generation" by rawrunprotected).
+fn hilbert(x: u32, y: u32) -> u32 {
+    let mut a = x ^ y;
+    let mut b = 0xFFFF ^ a;
+    let mut c = 0xFFFF ^ (x | y);
+    let mut d = x & (y ^ 0xFFFF);
+
+    let mut aa = a | (b >> 1);
+    let mut bb = (a >> 1) ^ a;
+    let mut cc = ((c >> 1) ^ (b & (d >> 1))) ^ c;
+    let mut dd = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
+
+    (a, b, c, d) = (aa, bb, cc, dd);
+    aa = (a & (a >> 2)) ^ (b & (b >> 2));
+    bb = (a & (b >> 2)) ^ (b & ((a ^ b) >> 2));
+    cc ^= (a & (c >> 2)) ^ (b & (d >> 2));
+    dd ^= (b & (c >> 2)) ^ ((a ^ b) & (d >> 2));
+
+    (a, b, c, d) = (aa, bb, cc, dd);
+    aa = (a & (a >> 4)) ^ (b & (b >> 4));
+    bb = (a & (b >> 4)) ^ (b & ((a ^ b) >> 4));
+    cc ^= (a & (c >> 4)) ^ (b & (d >> 4));
+    dd ^= (b & (c >> 4)) ^ ((a ^ b) & (d >> 4));
+
+    (a, b, c, d) = (aa, bb, cc, dd);
+    cc ^= (a & (c >> 8)) ^ (b & (d >> 8));
+    dd ^= (b & (c >> 8)) ^ ((a ^ b) & (d >> 8));
+
+    a = cc ^ (cc >> 1);
+    b = dd ^ (dd >> 1);
+
+    let mut i0 = x ^ y;
+    let mut i1 = b | (0xFFFF ^ (i0 | a));
+
+    i0 = (i0 | (i0 << 8)) & 0x00FF00FF;
+    i0 = (i0 | (i0 << 4)) & 0x0F0F0F0F;
+    i0 = (i0 | (i0 << 2)) & 0x33333333;
+    i0 = (i0 | (i0 << 1)) & 0x55555555;
+
+    i1 = (i1 | (i1 << 8)) & 0x00FF00FF;
+    i1 = (i1 | (i1 << 4)) & 0x0F0F0F0F;
+    i1 = (i1 | (i1 << 2)) & 0x33333333;
+    i1 = (i1 | (i1 << 1)) & 0x55555555;
+
+    (i1 << 1) | i0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> Bbox {
+        [x, y, x, y]
+    }
+
+    /// `n` unit boxes along a diagonal, with their index as the offset
+    fn leaves(n: usize) -> Vec<(Bbox, u64)> {
+        (0..n)
+            .map(|i| {
+                let v = i as f64;
+                ([v, -v, v + 1.0, -v + 1.0], i as u64)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn level_bounds_match_flatgeobuf() {
+        // 100 items: 7 parents and the root, stored root first
+        assert_eq!(level_bounds(100, 16), [8..108, 1..8, 0..1]);
+        assert_eq!(level_bounds(300, 16), [22..322, 3..22, 1..3, 0..1]);
+        assert_eq!(level_bounds(16, 16), [1..17, 0..1]);
+        // A single item still gets a root.
+        assert_eq!(level_bounds(1, 16), [1..2, 0..1]);
+    }
+
+    #[test]
+    fn hilbert_order_follows_the_curve() {
+        let boxes = [
+            point(10.0, 0.0),
+            point(0.0, 10.0),
+            point(0.0, 0.0),
+            point(10.0, 10.0),
+        ];
+        assert_eq!(hilbert_order(&boxes), [2, 1, 3, 0]);
+        assert!(hilbert_order(&[]).is_empty());
+        // Identical boxes have no extent, but still sort.
+        assert_eq!(hilbert_order(&[point(1.0, 1.0); 3]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn parents_cover_their_children() {
+        let leaves = leaves(40);
+        let nodes = build(&leaves, 4);
+        let bounds = level_bounds(40, 4);
+        assert_eq!(nodes.len(), 40 + 10 + 3 + 1);
+        assert_eq!(&nodes[bounds[0].clone()], &leaves[..]);
+
+        // The root box is the union of all leaves.
+        let all = leaves.iter().map(|(b, _)| *b).reduce(|a, b| union(&a, &b));
+        assert_eq!(nodes[0].0, all.unwrap());
+        assert_eq!(nodes[0].0, [0.0, -39.0, 40.0, 1.0]);
+
+        // Every parent points at its first child and bounds its children.
+        for level in 1..bounds.len() {
+            let children = bounds[level - 1].clone();
+            for (k, parent) in bounds[level].clone().enumerate() {
+                let first = children.start + k * 4;
+                let last = (first + 4).min(children.end);
+                assert_eq!(nodes[parent].1, first as u64);
+                let covered = nodes[first..last]
+                    .iter()
+                    .map(|(b, _)| *b)
+                    .reduce(|a, b| union(&a, &b));
+                assert_eq!(nodes[parent].0, covered.unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_serialize_as_40_little_endian_bytes() {
+        let bytes = to_bytes(&[([1.0, 2.0, 3.0, 4.0], 7)]);
+        assert_eq!(bytes.len(), 40);
+        assert_eq!(bytes[..8], 1.0f64.to_le_bytes());
+        assert_eq!(bytes[24..32], 4.0f64.to_le_bytes());
+        assert_eq!(bytes[32..], 7u64.to_le_bytes());
+    }
+
+    #[test]
+    fn flatbush_header_and_size() {
+        let boxes: Vec<Bbox> = leaves(20).into_iter().map(|(b, _)| b).collect();
+        let bytes = to_flatbush(&boxes, 16).unwrap();
+        assert_eq!(bytes[..2], [0xfb, 0x38]);
+        assert_eq!(bytes[2..4], 16u16.to_le_bytes());
+        assert_eq!(bytes[4..8], 20u32.to_le_bytes());
+        // 20 leaves, 2 parents and the root, with a box and a u16 index each
+        assert_eq!(bytes.len(), 8 + 23 * (32 + 2));
+        assert!(to_flatbush(&[], 16).is_none());
+    }
+}
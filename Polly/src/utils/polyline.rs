@@ -0,0 +1,120 @@
+//! Encoded polylines.
+//!
+//! Google's encoded polyline format: each coordinate is stored as the
+//! zig-zag, base64-like delta from the previous one, latitude first. Precision
+//! 5 is the Google default; Valhalla and `--geometry-encoding polyline` use 6.
+
+/// Encodes `[lon, lat]` pairs at the given precision (decimal places).
+pub fn encode(coords: &[Vec<f64>], precision: u32) -> String {
+    let factor = 10f64.powi(precision as i32);
+    let mut out = String::new();
+    let (mut prev_lat, mut prev_lon) = (0i64, 0i64);
+
+    for c in coords {
+        let lat = (c[1] * factor).round() as i64;
+        let lon = (c[0] * factor).round() as i64;
+        encode_value(lat - prev_lat, &mut out);
+        encode_value(lon - prev_lon, &mut out);
+        (prev_lat, prev_lon) = (lat, lon);
+    }
+
+    out
+}
+
+fn encode_value(delta: i64, out: &mut String) {
+    let mut v = if delta < 0 { !(delta << 1) } else { delta << 1 };
+    while v >= 0x20 {
+        out.push((((v & 0x1f) | 0x20) as u8 + 63) as char);
+        v >>= 5;
+    }
+    out.push((v as u8 + 63) as char);
+}
+
+/// Decodes a polyline at the given precision into `[lon, lat]` pairs.
+/// Decoding stops at the first malformed value.
+pub fn decode(encoded: &str, precision: u32) -> Vec<Vec<f64>> {
+    let factor = 10f64.powi(precision as i32);
+    let bytes = encoded.as_bytes();
+    let mut coords = Vec::new();
+    let (mut lat, mut lon) = (0i64, 0i64);
+    let mut i = 0;
+
+    let next_value = |i: &mut usize| -> Option<i64> {
+        let (mut result, mut shift) = (0i64, 0);
+        loop {
+            let b = i64::from(*bytes.get(*i)?) - 63;
+            *i += 1;
+            result |= (b & 0x1f) << shift;
+            shift += 5;
+            if b < 0x20 {
+                break;
+            }
+            if shift > 60 {
+                return None;
+            }
+        }
+        Some(if result & 1 != 0 {
+            !(result >> 1)
+        } else {
+            result >> 1
+        })
+    };
+
+    while i < bytes.len() {
+        let (Some(dlat), Some(dlon)) = (next_value(&mut i), next_value(&mut i)) else {
+            break;
+        };
+        lat += dlat;
+        lon += dlon;
+        coords.push(vec![lon as f64 / factor, lat as f64 / factor]);
+    }
+
+    coords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example of Google's polyline documentation, as [lon, lat]
+    fn example() -> Vec<Vec<f64>> {
+        vec![
+            vec![-120.2, 38.5],
+            vec![-120.95, 40.7],
+            vec![-126.453, 43.252],
+        ]
+    }
+
+    #[test]
+    fn encodes_the_reference_example() {
+        assert_eq!(encode(&example(), 5), "_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+        assert_eq!(decode("_p~iF~ps|U_ulLnnqC_mqNvxq`@", 5), example());
+    }
+
+    #[test]
+    fn round_trips_at_each_precision() {
+        let line = vec![
+            vec![127.920_451, 37.342_118],
+            vec![127.921_003, 37.341_876],
+            vec![127.919_994, 37.343_002],
+        ];
+        for precision in [5, 6] {
+            let decoded = decode(&encode(&line, precision), precision);
+            assert_eq!(decoded.len(), line.len());
+            let step = 10f64.powi(-(precision as i32));
+            for (a, b) in decoded.iter().zip(&line) {
+                assert!((a[0] - b[0]).abs() <= step / 2.0 + 1e-12);
+                assert!((a[1] - b[1]).abs() <= step / 2.0 + 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn decoding_stops_at_malformed_input() {
+        assert!(decode("", 5).is_empty());
+        // A value whose continuation never ends
+        let mut encoded = encode(&example()[..1], 5);
+        encoded.push('~');
+        assert_eq!(decode(&encoded, 5), example()[..1]);
+    }
+}
@@ -0,0 +1,140 @@
+//! Run reports.
+//!
+//! Data quality issues found while processing (e.g., gaps in a route's stop
+//! sequence) do not stop a run, but are listed per item in a `report.json`
+//! next to the output so they can be reviewed and reported upstream. The
+//! report also keeps how long each item took and how much it wrote, so slow
+//! upstream servers show up as numbers rather than a run that feels slow.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{now, write_atomic};
+
+/// File name of the run report within an output directory.
+pub const REPORT_FILE: &str = "report.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunReport {
+    pub generated_at: String,
+    /// Issues per item (e.g., route ID), in item order
+    pub issues: BTreeMap<String, Vec<Issue>>,
+    /// Timings per phase (e.g., "fetch", "snap")
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<String, PhaseMetrics>,
+}
+
+/// Durations and output sizes of the items of one phase
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PhaseMetrics {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<MetricsSummary>,
+    pub items: BTreeMap<String, ItemMetrics>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ItemMetrics {
+    pub ms: u64,
+    /// Bytes written for the item
+    pub bytes: u64,
+}
+
+/// Percentiles of the item durations of one phase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSummary {
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    /// Item that took longest
+    pub slowest: String,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    /// Machine-readable issue type (e.g., "missing_ordinal")
+    pub kind: String,
+    pub detail: String,
+    /// [lon, lat] the issue is located at, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<[f64; 2]>,
+}
+
+impl Issue {
+    pub fn new(kind: &str, detail: impl Into<String>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            detail: detail.into(),
+            at: None,
+        }
+    }
+
+    /// Locates the issue at a [lon, lat] position.
+    pub fn at(mut self, lon: f64, lat: f64) -> Self {
+        self.at = Some([lon, lat]);
+        self
+    }
+}
+
+impl RunReport {
+    /// Adds issues for `id`, keeping those already recorded.
+    pub fn extend(&mut self, id: &str, issues: Vec<Issue>) {
+        if !issues.is_empty() {
+            self.issues
+                .entry(id.to_string())
+                .or_default()
+                .extend(issues);
+        }
+    }
+
+    /// Records how long `id` took in `phase` and how many bytes it wrote.
+    pub fn record_timing(&mut self, phase: &str, id: &str, elapsed: Duration, bytes: usize) {
+        self.metrics
+            .entry(phase.to_string())
+            .or_default()
+            .items
+            .insert(
+                id.to_string(),
+                ItemMetrics {
+                    ms: elapsed.as_millis() as u64,
+                    bytes: bytes as u64,
+                },
+            );
+    }
+
+    /// Computes the duration percentiles of `phase` (nearest rank) and keeps
+    /// them in the report. `None` if no item was timed.
+    pub fn summarize(&mut self, phase: &str) -> Option<&MetricsSummary> {
+        let metrics = self.metrics.get_mut(phase)?;
+        let mut items: Vec<(&String, &ItemMetrics)> = metrics.items.iter().collect();
+        let (slowest, _) = *items.iter().max_by_key(|(_, m)| m.ms)?;
+        items.sort_by_key(|(_, m)| m.ms);
+
+        let percentile = |p: usize| items[(items.len() * p).div_ceil(100).max(1) - 1].1.ms;
+        metrics.summary = Some(MetricsSummary {
+            count: items.len(),
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: percentile(100),
+            slowest: slowest.clone(),
+            total_bytes: items.iter().map(|(_, m)| m.bytes).sum(),
+        });
+        metrics.summary.as_ref()
+    }
+
+    /// Writes the report to `dir`, replacing the previous one.
+    pub fn save(&mut self, dir: &Path) -> Result<()> {
+        self.generated_at = now().to_rfc3339();
+        write_atomic(&dir.join(REPORT_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
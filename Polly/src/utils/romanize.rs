@@ -0,0 +1,146 @@
+//! Hangul romanization.
+//!
+//! Implements the Revised Romanization of Korean for stop and direction names.
+//! Syllables are decomposed into jamo and transcribed with the common sound
+//! change rules between syllables (liaison, nasalization, and ㄹ assimilation).
+//! Morpheme-dependent exceptions are not handled.
+
+const SYLLABLE_BASE: u32 = 0xAC00;
+const SYLLABLE_LAST: u32 = 0xD7A3;
+
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+
+const MEDIALS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+
+/// Final consonants as pronounced before another consonant or at the end of a word.
+const FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// Final consonants carried over to a following vowel (liaison), e.g. 역앞 -> yeogap.
+const FINALS_LIAISON: [&str; 28] = [
+    "", "g", "kk", "ks", "n", "nj", "n", "d", "r", "lg", "lm", "lb", "ls", "lt", "lp", "r", "m",
+    "b", "bs", "s", "ss", "ng", "j", "ch", "k", "t", "p", "",
+];
+
+// Jamo indices used by the sound change rules
+const INITIAL_N: usize = 2;
+const INITIAL_R: usize = 5;
+const INITIAL_M: usize = 6;
+const INITIAL_SILENT: usize = 11;
+
+/// Decomposed Hangul syllable: (initial, medial, final) indices.
+fn decompose(c: char) -> Option<(usize, usize, usize)> {
+    let code = c as u32;
+    if !(SYLLABLE_BASE..=SYLLABLE_LAST).contains(&code) {
+        return None;
+    }
+
+    let offset = code - SYLLABLE_BASE;
+    Some((
+        (offset / (21 * 28)) as usize,
+        ((offset % (21 * 28)) / 28) as usize,
+        (offset % 28) as usize,
+    ))
+}
+
+/// Romanizes a Korean string, capitalizing the first letter of each word.
+/// Non-Hangul characters (digits, Latin letters, punctuation) are kept as-is.
+pub fn romanize(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut word_start = true;
+
+    for (i, &c) in chars.iter().enumerate() {
+        let Some((initial, medial, final_)) = decompose(c) else {
+            out.push(c);
+            word_start = !c.is_alphanumeric();
+            continue;
+        };
+
+        let prev = i.checked_sub(1).and_then(|p| decompose(chars[p]));
+        let next = chars.get(i + 1).and_then(|&n| decompose(n));
+
+        // Initial consonant, adjusted by the previous syllable's final
+        let initial_str = match prev {
+            Some((_, _, pf)) if pf != 0 && initial == INITIAL_SILENT => "",
+            // ㄴ/ㄹ + ㄹ -> ll
+            Some((_, _, 4 | 8)) if initial == INITIAL_R => "l",
+            // other finals + ㄹ -> n
+            Some((_, _, pf)) if pf != 0 && initial == INITIAL_R => "n",
+            _ => INITIALS[initial],
+        };
+
+        // Final consonant, adjusted by the next syllable's initial
+        let final_str = match next {
+            Some((ni, _, _)) if final_ != 0 && ni == INITIAL_SILENT => FINALS_LIAISON[final_],
+            Some((ni, _, _)) if ni == INITIAL_N || ni == INITIAL_M || ni == INITIAL_R => {
+                match FINALS[final_] {
+                    "k" => "ng",
+                    "t" => "n",
+                    "p" => "m",
+                    // ㄴ + ㄹ -> ll
+                    "n" if ni == INITIAL_R => "l",
+                    other => other,
+                }
+            }
+            _ => FINALS[final_],
+        };
+
+        // ㄹ + ㄴ -> ll (the initial side of the same rule)
+        let initial_str = match prev {
+            Some((_, _, 8)) if initial == INITIAL_N => "l",
+            _ => initial_str,
+        };
+
+        let syllable = format!("{}{}{}", initial_str, MEDIALS[medial], final_str);
+        if word_start {
+            let mut s = syllable.chars();
+            if let Some(first) = s.next() {
+                out.extend(first.to_uppercase());
+                out.push_str(s.as_str());
+            }
+        } else {
+            out.push_str(&syllable);
+        }
+        word_start = false;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn romanizes_syllables() {
+        assert_eq!(romanize("원주"), "Wonju");
+        assert_eq!(romanize("연세대"), "Yeonsedae");
+    }
+
+    #[test]
+    fn applies_sound_changes_between_syllables() {
+        // ㄹ after ㅇ is pronounced [n]
+        assert_eq!(romanize("종로"), "Jongno");
+        // ㄱ before ㄹ nasalizes, and the ㄹ becomes [n]
+        assert_eq!(romanize("독립"), "Dongnip");
+        // Liaison carries the final over to the vowel
+        assert_eq!(romanize("역앞"), "Yeogap");
+        // ㄴ + ㄹ -> ll
+        assert_eq!(romanize("신라"), "Silla");
+    }
+
+    #[test]
+    fn keeps_other_characters_and_capitalizes_words() {
+        assert_eq!(romanize("원주역 1번 출구"), "Wonjuyeok 1beon Chulgu");
+        assert_eq!(romanize("KT&G"), "KT&G");
+    }
+}
@@ -0,0 +1,113 @@
+//! Route lists.
+//!
+//! `--routes-file <FILE>` restricts `route` and `schedule` to the routes
+//! listed in a file (or on stdin, with `-`): one route number (e.g., "34-1")
+//! or ID (a TAGO route ID, or a schedule variant such as "34-1(평일)") per
+//! line. Blank lines and `#` comments are skipped, so the IDs printed by
+//! `changelog --ids` can drive a re-crawl of just the changed routes.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+#[derive(Debug, Default)]
+pub struct RoutesFile {
+    entries: BTreeSet<String>,
+}
+
+impl RoutesFile {
+    /// Reads the list from `path`, or from stdin if it is `-`.
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::read_from(path, io::stdin())
+    }
+
+    fn read_from(path: &Path, mut stdin: impl Read) -> Result<Self> {
+        let text = if path == Path::new("-") {
+            let mut text = String::new();
+            stdin
+                .read_to_string(&mut text)
+                .context("Reading the route list from stdin")?;
+            text
+        } else {
+            fs::read_to_string(path).with_context(|| format!("Reading route list {:?}", path))?
+        };
+        Ok(Self::parse(&text))
+    }
+
+    fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the route numbered `route_no`, with ID `route_id`, is listed.
+    pub fn contains(&self, route_no: &str, route_id: &str) -> bool {
+        self.entries.contains(route_no) || self.entries.contains(route_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "\
+# Changed since the last crawl
+34-1
+
+  WJB251000100  # trailing comment
+34-1(평일)
+#41
+";
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let listed = RoutesFile::parse(LIST);
+        assert_eq!(listed.len(), 3);
+        assert!(!listed.contains("41", ""));
+        assert_eq!(RoutesFile::parse("\n  # nothing\n").len(), 0);
+    }
+
+    #[test]
+    fn matches_by_number_or_id() {
+        let listed = RoutesFile::parse(LIST);
+        // By number: every ID of route 34-1
+        assert!(listed.contains("34-1", "WJB251000999"));
+        // By ID: only that route of number 100
+        assert!(listed.contains("100", "WJB251000100"));
+        assert!(!listed.contains("100", "WJB251000101"));
+        // Schedule variants match as IDs.
+        assert!(listed.contains("34", "34-1(평일)"));
+        assert!(!listed.contains("34", "34(평일)"));
+        // Numbers are matched whole, not as prefixes.
+        assert!(!listed.contains("34", "WJB251000034"));
+    }
+
+    #[test]
+    fn reads_a_dash_from_stdin() {
+        let listed = RoutesFile::read_from(Path::new("-"), LIST.as_bytes()).unwrap();
+        assert_eq!(listed.len(), 3);
+        assert!(listed.contains("34-1", ""));
+    }
+
+    #[test]
+    fn reads_files() {
+        let path = std::env::temp_dir().join(format!("polly-routes-{}.txt", std::process::id()));
+        fs::write(&path, LIST).unwrap();
+        let listed = RoutesFile::read_from(&path, io::empty()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(listed.len(), 3);
+
+        assert!(RoutesFile::read_from(&path, io::empty()).is_err());
+    }
+}
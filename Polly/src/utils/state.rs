@@ -0,0 +1,487 @@
+//! Crawl state database.
+//!
+//! `route` and `schedule` keep a small SQLite database, `crawl_state.db`, in
+//! their output directory. It remembers across runs, per route (or schedule
+//! variant):
+//!
+//! - when it was last fetched, the hash of its content, and when that last
+//!   changed, with every change logged for the `changelog` command;
+//! - for routes, the content last snapped, so a re-fetched but unchanged
+//!   route is not snapped again;
+//! - whether it is failed, and its history of failures; `--retry-failed`
+//!   re-processes the failed items.
+//!
+//! The cache validators of upstream responses (see [`Validators`]) are kept
+//! there as well, and `watch` keeps the fingerprints of the upstream route
+//! lists there.
+//!
+//! Bookkeeping never fails a run: database errors are logged and ignored.
+//! A dry run reads the database but changes nothing.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::utils::dry_run;
+use crate::utils::failures::{Failure, FailureManifest};
+use crate::utils::http::Validators;
+use crate::utils::now;
+
+/// File name of the database within an output directory.
+pub const STATE_FILE: &str = "crawl_state.db";
+
+/// Version of the schema below, kept in `PRAGMA user_version`
+const SCHEMA_VERSION: i64 = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS items (
+    subsystem       TEXT NOT NULL,
+    id              TEXT NOT NULL,
+    last_fetched_at TEXT,
+    content_hash    TEXT,
+    last_changed_at TEXT,
+    snapped_hash    TEXT,
+    failed_stage    TEXT,
+    failure_count   INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (subsystem, id)
+);
+CREATE TABLE IF NOT EXISTS failures (
+    subsystem TEXT NOT NULL,
+    id        TEXT NOT NULL,
+    stage     TEXT NOT NULL,
+    reason    TEXT NOT NULL,
+    kind      TEXT,
+    failed_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS failures_item ON failures (subsystem, id);
+CREATE TABLE IF NOT EXISTS changes (
+    subsystem  TEXT NOT NULL,
+    id         TEXT NOT NULL,
+    change     TEXT NOT NULL,
+    old_hash   TEXT,
+    new_hash   TEXT NOT NULL,
+    changed_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS validators (
+    subsystem     TEXT NOT NULL,
+    key           TEXT NOT NULL,
+    etag          TEXT,
+    last_modified TEXT,
+    PRIMARY KEY (subsystem, key)
+);
+CREATE TABLE IF NOT EXISTS upstreams (
+    name       TEXT PRIMARY KEY,
+    hash       TEXT NOT NULL,
+    count      INTEGER NOT NULL,
+    checked_at TEXT NOT NULL
+);
+";
+
+/// The database of the running command, with the subsystem it records
+static STATE: Mutex<Option<(Connection, &'static str)>> = Mutex::new(None);
+
+/// How a fetched item compares to the previous fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Changed,
+    Unchanged,
+}
+
+impl Change {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Changed => "changed",
+            Self::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// A logged content change, as listed by the `changelog` command
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEntry {
+    pub subsystem: String,
+    pub id: String,
+    pub change: String,
+    pub changed_at: String,
+}
+
+/// Opens (or creates) the database in `dir` for `subsystem` (`route` or
+/// `schedule`), which the functions below then record into.
+pub fn open(dir: &Path, subsystem: &'static str) -> Result<()> {
+    let path = dir.join(STATE_FILE);
+    let conn = if dry_run::is_enabled() {
+        // Nothing is written; without a database yet, start from an empty one.
+        if path.exists() {
+            Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        } else {
+            Connection::open_in_memory()
+        }
+    } else {
+        Connection::open(&path)
+    }
+    .with_context(|| format!("Opening crawl state {:?}", path))?;
+
+    if !dry_run::is_enabled() || !path.exists() {
+        migrate(&conn).with_context(|| format!("Preparing crawl state {:?}", path))?;
+    }
+    *STATE.lock().unwrap() = Some((conn, subsystem));
+    Ok(())
+}
+
+/// Version 2 added `upstreams`, version 3 `validators`; every table is
+/// created if missing, so older databases only need the schema applied again.
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version < SCHEMA_VERSION {
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    Ok(())
+}
+
+/// Runs `f` on the open database, if any. Errors are logged, not returned.
+fn with_state<T>(f: impl FnOnce(&Connection, &str) -> rusqlite::Result<T>) -> Option<T> {
+    let guard = STATE.lock().unwrap();
+    let (conn, subsystem) = guard.as_ref()?;
+    match f(conn, subsystem) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Crawl state update failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Like [`with_state`], for changes; skipped in a dry run.
+fn update(f: impl FnOnce(&Connection, &str) -> rusqlite::Result<()>) {
+    if !dry_run::is_enabled() {
+        with_state(f);
+    }
+}
+
+/// Records a successful fetch of `id` whose content hashes to `hash`,
+/// logging it as a change if the hash differs from the previous fetch.
+pub fn record_fetch(id: &str, hash: &str) {
+    let Some(change) = with_state(|conn, subsystem| {
+        let previous: Option<Option<String>> = conn
+            .query_row(
+                "SELECT content_hash FROM items WHERE subsystem = ?1 AND id = ?2",
+                params![subsystem, id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(match previous.flatten() {
+            None => Change::Added,
+            Some(old) if old != hash => Change::Changed,
+            Some(_) => Change::Unchanged,
+        })
+    }) else {
+        return;
+    };
+
+    update(|conn, subsystem| {
+        let time = now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO items (subsystem, id, last_fetched_at, content_hash, last_changed_at)
+             VALUES (?1, ?2, ?3, ?4, ?3)
+             ON CONFLICT (subsystem, id) DO UPDATE SET
+                 last_fetched_at = ?3,
+                 last_changed_at = CASE WHEN content_hash IS ?4 THEN last_changed_at ELSE ?3 END,
+                 content_hash = ?4,
+                 failed_stage = NULL",
+            params![subsystem, id, time, hash],
+        )?;
+        if change != Change::Unchanged {
+            conn.execute(
+                "INSERT INTO changes (subsystem, id, change, old_hash, new_hash, changed_at)
+                 SELECT ?1, ?2, ?3, (SELECT new_hash FROM changes WHERE subsystem = ?1 AND id = ?2
+                                     ORDER BY rowid DESC LIMIT 1), ?4, ?5",
+                params![subsystem, id, change.as_str(), hash, time],
+            )?;
+        }
+        Ok(())
+    });
+}
+
+/// Records a failure of `id` in `stage`. It stays failed until fetched
+/// again, or for a snap failure, snapped again.
+pub fn record_failure(id: &str, stage: &str, reason: &str, kind: Option<&str>) {
+    update(|conn, subsystem| {
+        let time = now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO items (subsystem, id, failed_stage, failure_count) VALUES (?1, ?2, ?3, 1)
+             ON CONFLICT (subsystem, id) DO UPDATE SET
+                 failed_stage = ?3, failure_count = failure_count + 1",
+            params![subsystem, id, stage],
+        )?;
+        conn.execute(
+            "INSERT INTO failures (subsystem, id, stage, reason, kind, failed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![subsystem, id, stage, reason, kind, time],
+        )?;
+        Ok(())
+    });
+}
+
+/// Hash of the content `id` was last fully snapped from.
+pub fn snapped_hash(id: &str) -> Option<String> {
+    with_state(|conn, subsystem| {
+        conn.query_row(
+            "SELECT snapped_hash FROM items WHERE subsystem = ?1 AND id = ?2",
+            params![subsystem, id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()
+        .map(Option::flatten)
+    })
+    .flatten()
+}
+
+/// Records that `id` was snapped without failures, fully (on the road
+/// network) from content hashing to `hash` if given.
+pub fn record_snap(id: &str, hash: Option<&str>) {
+    update(|conn, subsystem| {
+        conn.execute(
+            "UPDATE items SET snapped_hash = ?3,
+                 failed_stage = CASE WHEN failed_stage = 'snap' THEN NULL ELSE failed_stage END
+             WHERE subsystem = ?1 AND id = ?2",
+            params![subsystem, id, hash],
+        )?;
+        Ok(())
+    });
+}
+
+/// Validators of the response last saved for the request `key`.
+pub fn validators(key: &str) -> Option<Validators> {
+    with_state(|conn, subsystem| {
+        conn.query_row(
+            "SELECT etag, last_modified FROM validators WHERE subsystem = ?1 AND key = ?2",
+            params![subsystem, key],
+            |row| {
+                Ok(Validators {
+                    etag: row.get(0)?,
+                    last_modified: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+    })
+    .flatten()
+}
+
+/// Stores the validators of the response saved for the request `key`, or
+/// forgets them if it had none. Call once the response was saved: a later
+/// `304 Not Modified` skips the item.
+pub fn record_validators(key: &str, validators: Option<&Validators>) {
+    update(|conn, subsystem| {
+        match validators {
+            Some(v) => conn.execute(
+                "INSERT INTO validators (subsystem, key, etag, last_modified) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (subsystem, key) DO UPDATE SET etag = ?3, last_modified = ?4",
+                params![subsystem, key, v.etag, v.last_modified],
+            )?,
+            None => conn.execute(
+                "DELETE FROM validators WHERE subsystem = ?1 AND key = ?2",
+                params![subsystem, key],
+            )?,
+        };
+        Ok(())
+    });
+}
+
+/// The items that are still failed, each with its latest failure, or
+/// `None` if the database has no record of the subsystem yet.
+pub fn failed_items() -> Option<FailureManifest> {
+    with_state(|conn, subsystem| {
+        let known: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM items WHERE subsystem = ?1",
+            params![subsystem],
+            |row| row.get(0),
+        )?;
+        if known == 0 {
+            return Ok(None);
+        }
+
+        let mut statement = conn.prepare(
+            "SELECT f.id, f.stage, f.reason, f.kind, f.failed_at
+             FROM failures f JOIN items i ON i.subsystem = f.subsystem AND i.id = f.id
+             WHERE f.subsystem = ?1 AND i.failed_stage IS NOT NULL
+             ORDER BY f.rowid",
+        )?;
+        // Later failures of an item replace earlier ones.
+        let mut latest: BTreeMap<String, (Failure, String)> = BTreeMap::new();
+        let rows = statement.query_map(params![subsystem], |row| {
+            Ok((
+                Failure {
+                    id: row.get(0)?,
+                    stage: row.get(1)?,
+                    reason: row.get(2)?,
+                    kind: row.get(3)?,
+                },
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+        for row in rows {
+            let (failure, failed_at) = row?;
+            latest.insert(failure.id.clone(), (failure, failed_at));
+        }
+
+        let generated_at = latest
+            .values()
+            .map(|(_, at)| at.clone())
+            .max()
+            .unwrap_or_default();
+        Ok(Some(FailureManifest {
+            generated_at,
+            failures: latest.into_values().map(|(f, _)| f).collect(),
+        }))
+    })
+    .flatten()
+}
+
+/// Content changes logged in the database in `dir` at or after `since`
+/// (RFC 3339), oldest first. Opened read-only, beside any running command.
+pub fn changes(dir: &Path, since: &str) -> Result<Vec<ChangeEntry>> {
+    let path = dir.join(STATE_FILE);
+    let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("No crawl state at {:?}", path))?;
+    let mut statement = conn.prepare(
+        "SELECT subsystem, id, change, changed_at FROM changes
+         WHERE changed_at >= ?1 ORDER BY changed_at, rowid",
+    )?;
+    let rows = statement.query_map(params![since], |row| {
+        Ok(ChangeEntry {
+            subsystem: row.get(0)?,
+            id: row.get(1)?,
+            change: row.get(2)?,
+            changed_at: row.get(3)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Fingerprint (hash and item count) of the upstream `name` last crawled,
+/// as stored by [`record_upstream`] in the database in `dir`.
+pub fn upstream(dir: &Path, name: &str) -> Result<Option<(String, usize)>> {
+    let path = dir.join(STATE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Opening crawl state {:?}", path))?;
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version < 2 {
+        // Written before `watch` existed
+        return Ok(None);
+    }
+    let stored = conn
+        .query_row(
+            "SELECT hash, count FROM upstreams WHERE name = ?1",
+            params![name],
+            |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)),
+        )
+        .optional()?;
+    Ok(stored)
+}
+
+/// Stores the fingerprint of the upstream `name` in the database in `dir`
+/// (not in a dry run).
+pub fn record_upstream(dir: &Path, name: &str, hash: &str, count: usize) -> Result<()> {
+    if dry_run::is_enabled() {
+        return Ok(());
+    }
+    let path = dir.join(STATE_FILE);
+    let conn =
+        Connection::open(&path).with_context(|| format!("Opening crawl state {:?}", path))?;
+    migrate(&conn).with_context(|| format!("Preparing crawl state {:?}", path))?;
+    conn.execute(
+        "INSERT INTO upstreams (name, hash, count, checked_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (name) DO UPDATE SET hash = ?2, count = ?3, checked_at = ?4",
+        params![name, hash, count as i64, now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn version(conn: &Connection) -> i64 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn creates_the_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(version(&conn), SCHEMA_VERSION);
+        assert_eq!(
+            tables(&conn),
+            ["changes", "failures", "items", "upstreams", "validators"]
+        );
+    }
+
+    #[test]
+    fn upgrades_a_version_1_database_keeping_its_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (
+                subsystem TEXT NOT NULL, id TEXT NOT NULL, last_fetched_at TEXT,
+                content_hash TEXT, last_changed_at TEXT, snapped_hash TEXT,
+                failed_stage TEXT, failure_count INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (subsystem, id)
+            );
+            INSERT INTO items (subsystem, id, content_hash) VALUES ('route', 'WJB1', 'abc');
+            PRAGMA user_version = 1;",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+        assert_eq!(version(&conn), SCHEMA_VERSION);
+        assert!(tables(&conn).contains(&"upstreams".to_string()));
+        assert!(tables(&conn).contains(&"validators".to_string()));
+        let hash: String = conn
+            .query_row(
+                "SELECT content_hash FROM items WHERE id = 'WJB1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hash, "abc");
+    }
+
+    #[test]
+    fn migrating_again_changes_nothing() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO upstreams (name, hash, count, checked_at) VALUES ('routes', 'h', 3, 'now')",
+            [],
+        )
+        .unwrap();
+        migrate(&conn).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM upstreams", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
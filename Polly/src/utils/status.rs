@@ -0,0 +1,184 @@
+//! Run status file.
+//!
+//! `route` and `schedule` keep a `status.json` in their output directory that
+//! tells supervisors what the run is doing: its state (`running`, `ok`,
+//! `failed`), when it started, its progress, and the last error. While
+//! running, `updatedAt` advances with the progress, so a liveness probe can
+//! treat a stale file as a hung crawl. The metrics endpoint serves the same
+//! document on `/status`.
+
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::Local;
+use regex::Regex;
+use serde::Serialize;
+use tracing::debug;
+
+use crate::utils::{dry_run, write_atomic};
+
+/// File name of the status file within an output directory.
+pub const STATUS_FILE: &str = "status.json";
+
+/// Progress updates are written at most this often.
+const WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+static TRACKER: Mutex<Option<Tracker>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStatus {
+    pub state: State,
+    pub command: String,
+    pub pid: u32,
+    pub started_at: String,
+    /// Last change of the status; advances with the progress
+    pub updated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
+    /// Items that failed so far (see `failures.json`)
+    pub errors: usize,
+    /// Most recent error, whether it failed the run or only one item
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Running,
+    Ok,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    /// Phase of the run (e.g., `fetch`, `snap`, `schedule`)
+    pub phase: String,
+    pub done: usize,
+    pub total: usize,
+}
+
+struct Tracker {
+    path: PathBuf,
+    status: RunStatus,
+    last_write: Instant,
+}
+
+impl Tracker {
+    fn save(&mut self) {
+        self.last_write = Instant::now();
+        // A dry run leaves the output directory alone.
+        if dry_run::is_enabled() {
+            return;
+        }
+        if let Err(e) = write_status(&self.path, &self.status) {
+            debug!("Failed to write {:?}: {:#}", self.path, e);
+        }
+    }
+}
+
+fn write_status(path: &Path, status: &RunStatus) -> Result<()> {
+    write_atomic(path, serde_json::to_string_pretty(status)?)
+}
+
+/// Hides service keys in request URLs quoted by error messages, since the
+/// status file may be published along with the output directory.
+pub fn redact(message: &str) -> String {
+    static SERVICE_KEY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(serviceKey=)[^&\s)]*").unwrap());
+    SERVICE_KEY
+        .replace_all(message, "${1}REDACTED")
+        .into_owned()
+}
+
+fn timestamp() -> String {
+    Local::now().to_rfc3339()
+}
+
+/// Marks `command` as running, with its status file at `path`. A command run
+/// as a step of `pipeline` keeps reporting into the pipeline's status.
+pub fn start(command: &str, path: PathBuf) {
+    if TRACKER.lock().unwrap().is_some() {
+        return;
+    }
+    let started_at = timestamp();
+    let mut tracker = Tracker {
+        path,
+        status: RunStatus {
+            state: State::Running,
+            command: command.to_string(),
+            pid: std::process::id(),
+            started_at: started_at.clone(),
+            updated_at: started_at,
+            finished_at: None,
+            progress: None,
+            errors: 0,
+            last_error: None,
+        },
+        last_write: Instant::now(),
+    };
+    tracker.save();
+    *TRACKER.lock().unwrap() = Some(tracker);
+}
+
+/// Reports `done` of `total` items finished in `phase`.
+pub fn progress(phase: &str, done: usize, total: usize) {
+    let mut guard = TRACKER.lock().unwrap();
+    let Some(tracker) = guard.as_mut() else {
+        return;
+    };
+    tracker.status.progress = Some(Progress {
+        phase: phase.to_string(),
+        done,
+        total,
+    });
+    tracker.status.updated_at = timestamp();
+    if done == total || tracker.last_write.elapsed() >= WRITE_INTERVAL {
+        tracker.save();
+    }
+}
+
+/// Records an error that did not stop the run (e.g., one route failing).
+pub fn error(message: String) {
+    let mut guard = TRACKER.lock().unwrap();
+    if let Some(tracker) = guard.as_mut() {
+        tracker.status.errors += 1;
+        tracker.status.last_error = Some(redact(&message));
+        tracker.status.updated_at = timestamp();
+    }
+}
+
+/// Marks the run as finished with `result`.
+pub fn finish(result: &Result<()>) {
+    let mut guard = TRACKER.lock().unwrap();
+    let Some(tracker) = guard.as_mut() else {
+        return;
+    };
+    let now = timestamp();
+    match result {
+        Ok(()) => tracker.status.state = State::Ok,
+        Err(e) => {
+            tracker.status.state = State::Failed;
+            tracker.status.last_error = Some(redact(&format!("{:#}", e)));
+        }
+    }
+    tracker.status.updated_at = now.clone();
+    tracker.status.finished_at = Some(now);
+    tracker.save();
+}
+
+/// Forgets the finished run, so the next [`start`] tracks a new one (e.g.,
+/// each crawl of `watch`).
+pub fn reset() {
+    *TRACKER.lock().unwrap() = None;
+}
+
+/// The current status, if a run is being tracked.
+pub fn current() -> Option<RunStatus> {
+    TRACKER.lock().unwrap().as_ref().map(|t| t.status.clone())
+}
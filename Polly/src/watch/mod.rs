@@ -0,0 +1,201 @@
+//! Watch Module
+//!
+//! Keeps the storage directory up to date without crawling on a fixed
+//! schedule. Every interval, `watch` fetches only the TAGO route list and the
+//! main schedule page, and compares their fingerprints with those of the last
+//! crawl, kept in the crawl state database. Only when one changed does it run
+//! the pipeline, and only the step of the upstream that changed, so the
+//! municipal servers see one request per check instead of a full crawl.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::error::{ExitStatus, exit_code};
+use crate::pipeline::{self, PipelineArgs};
+use crate::route;
+use crate::schedule;
+use crate::settings::Settings;
+use crate::utils::i18n::Msg;
+use crate::utils::interrupt::Interrupt;
+use crate::utils::{alert, short_hash, state, status};
+
+/// Upstream names in the crawl state database
+const ROUTES: &str = "routes";
+const SCHEDULES: &str = "schedules";
+
+// ============================================================================
+// Argument Structure
+// ============================================================================
+
+#[derive(clap::Args)]
+pub struct WatchArgs {
+    /// Minutes between checks of the upstreams
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+
+    /// Check once, crawl if anything changed, and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Options of the triggered crawls, as with `pipeline`; `--skip-routes`
+    /// and `--skip-schedules` also stop watching that upstream
+    #[command(flatten)]
+    pipeline: PipelineArgs,
+}
+
+/// Hash and item count of an upstream listing
+pub struct Fingerprint {
+    pub hash: String,
+    pub count: usize,
+}
+
+impl Fingerprint {
+    /// Fingerprints `content`, a listing of `count` items in a stable order.
+    pub fn of(content: &impl Serialize, count: usize) -> Self {
+        let bytes = serde_json::to_vec(content).unwrap_or_default();
+        Self {
+            hash: short_hash(&bytes, 16),
+            count,
+        }
+    }
+}
+
+// ============================================================================
+// Main Execution
+// ============================================================================
+
+pub async fn run(args: WatchArgs, settings: &Settings) -> Result<()> {
+    let mut interrupt = Interrupt::install();
+    info!(
+        interval_min = args.interval,
+        "{}",
+        Msg::WatchStart(args.interval)
+    );
+
+    loop {
+        check(&args, settings).await?;
+        if args.once || interrupt.is_set() {
+            break;
+        }
+        tokio::select! {
+            _ = sleep(Duration::from_secs(args.interval * 60)) => {}
+            _ = interrupt.wait() => break,
+        }
+    }
+
+    info!("{}", Msg::WatchStopped);
+    Ok(())
+}
+
+/// Checks the upstreams once and crawls those that changed. A failed check or
+/// crawl is retried at the next interval; only configuration errors and
+/// Ctrl+C end the watch.
+async fn check(args: &WatchArgs, settings: &Settings) -> Result<()> {
+    let storage_dir = &args.pipeline.storage_dir;
+
+    let routes = if args.pipeline.skip_routes {
+        None
+    } else {
+        let probe = route::probe(settings, args.pipeline.city_code.as_deref()).await;
+        changed(ROUTES, probe, storage_dir)?
+    };
+    let schedules = if args.pipeline.skip_schedules {
+        None
+    } else {
+        changed(
+            SCHEDULES,
+            schedule::probe(settings, args.pipeline.ignore_robots).await,
+            storage_dir,
+        )?
+    };
+    if routes.is_none() && schedules.is_none() {
+        return Ok(());
+    }
+
+    let mut crawl = args.pipeline.clone();
+    crawl.skip_routes = routes.is_none();
+    crawl.skip_schedules = schedules.is_none();
+    let result = pipeline::run(crawl, settings).await;
+
+    // Each crawl reports and notifies as a run of its own.
+    status::finish(&result);
+    alert::send_summary(&settings.notify, &settings.http).await;
+    status::reset();
+
+    match result {
+        // The fingerprints are only stored once crawled, so a failed crawl
+        // is triggered again by the next check.
+        Ok(()) => {
+            for (name, fingerprint) in [(ROUTES, routes), (SCHEDULES, schedules)] {
+                if let Some(f) = fingerprint {
+                    state::record_upstream(storage_dir, name, &f.hash, f.count)?;
+                }
+            }
+            Ok(())
+        }
+        Err(e) if is_final(&e) => Err(e),
+        Err(e) => {
+            error!(outcome = "failed", "{}", Msg::WatchCrawlFailed(&e));
+            Ok(())
+        }
+    }
+}
+
+/// The new fingerprint of `name` if it differs from the stored one (or none
+/// is stored yet), else `None`. A failed probe counts as unchanged.
+fn changed(
+    name: &str,
+    probe: Result<Fingerprint>,
+    storage_dir: &Path,
+) -> Result<Option<Fingerprint>> {
+    let fingerprint = match probe {
+        Ok(f) => f,
+        Err(e) if is_final(&e) => return Err(e),
+        Err(e) => {
+            warn!(upstream = name, "{}", Msg::ProbeFailed(name, &e));
+            return Ok(None);
+        }
+    };
+    let stored = state::upstream(storage_dir, name)?;
+    if stored
+        .as_ref()
+        .is_some_and(|(hash, _)| *hash == fingerprint.hash)
+    {
+        info!(
+            upstream = name,
+            count = fingerprint.count,
+            outcome = "unchanged",
+            "{}",
+            Msg::UpstreamUnchanged(name)
+        );
+        return Ok(None);
+    }
+    info!(
+        upstream = name,
+        count = fingerprint.count,
+        outcome = "changed",
+        "{}",
+        Msg::UpstreamChanged {
+            name,
+            before: stored.map(|(_, count)| count),
+            after: fingerprint.count,
+        }
+    );
+    Ok(Some(fingerprint))
+}
+
+/// Errors that retrying at the next interval would not fix.
+fn is_final(err: &anyhow::Error) -> bool {
+    [
+        ExitStatus::Config,
+        ExitStatus::TagoAuth,
+        ExitStatus::Interrupted,
+    ]
+    .iter()
+    .any(|status| exit_code(err) == status.code())
+}